@@ -88,6 +88,10 @@ pub fn path_fixes(name: &str) -> String {
     format!("../data/fixes/{}.json", name)
 }
 
+pub fn path_signal_timing(map_name: &str) -> String {
+    format!("../data/signal_timing_sheets/{}.json", map_name)
+}
+
 pub fn path_camera_state(map_name: &str) -> String {
     format!("../data/camera_state/{}.json", map_name)
 }
@@ -6,7 +6,7 @@ mod split_ways;
 use abstutil::Timer;
 use geom::{Distance, FindClosest, Line, PolyLine, Pt2D};
 use kml::ExtraShapes;
-use map_model::raw::{OriginalBuilding, OriginalRoad, RawMap};
+use map_model::raw::{DrivingSide, OriginalBuilding, OriginalRoad, RawMap};
 use map_model::{osm, LaneID, OffstreetParking, Position, LANE_THICKNESS};
 
 pub struct Flags {
@@ -18,13 +18,21 @@ pub struct Flags {
     pub neighborhoods: Option<String>,
     pub clip: Option<String>,
     pub output: String,
+    // For places like the UK and Japan
+    pub drive_on_left: bool,
 }
 
 pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> RawMap {
+    let driving_side = if flags.drive_on_left {
+        DrivingSide::Left
+    } else {
+        DrivingSide::Right
+    };
     let mut map = split_ways::split_up_roads(
-        osm_reader::extract_osm(&flags.osm, &flags.clip, timer),
+        osm_reader::extract_osm(&flags.osm, &flags.clip, driving_side, timer),
         timer,
     );
+    map.driving_side = driving_side;
     clip::clip_map(&mut map, timer);
 
     // Need to do a first pass of removing cul-de-sacs here, or we wind up with loop PolyLines when doing the parking hint matching.
@@ -41,7 +49,14 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> RawMap {
     }
     if let Some(ref path) = flags.gtfs {
         timer.start("load GTFS");
-        map.bus_routes = gtfs::load(path).unwrap();
+        // Model a typical weekday
+        let (rail, bus) = gtfs::load(path)
+            .unwrap()
+            .only_weekday(gtfs::Weekday::Wednesday)
+            .into_iter()
+            .partition(|r| r.route_type.is_rail());
+        map.bus_routes = bus;
+        map.rail_routes = rail;
         timer.stop("load GTFS");
     }
 
@@ -12,8 +12,12 @@ fn main() {
         neighborhoods: args.optional("--neighborhoods"),
         clip: args.optional("--clip"),
         output: args.required("--output"),
+        drive_on_left: args.enabled("--drive_on_left"),
     };
     args.done();
+    if !flags.osm.ends_with(".osm") && !flags.osm.ends_with(".osm.pbf") {
+        panic!("--osm={} should be a .osm or .osm.pbf file", flags.osm);
+    }
 
     let mut timer = abstutil::Timer::new(&format!("generate {}", flags.output));
     let map = convert(&flags, &mut timer);
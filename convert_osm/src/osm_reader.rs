@@ -1,15 +1,54 @@
 use abstutil::{FileWithProgress, Timer};
 use geom::{GPSBounds, HashablePt2D, LonLat, PolyLine, Polygon, Pt2D, Ring};
-use map_model::raw::{OriginalBuilding, RawArea, RawBuilding, RawMap, RawRoad, RestrictionType};
+use map_model::raw::{
+    DrivingSide, OriginalBuilding, RawArea, RawBuilding, RawMap, RawRoad, RestrictionType,
+};
 use map_model::{osm, AreaType};
 use osm_xml;
+use osmpbfreader;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
+// Everything read from either input format. Iterating over this is always in ID order, so both
+// formats produce exactly the same RawMap.
+struct Document {
+    nodes: BTreeMap<i64, Node>,
+    ways: BTreeMap<i64, Way>,
+    relations: BTreeMap<i64, Relation>,
+}
+
+struct Node {
+    id: i64,
+    lon: f64,
+    lat: f64,
+    tags: BTreeMap<String, String>,
+}
+
+struct Way {
+    id: i64,
+    nodes: Vec<i64>,
+    tags: BTreeMap<String, String>,
+}
+
+struct Relation {
+    id: i64,
+    // With their role
+    members: Vec<(Member, String)>,
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+enum Member {
+    Node(i64),
+    Way(i64),
+    Relation(i64),
+}
+
 pub fn extract_osm(
     osm_path: &str,
     maybe_clip_path: &Option<String>,
+    driving_side: DrivingSide,
     timer: &mut Timer,
 ) -> (
     RawMap,
@@ -22,20 +61,24 @@ pub fn extract_osm(
     // Turn restrictions: (restriction type, from way ID, via node ID, to way ID)
     Vec<(RestrictionType, i64, i64, i64)>,
 ) {
-    let (reader, done) = FileWithProgress::new(osm_path).unwrap();
-    let doc = osm_xml::OSM::parse(reader).expect("OSM parsing failed");
+    let doc = if osm_path.ends_with(".pbf") {
+        read_pbf(osm_path, timer)
+    } else {
+        read_xml(osm_path, timer)
+    };
     println!(
         "OSM doc has {} nodes, {} ways, {} relations",
         doc.nodes.len(),
         doc.ways.len(),
         doc.relations.len()
     );
-    done(timer);
 
     let mut map = if let Some(ref path) = maybe_clip_path {
         read_osmosis_polygon(path)
     } else {
-        let mut m = RawMap::blank(abstutil::basename(osm_path));
+        // For foo.osm.pbf, the name should still be foo. Without a clipping polygon, the bounds
+        // cover every node read, which for .pbf is only the nodes something uses.
+        let mut m = RawMap::blank(abstutil::basename(osm_path.trim_end_matches(".pbf")));
         for node in doc.nodes.values() {
             m.gps_bounds.update(LonLat::new(node.lon, node.lat));
         }
@@ -55,8 +98,7 @@ pub fn extract_osm(
             Pt2D::forcibly_from_gps(LonLat::new(node.lon, node.lat), &map.gps_bounds).to_hashable();
         osm_node_ids.insert(pt, node.id);
 
-        let tags = tags_to_map(&node.tags);
-        if tags.get(osm::HIGHWAY) == Some(&"traffic_signals".to_string()) {
+        if node.tags.get(osm::HIGHWAY) == Some(&"traffic_signals".to_string()) {
             traffic_signals.insert(pt);
         }
     }
@@ -68,25 +110,26 @@ pub fn extract_osm(
 
         let mut valid = true;
         let mut gps_pts = Vec::new();
-        for node_ref in &way.nodes {
-            match doc.resolve_reference(node_ref) {
-                osm_xml::Reference::Node(node) => {
-                    gps_pts.push(LonLat::new(node.lon, node.lat));
-                }
-                // Don't handle nested ways/relations yet
-                _ => {
-                    valid = false;
-                }
+        for id in &way.nodes {
+            // The node might be missing from the extract
+            if let Some(node) = doc.nodes.get(id) {
+                gps_pts.push(LonLat::new(node.lon, node.lat));
+            } else {
+                valid = false;
             }
         }
         if !valid {
             continue;
         }
         let pts = map.gps_bounds.forcibly_convert(&gps_pts);
-        let mut tags = tags_to_map(&way.tags);
+        let mut tags = way.tags.clone();
         tags.insert(osm::OSM_WAY_ID.to_string(), way.id.to_string());
 
         if is_road(&tags) {
+            // Footways and cycleways don't have parking or sidewalks alongside them, so there's
+            // nothing to infer.
+            let off_street = is_off_street(&tags);
+
             // If there's no parking data in OSM already, then assume no parking and mark that it's
             // inferred.
             if !tags.contains_key(osm::PARKING_LEFT)
@@ -95,6 +138,7 @@ pub fn extract_osm(
                 && tags.get(osm::HIGHWAY) != Some(&"motorway".to_string())
                 && tags.get(osm::HIGHWAY) != Some(&"motorway_link".to_string())
                 && tags.get("junction") != Some(&"roundabout".to_string())
+                && !off_street
             {
                 tags.insert(osm::PARKING_BOTH.to_string(), "no_parking".to_string());
                 tags.insert(osm::INFERRED_PARKING.to_string(), "true".to_string());
@@ -102,15 +146,28 @@ pub fn extract_osm(
 
             // If there's no sidewalk data in OSM already, then make an assumption and mark that
             // it's inferred.
-            if !tags.contains_key(osm::SIDEWALK) {
+            if !tags.contains_key(osm::SIDEWALK)
+                && !tags.contains_key("sidewalk:both")
+                && !tags.contains_key("sidewalk:left")
+                && !tags.contains_key("sidewalk:right")
+                && !off_street
+            {
                 tags.insert(osm::INFERRED_SIDEWALKS.to_string(), "true".to_string());
                 if tags.get(osm::HIGHWAY) == Some(&"motorway".to_string())
                     || tags.get(osm::HIGHWAY) == Some(&"motorway_link".to_string())
                     || tags.get("junction") == Some(&"roundabout".to_string())
                 {
                     tags.insert(osm::SIDEWALK.to_string(), "none".to_string());
+                } else if tags.get(osm::HIGHWAY) == Some(&"service".to_string()) {
+                    // Alleys usually don't have sidewalks; pedestrians stick to the main roads.
+                    tags.insert(osm::SIDEWALK.to_string(), "none".to_string());
                 } else if tags.get("oneway") == Some(&"yes".to_string()) {
-                    tags.insert(osm::SIDEWALK.to_string(), "right".to_string());
+                    // On the curb side
+                    let side = match driving_side {
+                        DrivingSide::Right => "right",
+                        DrivingSide::Left => "left",
+                    };
+                    tags.insert(osm::SIDEWALK.to_string(), side.to_string());
                     if tags.get(osm::HIGHWAY) == Some(&"residential".to_string()) {
                         tags.insert(osm::SIDEWALK.to_string(), "both".to_string());
                     }
@@ -165,15 +222,15 @@ pub fn extract_osm(
     timer.start_iter("processing OSM relations", doc.relations.len());
     for rel in doc.relations.values() {
         timer.next();
-        let mut tags = tags_to_map(&rel.tags);
+        let mut tags = rel.tags.clone();
         tags.insert(osm::OSM_REL_ID.to_string(), rel.id.to_string());
         if let Some(at) = get_area_type(&tags) {
             if tags.get("type") == Some(&"multipolygon".to_string()) {
                 let mut ok = true;
                 let mut pts_per_way: Vec<Vec<Pt2D>> = Vec::new();
-                for member in &rel.members {
+                for (member, role) in &rel.members {
                     match member {
-                        osm_xml::Member::Way(osm_xml::UnresolvedReference::Way(id), ref role) => {
+                        Member::Way(id) => {
                             // If the way is clipped out, that's fine
                             if let Some(pts) = id_to_way.get(id) {
                                 if role == "outer" {
@@ -207,9 +264,9 @@ pub fn extract_osm(
             let mut from_way_id: Option<i64> = None;
             let mut via_node_id: Option<i64> = None;
             let mut to_way_id: Option<i64> = None;
-            for member in &rel.members {
+            for (member, role) in &rel.members {
                 match member {
-                    osm_xml::Member::Way(osm_xml::UnresolvedReference::Way(id), ref role) => {
+                    Member::Way(id) => {
                         if role == "from" {
                             from_way_id = Some(*id);
                         } else if role == "to" {
@@ -217,12 +274,12 @@ pub fn extract_osm(
                         }
                         // TODO Handle 'via' ways
                     }
-                    osm_xml::Member::Node(osm_xml::UnresolvedReference::Node(id), ref role) => {
+                    Member::Node(id) => {
                         if role == "via" {
                             via_node_id = Some(*id);
                         }
                     }
-                    _ => unreachable!(),
+                    Member::Relation(_) => unreachable!(),
                 }
             }
             if let (Some(from_way_id), Some(via_node_id), Some(to_way_id)) =
@@ -256,16 +313,189 @@ pub fn extract_osm(
     (map, roads, traffic_signals, osm_node_ids, turn_restrictions)
 }
 
-fn tags_to_map(raw_tags: &[osm_xml::Tag]) -> BTreeMap<String, String> {
+// osm_xml parses the whole file up-front. The strings are moved out of it, not copied, so the
+// Document doesn't double the memory needed.
+fn read_xml(path: &str, timer: &mut Timer) -> Document {
+    let (reader, done) = FileWithProgress::new(path).unwrap();
+    let doc = osm_xml::OSM::parse(reader).expect("OSM parsing failed");
+    done(timer);
+
+    let mut result = Document {
+        nodes: BTreeMap::new(),
+        ways: BTreeMap::new(),
+        relations: BTreeMap::new(),
+    };
+    for (_, node) in doc.nodes {
+        result.nodes.insert(
+            node.id,
+            Node {
+                id: node.id,
+                lon: node.lon,
+                lat: node.lat,
+                tags: tags_to_map(node.tags.into_iter().map(|tag| (tag.key, tag.val))),
+            },
+        );
+    }
+    for (_, way) in doc.ways {
+        // Don't handle nested ways/relations yet.
+        let nodes: Option<Vec<i64>> = way
+            .nodes
+            .iter()
+            .map(|node_ref| match node_ref {
+                osm_xml::UnresolvedReference::Node(id) => Some(*id),
+                _ => None,
+            })
+            .collect();
+        if let Some(nodes) = nodes {
+            result.ways.insert(
+                way.id,
+                Way {
+                    id: way.id,
+                    nodes,
+                    tags: tags_to_map(way.tags.into_iter().map(|tag| (tag.key, tag.val))),
+                },
+            );
+        } else {
+            println!(
+                "Way {} refers to something besides nodes, skipping it",
+                way.id
+            );
+        }
+    }
+    for (_, rel) in doc.relations {
+        let id = rel.id;
+        let members = rel
+            .members
+            .into_iter()
+            .map(|member| match member {
+                osm_xml::Member::Node(osm_xml::UnresolvedReference::Node(id), role) => {
+                    (Member::Node(id), role)
+                }
+                osm_xml::Member::Way(osm_xml::UnresolvedReference::Way(id), role) => {
+                    (Member::Way(id), role)
+                }
+                osm_xml::Member::Relation(osm_xml::UnresolvedReference::Relation(id), role) => {
+                    (Member::Relation(id), role)
+                }
+                _ => panic!("Relation {} has a weird member {:?}", id, member),
+            })
+            .collect();
+        result.relations.insert(
+            id,
+            Relation {
+                id,
+                members,
+                tags: tags_to_map(rel.tags.into_iter().map(|tag| (tag.key, tag.val))),
+            },
+        );
+    }
+    result
+}
+
+// The first pass over the file keeps only the ways and relations that'll become something, and
+// later passes pick up the ways and nodes they refer to. So the rest of a big extract never has to
+// fit in memory.
+fn read_pbf(path: &str, timer: &mut Timer) -> Document {
+    timer.start(&format!("read {}", path));
+    let mut pbf = osmpbfreader::OsmPbfReader::new(BufReader::new(File::open(path).unwrap()));
+    let objs = pbf
+        .get_objs_and_deps(keep_pbf_obj)
+        .expect("OSM parsing failed");
+    timer.stop(&format!("read {}", path));
+
+    let mut result = Document {
+        nodes: BTreeMap::new(),
+        ways: BTreeMap::new(),
+        relations: BTreeMap::new(),
+    };
+    for obj in objs.into_iter().map(|(_, obj)| obj) {
+        match obj {
+            osmpbfreader::OsmObj::Node(node) => {
+                result.nodes.insert(
+                    node.id.0,
+                    Node {
+                        id: node.id.0,
+                        // Dividing, not multiplying by 1e-7 like lon() does, rounds to the same f64
+                        // as parsing the decimal degrees in XML.
+                        lon: (node.decimicro_lon as f64) / 1e7,
+                        lat: (node.decimicro_lat as f64) / 1e7,
+                        tags: tags_to_map(
+                            node.tags
+                                .iter()
+                                .map(|(k, v)| (k.to_string(), v.to_string())),
+                        ),
+                    },
+                );
+            }
+            osmpbfreader::OsmObj::Way(way) => {
+                result.ways.insert(
+                    way.id.0,
+                    Way {
+                        id: way.id.0,
+                        nodes: way.nodes.iter().map(|id| id.0).collect(),
+                        tags: tags_to_map(
+                            way.tags.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+                        ),
+                    },
+                );
+            }
+            osmpbfreader::OsmObj::Relation(rel) => {
+                let members = rel
+                    .refs
+                    .iter()
+                    .map(|r| {
+                        let member = match r.member {
+                            osmpbfreader::OsmId::Node(id) => Member::Node(id.0),
+                            osmpbfreader::OsmId::Way(id) => Member::Way(id.0),
+                            osmpbfreader::OsmId::Relation(id) => Member::Relation(id.0),
+                        };
+                        (member, r.role.to_string())
+                    })
+                    .collect();
+                result.relations.insert(
+                    rel.id.0,
+                    Relation {
+                        id: rel.id.0,
+                        members,
+                        tags: tags_to_map(
+                            rel.tags.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+                        ),
+                    },
+                );
+            }
+        }
+    }
+    result
+}
+
+fn keep_pbf_obj(obj: &osmpbfreader::OsmObj) -> bool {
+    let tags = tags_to_map(
+        obj.tags()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string())),
+    );
+    match obj {
+        // Only the ones that kept ways and relations refer to
+        osmpbfreader::OsmObj::Node(_) => false,
+        osmpbfreader::OsmObj::Way(_) => {
+            is_road(&tags)
+                || is_bldg(&tags)
+                || get_area_type(&tags).is_some()
+                || tags.get("natural") == Some(&"coastline".to_string())
+        }
+        osmpbfreader::OsmObj::Relation(_) => {
+            (get_area_type(&tags).is_some()
+                && tags.get("type") == Some(&"multipolygon".to_string()))
+                || tags.get("type") == Some(&"restriction".to_string())
+        }
+    }
+}
+
+fn tags_to_map<I: Iterator<Item = (String, String)>>(raw_tags: I) -> BTreeMap<String, String> {
     raw_tags
-        .iter()
-        .filter_map(|tag| {
+        .filter(|(key, _)| {
             // Toss out really useless metadata.
-            if tag.key.starts_with("tiger:") || tag.key.starts_with("old_name:") {
-                None
-            } else {
-                Some((tag.key.clone(), tag.val.clone()))
-            }
+            !key.starts_with("tiger:") && !key.starts_with("old_name:")
         })
         .collect()
 }
@@ -275,26 +505,39 @@ fn is_road(tags: &BTreeMap<String, String>) -> bool {
         return false;
     }
 
+    // Pedestrian plazas and other areas aren't paths through them
+    if tags.get("area") == Some(&"yes".to_string()) {
+        return false;
+    }
+    // Sidewalks and crossings mapped as separate ways would be redundant with the sidewalks and
+    // crosswalks of the road they're alongside. That road is usually tagged sidewalk=separate, and
+    // gets its own sidewalks instead. Nothing snaps these ways to their road yet, so a footpath
+    // that only meets the rest of the network through one of them winds up disconnected.
+    if tags.get("footway") == Some(&"sidewalk".to_string())
+        || tags.get("footway") == Some(&"crossing".to_string())
+    {
+        return false;
+    }
+    // Keep alleys, but not every driveway and parking lot aisle.
+    if tags.get(osm::HIGHWAY) == Some(&"service".to_string()) {
+        if let Some(service) = tags.get("service") {
+            if service != "alley" {
+                return false;
+            }
+        }
+    }
+
     // https://github.com/Project-OSRM/osrm-backend/blob/master/profiles/car.lua is another
     // potential reference
     for &value in &[
         // List of non-car types from https://wiki.openstreetmap.org/wiki/Key:highway
-        // TODO Footways are very useful, but they need more work to associate with main roads
-        "footway",
-        "living_street",
-        "pedestrian",
         "track",
         "bus_guideway",
         "escape",
         "raceway",
         "bridleway",
-        "steps",
-        "path",
-        "cycleway",
         "proposed",
         "construction",
-        // This one's debatable. Includes alleys.
-        "service",
         // more discovered manually
         "abandoned",
         "elevator",
@@ -309,6 +552,16 @@ fn is_road(tags: &BTreeMap<String, String>) -> bool {
     true
 }
 
+// Roads without any cars
+fn is_off_street(tags: &BTreeMap<String, String>) -> bool {
+    match tags.get(osm::HIGHWAY).map(|hwy| hwy.as_ref()) {
+        Some("footway") | Some("pedestrian") | Some("steps") | Some("cycleway") | Some("path") => {
+            true
+        }
+        _ => false,
+    }
+}
+
 fn is_bldg(tags: &BTreeMap<String, String>) -> bool {
     tags.contains_key("building")
 }
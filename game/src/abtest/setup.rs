@@ -128,6 +128,8 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
             )
             .expect("loading scenario failed");
 
+            // Each side writes its own event log
+            let event_log = ui.primary.current_flags.sim_flags.opts.event_log.clone();
             {
                 timer.start("load primary");
                 if ui.primary.current_flags.sim_flags.rng_seed.is_none() {
@@ -136,6 +138,9 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                 ui.primary.current_flags.sim_flags.opts.run_name =
                     format!("{} with {}", test.test_name, test.edits1_name);
                 ui.primary.current_flags.sim_flags.opts.savestate_every = None;
+                ui.primary.current_flags.sim_flags.opts.event_log = event_log
+                    .as_ref()
+                    .map(|log| log.with_suffix(&test.edits1_name));
 
                 apply_map_edits(
                     &mut ui.primary,
@@ -169,18 +174,10 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                             opts: SimOptions {
                                 run_name: format!("{} with {}", test.test_name, test.edits2_name),
                                 savestate_every: None,
-                                use_freeform_policy_everywhere: current_flags
-                                    .sim_flags
-                                    .opts
-                                    .use_freeform_policy_everywhere,
-                                disable_block_the_box: current_flags
-                                    .sim_flags
-                                    .opts
-                                    .disable_block_the_box,
-                                recalc_lanechanging: current_flags
-                                    .sim_flags
-                                    .opts
-                                    .recalc_lanechanging,
+                                event_log: event_log
+                                    .as_ref()
+                                    .map(|log| log.with_suffix(&test.edits2_name)),
+                                ..current_flags.sim_flags.opts.clone()
                             },
                         },
                         ..current_flags.clone()
@@ -1,4 +1,4 @@
-use crate::edit::apply_map_edits;
+use crate::edit::{apply_map_edits, without_timed_edits};
 use crate::game::{State, Transition, WizardState};
 use crate::sandbox::{GameplayMode, SandboxMode};
 use crate::ui::UI;
@@ -127,7 +127,10 @@ impl State for ChallengeSplash {
             return Transition::Push(WizardState::new(Box::new(move |wiz, ctx, ui| {
                 let mut wizard = wiz.wrap(ctx);
                 let (_, new_edits) = wizard.choose("Load which map edits?", || {
-                    Choice::from(abstutil::load_all_objects(abstutil::EDITS, &map_name))
+                    Choice::from(without_timed_edits(abstutil::load_all_objects(
+                        abstutil::EDITS,
+                        &map_name,
+                    )))
                 })?;
                 if &map_name != ui.primary.map.get_name() {
                     ui.switch_map(ctx, &map_name);
@@ -137,6 +140,7 @@ impl State for ChallengeSplash {
                 ui.primary
                     .map
                     .recalculate_pathfinding_after_edits(&mut Timer::new("finalize loaded edits"));
+                ui.primary.clear_sim();
                 Some(Transition::PopThenReplace(Box::new(SandboxMode::new(
                     ctx,
                     ui,
@@ -148,6 +152,7 @@ impl State for ChallengeSplash {
             if &self.challenge.map_name != ui.primary.map.get_name() {
                 ui.switch_map(ctx, &self.challenge.map_name);
             }
+            ui.primary.clear_sim();
             return Transition::Replace(Box::new(SandboxMode::new(
                 ctx,
                 ui,
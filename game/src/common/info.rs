@@ -99,8 +99,12 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
             }
 
             txt.add(Line(""));
-            if let Some(types) = l.get_turn_restrictions(r) {
-                txt.add(Line(format!("Turn restriction for this lane: {:?}", types)));
+            // From turn:lanes tags. Repainting lanes doesn't change these.
+            if let Some(types) = l.get_turn_restrictions() {
+                txt.add(Line("Turns allowed from this lane:"));
+                for t in types {
+                    txt.add(Line(format!("- {:?}", t)));
+                }
             }
             for (restriction, to) in &r.turn_restrictions {
                 txt.add(Line(format!(
@@ -252,7 +256,7 @@ fn info_for(id: ID, ui: &UI, ctx: &EventCtx) -> Text {
             let all_arrivals = &sim.get_analytics().bus_arrivals;
             let passengers = &sim.get_analytics().total_bus_passengers;
             for r in map.get_routes_serving_stop(id) {
-                txt.add_appended(vec![Line("- Route "), Line(&r.name).fg(name_color)]);
+                txt.add_appended(vec![Line("- Route "), Line(r.full_name()).fg(name_color)]);
                 let arrivals: Vec<(Duration, CarID)> = all_arrivals
                     .iter()
                     .filter(|(_, _, route, stop)| r.id == *route && id == *stop)
@@ -151,7 +151,7 @@ impl CommonState {
                 if let Some(r) = ui.primary.sim.bus_route_id(c) {
                     osd.append_all(vec![
                         Line(" serving "),
-                        Line(&map.get_br(r).name).fg(name_color),
+                        Line(map.get_br(r).full_name()).fg(name_color),
                     ]);
                 }
             }
@@ -161,7 +161,7 @@ impl CommonState {
                 let routes = map.get_routes_serving_stop(bs);
                 let len = routes.len();
                 for (idx, n) in routes.into_iter().enumerate() {
-                    osd.append(Line(&n.name).fg(name_color));
+                    osd.append(Line(n.full_name()).fg(name_color));
                     if idx != len - 1 {
                         osd.append(Line(", "));
                     }
@@ -2,7 +2,7 @@ use crate::edit::apply_map_edits;
 use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::ID;
 use crate::ui::UI;
-use ezgui::{hotkey, Button, Choice, Color, EventCtx, GfxCtx, Key, ScreenPt};
+use ezgui::{hotkey, Button, Choice, Color, EventCtx, GfxCtx, Key, Line, ScreenPt, Text};
 use map_model::{
     connectivity, EditCmd, IntersectionType, LaneID, LaneType, Map, PathConstraints, RoadID,
 };
@@ -263,7 +263,7 @@ impl LaneEditor {
         None
     }
 
-    pub fn draw(&self, g: &mut GfxCtx) {
+    pub fn draw(&self, g: &mut GfxCtx, ui: &UI) {
         for (idx, p) in self.brushes.iter().enumerate() {
             if self.active_idx == Some(idx) {
                 p.enabled_btn.draw(g);
@@ -271,6 +271,18 @@ impl LaneEditor {
                 p.btn.draw(g);
             }
         }
+
+        // Show the turns that turn:lanes tags allow, since repainting lanes doesn't change them.
+        if let Some(ID::Lane(l)) = ui.primary.current_selection {
+            if let Some(types) = ui.primary.map.get_l(l).get_turn_restrictions() {
+                let mut txt = Text::new();
+                txt.add(Line("Turns allowed from this lane:"));
+                for t in types {
+                    txt.add(Line(format!("- {:?}", t)));
+                }
+                g.draw_mouse_tooltip(&txt);
+            }
+        }
     }
 }
 
@@ -182,8 +182,11 @@ impl State for EditMode {
             ui.primary
                 .map
                 .recalculate_pathfinding_after_edits(&mut Timer::new("apply pending map edits"));
-            // Parking state might've changed
-            ui.primary.clear_sim();
+            // Parking state might've changed. A sim that's already running handled the edits as
+            // they happened.
+            if ui.primary.sim.is_empty() {
+                ui.primary.clear_sim();
+            }
             return Transition::Replace(Box::new(SandboxMode::new(ctx, ui, self.mode.clone())));
         }
 
@@ -314,7 +317,10 @@ impl State for EditMode {
         } else {
             let color = ui.cs.get_def("unzoomed map diffs", Color::RED);
             for l in edits.original_lts.keys().chain(&edits.reversed_lanes) {
-                g.draw_polygon(color, &ctx.map.get_parent(*l).get_thick_polygon().unwrap());
+                g.draw_polygon(
+                    color,
+                    &ctx.map.get_parent(*l).get_thick_polygon(ctx.map).unwrap(),
+                );
             }
 
             for i in &edits.changed_intersections {
@@ -327,7 +333,7 @@ impl State for EditMode {
         self.menu.draw(g);
         self.general_tools.draw(g);
         if self.mode.can_edit_lanes() {
-            self.lane_editor.draw(g);
+            self.lane_editor.draw(g, ui);
         }
     }
 }
@@ -380,7 +386,10 @@ fn load_edits(wiz: &mut Wizard, ctx: &mut EventCtx, ui: &mut UI) -> Option<Trans
     // TODO Exclude current
     let map_name = ui.primary.map.get_name().to_string();
     let (_, new_edits) = wizard.choose("Load which map edits?", || {
-        let mut list = Choice::from(abstutil::load_all_objects(abstutil::EDITS, &map_name));
+        let mut list = Choice::from(without_timed_edits(abstutil::load_all_objects(
+            abstutil::EDITS,
+            &map_name,
+        )));
         list.push(Choice::new("no_edits", MapEdits::new(map_name.clone())));
         list
     })?;
@@ -397,11 +406,26 @@ pub fn apply_map_edits(
 ) {
     edits.dirty = true;
     let mut timer = Timer::new("apply map edits");
+    if !edits.timed_commands.is_empty() {
+        println!(
+            "WARNING: Ignoring {} timed edits; only headless switches them on and off",
+            edits.timed_commands.len()
+        );
+        edits.timed_commands.clear();
+    }
 
-    let (lanes_changed, roads_changed, turns_deleted, turns_added, mut modified_intersections) =
-        bundle.map.apply_edits(edits, &mut timer);
+    let effects = bundle.map.apply_edits(edits, &mut timer);
+    // Keep a running simulation going. Agents caught on anything that changed reroute or vanish.
+    // An empty sim is just reset later, so don't pay for pathfinding after every edit.
+    if !bundle.sim.is_empty() {
+        bundle.map.recalculate_pathfinding_after_edits(&mut timer);
+        bundle
+            .sim
+            .handle_live_edits(&bundle.map, &effects, &mut timer);
+    }
+    let mut modified_intersections = effects.changed_intersections;
 
-    for l in lanes_changed {
+    for l in effects.changed_lanes {
         bundle.draw_map.lanes[l.0] = DrawLane::new(
             bundle.map.get_l(l),
             &bundle.map,
@@ -411,17 +435,18 @@ pub fn apply_map_edits(
         )
         .finish(ctx.prerender);
     }
-    for r in roads_changed {
+    // TODO We just care about contraflow roads here
+    for r in effects.changed_roads {
         bundle.draw_map.roads[r.0] =
             DrawRoad::new(bundle.map.get_r(r), &bundle.map, cs, ctx.prerender);
     }
 
     let mut lanes_of_modified_turns: BTreeSet<LaneID> = BTreeSet::new();
-    for t in turns_deleted {
+    for t in effects.deleted_turns {
         lanes_of_modified_turns.insert(t.src);
         modified_intersections.insert(t.parent);
     }
-    for t in &turns_added {
+    for t in &effects.added_turns {
         lanes_of_modified_turns.insert(t.src);
         modified_intersections.insert(t.parent);
     }
@@ -436,3 +461,10 @@ pub fn apply_map_edits(
         );
     }
 }
+
+// The game doesn't switch timed edits on and off as the sim runs, so don't offer them.
+pub fn without_timed_edits(all: Vec<(String, MapEdits)>) -> Vec<(String, MapEdits)> {
+    all.into_iter()
+        .filter(|(_, edits)| edits.timed_commands.is_empty())
+        .collect()
+}
@@ -13,7 +13,8 @@ use ezgui::{
 };
 use geom::Duration;
 use map_model::{
-    ControlTrafficSignal, EditCmd, IntersectionID, Phase, TurnGroupID, TurnPriority, TurnType,
+    ControlTrafficSignal, EditCmd, IntersectionID, Phase, PhaseType, SignalPlan, TurnGroupID,
+    TurnPriority, TurnType,
 };
 use std::collections::BTreeSet;
 use std::time::Instant;
@@ -35,6 +36,7 @@ impl TrafficSignalEditor {
                 (hotkey(Key::UpArrow), "select previous phase"),
                 (hotkey(Key::DownArrow), "select next phase"),
                 (hotkey(Key::D), "change phase duration"),
+                (hotkey(Key::A), "change phase type"),
                 (hotkey(Key::K), "move current phase up"),
                 (hotkey(Key::J), "move current phase down"),
                 (hotkey(Key::Backspace), "delete current phase"),
@@ -47,6 +49,7 @@ impl TrafficSignalEditor {
                     "convert to dedicated pedestrian scramble phase",
                 ),
                 (hotkey(Key::O), "change signal offset"),
+                (hotkey(Key::T), "manage time-of-day plans"),
                 (lctrl(Key::P), "preview changes"),
                 (hotkey(Key::Escape), "quit"),
             ],
@@ -54,7 +57,7 @@ impl TrafficSignalEditor {
         );
         TrafficSignalEditor {
             menu,
-            diagram: TrafficSignalDiagram::new(id, 0, ui, ctx),
+            diagram: TrafficSignalDiagram::new(id, None, 0, ui, ctx),
             groups: DrawTurnGroup::for_i(id, &ui.primary.map),
             group_selected: None,
         }
@@ -70,8 +73,9 @@ impl State for TrafficSignalEditor {
         self.menu.set_info(
             ctx,
             Text::from(Line(format!(
-                "Signal offset: {}",
-                orig_signal.offset.minimal_tostring()
+                "{}, signal offset: {}",
+                describe_plan(orig_signal, self.diagram.plan),
+                orig_signal.get_offset(self.diagram.plan).minimal_tostring()
             ))),
         );
         ctx.canvas.handle_event(ctx.input);
@@ -91,7 +95,8 @@ impl State for TrafficSignalEditor {
 
         if let Some(id) = self.group_selected {
             let mut new_signal = orig_signal.clone();
-            let phase = &mut new_signal.phases[self.diagram.current_phase()];
+            let phase =
+                &mut new_signal.get_phases_mut(self.diagram.plan)[self.diagram.current_phase()];
             // Just one key to toggle between the 3 states
             let next_priority = match phase.get_priority_of_group(id) {
                 TurnPriority::Banned => {
@@ -139,10 +144,16 @@ impl State for TrafficSignalEditor {
 
         if self.menu.action("change phase duration") {
             return Transition::Push(change_phase_duration(
-                orig_signal.phases[self.diagram.current_phase()].duration,
+                orig_signal.get_phases(self.diagram.plan)[self.diagram.current_phase()].duration,
+            ));
+        } else if self.menu.action("change phase type") {
+            return Transition::Push(change_phase_type(
+                orig_signal.get_phases(self.diagram.plan)[self.diagram.current_phase()].clone(),
             ));
         } else if self.menu.action("change signal offset") {
-            return Transition::Push(change_offset(orig_signal.offset));
+            return Transition::Push(change_offset(orig_signal.get_offset(self.diagram.plan)));
+        } else if self.menu.action("manage time-of-day plans") {
+            return Transition::Push(manage_plans(self.diagram.i, self.diagram.plan));
         } else if self.menu.action("choose a preset signal") {
             return Transition::Push(change_preset(self.diagram.i));
         } else if self.menu.action("reset to original") {
@@ -151,7 +162,7 @@ impl State for TrafficSignalEditor {
                     .remove(0)
                     .1;
             change_traffic_signal(new_signal, ui, ctx);
-            self.diagram = TrafficSignalDiagram::new(self.diagram.i, 0, ui, ctx);
+            self.diagram = TrafficSignalDiagram::new(self.diagram.i, None, 0, ui, ctx);
             return Transition::Keep;
         }
 
@@ -166,23 +177,44 @@ impl State for TrafficSignalEditor {
 
         if current_phase != 0 && self.menu.action("move current phase up") {
             let mut new_signal = orig_signal.clone();
-            new_signal.phases.swap(current_phase, current_phase - 1);
+            new_signal
+                .get_phases_mut(self.diagram.plan)
+                .swap(current_phase, current_phase - 1);
             change_traffic_signal(new_signal, ui, ctx);
-            self.diagram = TrafficSignalDiagram::new(self.diagram.i, current_phase - 1, ui, ctx);
-        } else if current_phase != orig_signal.phases.len() - 1
+            self.diagram = TrafficSignalDiagram::new(
+                self.diagram.i,
+                self.diagram.plan,
+                current_phase - 1,
+                ui,
+                ctx,
+            );
+        } else if current_phase != orig_signal.get_phases(self.diagram.plan).len() - 1
             && self.menu.action("move current phase down")
         {
             let mut new_signal = orig_signal.clone();
-            new_signal.phases.swap(current_phase, current_phase + 1);
+            new_signal
+                .get_phases_mut(self.diagram.plan)
+                .swap(current_phase, current_phase + 1);
             change_traffic_signal(new_signal, ui, ctx);
-            self.diagram = TrafficSignalDiagram::new(self.diagram.i, current_phase + 1, ui, ctx);
-        } else if orig_signal.phases.len() > 1 && self.menu.action("delete current phase") {
+            self.diagram = TrafficSignalDiagram::new(
+                self.diagram.i,
+                self.diagram.plan,
+                current_phase + 1,
+                ui,
+                ctx,
+            );
+        } else if orig_signal.get_phases(self.diagram.plan).len() > 1
+            && self.menu.action("delete current phase")
+        {
             let mut new_signal = orig_signal.clone();
-            new_signal.phases.remove(current_phase);
-            let num_phases = new_signal.phases.len();
+            new_signal
+                .get_phases_mut(self.diagram.plan)
+                .remove(current_phase);
+            let num_phases = new_signal.get_phases(self.diagram.plan).len();
             change_traffic_signal(new_signal, ui, ctx);
             self.diagram = TrafficSignalDiagram::new(
                 self.diagram.i,
+                self.diagram.plan,
                 if current_phase == num_phases {
                     current_phase - 1
                 } else {
@@ -193,9 +225,17 @@ impl State for TrafficSignalEditor {
             );
         } else if self.menu.action("add a new empty phase") {
             let mut new_signal = orig_signal.clone();
-            new_signal.phases.insert(current_phase + 1, Phase::new());
+            new_signal
+                .get_phases_mut(self.diagram.plan)
+                .insert(current_phase + 1, Phase::new());
             change_traffic_signal(new_signal, ui, ctx);
-            self.diagram = TrafficSignalDiagram::new(self.diagram.i, current_phase + 1, ui, ctx);
+            self.diagram = TrafficSignalDiagram::new(
+                self.diagram.i,
+                self.diagram.plan,
+                current_phase + 1,
+                ui,
+                ctx,
+            );
         } else if has_sidewalks && self.menu.action("add a new pedestrian scramble phase") {
             let mut phase = Phase::new();
             for g in orig_signal.turn_groups.values() {
@@ -204,24 +244,33 @@ impl State for TrafficSignalEditor {
                 }
             }
             let mut new_signal = orig_signal.clone();
-            new_signal.phases.insert(current_phase + 1, phase);
+            new_signal
+                .get_phases_mut(self.diagram.plan)
+                .insert(current_phase + 1, phase);
             change_traffic_signal(new_signal, ui, ctx);
-            self.diagram = TrafficSignalDiagram::new(self.diagram.i, current_phase + 1, ui, ctx);
+            self.diagram = TrafficSignalDiagram::new(
+                self.diagram.i,
+                self.diagram.plan,
+                current_phase + 1,
+                ui,
+                ctx,
+            );
         } else if has_sidewalks
             && self
                 .menu
                 .action("convert to dedicated pedestrian scramble phase")
         {
             let mut new_signal = orig_signal.clone();
-            new_signal.convert_to_ped_scramble(&ui.primary.map);
+            new_signal.convert_to_ped_scramble(self.diagram.plan, &ui.primary.map);
             change_traffic_signal(new_signal, ui, ctx);
-            self.diagram = TrafficSignalDiagram::new(self.diagram.i, 0, ui, ctx);
+            self.diagram = TrafficSignalDiagram::new(self.diagram.i, self.diagram.plan, 0, ui, ctx);
         }
 
         if self.menu.action("preview changes") {
             return Transition::PushWithMode(
                 Box::new(PreviewTrafficSignal::new(
                     self.diagram.i,
+                    self.diagram.plan,
                     current_phase,
                     ui,
                     ctx,
@@ -241,7 +290,7 @@ impl State for TrafficSignalEditor {
         }
 
         let signal = ui.primary.map.get_traffic_signal(self.diagram.i);
-        let phase = &signal.phases[self.diagram.current_phase()];
+        let phase = &signal.get_phases(self.diagram.plan)[self.diagram.current_phase()];
         let ctx = ui.draw_ctx();
         let mut batch = GeomBatch::new();
         draw_signal_phase(phase, self.diagram.i, None, &mut batch, &ctx);
@@ -327,9 +376,69 @@ fn change_phase_duration(current_duration: Duration) -> Box<dyn State> {
             let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
             let mut signal = ui.primary.map.get_traffic_signal(editor.diagram.i).clone();
             let idx = editor.diagram.current_phase();
-            signal.phases[idx].duration = Duration::seconds(new_duration as f64);
+            let phase = &mut signal.get_phases_mut(editor.diagram.plan)[idx];
+            phase.duration = Duration::seconds(new_duration as f64);
+            // The duration is the maximum green for actuated phases.
+            if let PhaseType::Actuated {
+                ref mut min_green, ..
+            } = phase.phase_type
+            {
+                *min_green = (*min_green).min(Duration::seconds(new_duration as f64));
+            }
             change_traffic_signal(signal, ui, ctx);
-            editor.diagram = TrafficSignalDiagram::new(editor.diagram.i, idx, ui, ctx);
+            editor.diagram =
+                TrafficSignalDiagram::new(editor.diagram.i, editor.diagram.plan, idx, ui, ctx);
+        })))
+    }))
+}
+
+fn change_phase_type(phase: Phase) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, _| {
+        let mut wizard = wiz.wrap(ctx);
+        let fixed = "fixed duration";
+        let actuated = "actuated by demand";
+        let phase_type = if wizard.choose_string("What type of phase?", || vec![fixed, actuated])?
+            == fixed
+        {
+            PhaseType::Fixed
+        } else {
+            let (min_green, gap) = match phase.phase_type {
+                PhaseType::Fixed => (Duration::seconds(10.0), Duration::seconds(3.0)),
+                PhaseType::Actuated { min_green, gap } => (min_green, gap),
+            };
+            let min_green = wizard.input_usize_prefilled(
+                &format!(
+                    "Minimum green time (seconds)? The maximum is the phase duration, {}",
+                    phase.duration
+                ),
+                format!("{}", min_green.inner_seconds() as usize),
+            )?;
+            let gap = wizard.input_usize_prefilled(
+                "Extend the phase if the next arrival is within how many seconds?",
+                format!("{}", gap.inner_seconds() as usize),
+            )?;
+            if min_green == 0 || gap == 0 || Duration::seconds(min_green as f64) > phase.duration {
+                return Some(Transition::Replace(msg(
+                    "Error",
+                    vec![format!(
+                        "Minimum green and gap must be positive, and the minimum green can't exceed the phase duration of {}",
+                        phase.duration
+                    )],
+                )));
+            }
+            PhaseType::Actuated {
+                min_green: Duration::seconds(min_green as f64),
+                gap: Duration::seconds(gap as f64),
+            }
+        };
+        Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+            let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+            let mut signal = ui.primary.map.get_traffic_signal(editor.diagram.i).clone();
+            let idx = editor.diagram.current_phase();
+            signal.get_phases_mut(editor.diagram.plan)[idx].phase_type = phase_type;
+            change_traffic_signal(signal, ui, ctx);
+            editor.diagram =
+                TrafficSignalDiagram::new(editor.diagram.i, editor.diagram.plan, idx, ui, ctx);
         })))
     }))
 }
@@ -343,10 +452,11 @@ fn change_offset(current_duration: Duration) -> Box<dyn State> {
         Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
             let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
             let mut signal = ui.primary.map.get_traffic_signal(editor.diagram.i).clone();
-            signal.offset = Duration::seconds(new_duration as f64);
+            signal.set_offset(editor.diagram.plan, Duration::seconds(new_duration as f64));
             change_traffic_signal(signal, ui, ctx);
             editor.diagram = TrafficSignalDiagram::new(
                 editor.diagram.i,
+                editor.diagram.plan,
                 editor.diagram.current_phase(),
                 ui,
                 ctx,
@@ -357,53 +467,144 @@ fn change_offset(current_duration: Duration) -> Box<dyn State> {
 
 fn change_preset(i: IntersectionID) -> Box<dyn State> {
     WizardState::new(Box::new(move |wiz, ctx, ui| {
-        let (_, new_signal) =
-            wiz.wrap(ctx)
-                .choose("Use which preset for this intersection?", || {
-                    Choice::from(ControlTrafficSignal::get_possible_policies(
-                        &ui.primary.map,
-                        i,
-                    ))
-                })?;
+        let (_, preset) = wiz
+            .wrap(ctx)
+            .choose("Use which preset for this plan?", || {
+                Choice::from(ControlTrafficSignal::get_possible_policies(
+                    &ui.primary.map,
+                    i,
+                ))
+            })?;
         Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
             let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
-            change_traffic_signal(new_signal, ui, ctx);
-            editor.diagram = TrafficSignalDiagram::new(editor.diagram.i, 0, ui, ctx);
+            // Only replace the phases of the plan being edited; the other plans stay.
+            let mut signal = ui.primary.map.get_traffic_signal(i).clone();
+            *signal.get_phases_mut(editor.diagram.plan) = preset.phases;
+            change_traffic_signal(signal, ui, ctx);
+            editor.diagram = TrafficSignalDiagram::new(i, editor.diagram.plan, 0, ui, ctx);
         })))
     }))
 }
 
+fn manage_plans(i: IntersectionID, current: Option<usize>) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let mut signal = ui.primary.map.get_traffic_signal(i).clone();
+
+        let add = "add a new plan, starting from a copy of this one";
+        let change_hours = "change the hours of this plan";
+        let delete = "delete this plan";
+        let mut choices = vec![format!("edit the {}", describe_plan(&signal, None))];
+        for idx in 0..signal.plans.len() {
+            choices.push(format!("edit the {}", describe_plan(&signal, Some(idx))));
+        }
+        choices.push(add.to_string());
+        if current.is_some() {
+            choices.push(change_hours.to_string());
+            choices.push(delete.to_string());
+        }
+        let choice = wizard.choose_string("Manage time-of-day plans", || choices.clone())?;
+
+        let new_plan = if choice == add || choice == change_hours {
+            let start = wizard.input_time_slider(
+                "When should the plan start?",
+                Duration::ZERO,
+                Duration::END_OF_DAY,
+            )?;
+            let end = wizard.input_time_slider(
+                "When should the plan end?",
+                start,
+                Duration::END_OF_DAY,
+            )?;
+            if choice == add {
+                let plan = SignalPlan {
+                    start,
+                    end,
+                    phases: signal.get_phases(current).clone(),
+                    offset: signal.get_offset(current),
+                };
+                signal.plans.push(plan);
+                Some(signal.plans.len() - 1)
+            } else {
+                let plan = &mut signal.plans[current.unwrap()];
+                plan.start = start;
+                plan.end = end;
+                current
+            }
+        } else if choice == delete {
+            signal.plans.remove(current.unwrap());
+            None
+        } else {
+            // Just switch to editing another plan
+            let plan = (0..signal.plans.len())
+                .find(|idx| choice == format!("edit the {}", describe_plan(&signal, Some(*idx))));
+            return Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+                let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                editor.diagram = TrafficSignalDiagram::new(i, plan, 0, ui, ctx);
+            })));
+        };
+
+        if let Err(err) = signal.clone().validate() {
+            return Some(Transition::Replace(msg("Error", vec![err])));
+        }
+        Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+            let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+            change_traffic_signal(signal, ui, ctx);
+            editor.diagram = TrafficSignalDiagram::new(i, new_plan, 0, ui, ctx);
+        })))
+    }))
+}
+
+fn describe_plan(signal: &ControlTrafficSignal, plan: Option<usize>) -> String {
+    match plan {
+        Some(idx) => format!(
+            "plan from {} to {}",
+            signal.plans[idx].start.minimal_tostring(),
+            signal.plans[idx].end.minimal_tostring()
+        ),
+        None => "default plan".to_string(),
+    }
+}
+
 fn check_for_missing_groups(
     mut signal: ControlTrafficSignal,
     diagram: &mut TrafficSignalDiagram,
     ui: &mut UI,
     ctx: &mut EventCtx,
 ) -> Transition {
-    let mut missing: BTreeSet<TurnGroupID> = signal.turn_groups.keys().cloned().collect();
-    for phase in &signal.phases {
-        for g in &phase.protected_groups {
-            missing.remove(g);
+    // Every plan has to cover every turn group.
+    let mut plans = vec![None];
+    plans.extend((0..signal.plans.len()).map(Some));
+    for plan in plans {
+        let mut missing: BTreeSet<TurnGroupID> = signal.turn_groups.keys().cloned().collect();
+        for phase in signal.get_phases(plan) {
+            for g in &phase.protected_groups {
+                missing.remove(g);
+            }
+            for g in &phase.yield_groups {
+                missing.remove(g);
+            }
         }
-        for g in &phase.yield_groups {
-            missing.remove(g);
+        if missing.is_empty() {
+            continue;
         }
+        let num_missing = missing.len();
+        let description = describe_plan(&signal, plan);
+        let mut phase = Phase::new();
+        phase.yield_groups = missing;
+        signal.get_phases_mut(plan).push(phase);
+        let last_phase = signal.get_phases(plan).len() - 1;
+        change_traffic_signal(signal, ui, ctx);
+        *diagram = TrafficSignalDiagram::new(diagram.i, plan, last_phase, ui, ctx);
+
+        return Transition::Push(msg("Error: missing turns", vec![format!("{} turns are missing from the {} of this traffic signal", num_missing, description), "They've all been added as a new last phase. Please update your changes to include them.".to_string()]));
     }
-    if missing.is_empty() {
-        let i = signal.id;
-        if let Err(err) = signal.validate() {
-            panic!("Edited traffic signal {} finalized with errors: {}", i, err);
-        }
-        return Transition::Pop;
+
+    let i = signal.id;
+    if let Err(err) = signal.validate() {
+        panic!("Edited traffic signal {} finalized with errors: {}", i, err);
     }
-    let num_missing = missing.len();
-    let mut phase = Phase::new();
-    phase.yield_groups = missing;
-    signal.phases.push(phase);
-    let last_phase = signal.phases.len() - 1;
-    change_traffic_signal(signal, ui, ctx);
-    *diagram = TrafficSignalDiagram::new(diagram.i, last_phase, ui, ctx);
-
-    Transition::Push(msg("Error: missing turns", vec![format!("{} turns are missing from this traffic signal", num_missing), "They've all been added as a new last phase. Please update your changes to include them.".to_string()]))
+    Transition::Pop
 }
 
 // TODO Show diagram, auto-sync the phase.
@@ -416,16 +617,19 @@ struct PreviewTrafficSignal {
 impl PreviewTrafficSignal {
     fn new(
         i: IntersectionID,
+        plan: Option<usize>,
         phase_idx: usize,
         ui: &mut UI,
         ctx: &EventCtx,
     ) -> PreviewTrafficSignal {
-        // Start at the current phase
+        // Start at the current phase, during the hours of the plan being edited
         let signal = ui.primary.map.get_traffic_signal(i);
         // TODO Use the offset correctly
-        let mut step = Duration::ZERO;
-        for idx in 0..phase_idx {
-            step += signal.phases[idx].duration;
+        let mut step = plan
+            .map(|idx| signal.plans[idx].start)
+            .unwrap_or(Duration::ZERO);
+        for phase in signal.get_phases(plan).iter().take(phase_idx) {
+            step += phase.duration;
         }
         ui.primary.sim.step(&ui.primary.map, step);
 
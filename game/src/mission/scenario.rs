@@ -10,7 +10,7 @@ use ezgui::{
     MenuUnderButton, ModalMenu, Text, Wizard, WrappedWizard,
 };
 use geom::{Distance, Duration, PolyLine};
-use map_model::{BuildingID, IntersectionID, Map, Neighborhood};
+use map_model::{BuildingID, IntersectionID, Map, Neighborhood, TransitRide};
 use sim::{
     BorderSpawnOverTime, DrivingGoal, OriginDestination, Scenario, SeedParkedCars, SidewalkPOI,
     SidewalkSpot, SpawnOverTime, SpawnTrip,
@@ -51,7 +51,7 @@ impl ScenarioManager {
                 }
                 SpawnTrip::UsingBike(_, ref spot, _)
                 | SpawnTrip::JustWalking(_, ref spot, _)
-                | SpawnTrip::UsingTransit(_, ref spot, _, _, _, _) => match spot.connection {
+                | SpawnTrip::UsingTransit(_, ref spot, _, _) => match spot.connection {
                     SidewalkPOI::Building(b) => {
                         trips_from_bldg.insert(b, idx);
                     }
@@ -75,7 +75,7 @@ impl ScenarioManager {
                     }
                 },
                 SpawnTrip::JustWalking(_, _, ref spot)
-                | SpawnTrip::UsingTransit(_, _, ref spot, _, _, _) => match spot.connection {
+                | SpawnTrip::UsingTransit(_, _, ref spot, _) => match spot.connection {
                     SidewalkPOI::Building(b) => {
                         trips_to_bldg.insert(b, idx);
                     }
@@ -190,6 +190,7 @@ impl State for ScenarioManager {
                 wizard: Wizard::new(),
             }));
         } else if self.menu.action("instantiate") {
+            ui.primary.clear_sim();
             return Transition::PopThenReplace(Box::new(SandboxMode::new(
                 ctx,
                 ui,
@@ -580,12 +581,19 @@ fn describe(trip: &SpawnTrip, home: OD) -> String {
             sidewalk_spot(start),
             sidewalk_spot(goal)
         ),
-        SpawnTrip::UsingTransit(depart, start, goal, route, _, _) => format!(
-            "{}: bus from {} to {} using {}",
+        SpawnTrip::UsingTransit(depart, start, goal, rides) => format!(
+            "{}: transit from {} to {} using {}",
             depart,
             sidewalk_spot(start),
             sidewalk_spot(goal),
-            route
+            rides
+                .iter()
+                .map(|ride| match ride {
+                    TransitRide::Bus(route, _, _) => route.to_string(),
+                    TransitRide::Train(line, _, _) => line.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
         ),
     }
 }
@@ -611,9 +619,7 @@ fn other_endpt(trip: &SpawnTrip, home: OD, map: &Map) -> ID {
         }
         SpawnTrip::UsingBike(_, start, goal) => (sidewalk_spot(start), driving_goal(goal)),
         SpawnTrip::JustWalking(_, start, goal) => (sidewalk_spot(start), sidewalk_spot(goal)),
-        SpawnTrip::UsingTransit(_, start, goal, _, _, _) => {
-            (sidewalk_spot(start), sidewalk_spot(goal))
-        }
+        SpawnTrip::UsingTransit(_, start, goal, _) => (sidewalk_spot(start), sidewalk_spot(goal)),
     };
     let home_id = match home {
         OD::Bldg(b) => ID::Building(b),
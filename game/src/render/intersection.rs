@@ -6,6 +6,7 @@ use crate::render::{
 use abstutil::Timer;
 use ezgui::{Color, Drawable, GeomBatch, GfxCtx, Prerender};
 use geom::{Angle, Distance, Duration, Line, PolyLine, Polygon, Pt2D, Ring, EPSILON_DIST};
+use map_model::raw::DrivingSide;
 use map_model::{
     Intersection, IntersectionID, IntersectionType, Map, Road, RoadWithStopSign, Turn, TurnID,
     TurnType, LANE_THICKNESS,
@@ -66,7 +67,7 @@ impl DrawIntersection {
                 let r = map.get_r(*i.roads.iter().next().unwrap());
                 default_geom.extend(
                     cs.get_def("incoming border node arrow", Color::PURPLE),
-                    calculate_border_arrows(i, r, timer),
+                    calculate_border_arrows(i, r, map, timer),
                 );
             }
             IntersectionType::StopSign => {
@@ -105,10 +106,12 @@ impl DrawIntersection {
             // TODO warn
             return None;
         }
-        let last_line = rightmost
-            .exact_slice(Distance::ZERO, rightmost.length() - trim_back)
-            .last_line()
-            .shift_right(1.0 * LANE_THICKNESS);
+        let last_line = map.get_driving_side().right_shift_line(
+            &rightmost
+                .exact_slice(Distance::ZERO, rightmost.length() - trim_back)
+                .last_line(),
+            1.0 * LANE_THICKNESS,
+        );
 
         let octagon = make_octagon(last_line.pt2(), Distance::meters(1.0), last_line.angle());
         let pole = Line::new(
@@ -140,14 +143,13 @@ impl Renderable for DrawIntersection {
             if self.intersection_type == IntersectionType::TrafficSignal
                 && opts.suppress_traffic_signal_details != Some(self.id)
             {
-                let signal = ctx.map.get_traffic_signal(self.id);
                 let mut maybe_redraw = self.draw_traffic_signal.borrow_mut();
                 let recalc = maybe_redraw
                     .as_ref()
                     .map(|(_, t)| *t != ctx.sim.time())
                     .unwrap_or(true);
                 if recalc {
-                    let (_, phase, t) = signal.current_phase_and_remaining_time(ctx.sim.time());
+                    let (_, phase, t) = ctx.sim.current_signal_phase(self.id, ctx.map);
                     let mut batch = GeomBatch::new();
                     draw_signal_phase(phase, self.id, Some(t), &mut batch, ctx);
                     *maybe_redraw = Some((g.prerender.upload(batch), ctx.sim.time()));
@@ -182,28 +184,48 @@ pub fn calculate_corners(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<
                 continue;
             }
 
+            let l1 = map.get_l(turn.id.src);
+            let l2 = map.get_l(turn.id.dst);
+
             // Special case for dead-ends: just thicken the geometry.
             if i.roads.len() == 1 {
-                corners.push(turn.geom.make_polygons(LANE_THICKNESS));
+                corners.push(turn.geom.make_polygons(l1.width));
                 continue;
             }
+            let side = map.get_driving_side();
 
-            let l1 = map.get_l(turn.id.src);
-            let l2 = map.get_l(turn.id.dst);
-
-            let src_line = l1.last_line().shift_left(LANE_THICKNESS / 2.0);
-            let dst_line = l2.first_line().shift_left(LANE_THICKNESS / 2.0);
+            let src_line = side.left_shift_line(&l1.last_line(), l1.width / 2.0);
+            let dst_line = side.left_shift_line(&l2.first_line(), l2.width / 2.0);
 
             let pt_maybe_in_intersection = src_line.infinite().intersection(&dst_line.infinite());
             // Now find all of the points on the intersection polygon between the two sidewalks.
-            let corner1 = l1.last_line().shift_right(LANE_THICKNESS / 2.0).pt2();
-            let corner2 = l2.first_line().shift_right(LANE_THICKNESS / 2.0).pt1();
+            let corner1 = side.right_shift_line(&l1.last_line(), l1.width / 2.0).pt2();
+            let corner2 = side
+                .right_shift_line(&l2.first_line(), l2.width / 2.0)
+                .pt1();
             // Intersection polygons are constructed in clockwise order, so do corner2 to corner1.
+            // When driving on the left, the corner goes the other way around the polygon.
             // TODO This threshold is higher than the 0.1 intersection polygons use to dedupe
             // because of jagged lane teeth from bad polyline shifting. Seemingly.
-            if let Some(mut pts_between) =
-                Pt2D::find_pts_between(&i.polygon.points(), corner2, corner1, Distance::meters(0.5))
-            {
+            let maybe_pts = match side {
+                DrivingSide::Right => Pt2D::find_pts_between(
+                    &i.polygon.points(),
+                    corner2,
+                    corner1,
+                    Distance::meters(0.5),
+                ),
+                DrivingSide::Left => Pt2D::find_pts_between(
+                    &i.polygon.points(),
+                    corner1,
+                    corner2,
+                    Distance::meters(0.5),
+                )
+                .map(|mut pts| {
+                    pts.reverse();
+                    pts
+                }),
+            };
+            if let Some(mut pts_between) = maybe_pts {
                 pts_between.push(src_line.pt2());
                 // If the intersection of the two lines isn't actually inside, then just exclude
                 // this point. Or if src_line and dst_line were parallel (actually, colinear), then
@@ -230,21 +252,31 @@ pub fn calculate_corners(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<
     corners
 }
 
-fn calculate_border_arrows(i: &Intersection, r: &Road, timer: &mut Timer) -> Vec<Polygon> {
+fn calculate_border_arrows(
+    i: &Intersection,
+    r: &Road,
+    map: &Map,
+    timer: &mut Timer,
+) -> Vec<Polygon> {
+    let side = map.get_driving_side();
     let mut result = Vec::new();
 
     // These arrows should point from the void to the road
     if !i.outgoing_lanes.is_empty() {
         // The line starts at the border and points down the road
         let (line, width) = if r.dst_i == i.id {
-            let width = (r.children_forwards.len() as f64) * LANE_THICKNESS;
+            let width = r.width(false, map);
             (
-                r.center_pts.last_line().shift_left(width / 2.0).reverse(),
+                side.left_shift_line(&r.center_pts.last_line(), width / 2.0)
+                    .reverse(),
                 width,
             )
         } else {
-            let width = (r.children_forwards.len() as f64) * LANE_THICKNESS;
-            (r.center_pts.first_line().shift_right(width / 2.0), width)
+            let width = r.width(true, map);
+            (
+                side.right_shift_line(&r.center_pts.first_line(), width / 2.0),
+                width,
+            )
         };
         result.push(
             // DEGENERATE_INTERSECTION_HALF_LENGTH is 5m...
@@ -261,14 +293,18 @@ fn calculate_border_arrows(i: &Intersection, r: &Road, timer: &mut Timer) -> Vec
     if !i.incoming_lanes.is_empty() {
         // The line starts at the border and points down the road
         let (line, width) = if r.dst_i == i.id {
-            let width = (r.children_forwards.len() as f64) * LANE_THICKNESS;
+            let width = r.width(true, map);
             (
-                r.center_pts.last_line().shift_right(width / 2.0).reverse(),
+                side.right_shift_line(&r.center_pts.last_line(), width / 2.0)
+                    .reverse(),
                 width,
             )
         } else {
-            let width = (r.children_backwards.len() as f64) * LANE_THICKNESS;
-            (r.center_pts.first_line().shift_left(width / 2.0), width)
+            let width = r.width(false, map);
+            (
+                side.left_shift_line(&r.center_pts.first_line(), width / 2.0),
+                width,
+            )
         };
         result.push(
             PolyLine::new(vec![
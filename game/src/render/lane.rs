@@ -42,7 +42,7 @@ impl DrawLane {
         timer: &mut Timer,
     ) -> AlmostDrawLane {
         let road = map.get_r(lane.parent);
-        let polygon = lane.lane_center_pts.make_polygons(LANE_THICKNESS);
+        let polygon = lane.lane_center_pts.make_polygons(lane.width);
 
         let mut draw = GeomBatch::new();
         draw.push(
@@ -76,7 +76,7 @@ impl DrawLane {
                 LaneType::Driving | LaneType::Bus => {
                     draw.extend(
                         cs.get("general road marking"),
-                        calculate_driving_lines(lane, road, timer),
+                        calculate_driving_lines(map, lane, road, timer),
                     );
                     draw.extend(
                         cs.get("general road marking"),
@@ -88,14 +88,14 @@ impl DrawLane {
                     draw.push(
                         cs.get("road center line"),
                         lane.lane_center_pts
-                            .shift_right(LANE_THICKNESS / 2.0)
+                            .shift_right(lane.width / 2.0)
                             .get(timer)
                             .make_polygons(Distance::meters(0.25)),
                     );
                     draw.push(
                         cs.get("road center line"),
                         lane.lane_center_pts
-                            .shift_left(LANE_THICKNESS / 2.0)
+                            .shift_left(lane.width / 2.0)
                             .get(timer)
                             .make_polygons(Distance::meters(0.25)),
                     );
@@ -132,9 +132,9 @@ impl Renderable for DrawLane {
     }
 
     fn get_outline(&self, map: &Map) -> Polygon {
-        map.get_l(self.id)
-            .lane_center_pts
-            .to_thick_boundary(LANE_THICKNESS, OUTLINE_THICKNESS)
+        let lane = map.get_l(self.id);
+        lane.lane_center_pts
+            .to_thick_boundary(lane.width, OUTLINE_THICKNESS)
             .unwrap_or_else(|| self.polygon.clone())
     }
 
@@ -166,9 +166,8 @@ fn calculate_sidewalk_lines(lane: &Lane) -> Vec<Polygon> {
         let (pt, angle) = lane.dist_along(dist_along);
         // Reuse perp_line. Project away an arbitrary amount
         let pt2 = pt.project_away(Distance::meters(1.0), angle);
-        result.push(
-            perp_line(Line::new(pt, pt2), LANE_THICKNESS).make_polygons(Distance::meters(0.25)),
-        );
+        result
+            .push(perp_line(Line::new(pt, pt2), lane.width).make_polygons(Distance::meters(0.25)));
         dist_along += tile_every;
     }
 
@@ -187,7 +186,7 @@ fn calculate_parking_lines(lane: &Lane) -> Vec<Polygon> {
             let perp_angle = lane_angle.rotate_degs(270.0);
             // Find the outside of the lane. Actually, shift inside a little bit, since the line will
             // have thickness, but shouldn't really intersect the adjacent line when drawn.
-            let t_pt = pt.project_away(LANE_THICKNESS * 0.4, perp_angle);
+            let t_pt = pt.project_away(lane.width * 0.4, perp_angle);
             // The perp leg
             let p1 = t_pt.project_away(leg_length, perp_angle.opposite());
             result.push(Line::new(t_pt, p1).make_polygons(Distance::meters(0.25)));
@@ -203,15 +202,20 @@ fn calculate_parking_lines(lane: &Lane) -> Vec<Polygon> {
     result
 }
 
-fn calculate_driving_lines(lane: &Lane, parent: &Road, timer: &mut Timer) -> Vec<Polygon> {
-    // The leftmost lanes don't have dashed lines.
+fn calculate_driving_lines(
+    map: &Map,
+    lane: &Lane,
+    parent: &Road,
+    timer: &mut Timer,
+) -> Vec<Polygon> {
+    // The innermost lanes don't have dashed lines.
     let (dir, idx) = parent.dir_and_offset(lane.id);
     if idx == 0 || (dir && parent.children_forwards[idx - 1].1 == LaneType::SharedLeftTurn) {
         return Vec::new();
     }
-    let lane_edge_pts = lane
-        .lane_center_pts
-        .shift_left(LANE_THICKNESS / 2.0)
+    let lane_edge_pts = map
+        .get_driving_side()
+        .left_shift(&lane.lane_center_pts, lane.width / 2.0)
         .get(timer);
     dashed_lines(
         &lane_edge_pts,
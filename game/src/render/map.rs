@@ -18,13 +18,15 @@ use map_model::{
     RoadID, Traversable, LANE_THICKNESS,
 };
 use sim::{
-    AgentMetadata, CarStatus, DrawCarInput, DrawPedestrianInput, GetDrawAgents, UnzoomedAgent,
-    VehicleType,
+    AgentMetadata, CarStatus, DrawCarInput, DrawPedestrianInput, DrawTrainInput, GetDrawAgents,
+    UnzoomedAgent, VehicleType,
 };
 use std::borrow::Borrow;
 use std::cell::RefCell;
 use std::collections::HashMap;
 
+const TRAIN_WIDTH: Distance = Distance::const_meters(2.65);
+
 pub struct DrawMap {
     pub roads: Vec<DrawRoad>,
     pub lanes: Vec<DrawLane>,
@@ -42,6 +44,7 @@ pub struct DrawMap {
     pub draw_all_unzoomed_intersections: Drawable,
     pub draw_all_buildings: Drawable,
     pub draw_all_areas: Drawable,
+    pub draw_all_rail: Drawable,
 
     quadtree: QuadTree<ID>,
 }
@@ -68,7 +71,7 @@ impl DrawMap {
         for r in road_refs {
             all_roads.push(
                 osm_rank_to_color(cs, r.get_rank()),
-                r.get_thick_polygon().get(timer),
+                r.get_thick_polygon(map).get(timer),
             );
             if false {
                 all_roads.push(
@@ -156,13 +159,18 @@ impl DrawMap {
 
             let mut closest: FindClosest<DirectedRoadID> = FindClosest::new(&map.get_bounds());
             for r in map.all_roads().iter() {
+                let side = map.get_driving_side();
                 closest.add(
                     r.id.forwards(),
-                    r.center_pts.shift_right(LANE_THICKNESS).get(timer).points(),
+                    side.right_shift(&r.center_pts, LANE_THICKNESS)
+                        .get(timer)
+                        .points(),
                 );
                 closest.add(
                     r.id.backwards(),
-                    r.center_pts.shift_left(LANE_THICKNESS).get(timer).points(),
+                    side.left_shift(&r.center_pts, LANE_THICKNESS)
+                        .get(timer)
+                        .points(),
                 );
             }
 
@@ -199,6 +207,24 @@ impl DrawMap {
         let draw_all_areas = ctx.prerender.upload(all_areas);
         timer.stop("upload all areas");
 
+        // Rail lines don't follow any roads, so they're not part of anything else.
+        let mut all_rail = GeomBatch::new();
+        for line in map.get_all_rail_lines() {
+            for track in &line.tracks {
+                all_rail.push(
+                    cs.get_def("rail track", Color::grey(0.3)),
+                    track.geom.make_polygons(Distance::meters(3.0)),
+                );
+            }
+        }
+        for station in map.all_stations() {
+            all_rail.push(
+                cs.get_def("rail station", Color::PURPLE),
+                Circle::new(station.pt, Distance::meters(6.0)).to_polygon(),
+            );
+        }
+        let draw_all_rail = ctx.prerender.upload(all_rail);
+
         let boundary_polygon = ctx.prerender.upload_borrowed(vec![(
             cs.get_def("map background", Color::grey(0.87)),
             map.get_boundary_polygon(),
@@ -246,6 +272,7 @@ impl DrawMap {
             draw_all_unzoomed_intersections,
             draw_all_buildings,
             draw_all_areas,
+            draw_all_rail,
 
             agents: RefCell::new(AgentCache {
                 time: None,
@@ -329,6 +356,26 @@ impl AgentCache {
         self.agents_per_on.insert(on, agents);
     }
 
+    // Trains aren't on any lane, so they're not in the quadtree. There aren't many, so just draw
+    // all of them.
+    pub fn draw_zoomed_trains(
+        &self,
+        source: &dyn GetDrawAgents,
+        map: &Map,
+        acs: AgentColorScheme,
+        cs: &ColorScheme,
+        g: &mut GfxCtx,
+    ) {
+        let mut batch = GeomBatch::new();
+        for train in source.get_all_draw_trains(map) {
+            batch.push(
+                acs.zoomed_color_train(&train, cs),
+                train.body.make_polygons(TRAIN_WIDTH),
+            );
+        }
+        batch.draw(g);
+    }
+
     pub fn invalidate_cache(&mut self) {
         self.time = None;
         self.agents_per_on.clear();
@@ -361,6 +408,16 @@ impl AgentCache {
                     .to_polygon(),
             );
         }
+        for train in source.get_all_draw_trains(map) {
+            batch.push(
+                acs.unzoomed_color_train(&train, cs),
+                Circle::new(
+                    train.body.last_pt(),
+                    Distance::meters(10.0) / g.canvas.cam_zoom,
+                )
+                .to_polygon(),
+            );
+        }
 
         let draw = g.upload(batch);
         g.redraw(&draw);
@@ -407,6 +464,22 @@ impl AgentColorScheme {
         }
     }
 
+    pub fn unzoomed_color_train(self, train: &DrawTrainInput, cs: &ColorScheme) -> Color {
+        match self {
+            AgentColorScheme::VehicleTypes => {
+                cs.get_def("unzoomed train", Color::PURPLE.alpha(0.5))
+            }
+            _ => self.by_metadata(&train.metadata),
+        }
+    }
+
+    pub fn zoomed_color_train(self, train: &DrawTrainInput, cs: &ColorScheme) -> Color {
+        match self {
+            AgentColorScheme::VehicleTypes => cs.get_def("train", Color::PURPLE),
+            _ => self.by_metadata(&train.metadata),
+        }
+    }
+
     pub fn unzoomed_radius(self, agent: &UnzoomedAgent) -> Distance {
         if self == AgentColorScheme::Delay
             && agent.metadata.occupying_intersection
@@ -481,6 +554,7 @@ impl AgentColorScheme {
                     ("car", cs.get("unzoomed car")),
                     ("bike", cs.get("unzoomed bike")),
                     ("bus", cs.get("unzoomed bus")),
+                    ("train", cs.get("unzoomed train")),
                     ("pedestrian", cs.get("unzoomed pedestrian")),
                 ],
             ),
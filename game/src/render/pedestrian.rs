@@ -204,10 +204,12 @@ impl DrawPedCrowd {
         let pl_shifted = match input.location {
             PedCrowdLocation::Sidewalk(on, contraflow) => {
                 let pl_slice = on.exact_slice(input.low, input.high, map);
+                // Keep to the same side that vehicles drive on
+                let side = map.get_driving_side();
                 if contraflow {
-                    pl_slice.shift_left(LANE_THICKNESS / 4.0).unwrap()
+                    side.left_shift(&pl_slice, LANE_THICKNESS / 4.0).unwrap()
                 } else {
-                    pl_slice.shift_right(LANE_THICKNESS / 4.0).unwrap()
+                    side.right_shift(&pl_slice, LANE_THICKNESS / 4.0).unwrap()
                 }
             }
             PedCrowdLocation::FrontPath(b) => map
@@ -5,7 +5,7 @@ use ezgui::{
     ScreenPt, Scroller, Text,
 };
 use geom::{Circle, Distance, Duration, Line, Polygon, Pt2D};
-use map_model::{IntersectionID, Phase, TurnPriority, TurnType, LANE_THICKNESS};
+use map_model::{IntersectionID, Phase, PhaseType, TurnPriority, TurnType, LANE_THICKNESS};
 
 // Only draws a box when time_left is present
 pub fn draw_signal_phase(
@@ -269,6 +269,8 @@ const PERCENT_WIDTH: f64 = 0.15;
 
 pub struct TrafficSignalDiagram {
     pub i: IntersectionID,
+    // None is the default plan
+    pub plan: Option<usize>,
     labels: Vec<Text>,
     top_left: Pt2D,
     zoom: f64,
@@ -281,6 +283,7 @@ pub struct TrafficSignalDiagram {
 impl TrafficSignalDiagram {
     pub fn new(
         i: IntersectionID,
+        plan: Option<usize>,
         current_phase: usize,
         ui: &UI,
         ctx: &EventCtx,
@@ -294,7 +297,7 @@ impl TrafficSignalDiagram {
                 b.max_y - b.min_y,
             )
         };
-        let phases = &ui.primary.map.get_traffic_signal(i).phases;
+        let phases = ui.primary.map.get_traffic_signal(i).get_phases(plan);
 
         let zoom = ctx.canvas.window_width * PERCENT_WIDTH / intersection_width;
         let item_dims = ScreenDims::new(
@@ -313,21 +316,29 @@ impl TrafficSignalDiagram {
         );
         let mut labels = Vec::new();
         for (idx, phase) in phases.iter().enumerate() {
-            labels.push(Text::from(Line(format!(
-                "Phase {}: {}",
-                idx + 1,
-                phase.duration.minimal_tostring()
-            ))));
+            labels.push(Text::from(Line(match phase.phase_type {
+                PhaseType::Fixed => {
+                    format!("Phase {}: {}", idx + 1, phase.duration.minimal_tostring())
+                }
+                PhaseType::Actuated { min_green, gap } => format!(
+                    "Phase {}: actuated, {} to {}, gap {}",
+                    idx + 1,
+                    min_green.minimal_tostring(),
+                    phase.duration.minimal_tostring(),
+                    gap.minimal_tostring()
+                ),
+            })));
         }
 
         TrafficSignalDiagram {
             i,
+            plan,
             labels,
             top_left,
             zoom,
             scroller,
 
-            _new_scroller: make_new_scroller(i, &ui.draw_ctx(), ctx),
+            _new_scroller: make_new_scroller(i, plan, &ui.draw_ctx(), ctx),
         }
     }
 
@@ -353,7 +364,7 @@ impl TrafficSignalDiagram {
     }
 
     pub fn draw(&self, g: &mut GfxCtx, ctx: &DrawCtx) {
-        let phases = &ctx.map.get_traffic_signal(self.i).phases;
+        let phases = ctx.map.get_traffic_signal(self.i).get_phases(self.plan);
 
         for (idx, rect) in self.scroller.draw(g) {
             g.fork(self.top_left, ScreenPt::new(rect.x1, rect.y1), self.zoom);
@@ -370,7 +381,12 @@ impl TrafficSignalDiagram {
     }
 }
 
-fn make_new_scroller(i: IntersectionID, draw_ctx: &DrawCtx, ctx: &EventCtx) -> NewScroller {
+fn make_new_scroller(
+    i: IntersectionID,
+    plan: Option<usize>,
+    draw_ctx: &DrawCtx,
+    ctx: &EventCtx,
+) -> NewScroller {
     let zoom = 15.0;
 
     // TODO Nicer API would be passing in a list of (GeomBatch, MultiText)s each starting at the
@@ -382,7 +398,13 @@ fn make_new_scroller(i: IntersectionID, draw_ctx: &DrawCtx, ctx: &EventCtx) -> N
     // but this is close enough.
     let bounds = draw_ctx.map.get_i(i).polygon.get_bounds();
     let mut y_offset = 0.0;
-    for (idx, phase) in draw_ctx.map.get_traffic_signal(i).phases.iter().enumerate() {
+    for (idx, phase) in draw_ctx
+        .map
+        .get_traffic_signal(i)
+        .get_phases(plan)
+        .iter()
+        .enumerate()
+    {
         let mut batch = GeomBatch::new();
         draw_signal_phase(phase, i, None, &mut batch, draw_ctx);
         for (color, poly) in batch.consume() {
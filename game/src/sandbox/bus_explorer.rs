@@ -5,13 +5,16 @@ use crate::ui::UI;
 use ezgui::{
     Choice, Color, EventCtx, GeomBatch, GfxCtx, Key, Line, MenuUnderButton, Text, WarpingItemSlider,
 };
-use geom::{Circle, Distance, Pt2D};
+use geom::{Circle, Distance, Duration, Pt2D};
 use map_model::{BusRoute, BusRouteID, BusStopID, PathConstraints, PathRequest, PathStep};
+use sim::BusStopStats;
 
 pub struct ShowBusRoute {
     colorer: RoadColorer,
     labels: Vec<(Text, Pt2D)>,
     bus_locations: Vec<Pt2D>,
+    // Colored by how well buses are keeping to the schedule there
+    stop_adherence: Vec<(Pt2D, Color)>,
 }
 
 pub struct BusRouteExplorer {
@@ -28,9 +31,18 @@ impl ShowBusRoute {
             bus_locations.push(pt);
         }
 
-        let mut txt = Text::prompt(&route.name);
+        let mut txt = Text::prompt(&route.full_name());
         txt.add(Line(format!("{} buses", bus_locations.len())));
-        let mut colorer = RoadColorerBuilder::new(txt, vec![("route", Color::RED)]);
+        let mut colorer = RoadColorerBuilder::new(
+            txt,
+            vec![
+                ("route", Color::RED),
+                ("stop: early", Color::CYAN),
+                ("stop: on time", Color::GREEN),
+                ("stop: late", Color::YELLOW),
+                ("stop: very late", Color::PURPLE),
+            ],
+        );
         for (stop1, stop2) in
             route
                 .stops
@@ -66,10 +78,32 @@ impl ShowBusRoute {
             ));
         }
 
+        let mut stop_adherence = Vec::new();
+        for (bs, stats) in
+            ui.primary
+                .sim
+                .get_analytics()
+                .bus_stop_stats(ui.primary.sim.time(), route.id, map)
+        {
+            if let Some(dt) = stats.avg_schedule_deviation() {
+                let color = if dt < -Duration::minutes(1) {
+                    Color::CYAN
+                } else if dt <= Duration::minutes(1) {
+                    Color::GREEN
+                } else if dt <= Duration::minutes(5) {
+                    Color::YELLOW
+                } else {
+                    Color::PURPLE
+                };
+                stop_adherence.push((map.get_bs(bs).sidewalk_pos.pt(map), color));
+            }
+        }
+
         ShowBusRoute {
             colorer: colorer.build(ctx, map),
             labels,
             bus_locations,
+            stop_adherence,
         }
     }
 
@@ -81,6 +115,9 @@ impl ShowBusRoute {
 
         let mut batch = GeomBatch::new();
         let radius = Distance::meters(20.0) / g.canvas.cam_zoom;
+        for (pt, color) in &self.stop_adherence {
+            batch.push(*color, Circle::new(*pt, radius).to_polygon());
+        }
         for pt in &self.bus_locations {
             batch.push(Color::BLUE, Circle::new(*pt, radius).to_polygon());
         }
@@ -124,17 +161,26 @@ impl BusRouteExplorer {
         ui: &UI,
         ctx: &mut EventCtx,
     ) -> BusRouteExplorer {
+        let mut all_stats = ui.primary.sim.get_analytics().bus_stop_stats(
+            ui.primary.sim.time(),
+            route.id,
+            &ui.primary.map,
+        );
         let stops: Vec<(Pt2D, BusStopID, Text)> = route
             .stops
             .iter()
             .map(|bs| {
                 let stop = ui.primary.map.get_bs(*bs);
-                (stop.sidewalk_pos.pt(&ui.primary.map), stop.id, Text::new())
+                let txt = all_stats
+                    .remove(bs)
+                    .map(|stats| describe_stats(&stats))
+                    .unwrap_or_else(Text::new);
+                (stop.sidewalk_pos.pt(&ui.primary.map), stop.id, txt)
             })
             .collect();
         let mut slider = WarpingItemSlider::new(
             stops,
-            &format!("Bus Route Explorer for {}", route.name),
+            &format!("Bus Route Explorer for {}", route.full_name()),
             "stop",
             ctx,
         );
@@ -178,6 +224,51 @@ impl State for BusRouteExplorer {
     }
 }
 
+fn describe_stats(stats: &BusStopStats) -> Text {
+    let mut txt = Text::new();
+    if let Some(dt) = stats.avg_schedule_deviation() {
+        if dt < Duration::ZERO {
+            txt.add(Line(format!(
+                "Buses are {} early on average",
+                (-dt).minimal_tostring()
+            )));
+        } else {
+            txt.add(Line(format!(
+                "Buses are {} late on average",
+                dt.minimal_tostring()
+            )));
+        }
+    }
+    if let Some(cv) = stats.headway_irregularity() {
+        // Bunching makes the headways much less regular
+        txt.add(Line(format!(
+            "{} arrivals, headway variation {:.2}",
+            stats.headways.len() + 1,
+            cv
+        )));
+    }
+    if let Some(dt) = stats.avg_dwell_time() {
+        txt.add(Line(format!(
+            "Buses wait here {} on average",
+            dt.minimal_tostring()
+        )));
+    }
+    if let Some(dt) = stats.avg_passenger_wait() {
+        txt.add(Line(format!(
+            "{} passengers waited {} on average",
+            stats.passenger_waits.len(),
+            dt.minimal_tostring()
+        )));
+    }
+    if !stats.denied_boardings.is_empty() {
+        txt.add(Line(format!(
+            "Full buses left passengers behind {} times",
+            stats.denied_boardings.len()
+        )));
+    }
+    txt
+}
+
 pub struct BusRoutePicker;
 impl BusRoutePicker {
     pub fn new(ui: &UI, menu: &mut MenuUnderButton) -> Option<Box<dyn State>> {
@@ -199,9 +290,9 @@ impl BusRoutePicker {
 fn make_bus_route_picker(routes: Vec<BusRouteID>, start: Option<BusStopID>) -> Box<dyn State> {
     WizardState::new(Box::new(move |wiz, ctx, ui| {
         let (_, id) = wiz.wrap(ctx).choose("Explore which bus route?", || {
-            let mut choices: Vec<(&String, BusRouteID)> = routes
+            let mut choices: Vec<(String, BusRouteID)> = routes
                 .iter()
-                .map(|id| (&ui.primary.map.get_br(*id).name, *id))
+                .map(|id| (ui.primary.map.get_br(*id).full_name(), *id))
                 .collect();
             // TODO Sort first by length, then lexicographically
             choices.sort_by_key(|(name, _)| name.to_string());
@@ -1,12 +1,17 @@
-use crate::game::{msg, Transition};
+use crate::game::{msg, State, Transition};
 use crate::sandbox::gameplay::faster_trips::faster_trips_panel;
 use crate::sandbox::gameplay::{manage_overlays, GameplayState};
 use crate::sandbox::overlays::Overlays;
 use crate::ui::UI;
+use abstutil::Timer;
 use ezgui::{hotkey, EventCtx, Key, ModalMenu};
 use geom::Duration;
+use map_model::{EditCmd, MapEdits, Phase};
 use sim::TripMode;
 
+// The headless signal timing optimizer saves its suggestions here by default.
+const OPTIMIZED_SIGNALS: &str = "optimized_signals";
+
 pub struct FixTrafficSignals {
     time: Duration,
 }
@@ -18,6 +23,7 @@ impl FixTrafficSignals {
                 "Fix traffic signals",
                 vec![
                     (hotkey(Key::F), "find slowest traffic signals"),
+                    (hotkey(Key::S), "show the optimizer's suggestions"),
                     (hotkey(Key::H), "help"),
                 ],
                 ctx,
@@ -60,6 +66,10 @@ impl GameplayState for FixTrafficSignals {
             menu.set_info(ctx, faster_trips_panel(TripMode::Drive, ui));
         }
 
+        if menu.action("show the optimizer's suggestions") {
+            return Some(Transition::Push(optimizer_suggestions(ui)));
+        }
+
         if menu.action("help") {
             return Some(Transition::Push(msg(
                 "Help",
@@ -71,3 +81,47 @@ impl GameplayState for FixTrafficSignals {
         None
     }
 }
+
+fn optimizer_suggestions(ui: &UI) -> Box<dyn State> {
+    let map = &ui.primary.map;
+    let path = abstutil::path1_json(map.get_name(), abstutil::EDITS, OPTIMIZED_SIGNALS);
+    if !std::path::Path::new(&path).exists() {
+        return msg(
+            "No suggestions",
+            vec![format!(
+                "Run the headless signal timing optimizer first. It saves its suggestions as the {} edits.",
+                OPTIMIZED_SIGNALS
+            )],
+        );
+    }
+
+    let edits = MapEdits::load(map.get_name(), OPTIMIZED_SIGNALS, &mut Timer::throwaway());
+    let mut lines = Vec::new();
+    for cmd in &edits.commands {
+        if let EditCmd::ChangeTrafficSignal(ref signal) = cmd {
+            lines.push(format!(
+                "{}: phases of {} (currently {})",
+                signal.id,
+                describe_durations(&signal.phases),
+                describe_durations(&map.get_traffic_signal(signal.id).phases)
+            ));
+        }
+    }
+    if lines.is_empty() {
+        lines.push("The optimizer couldn't improve any traffic signals.".to_string());
+    } else {
+        lines.push(format!(
+            "Load the {} edits to try all of these.",
+            OPTIMIZED_SIGNALS
+        ));
+    }
+    msg("Suggestions from the optimizer", lines)
+}
+
+fn describe_durations(phases: &Vec<Phase>) -> String {
+    phases
+        .iter()
+        .map(|p| p.duration.minimal_tostring())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -111,7 +111,11 @@ impl GameplayRunner {
             GameplayMode::FasterTrips(trip_mode) => faster_trips::FasterTrips::new(trip_mode, ctx),
             GameplayMode::FixTrafficSignals => fix_traffic_signals::FixTrafficSignals::new(ctx),
         };
+        // Coming back from edit mode, the sim keeps running.
         ctx.loading_screen("instantiate scenario", |_, timer| {
+            if !ui.primary.sim.is_empty() {
+                return;
+            }
             if let Some(scenario) = mode.scenario(ui, timer) {
                 scenario.instantiate(
                     &mut ui.primary.sim,
@@ -160,6 +164,7 @@ fn change_scenario(wiz: &mut Wizard, ctx: &mut EventCtx, ui: &mut UI) -> Option<
             list.push("just buses".to_string());
             list
         })?;
+    ui.primary.clear_sim();
     Some(Transition::PopThenReplace(Box::new(SandboxMode::new(
         ctx,
         ui,
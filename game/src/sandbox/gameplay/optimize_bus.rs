@@ -138,6 +138,18 @@ fn bus_route_panel(id: BusRouteID, ui: &UI, stat: Statistic) -> Text {
 
     let route = ui.primary.map.get_br(id);
     let mut txt = Text::new();
+    let denied = ui
+        .primary
+        .sim
+        .get_analytics()
+        .bus_denied_boardings
+        .iter()
+        .filter(|(t, r, _)| *t <= ui.primary.sim.time() && *r == id)
+        .count();
+    txt.add(Line(format!(
+        "{} passengers left behind by full buses",
+        denied
+    )));
     txt.add(Line(format!("{} delay between stops", stat)));
     for idx1 in 0..route.stops.len() {
         let idx2 = if idx1 == route.stops.len() - 1 {
@@ -388,17 +388,13 @@ fn schedule_trip(
                 }
             };
             let ped_speed = Scenario::rand_ped_speed(rng);
-            if let Some((stop1, stop2, route)) =
-                map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
-            {
+            if let Some(rides) = map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos) {
                 sim.schedule_trip(
                     sim.time(),
                     TripSpec::UsingTransit {
                         start,
                         goal,
-                        route,
-                        stop1,
-                        stop2,
+                        rides,
                         ped_speed,
                     },
                     map,
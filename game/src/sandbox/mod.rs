@@ -320,7 +320,7 @@ impl State for SandboxMode {
         }
 
         if self.menu.action("edit mode") {
-            ui.primary.clear_sim();
+            // Edits are applied to the running sim live.
             return Transition::Replace(Box::new(EditMode::new(ctx, self.gameplay.mode.clone())));
         }
         if self.speed.is_paused() {
@@ -159,6 +159,7 @@ impl UI {
             if layers.show_buildings {
                 g.redraw(&self.primary.draw_map.draw_all_buildings);
             }
+            g.redraw(&self.primary.draw_map.draw_all_rail);
 
             if layers.show_extra_shapes {
                 for es in &self.primary.draw_map.extra_shapes {
@@ -229,6 +230,9 @@ impl UI {
                     }
                 }
             }
+            // Grade-separated, so draw on top of everything else
+            g.redraw(&self.primary.draw_map.draw_all_rail);
+            cache.draw_zoomed_trains(source, &self.primary.map, self.agent_cs, &self.cs, g);
         }
 
         if let Some(i) = sample_intersection {
@@ -1,6 +1,6 @@
 use crate::{trim_f64, Duration, Speed};
 use serde_derive::{Deserialize, Serialize};
-use std::{cmp, f64, fmt, ops};
+use std::{cmp, f64, fmt, iter, ops};
 
 // In meters. Can be negative.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -34,6 +34,10 @@ impl Distance {
         Distance::meters(0.0254 * value)
     }
 
+    pub fn feet(value: f64) -> Distance {
+        Distance::meters(0.3048 * value)
+    }
+
     pub fn miles(value: f64) -> Distance {
         Distance::meters(1609.34 * value)
     }
@@ -75,6 +79,12 @@ impl ops::Add for Distance {
     }
 }
 
+impl iter::Sum for Distance {
+    fn sum<I: Iterator<Item = Distance>>(iter: I) -> Distance {
+        iter.fold(Distance::ZERO, |a, b| a + b)
+    }
+}
+
 impl ops::AddAssign for Distance {
     fn add_assign(&mut self, other: Distance) {
         *self = *self + other;
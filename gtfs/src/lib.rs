@@ -1,84 +1,471 @@
 use abstutil::elapsed_seconds;
 use failure::Error;
-use geom::LonLat;
-use itertools::Itertools;
+use geom::{Duration, LonLat};
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
+use std::path::Path;
 use std::time::Instant;
 
+pub struct GTFS {
+    pub routes: Vec<Route>,
+    // Keyed by service_id. Empty if the feed has no calendar.txt.
+    pub services: BTreeMap<String, Service>,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Route {
+    pub id: String,
     pub name: String,
-    pub stops: Vec<LonLat>,
+    pub route_type: RouteType,
+    // Usually one or two. Forwards (direction_id 0) comes first.
+    pub directions: Vec<DirectedRoute>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub enum RouteType {
+    // Includes streetcars
+    LightRail,
+    Subway,
+    Rail,
+    Bus,
+    // Ferries, cable cars, etc
+    Other(usize),
+}
+
+impl RouteType {
+    fn new(code: usize) -> RouteType {
+        match code {
+            0 => RouteType::LightRail,
+            1 => RouteType::Subway,
+            2 => RouteType::Rail,
+            3 => RouteType::Bus,
+            x => RouteType::Other(x),
+        }
+    }
+
+    // Runs on its own tracks, not on roads.
+    pub fn is_rail(self) -> bool {
+        match self {
+            RouteType::LightRail | RouteType::Subway | RouteType::Rail => true,
+            RouteType::Bus | RouteType::Other(_) => false,
+        }
+    }
 }
 
-pub fn load(dir_path: &str) -> Result<Vec<Route>, Error> {
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DirectedRoute {
+    pub forwards: bool,
+    // The stops of the trip visiting the most stops in this direction. Other trips might only serve
+    // part of this.
+    pub stops: Vec<Stop>,
+    // From shapes.txt for that same trip. Empty if the feed doesn't have shapes.
+    pub shape: Vec<LonLat>,
+    pub trips: Vec<Trip>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Stop {
+    pub id: String,
+    pub name: String,
+    pub pt: LonLat,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Trip {
+    pub id: String,
+    pub service_id: String,
+    pub stop_times: Vec<StopTime>,
+    // If non-empty, stop_times is just a template, repeated every headway.
+    pub frequencies: Vec<Frequency>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct StopTime {
+    pub stop: Stop,
+    // Relative to midnight at the start of the service day. Can exceed 24 hours.
+    pub arrival: Duration,
+    pub departure: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Frequency {
+    pub start: Duration,
+    pub end: Duration,
+    pub headway: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Service {
+    // Indexed by Weekday
+    pub days: [bool; 7],
+    // YYYYMMDD
+    pub start_date: String,
+    pub end_date: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl GTFS {
+    // Drop all trips that don't run on this day of the week. Trips whose service isn't in
+    // calendar.txt are kept.
+    pub fn only_weekday(self, day: Weekday) -> Vec<Route> {
+        let services = self.services;
+        let mut routes = self.routes;
+        for route in &mut routes {
+            for dir in &mut route.directions {
+                dir.trips.retain(|trip| {
+                    services
+                        .get(&trip.service_id)
+                        .map(|s| s.days[day as usize])
+                        .unwrap_or(true)
+                });
+            }
+        }
+        routes
+    }
+}
+
+impl Route {
+    // The stops of every direction, one after the other.
+    pub fn all_stops(&self) -> Vec<&Stop> {
+        self.directions
+            .iter()
+            .flat_map(|d| d.stops.iter())
+            .collect()
+    }
+}
+
+impl Trip {
+    // Every time this trip actually runs. The stop times are exact, unless the trip has
+    // frequencies.
+    pub fn runs(&self) -> Vec<Vec<StopTime>> {
+        if self.frequencies.is_empty() {
+            return vec![self.stop_times.clone()];
+        }
+        let template_start = self.stop_times[0].departure;
+        let mut runs = Vec::new();
+        for freq in &self.frequencies {
+            let mut start = freq.start;
+            while start < freq.end {
+                runs.push(
+                    self.stop_times
+                        .iter()
+                        .map(|st| StopTime {
+                            stop: st.stop.clone(),
+                            arrival: st.arrival - template_start + start,
+                            departure: st.departure - template_start + start,
+                        })
+                        .collect(),
+                );
+                start += freq.headway;
+            }
+        }
+        runs
+    }
+}
+
+#[derive(Deserialize)]
+struct RouteRecord {
+    route_id: String,
+    route_short_name: Option<String>,
+    route_long_name: Option<String>,
+    route_type: usize,
+}
+
+#[derive(Deserialize)]
+struct StopRecord {
+    stop_id: String,
+    stop_name: Option<String>,
+    stop_lat: f64,
+    stop_lon: f64,
+}
+
+#[derive(Deserialize)]
+struct CalendarRecord {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+#[derive(Deserialize)]
+struct TripRecord {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    direction_id: Option<u8>,
+    shape_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StopTimeRecord {
+    trip_id: String,
+    arrival_time: Option<String>,
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: usize,
+}
+
+#[derive(Deserialize)]
+struct FrequencyRecord {
+    trip_id: String,
+    start_time: String,
+    end_time: String,
+    headway_secs: f64,
+}
+
+#[derive(Deserialize)]
+struct ShapeRecord {
+    shape_id: String,
+    shape_pt_lat: f64,
+    shape_pt_lon: f64,
+    shape_pt_sequence: usize,
+}
+
+pub fn load(dir_path: &str) -> Result<GTFS, Error> {
     println!("Loading GTFS from {}", dir_path);
     let timer = Instant::now();
 
-    let mut route_id_to_name: HashMap<String, String> = HashMap::new();
-    for rec in csv::Reader::from_reader(File::open(format!("{}/routes.txt", dir_path))?).records() {
-        let rec = rec?;
-        route_id_to_name.insert(rec[0].to_string(), rec[2].to_string());
+    // Keyed by route ID
+    let mut route_info: BTreeMap<String, (String, RouteType)> = BTreeMap::new();
+    for rec in read_records::<RouteRecord>(dir_path, "routes.txt")? {
+        let name = match (rec.route_short_name, rec.route_long_name) {
+            (Some(name), _) | (None, Some(name)) => name,
+            (None, None) => rec.route_id.clone(),
+        };
+        route_info.insert(rec.route_id, (name, RouteType::new(rec.route_type)));
     }
 
-    let mut stop_id_to_pt: HashMap<String, LonLat> = HashMap::new();
-    for rec in csv::Reader::from_reader(File::open(format!("{}/stops.txt", dir_path))?).records() {
-        let rec = rec?;
-        let lon: f64 = rec[5].parse()?;
-        let lat: f64 = rec[4].parse()?;
-        stop_id_to_pt.insert(rec[0].to_string(), LonLat::new(lon, lat));
+    let mut stops: HashMap<String, Stop> = HashMap::new();
+    for rec in read_records::<StopRecord>(dir_path, "stops.txt")? {
+        stops.insert(
+            rec.stop_id.clone(),
+            Stop {
+                id: rec.stop_id,
+                name: rec.stop_name.unwrap_or_else(String::new),
+                pt: LonLat::new(rec.stop_lon, rec.stop_lat),
+            },
+        );
     }
 
-    let mut trip_id_to_route_id_and_direction: HashMap<String, (String, bool)> = HashMap::new();
-    for rec in csv::Reader::from_reader(File::open(format!("{}/trips.txt", dir_path))?).records() {
-        let rec = rec?;
-        trip_id_to_route_id_and_direction
-            .insert(rec[2].to_string(), (rec[0].to_string(), &rec[5] == "0"));
+    let mut services: BTreeMap<String, Service> = BTreeMap::new();
+    for rec in read_optional_records::<CalendarRecord>(dir_path, "calendar.txt")? {
+        services.insert(
+            rec.service_id,
+            Service {
+                days: [
+                    rec.monday == 1,
+                    rec.tuesday == 1,
+                    rec.wednesday == 1,
+                    rec.thursday == 1,
+                    rec.friday == 1,
+                    rec.saturday == 1,
+                    rec.sunday == 1,
+                ],
+                start_date: rec.start_date,
+                end_date: rec.end_date,
+            },
+        );
     }
 
-    // Each (directed) route has many trips. Ignore all but the first and assume the list of stops
-    // is the same. Also assume that records with the same trip are contiguous and that
-    // stop_sequence is monotonic.
-    let mut directed_routes: HashMap<(String, bool), Vec<LonLat>> = HashMap::new();
-    let mut reader = csv::Reader::from_reader(File::open(format!("{}/stop_times.txt", dir_path))?);
-    for (key, group) in reader
-        .records()
-        .group_by(|rec| rec.as_ref().unwrap()[0].to_string())
-        .into_iter()
-    {
-        let (route_id, forwards) = trip_id_to_route_id_and_direction[&key].clone();
-        if directed_routes.contains_key(&(route_id.clone(), forwards)) {
-            continue;
+    let mut stop_times: HashMap<String, Vec<StopTimeRecord>> = HashMap::new();
+    for rec in read_records::<StopTimeRecord>(dir_path, "stop_times.txt")? {
+        stop_times
+            .entry(rec.trip_id.clone())
+            .or_insert_with(Vec::new)
+            .push(rec);
+    }
+
+    let mut frequencies: HashMap<String, Vec<Frequency>> = HashMap::new();
+    for rec in read_optional_records::<FrequencyRecord>(dir_path, "frequencies.txt")? {
+        frequencies
+            .entry(rec.trip_id)
+            .or_insert_with(Vec::new)
+            .push(Frequency {
+                start: Duration::parse(&rec.start_time)?,
+                end: Duration::parse(&rec.end_time)?,
+                headway: Duration::seconds(rec.headway_secs),
+            });
+    }
+
+    let mut shapes: HashMap<String, Vec<(usize, LonLat)>> = HashMap::new();
+    for rec in read_optional_records::<ShapeRecord>(dir_path, "shapes.txt")? {
+        shapes.entry(rec.shape_id).or_insert_with(Vec::new).push((
+            rec.shape_pt_sequence,
+            LonLat::new(rec.shape_pt_lon, rec.shape_pt_lat),
+        ));
+    }
+
+    // Keyed by (route ID, forwards)
+    let mut directed_routes: BTreeMap<(String, bool), DirectedRoute> = BTreeMap::new();
+    for rec in read_records::<TripRecord>(dir_path, "trips.txt")? {
+        let mut records = match stop_times.remove(&rec.trip_id) {
+            Some(records) => records,
+            None => {
+                println!("WARNING: Trip {} has no stop times", rec.trip_id);
+                continue;
+            }
+        };
+        records.sort_by_key(|st| st.stop_sequence);
+        // One bad trip shouldn't ruin the rest of the feed
+        let stop_times = match interpolate_stop_times(&rec.trip_id, records, &stops) {
+            Ok(stop_times) => stop_times,
+            Err(err) => {
+                println!("WARNING: Skipping trip {}: {}", rec.trip_id, err);
+                continue;
+            }
+        };
+        let trip = Trip {
+            stop_times,
+            frequencies: frequencies.remove(&rec.trip_id).unwrap_or_else(Vec::new),
+            id: rec.trip_id,
+            service_id: rec.service_id,
+        };
+
+        let forwards = rec.direction_id.unwrap_or(0) == 0;
+        let dir = directed_routes
+            .entry((rec.route_id, forwards))
+            .or_insert_with(|| DirectedRoute {
+                forwards,
+                stops: Vec::new(),
+                shape: Vec::new(),
+                trips: Vec::new(),
+            });
+        // Describe the direction using the trip that visits the most stops.
+        if trip.stop_times.len() > dir.stops.len() {
+            dir.stops = trip.stop_times.iter().map(|st| st.stop.clone()).collect();
+            dir.shape = rec
+                .shape_id
+                .and_then(|id| shapes.get(&id))
+                .map(|pts| {
+                    let mut pts = pts.clone();
+                    pts.sort_by_key(|(seq, _)| *seq);
+                    pts.into_iter().map(|(_, pt)| pt).collect()
+                })
+                .unwrap_or_else(Vec::new);
         }
-        directed_routes.insert(
-            (route_id, forwards),
-            group.map(|rec| stop_id_to_pt[&rec.unwrap()[3]]).collect(),
-        );
+        dir.trips.push(trip);
     }
 
-    // Group together the pairs of directed routes
-    let route_ids: BTreeSet<String> = directed_routes
-        .keys()
-        .map(|(id, _)| id.to_string())
-        .collect();
-    let mut results = Vec::new();
-    for route_id in route_ids {
-        let mut stops = directed_routes
-            .remove(&(route_id.clone(), true))
-            .unwrap_or_else(Vec::new);
-        if let Some(more_stops) = directed_routes.remove(&(route_id.clone(), false)) {
-            stops.extend(more_stops);
+    let mut routes: Vec<Route> = Vec::new();
+    for ((route_id, _), dir) in directed_routes {
+        // BTreeMap order puts backwards before forwards for the same route.
+        if routes.last().map(|r| r.id != route_id).unwrap_or(true) {
+            let (name, route_type) = route_info
+                .get(&route_id)
+                .cloned()
+                .unwrap_or_else(|| (route_id.clone(), RouteType::Bus));
+            routes.push(Route {
+                name,
+                route_type,
+                id: route_id,
+                directions: Vec::new(),
+            });
+        }
+        let route = routes.last_mut().unwrap();
+        if dir.forwards {
+            route.directions.insert(0, dir);
+        } else {
+            route.directions.push(dir);
         }
-        assert!(!stops.is_empty());
-        results.push(Route {
-            name: route_id_to_name[&route_id].to_string(),
-            stops,
-        });
     }
-    assert!(directed_routes.is_empty());
 
     println!("Loading GTFS took {}s", elapsed_seconds(timer));
+    Ok(GTFS { routes, services })
+}
+
+// GTFS only requires times at some stops (timepoints). Fill in the others, assuming the vehicle
+// takes the same time between each stop.
+fn interpolate_stop_times(
+    trip_id: &str,
+    records: Vec<StopTimeRecord>,
+    stops: &HashMap<String, Stop>,
+) -> Result<Vec<StopTime>, Error> {
+    let mut known: Vec<Option<(Duration, Duration)>> = Vec::new();
+    for rec in &records {
+        let arrival = rec.arrival_time.as_ref().or(rec.departure_time.as_ref());
+        let departure = rec.departure_time.as_ref().or(rec.arrival_time.as_ref());
+        known.push(match (arrival, departure) {
+            (Some(a), Some(d)) => Some((Duration::parse(a)?, Duration::parse(d)?)),
+            _ => None,
+        });
+    }
+    if known.first().map(|t| t.is_none()).unwrap_or(true)
+        || known.last().map(|t| t.is_none()).unwrap_or(true)
+    {
+        return Err(failure::format_err!(
+            "Trip {} doesn't have times at its first and last stop",
+            trip_id
+        ));
+    }
+
+    let mut results = Vec::new();
+    let mut prev_idx = 0;
+    for (idx, rec) in records.into_iter().enumerate() {
+        let (arrival, departure) = match known[idx] {
+            Some(times) => {
+                prev_idx = idx;
+                times
+            }
+            None => {
+                let next_idx = idx + known[idx..].iter().position(|t| t.is_some()).unwrap();
+                let t1 = known[prev_idx].unwrap().1;
+                let t2 = known[next_idx].unwrap().0;
+                let pct = ((idx - prev_idx) as f64) / ((next_idx - prev_idx) as f64);
+                let t = t1 + pct * (t2 - t1);
+                (t, t)
+            }
+        };
+        let stop = match stops.get(&rec.stop_id) {
+            Some(stop) => stop.clone(),
+            None => {
+                return Err(failure::format_err!(
+                    "Trip {} uses unknown stop {}",
+                    trip_id,
+                    rec.stop_id
+                ));
+            }
+        };
+        results.push(StopTime {
+            stop,
+            arrival,
+            departure,
+        });
+    }
     Ok(results)
 }
+
+fn read_records<T: DeserializeOwned>(dir_path: &str, file: &str) -> Result<Vec<T>, Error> {
+    let mut results = Vec::new();
+    for rec in csv::Reader::from_reader(File::open(format!("{}/{}", dir_path, file))?).deserialize()
+    {
+        results.push(rec?);
+    }
+    Ok(results)
+}
+
+// frequencies.txt, shapes.txt, and calendar.txt are optional in GTFS.
+fn read_optional_records<T: DeserializeOwned>(dir_path: &str, file: &str) -> Result<Vec<T>, Error> {
+    if Path::new(&format!("{}/{}", dir_path, file)).exists() {
+        read_records(dir_path, file)
+    } else {
+        Ok(Vec::new())
+    }
+}
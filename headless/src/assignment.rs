@@ -0,0 +1,170 @@
+use crate::compare::describe_histogram;
+use crate::reject_timed_edits;
+use abstutil::Timer;
+use geom::{Duration, Statistic};
+use map_model::{LaneID, Map, Traversable};
+use serde_derive::Serialize;
+use sim::{CarID, Scenario, Sim, SimFlags, TripMode};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Runs one scenario over and over. After each day, the travel times cars experienced are fed back
+// into routing, until few cars change their route. Costs are averaged over every day so far (the
+// method of successive averages), so routes don't flip-flop between two options forever.
+#[derive(Serialize)]
+pub struct AssignmentReport {
+    pub map_name: String,
+    pub scenario_name: String,
+    pub rng_seed: u8,
+    // Stop once the fraction of cars changing routes is at most this
+    pub threshold: f64,
+    pub converged: bool,
+    pub iterations: Vec<IterationSummary>,
+}
+
+#[derive(Serialize)]
+pub struct IterationSummary {
+    // Starting from 1
+    pub iteration: usize,
+    pub finished_at: Duration,
+    pub finished_trips: usize,
+    pub aborted_trips: usize,
+    pub driving_trips: BTreeMap<Statistic, Duration>,
+    pub cars: usize,
+    // Cars whose route is different than the previous iteration
+    pub changed_routes: usize,
+    // The average change in the cost of each lane and turn with a cost so far. Costs for the next
+    // iteration are the running average over all iterations so far.
+    pub mean_cost_change: Duration,
+}
+
+pub fn run_assignment(
+    sim_flags: &SimFlags,
+    scenario_name: &str,
+    max_iterations: usize,
+    threshold: f64,
+    timer: &mut Timer,
+) -> AssignmentReport {
+    let map: Map = abstutil::read_binary(&sim_flags.load, timer)
+        .expect(&format!("Couldn't load map from {}", sim_flags.load));
+    let scenario: Scenario = abstutil::read_binary(
+        &abstutil::path1_bin(map.get_name(), abstutil::SCENARIOS, scenario_name),
+        timer,
+    )
+    .expect("loading scenario failed");
+    assign(map, &scenario, sim_flags, max_iterations, threshold, timer)
+}
+
+pub fn assign(
+    mut map: Map,
+    scenario: &Scenario,
+    sim_flags: &SimFlags,
+    max_iterations: usize,
+    threshold: f64,
+    timer: &mut Timer,
+) -> AssignmentReport {
+    assert!(max_iterations > 0);
+    reject_timed_edits(&map, "Traffic assignment");
+    let scenario_name = &scenario.scenario_name;
+    // Every day must start from the same seed, or route changes are meaningless.
+    let rng_seed = sim_flags.rng_seed.unwrap_or(42);
+
+    let mut costs: BTreeMap<Traversable, Duration> = BTreeMap::new();
+    let mut prev_routes: BTreeMap<CarID, Vec<LaneID>> = BTreeMap::new();
+    let mut iterations = Vec::new();
+    let mut converged = false;
+    for iteration in 1..=max_iterations {
+        timer.start(&format!("run {} iteration {}", scenario_name, iteration));
+
+        let mut flags = sim_flags.clone();
+        flags.rng_seed = Some(rng_seed);
+        flags.opts.run_name = format!("{} iteration {}", scenario_name, iteration);
+        flags.opts.event_log = flags
+            .opts
+            .event_log
+            .map(|log| log.with_suffix(&format!("iteration{}", iteration)));
+        let mut rng = flags.make_rng();
+        let mut sim = Sim::new(&map, flags.opts.clone(), timer);
+        scenario.instantiate(&mut sim, &map, &mut rng, timer);
+        sim.just_run_until_done(&map, None);
+
+        let analytics = sim.get_analytics();
+        let (all, aborted_trips, per_mode) = analytics.all_finished_trips(sim.time());
+        let routes = analytics.car_routes.clone();
+        let changed_routes = routes
+            .iter()
+            .filter(|(car, route)| prev_routes.get(car) != Some(route))
+            .count();
+
+        let observed = analytics.car_travel_times();
+        let total_change = average_costs(&mut costs, &observed, iteration, |t| {
+            t.length(&map) / t.speed_limit(&map)
+        });
+
+        iterations.push(IterationSummary {
+            iteration,
+            finished_at: sim.time(),
+            finished_trips: all.count(),
+            aborted_trips,
+            driving_trips: describe_histogram(&per_mode[&TripMode::Drive]),
+            cars: routes.len(),
+            changed_routes,
+            mean_cost_change: if costs.is_empty() {
+                Duration::ZERO
+            } else {
+                total_change * (1.0 / (costs.len() as f64))
+            },
+        });
+        println!(
+            "Iteration {}: {} of {} cars changed routes",
+            iteration,
+            changed_routes,
+            routes.len()
+        );
+        timer.stop(&format!("run {} iteration {}", scenario_name, iteration));
+
+        // The first iteration has nothing to compare against.
+        if iteration > 1 && (changed_routes as f64) <= threshold * (routes.len() as f64) {
+            converged = true;
+            break;
+        }
+        if iteration < max_iterations {
+            map.set_driving_travel_times(costs.clone(), timer);
+        }
+        prev_routes = routes;
+    }
+
+    AssignmentReport {
+        map_name: map.get_name().to_string(),
+        scenario_name: scenario_name.to_string(),
+        rng_seed,
+        threshold,
+        converged,
+        iterations,
+    }
+}
+
+// One step of the method of successive averages: move each cost 1/iteration of the way towards
+// what was observed. Anything nobody used this time is observed at its free-flow cost, so stale
+// congestion fades away instead of scaring cars off forever. Returns the total change.
+pub fn average_costs<F: Fn(Traversable) -> Duration>(
+    costs: &mut BTreeMap<Traversable, Duration>,
+    observed: &BTreeMap<Traversable, Duration>,
+    iteration: usize,
+    free_flow: F,
+) -> Duration {
+    let all: BTreeSet<Traversable> = costs.keys().chain(observed.keys()).cloned().collect();
+    let mut total_change = Duration::ZERO;
+    for t in all {
+        // Earlier iterations routed using free-flow for anything without a cost.
+        let old = costs.get(&t).cloned().unwrap_or_else(|| free_flow(t));
+        let time = observed.get(&t).cloned().unwrap_or_else(|| free_flow(t));
+        let new_cost = old + (time - old) * (1.0 / (iteration as f64));
+        total_change += if new_cost > old {
+            new_cost - old
+        } else {
+            old - new_cost
+        };
+        costs.insert(t, new_cost);
+    }
+    total_change
+}
@@ -0,0 +1,209 @@
+use crate::{deterministic_flags, rng_seed};
+use abstutil::Timer;
+use geom::{Duration, DurationHistogram, Statistic};
+use map_model::{Map, MapEdits};
+use serde_derive::Serialize;
+use sim::{FinishedTrips, ModeChoice, Scenario, Sim, SimFlags, TripID, TripMode};
+use std::collections::BTreeMap;
+
+// Runs one scenario against several sets of map edits, each from the same RNG seed, and compares
+// every run against the first one.
+#[derive(Serialize)]
+pub struct ComparisonReport {
+    pub map_name: String,
+    pub scenario_name: String,
+    pub rng_seed: u8,
+    pub runs: Vec<RunSummary>,
+    // Each run (except the baseline) compared against runs[0]
+    pub comparisons: Vec<TripComparison>,
+}
+
+#[derive(Serialize)]
+pub struct RunSummary {
+    pub edits_name: String,
+    pub finished_at: Duration,
+    pub finished_trips: usize,
+    pub aborted_trips: usize,
+    pub unfinished_trips: usize,
+    pub all_trips: BTreeMap<Statistic, Duration>,
+    pub per_mode: BTreeMap<TripMode, BTreeMap<Statistic, Duration>>,
+    // Only filled out when the scenario uses a mode choice model
+    pub mode_split: BTreeMap<TripMode, usize>,
+}
+
+#[derive(Serialize)]
+pub struct TripComparison {
+    pub baseline: String,
+    pub edits_name: String,
+    pub per_mode: BTreeMap<TripMode, ModeDeltas>,
+    // Only trips that finished in both runs. (trip, mode, baseline duration, this duration)
+    pub trips: Vec<(TripID, TripMode, Duration, Duration)>,
+}
+
+#[derive(Serialize)]
+pub struct ModeDeltas {
+    pub same: usize,
+    pub faster: usize,
+    pub slower: usize,
+    // Over every trip, including the unchanged ones. Negative means this run is faster than the
+    // baseline.
+    pub delta_p50: Option<Duration>,
+    pub delta_p90: Option<Duration>,
+    pub delta_p99: Option<Duration>,
+}
+
+pub fn run_comparison(
+    sim_flags: &SimFlags,
+    scenario_name: &str,
+    all_edits: Vec<String>,
+    // Overrides the scenario's own model, if any
+    mode_choice: Option<ModeChoice>,
+    timer: &mut Timer,
+) -> ComparisonReport {
+    assert!(!all_edits.is_empty());
+    let mut runs = Vec::new();
+    let mut all_trips = Vec::new();
+    let mut map_name = String::new();
+    for edits_name in all_edits {
+        timer.start(&format!("run {} with {}", scenario_name, edits_name));
+
+        let mut map: Map = abstutil::read_binary(&sim_flags.load, timer)
+            .expect(&format!("Couldn't load map from {}", sim_flags.load));
+        map_name = map.get_name().to_string();
+        let edits = MapEdits::load(&map_name, &edits_name, timer);
+        map.apply_edits(edits, timer);
+        map.recalculate_pathfinding_after_edits(timer);
+
+        let mut scenario: Scenario = abstutil::read_binary(
+            &abstutil::path1_bin(&map_name, abstutil::SCENARIOS, scenario_name),
+            timer,
+        )
+        .expect("loading scenario failed");
+        if mode_choice.is_some() {
+            scenario.mode_choice = mode_choice.clone();
+        }
+
+        let mut flags = deterministic_flags(sim_flags, &edits_name);
+        flags.opts.run_name = format!("{} with {}", scenario_name, edits_name);
+        let mut rng = flags.make_rng();
+        let mut sim = Sim::new(&map, flags.opts.clone(), timer);
+        scenario.instantiate(&mut sim, &map, &mut rng, timer);
+        if map.get_edits().timed_commands.is_empty() {
+            sim.just_run_until_done(&map, None);
+        } else {
+            // Timed edits change the map as the sim runs. Each run loads its own copy.
+            while !sim.is_done() {
+                sim.step_with_timed_edits(&mut map, Duration::seconds(30.0), timer);
+            }
+        }
+
+        runs.push(summarize(&sim, edits_name));
+        all_trips.push(sim.get_finished_trips());
+
+        timer.stop(&format!("run {} with {}", scenario_name, edits_name));
+    }
+
+    let mut comparisons = Vec::new();
+    let mut trips_iter = all_trips.into_iter();
+    let baseline_trips = trips_iter.next().unwrap();
+    for (idx, trips) in trips_iter.enumerate() {
+        comparisons.push(compare_trips(
+            runs[0].edits_name.clone(),
+            &baseline_trips,
+            runs[idx + 1].edits_name.clone(),
+            trips,
+        ));
+    }
+
+    ComparisonReport {
+        map_name,
+        scenario_name: scenario_name.to_string(),
+        rng_seed: rng_seed(sim_flags),
+        runs,
+        comparisons,
+    }
+}
+
+fn summarize(sim: &Sim, edits_name: String) -> RunSummary {
+    let (all, aborted_trips, per_mode) = sim.get_analytics().all_finished_trips(sim.time());
+    RunSummary {
+        edits_name,
+        finished_at: sim.time(),
+        finished_trips: all.count(),
+        aborted_trips,
+        unfinished_trips: sim.get_finished_trips().unfinished_trips,
+        all_trips: describe_histogram(&all),
+        per_mode: per_mode
+            .into_iter()
+            .map(|(mode, distrib)| (mode, describe_histogram(&distrib)))
+            .collect(),
+        mode_split: sim.get_analytics().mode_split(),
+    }
+}
+
+pub(crate) fn describe_histogram(distrib: &DurationHistogram) -> BTreeMap<Statistic, Duration> {
+    if distrib.count() == 0 {
+        return BTreeMap::new();
+    }
+    Statistic::all()
+        .into_iter()
+        .map(|stat| (stat, distrib.select(stat)))
+        .collect()
+}
+
+pub fn compare_trips(
+    baseline: String,
+    baseline_trips: &FinishedTrips,
+    edits_name: String,
+    trips: FinishedTrips,
+) -> TripComparison {
+    let before: BTreeMap<TripID, Duration> = baseline_trips
+        .finished_trips
+        .iter()
+        .map(|(id, _, dt)| (*id, *dt))
+        .collect();
+
+    let mut cmp = TripComparison {
+        baseline,
+        edits_name,
+        per_mode: BTreeMap::new(),
+        trips: Vec::new(),
+    };
+    let mut deltas_per_mode: BTreeMap<TripMode, Vec<Duration>> = BTreeMap::new();
+    for (id, mode, dt) in trips.finished_trips {
+        if let Some(dt1) = before.get(&id) {
+            cmp.trips.push((id, mode, *dt1, dt));
+            deltas_per_mode
+                .entry(mode)
+                .or_insert_with(Vec::new)
+                .push(dt - *dt1);
+        }
+    }
+
+    // DurationHistogram doesn't handle negative values, so sort the deltas manually.
+    for (mode, mut deltas) in deltas_per_mode {
+        let same = deltas.iter().filter(|dt| **dt == Duration::ZERO).count();
+        let faster = deltas.iter().filter(|dt| **dt < Duration::ZERO).count();
+        let slower = deltas.len() - same - faster;
+        deltas.sort();
+        let pct = |p: f64| {
+            if deltas.is_empty() {
+                None
+            } else {
+                Some(deltas[(p * deltas.len() as f64).floor() as usize])
+            }
+        };
+        cmp.per_mode.insert(
+            mode,
+            ModeDeltas {
+                same,
+                faster,
+                slower,
+                delta_p50: pct(0.5),
+                delta_p90: pct(0.9),
+                delta_p99: pct(0.99),
+            },
+        );
+    }
+    cmp
+}
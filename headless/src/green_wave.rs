@@ -0,0 +1,409 @@
+use crate::{deterministic_flags, reject_timed_edits};
+use abstutil::Timer;
+use geom::{Distance, Duration, Pt2D, Speed};
+use map_model::{
+    ControlTrafficSignal, EditCmd, IntersectionID, Map, PathConstraints, PathRequest, PathStep,
+    Position, RoadID, Traversable, TurnGroupID, TurnType,
+};
+use serde_derive::Serialize;
+use sim::{CarID, CarStatus, GetDrawAgents, Scenario, Sim, SimFlags};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Coordinates the traffic signals along a corridor, so that a platoon driving at the progression
+// speed reaches each signal just as the phase serving it starts. The first signal keeps its
+// offset; every other one is shifted relative to it. Then the corridor is simulated before and
+// after, counting how often vehicles driving along it have to stop.
+#[derive(Serialize)]
+pub struct GreenWaveReport {
+    pub map_name: String,
+    pub edits_name: String,
+    // Only if the edits were asked for by name
+    pub saved_edits: bool,
+    pub scenario_name: String,
+    pub corridor: Vec<IntersectionID>,
+    pub progression_speed: Speed,
+    pub signals: Vec<SignalOffset>,
+    // Things that make the wave imperfect, like signals with different cycle lengths
+    pub warnings: Vec<String>,
+    pub before: CorridorStops,
+    pub after: CorridorStops,
+}
+
+#[derive(Serialize)]
+pub struct SignalOffset {
+    pub id: IntersectionID,
+    // When the platoon reaches this signal, after leaving the first one
+    pub arrival: Duration,
+    pub cycle_length: Duration,
+    // The phase serving the corridor
+    pub phase: usize,
+    pub old_offset: Duration,
+    pub new_offset: Duration,
+}
+
+#[derive(Serialize)]
+pub struct CorridorStops {
+    // Every vehicle that drove on some part of the corridor, in the direction of travel
+    pub vehicles: usize,
+    pub stops: usize,
+    pub stops_per_vehicle: f64,
+}
+
+pub fn run_green_wave(
+    sim_flags: &SimFlags,
+    corridor: Vec<IntersectionID>,
+    progression_speed: Speed,
+    // If None, use a small run
+    scenario_name: Option<String>,
+    // If None, the coordinated signals are only simulated, not saved
+    save_edits: Option<String>,
+    timer: &mut Timer,
+) -> GreenWaveReport {
+    let mut map: Map = abstutil::read_binary(&sim_flags.load, timer)
+        .expect(&format!("Couldn't load map from {}", sim_flags.load));
+    reject_timed_edits(&map, "Green wave coordination");
+    let corridor = if corridor.len() == 2 {
+        corridor_between(&map, corridor[0], corridor[1]).expect(&format!(
+            "Can't drive from {} to {}",
+            corridor[0], corridor[1]
+        ))
+    } else {
+        corridor
+    };
+    let (signals, offsets, warnings) = green_wave_offsets(&map, &corridor, progression_speed)
+        .unwrap_or_else(|err| panic!("Can't coordinate the corridor: {}", err));
+    for warning in &warnings {
+        println!("Warning: {}", warning);
+    }
+
+    let scenario = if let Some(ref name) = scenario_name {
+        abstutil::read_binary(
+            &abstutil::path1_bin(map.get_name(), abstutil::SCENARIOS, name),
+            timer,
+        )
+        .expect("loading scenario failed")
+    } else {
+        Scenario::small_run(&map)
+    };
+    let roads = corridor_roads(&map, &corridor).unwrap();
+
+    timer.start("simulate before coordinating");
+    let before = measure_stops(
+        &map, &scenario, &corridor, &roads, sim_flags, "before", timer,
+    );
+    timer.stop("simulate before coordinating");
+
+    let edits_name = save_edits
+        .clone()
+        .unwrap_or_else(|| "green_wave".to_string());
+    let mut edits = map.get_edits().clone();
+    edits.edits_name = edits_name.clone();
+    for signal in signals {
+        edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+    }
+    edits.dirty = true;
+    map.apply_edits(edits, timer);
+    map.recalculate_pathfinding_after_edits(timer);
+    if save_edits.is_some() {
+        map.save_edits();
+    }
+
+    timer.start("simulate after coordinating");
+    let after = measure_stops(
+        &map, &scenario, &corridor, &roads, sim_flags, "after", timer,
+    );
+    timer.stop("simulate after coordinating");
+
+    GreenWaveReport {
+        map_name: map.get_name().to_string(),
+        edits_name,
+        saved_edits: save_edits.is_some(),
+        scenario_name: scenario.scenario_name.clone(),
+        corridor,
+        progression_speed,
+        signals: offsets,
+        warnings,
+        before,
+        after,
+    }
+}
+
+// Follows the shortest driving path between two intersections.
+pub fn corridor_between(
+    map: &Map,
+    from: IntersectionID,
+    to: IntersectionID,
+) -> Option<Vec<IntersectionID>> {
+    let mut best: Option<Vec<IntersectionID>> = None;
+    for l1 in &map.get_i(from).outgoing_lanes {
+        if !map.get_l(*l1).is_driving() {
+            continue;
+        }
+        for l2 in &map.get_i(to).incoming_lanes {
+            if !map.get_l(*l2).is_driving() {
+                continue;
+            }
+            let path = match map.pathfind(PathRequest {
+                start: Position::new(*l1, Distance::ZERO),
+                end: Position::new(*l2, map.get_l(*l2).length()),
+                constraints: PathConstraints::Car,
+            }) {
+                Some(path) => path,
+                None => {
+                    continue;
+                }
+            };
+            let mut corridor = vec![from];
+            for step in path.get_steps() {
+                if let PathStep::Turn(t) = step {
+                    corridor.push(t.parent);
+                }
+            }
+            corridor.push(to);
+            if best
+                .as_ref()
+                .map(|b| corridor.len() < b.len())
+                .unwrap_or(true)
+            {
+                best = Some(corridor);
+            }
+        }
+    }
+    best
+}
+
+// Returns the changed signals, a description of each, and warnings. Only the default plan of each
+// signal is coordinated; time-of-day plans are left alone.
+pub fn green_wave_offsets(
+    map: &Map,
+    corridor: &Vec<IntersectionID>,
+    progression_speed: Speed,
+) -> Result<(Vec<ControlTrafficSignal>, Vec<SignalOffset>, Vec<String>), String> {
+    if corridor.len() < 2 {
+        return Err("A corridor needs at least two intersections".to_string());
+    }
+    if progression_speed <= Speed::ZERO {
+        return Err(format!("Bad progression speed {}", progression_speed));
+    }
+    let roads = corridor_roads(map, corridor)?;
+
+    let mut signals = Vec::new();
+    let mut offsets = Vec::new();
+    let mut warnings = Vec::new();
+    // When the corridor phase of the first signal starts, relative to midnight
+    let mut reference: Option<(Duration, Duration)> = None;
+    let mut dist = Distance::ZERO;
+    for (idx, i) in corridor.iter().enumerate() {
+        if idx > 0 {
+            dist += map.get_r(roads[idx - 1]).center_pts.length();
+        }
+        if !map.get_i(*i).is_traffic_signal() {
+            continue;
+        }
+        let mut signal = map.get_traffic_signal(*i).clone();
+        let from = if idx > 0 { Some(roads[idx - 1]) } else { None };
+        let to = roads.get(idx).cloned();
+        let phase = corridor_phase(&signal, from, to)
+            .ok_or_else(|| format!("No phase at {} serves the corridor", i))?;
+        if signal.is_actuated() {
+            warnings.push(format!(
+                "{} is actuated, so its offset won't hold for long",
+                i
+            ));
+        }
+
+        let mut cycle_length = Duration::ZERO;
+        let mut phase_start = Duration::ZERO;
+        for (p_idx, p) in signal.phases.iter().enumerate() {
+            if p_idx == phase {
+                phase_start = cycle_length;
+            }
+            cycle_length += p.duration;
+        }
+
+        let arrival = dist / progression_speed;
+        let old_offset = signal.offset;
+        // The signal is at (now + offset) % cycle_length in its cycle. Make the corridor phase
+        // start when the platoon arrives.
+        let (start, first_cycle) = *reference.get_or_insert_with(|| {
+            (
+                positive_mod(phase_start - old_offset, cycle_length),
+                cycle_length,
+            )
+        });
+        if cycle_length != first_cycle {
+            warnings.push(format!(
+                "{} has a cycle of {}, but the first signal's is {}. They'll drift apart.",
+                i, cycle_length, first_cycle
+            ));
+        }
+        let new_offset = positive_mod(phase_start - start - arrival, cycle_length);
+        signal.offset = new_offset;
+
+        offsets.push(SignalOffset {
+            id: *i,
+            arrival,
+            cycle_length,
+            phase,
+            old_offset,
+            new_offset,
+        });
+        if new_offset != old_offset {
+            signals.push(signal);
+        }
+    }
+    if offsets.is_empty() {
+        return Err("No traffic signals along the corridor".to_string());
+    }
+    Ok((signals, offsets, warnings))
+}
+
+// The road between each consecutive pair of intersections, driveable in the corridor's direction
+fn corridor_roads(map: &Map, corridor: &Vec<IntersectionID>) -> Result<Vec<RoadID>, String> {
+    let mut roads = Vec::new();
+    for pair in corridor.windows(2) {
+        let r = map
+            .get_i(pair[0])
+            .roads
+            .iter()
+            .map(|r| map.get_r(*r))
+            .find(|r| {
+                (r.src_i == pair[0] && r.dst_i == pair[1])
+                    || (r.src_i == pair[1] && r.dst_i == pair[0])
+            })
+            .ok_or_else(|| format!("No road between {} and {}", pair[0], pair[1]))?;
+        if !r
+            .outgoing_lanes(pair[0])
+            .iter()
+            .any(|(l, _)| map.get_l(*l).is_driving())
+        {
+            return Err(format!(
+                "Can't drive from {} to {} along {}",
+                pair[0], pair[1], r.id
+            ));
+        }
+        roads.push(r.id);
+    }
+    Ok(roads)
+}
+
+// The first phase protecting the movement along the corridor, or failing that, the first one
+// where it yields. At the ends of the corridor, prefer going straight.
+fn corridor_phase(
+    signal: &ControlTrafficSignal,
+    from: Option<RoadID>,
+    to: Option<RoadID>,
+) -> Option<usize> {
+    let mut groups: Vec<&TurnGroupID> = signal
+        .turn_groups
+        .iter()
+        .filter(|(id, g)| {
+            g.turn_type != TurnType::Crosswalk
+                && from.map(|r| id.from == r).unwrap_or(true)
+                && to.map(|r| id.to == r).unwrap_or(true)
+        })
+        .map(|(id, _)| id)
+        .collect();
+    if let Some(straight) = groups
+        .iter()
+        .find(|id| signal.turn_groups[**id].turn_type == TurnType::Straight)
+        .cloned()
+    {
+        groups = vec![straight];
+    }
+
+    for protected in vec![true, false] {
+        for (idx, phase) in signal.phases.iter().enumerate() {
+            let set = if protected {
+                &phase.protected_groups
+            } else {
+                &phase.yield_groups
+            };
+            if groups.iter().any(|g| set.contains(*g)) {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+// Vehicles are sampled every second. A stop is a vehicle on the corridor that was moving, and
+// then didn't.
+fn measure_stops(
+    map: &Map,
+    scenario: &Scenario,
+    corridor: &Vec<IntersectionID>,
+    roads: &Vec<RoadID>,
+    sim_flags: &SimFlags,
+    // Keeps the event logs of the two runs apart
+    label: &str,
+    timer: &mut Timer,
+) -> CorridorStops {
+    let mut on: Vec<Traversable> = Vec::new();
+    for (idx, r) in roads.iter().enumerate() {
+        for (l, _) in map.get_r(*r).outgoing_lanes(corridor[idx]) {
+            if map.get_l(*l).is_driving() {
+                on.push(Traversable::Lane(*l));
+                for t in map.get_turns_from_lane(*l) {
+                    if roads.get(idx + 1) == Some(&map.get_l(t.id.dst).parent) {
+                        on.push(Traversable::Turn(t.id));
+                    }
+                }
+            }
+        }
+    }
+
+    let flags = deterministic_flags(sim_flags, label);
+    let mut rng = flags.make_rng();
+    let mut sim = Sim::new(map, flags.opts.clone(), timer);
+    scenario.instantiate(&mut sim, map, &mut rng, timer);
+
+    let mut vehicles: BTreeSet<CarID> = BTreeSet::new();
+    let mut stops = 0;
+    // Where each vehicle was last sample, and if it was moving then
+    let mut last_seen: BTreeMap<CarID, (Pt2D, bool)> = BTreeMap::new();
+    while !sim.is_done() && sim.time() < Duration::END_OF_DAY {
+        sim.step(map, Duration::seconds(1.0));
+        let mut seen = BTreeMap::new();
+        for t in &on {
+            for car in sim.get_draw_cars(*t, map) {
+                if car.status != CarStatus::Moving {
+                    continue;
+                }
+                let pt = car.body.last_pt();
+                let moving = match last_seen.get(&car.id) {
+                    Some((prev, was_moving)) => {
+                        let moving = prev.dist_to(pt) > Distance::meters(0.1);
+                        if *was_moving && !moving {
+                            stops += 1;
+                        }
+                        moving
+                    }
+                    None => true,
+                };
+                vehicles.insert(car.id);
+                seen.insert(car.id, (pt, moving));
+            }
+        }
+        last_seen = seen;
+    }
+
+    CorridorStops {
+        vehicles: vehicles.len(),
+        stops,
+        stops_per_vehicle: if vehicles.is_empty() {
+            0.0
+        } else {
+            (stops as f64) / (vehicles.len() as f64)
+        },
+    }
+}
+
+pub fn positive_mod(x: Duration, m: Duration) -> Duration {
+    let r = x % m;
+    if r < Duration::ZERO {
+        r + m
+    } else {
+        r
+    }
+}
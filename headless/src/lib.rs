@@ -0,0 +1,33 @@
+pub mod assignment;
+pub mod compare;
+pub mod green_wave;
+pub mod signal_timing;
+
+use map_model::Map;
+use sim::SimFlags;
+
+// Every run must start from the same seed, or comparing runs is meaningless.
+pub fn rng_seed(flags: &SimFlags) -> u8 {
+    flags.rng_seed.unwrap_or(42)
+}
+
+// The flags for one of several runs being compared. The label keeps the event logs apart.
+pub fn deterministic_flags(flags: &SimFlags, label: &str) -> SimFlags {
+    let mut flags = flags.clone();
+    flags.rng_seed = Some(rng_seed(&flags));
+    flags.opts.event_log = flags.opts.event_log.map(|log| log.with_suffix(label));
+    flags
+}
+
+// Runners that reuse one map for many simulations can't let timed edits change it underneath them.
+pub fn reject_timed_edits(map: &Map, runner: &str) {
+    let edits = map.get_edits();
+    if !edits.timed_commands.is_empty() {
+        panic!(
+            "{} can't handle timed edits, but {} has {}. Bake them in or remove them first.",
+            runner,
+            edits.edits_name,
+            edits.timed_commands.len()
+        );
+    }
+}
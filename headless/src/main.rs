@@ -1,6 +1,8 @@
 use abstutil::{CmdArgs, Timer};
-use geom::Duration;
-use sim::{GetDrawAgents, Scenario, SimFlags};
+use geom::{Duration, Speed};
+use headless::{assignment, compare, green_wave, signal_timing};
+use map_model::{IntersectionID, Map};
+use sim::{GetDrawAgents, ModeChoice, Scenario, Sim, SimFlags};
 
 fn main() {
     let mut args = CmdArgs::new();
@@ -10,20 +12,252 @@ fn main() {
     let enable_profiler = args.enabled("--enable_profiler");
     // Every 0.1s, pretend to draw everything to make sure there are no bugs.
     let paranoia = args.enabled("--paranoia");
+    // Instead of one run, run a scenario against a comma-separated list of edits and write a
+    // comparison report.
+    let compare_scenario = args.optional("--compare_scenario");
+    let compare_edits = args.optional("--compare_edits");
+    let report_path = args.optional("--report");
+    // Pick modes based on travel times, instead of using the scenario's fixed modes
+    let mode_choice = if args.enabled("--mode_choice") {
+        Some(ModeChoice::default_logit())
+    } else {
+        None
+    };
+    // Instead of one run, repeat a scenario, feeding the travel times cars experienced back into
+    // their routing, and write a report about convergence.
+    let assign_scenario = args.optional("--assign_scenario");
+    let iterations = args
+        .optional_parse("--iterations", |s| s.parse::<usize>())
+        .unwrap_or(10);
+    // Stop iterating once at most this fraction of cars change routes
+    let threshold = args
+        .optional_parse("--threshold", |s| s.parse::<f64>())
+        .unwrap_or(0.01);
+    // Instead of one run, coordinate the signal offsets along a corridor and compare stops along
+    // the corridor before and after. The corridor is a comma-separated list of
+    // intersection IDs, or just the two ends to follow the driving path between them.
+    let green_wave = args.optional("--green_wave");
+    let progression_speed = Speed::miles_per_hour(
+        args.optional_parse("--progression_speed_mph", |s| s.parse::<f64>())
+            .unwrap_or(25.0),
+    );
+    // If this is missing, use a small run
+    let green_wave_scenario = args.optional("--green_wave_scenario");
+    // If this is missing, the coordinated signals aren't saved as edits
+    let green_wave_edits = args.optional("--green_wave_edits");
+    // Instead of one run, search for better timing at a comma-separated list of traffic signals
+    // by simulating a scenario over and over, then save the best timing as edits, if it helped.
+    let optimize_signals = args.optional("--optimize_signals");
+    let optimize_scenario = args.optional("--optimize_scenario");
+    let max_evaluations = args
+        .optional_parse("--max_evaluations", |s| s.parse::<usize>())
+        .unwrap_or(100);
+    let optimize_until = args
+        .optional_parse("--optimize_until", Duration::parse)
+        .unwrap_or(Duration::END_OF_DAY);
+    // The traffic signal challenge in the game looks for this name.
+    let optimize_edits = args
+        .optional("--optimize_edits")
+        .unwrap_or_else(|| "optimized_signals".to_string());
+    // Instead of running anything, check a scenario against the map and report problems.
+    let validate_scenario = args.optional("--validate_scenario");
+    // Needs --record_trajectories
+    let trajectories_csv = args.optional("--trajectories_csv");
+    let trajectories_geojson = args.optional("--trajectories_geojson");
     args.done();
 
+    if mode_choice.is_some() && sim_flags.load.starts_with("../data/save/") {
+        panic!("--mode_choice can't be used with a savestate; its trips are already scheduled");
+    }
+
+    if let Some(scenario_name) = compare_scenario {
+        let all_edits = compare_edits
+            .expect("--compare_scenario needs --compare_edits")
+            .split(',')
+            .map(|s| s.to_string())
+            .collect();
+        let mut timer = Timer::new("compare edits");
+        let report = compare::run_comparison(
+            &sim_flags,
+            &scenario_name,
+            all_edits,
+            mode_choice,
+            &mut timer,
+        );
+        let path = report_path.unwrap_or_else(|| {
+            format!(
+                "../data/comparisons/{}_{}.json",
+                report.map_name, report.scenario_name
+            )
+        });
+        abstutil::write_json(&path, &report).expect(&format!("Couldn't write {}", path));
+        timer.done();
+        println!("Wrote comparison report to {}", path);
+        return;
+    }
+
+    if let Some(scenario_name) = assign_scenario {
+        let mut timer = Timer::new("traffic assignment");
+        let report = assignment::run_assignment(
+            &sim_flags,
+            &scenario_name,
+            iterations,
+            threshold,
+            &mut timer,
+        );
+        let path = report_path.unwrap_or_else(|| {
+            format!(
+                "../data/assignment/{}_{}.json",
+                report.map_name, report.scenario_name
+            )
+        });
+        abstutil::write_json(&path, &report).expect(&format!("Couldn't write {}", path));
+        timer.done();
+        println!(
+            "{} after {} iterations. Wrote report to {}",
+            if report.converged {
+                "Converged"
+            } else {
+                "Didn't converge"
+            },
+            report.iterations.len(),
+            path
+        );
+        return;
+    }
+
+    if let Some(corridor) = green_wave {
+        let corridor: Vec<IntersectionID> = corridor
+            .split(',')
+            .map(|i| {
+                IntersectionID(
+                    i.parse::<usize>()
+                        .expect(&format!("Bad intersection {}", i)),
+                )
+            })
+            .collect();
+        let mut timer = Timer::new("green wave");
+        let report = green_wave::run_green_wave(
+            &sim_flags,
+            corridor,
+            progression_speed,
+            green_wave_scenario,
+            green_wave_edits,
+            &mut timer,
+        );
+        let path = report_path.unwrap_or_else(|| {
+            format!(
+                "../data/green_waves/{}_{}.json",
+                report.map_name, report.edits_name
+            )
+        });
+        abstutil::write_json(&path, &report).expect(&format!("Couldn't write {}", path));
+        timer.done();
+        if report.saved_edits {
+            println!("Saved edits {}", report.edits_name);
+        }
+        println!(
+            "Stops per vehicle along the corridor went from {:.2} to {:.2}. Wrote report to {}",
+            report.before.stops_per_vehicle, report.after.stops_per_vehicle, path
+        );
+        return;
+    }
+
+    if let Some(intersections) = optimize_signals {
+        let intersections: Vec<IntersectionID> = intersections
+            .split(',')
+            .map(|i| {
+                IntersectionID(
+                    i.parse::<usize>()
+                        .expect(&format!("Bad intersection {}", i)),
+                )
+            })
+            .collect();
+        let scenario_name =
+            optimize_scenario.expect("--optimize_signals needs --optimize_scenario");
+        let mut timer = Timer::new("optimize traffic signals");
+        let report = signal_timing::optimize_signals(
+            &sim_flags,
+            &scenario_name,
+            intersections,
+            max_evaluations,
+            optimize_until,
+            optimize_edits,
+            &mut timer,
+        );
+        let path = report_path.unwrap_or_else(|| {
+            format!(
+                "../data/signal_timing/{}_{}.json",
+                report.map_name, report.edits_name
+            )
+        });
+        abstutil::write_json(&path, &report).expect(&format!("Couldn't write {}", path));
+        timer.done();
+        if report.saved_edits {
+            println!("Saved edits {}", report.edits_name);
+        } else {
+            println!("Nothing beat the current signals, so no edits were saved");
+        }
+        println!(
+            "Total delay went from {} to {} after {} simulations. Wrote report to {}",
+            report.baseline_delay,
+            report.best_delay,
+            report.evaluations.len() + 1,
+            path
+        );
+        return;
+    }
+
+    if let Some(scenario_name) = validate_scenario {
+        let mut timer = Timer::new("validate scenario");
+        let map: Map = abstutil::read_binary(&sim_flags.load, &mut timer)
+            .expect(&format!("Couldn't load map from {}", sim_flags.load));
+        let path = abstutil::path1_bin(map.get_name(), abstutil::SCENARIOS, &scenario_name);
+        let scenario: Scenario = abstutil::read_binary(&path, &mut timer)
+            .expect(&format!("Couldn't load scenario from {}", path));
+        let problems = scenario.validate(&map, &mut timer);
+        timer.done();
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if problems.is_empty() {
+            println!("{} looks fine", scenario.scenario_name);
+        } else {
+            println!("{} has {} problems", scenario.scenario_name, problems.len());
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut timer = Timer::new("setup headless");
-    let (map, mut sim, mut rng) = sim_flags.load(&mut timer);
+    let (mut map, mut sim, mut rng) =
+        if mode_choice.is_some() && sim_flags.load.starts_with("../data/scenarios/") {
+            // SimFlags would instantiate the scenario with its fixed modes, so do it here.
+            let mut scenario: Scenario = abstutil::read_binary(&sim_flags.load, &mut timer)
+                .expect("loading scenario failed");
+            scenario.mode_choice = mode_choice.clone();
+            let mut flags = sim_flags.clone();
+            flags.load = abstutil::path_map(&scenario.map_name);
+            if flags.opts.run_name == "unnamed" {
+                flags.opts.run_name = scenario.scenario_name.clone();
+            }
+            let (map, mut sim, mut rng) = flags.load(&mut timer);
+            scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+            (map, sim, rng)
+        } else {
+            sim_flags.load(&mut timer)
+        };
 
     // TODO not the ideal way to distinguish what thing we loaded
     if sim_flags.load.starts_with("../data/raw_maps/")
         || sim_flags.load.starts_with("../data/maps/")
     {
-        let s = if let Some(n) = num_agents {
+        let mut s = if let Some(n) = num_agents {
             Scenario::scaled_run(&map, n)
         } else {
             Scenario::small_run(&map)
         };
+        s.mode_choice = mode_choice;
         s.instantiate(&mut sim, &map, &mut rng, &mut timer);
     }
     timer.done();
@@ -38,29 +272,51 @@ fn main() {
                 .unwrap();
         }
     }
-    let timer = Timer::new("run sim until done");
-    sim.run_until_done(
-        &map,
-        move |sim, map| {
-            // TODO We want to savestate at the end of this time; this'll happen at the beginning.
-            if Some(sim.time()) == save_at {
-                sim.save();
-                // Some simulations run for a really long time, just do this.
-                if enable_profiler {
-                    #[cfg(feature = "profiler")]
-                    {
-                        cpuprofiler::PROFILER.lock().unwrap().stop().unwrap();
-                    }
+    let callback = move |sim: &Sim, map: &Map| {
+        // TODO We want to savestate at the end of this time; this'll happen at the beginning.
+        if Some(sim.time()) == save_at {
+            sim.save();
+            // Some simulations run for a really long time, just do this.
+            if enable_profiler {
+                #[cfg(feature = "profiler")]
+                {
+                    cpuprofiler::PROFILER.lock().unwrap().stop().unwrap();
                 }
             }
-            if paranoia {
-                sim.get_all_draw_cars(map);
-            }
-        },
-        None,
-    );
+        }
+        if paranoia {
+            sim.get_all_draw_cars(map);
+        }
+    };
+    let mut timer = Timer::new("run sim until done");
+    if map.get_edits().timed_commands.is_empty() {
+        sim.run_until_done(&map, callback, None);
+    } else {
+        // Timed edits change the map as the sim runs.
+        while !sim.is_done() {
+            sim.step_with_timed_edits(&mut map, Duration::seconds(30.0), &mut timer);
+            callback(&sim, &map);
+        }
+    }
     timer.done();
     println!("Done at {}", sim.time());
+    if trajectories_csv.is_some() || trajectories_geojson.is_some() {
+        let trajectories = sim
+            .get_trajectories()
+            .expect("Exporting trajectories needs --record_trajectories");
+        if let Some(path) = trajectories_csv {
+            trajectories
+                .export_csv(&path, map.get_gps_bounds())
+                .expect(&format!("Couldn't write {}", path));
+            println!("Wrote trajectories to {}", path);
+        }
+        if let Some(path) = trajectories_geojson {
+            trajectories
+                .export_geojson(&path, map.get_gps_bounds())
+                .expect(&format!("Couldn't write {}", path));
+            println!("Wrote trajectories to {}", path);
+        }
+    }
     if enable_profiler && save_at.is_none() {
         #[cfg(feature = "profiler")]
         {
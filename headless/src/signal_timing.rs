@@ -0,0 +1,244 @@
+use crate::{deterministic_flags, reject_timed_edits, rng_seed};
+use abstutil::Timer;
+use geom::Duration;
+use map_model::{
+    ControlTrafficSignal, EditCmd, IntersectionID, Map, MapEdits, PhaseType, Traversable,
+};
+use serde_derive::Serialize;
+use sim::{Scenario, Sim, SimFlags};
+
+// Phase durations change in steps of this much, and stay within these bounds.
+const STEP: Duration = Duration::const_seconds(5.0);
+const MIN_PHASE: Duration = Duration::const_seconds(5.0);
+const MAX_PHASE: Duration = Duration::const_seconds(120.0);
+
+// Searches for better timing at some traffic signals by simulating a scenario over and over,
+// minimizing the total delay at those intersections and on the lanes approaching them. Starting
+// from the current signals, every neighbor is tried -- one phase made longer or shorter, or two
+// adjacent phases swapped -- and the search moves to the best one, until nothing helps or the
+// evaluation budget runs out. Only the default plan of each signal is changed.
+#[derive(Serialize)]
+pub struct SignalTimingReport {
+    pub map_name: String,
+    pub scenario_name: String,
+    pub edits_name: String,
+    // Only if some candidate beat the current signals
+    pub saved_edits: bool,
+    pub rng_seed: u8,
+    pub intersections: Vec<IntersectionID>,
+    pub baseline_delay: Duration,
+    pub best_delay: Duration,
+    // Every candidate tried, in order
+    pub evaluations: Vec<Evaluation>,
+}
+
+#[derive(Serialize)]
+pub struct Evaluation {
+    // Starting from 1. Each round tries every neighbor of the best signals so far.
+    pub round: usize,
+    pub change: String,
+    pub total_delay: Duration,
+}
+
+pub fn optimize_signals(
+    sim_flags: &SimFlags,
+    scenario_name: &str,
+    intersections: Vec<IntersectionID>,
+    max_evaluations: usize,
+    // Stop each simulation here, if it's not done yet
+    time_limit: Duration,
+    edits_name: String,
+    timer: &mut Timer,
+) -> SignalTimingReport {
+    assert!(!intersections.is_empty());
+    let mut map: Map = abstutil::read_binary(&sim_flags.load, timer)
+        .expect(&format!("Couldn't load map from {}", sim_flags.load));
+    reject_timed_edits(&map, "Signal timing optimization");
+    let scenario: Scenario = abstutil::read_binary(
+        &abstutil::path1_bin(map.get_name(), abstutil::SCENARIOS, scenario_name),
+        timer,
+    )
+    .expect("loading scenario failed");
+    for i in &intersections {
+        if !map.get_i(*i).is_traffic_signal() {
+            panic!("{} isn't a traffic signal", i);
+        }
+    }
+
+    let opt = Optimizer {
+        sim_flags: sim_flags.clone(),
+        scenario,
+        time_limit,
+        orig_edits: map.get_edits().clone(),
+        edits_name: edits_name.clone(),
+        original: intersections
+            .iter()
+            .map(|i| map.get_traffic_signal(*i).clone())
+            .collect(),
+    };
+
+    let mut best = opt.original.clone();
+    timer.start("simulate the current signals");
+    let baseline_delay = opt.total_delay(&mut map, &best, "baseline", timer);
+    timer.stop("simulate the current signals");
+    let mut best_delay = baseline_delay;
+    let mut evaluations = Vec::new();
+    let mut round = 0;
+    let mut out_of_budget = false;
+    while !out_of_budget {
+        round += 1;
+        let mut improved: Option<(Vec<ControlTrafficSignal>, Duration)> = None;
+        'round: for (idx, signal) in best.iter().enumerate() {
+            for (change, candidate) in neighbors(signal) {
+                if evaluations.len() == max_evaluations {
+                    out_of_budget = true;
+                    break 'round;
+                }
+                let mut signals = best.clone();
+                signals[idx] = candidate;
+                let name = format!("{}: {}", signal.id, change);
+                timer.start(&format!("simulate {}", name));
+                let label = format!("evaluation{}", evaluations.len() + 1);
+                let delay = opt.total_delay(&mut map, &signals, &label, timer);
+                timer.stop(&format!("simulate {}", name));
+                evaluations.push(Evaluation {
+                    round,
+                    change: name,
+                    total_delay: delay,
+                });
+                if delay < improved.as_ref().map(|(_, d)| *d).unwrap_or(best_delay) {
+                    improved = Some((signals, delay));
+                }
+            }
+        }
+        if let Some((signals, delay)) = improved {
+            best = signals;
+            best_delay = delay;
+        } else {
+            break;
+        }
+    }
+
+    // Don't overwrite edits with something no better than what's already there
+    let saved_edits = best_delay < baseline_delay;
+    if saved_edits {
+        opt.apply(&mut map, &best, timer);
+        map.save_edits();
+    }
+
+    SignalTimingReport {
+        map_name: map.get_name().to_string(),
+        scenario_name: scenario_name.to_string(),
+        edits_name,
+        saved_edits,
+        rng_seed: rng_seed(sim_flags),
+        intersections,
+        baseline_delay,
+        best_delay,
+        evaluations,
+    }
+}
+
+struct Optimizer {
+    sim_flags: SimFlags,
+    scenario: Scenario,
+    time_limit: Duration,
+    orig_edits: MapEdits,
+    edits_name: String,
+    original: Vec<ControlTrafficSignal>,
+}
+
+impl Optimizer {
+    // Replaces whatever signals were tried before.
+    fn apply(&self, map: &mut Map, signals: &Vec<ControlTrafficSignal>, timer: &mut Timer) {
+        let mut edits = self.orig_edits.clone();
+        edits.edits_name = self.edits_name.clone();
+        for (signal, orig) in signals.iter().zip(self.original.iter()) {
+            if signal != orig {
+                edits
+                    .commands
+                    .push(EditCmd::ChangeTrafficSignal(signal.clone()));
+            }
+        }
+        edits.dirty = true;
+        map.apply_edits(edits, timer);
+        map.recalculate_pathfinding_after_edits(timer);
+    }
+
+    fn total_delay(
+        &self,
+        map: &mut Map,
+        signals: &Vec<ControlTrafficSignal>,
+        // Keeps the event logs of every run apart
+        label: &str,
+        timer: &mut Timer,
+    ) -> Duration {
+        self.apply(map, signals, timer);
+
+        let flags = deterministic_flags(&self.sim_flags, label);
+        let mut rng = flags.make_rng();
+        let mut sim = Sim::new(map, flags.opts.clone(), timer);
+        self.scenario.instantiate(&mut sim, map, &mut rng, timer);
+        while !sim.is_done() && sim.time() < self.time_limit {
+            let dt = Duration::seconds(30.0).min(self.time_limit - sim.time());
+            sim.step(map, dt);
+        }
+
+        // Cars queued behind the first one haven't asked to turn yet, so the intersection alone
+        // misses them. The first car's wait counts on both, so it weighs a bit more.
+        let mut total = Duration::ZERO;
+        for signal in signals {
+            total += sim.get_total_intersection_delay(signal.id);
+            for l in &map.get_i(signal.id).incoming_lanes {
+                if !map.get_l(*l).is_driving() {
+                    continue;
+                }
+                let on = Traversable::Lane(*l);
+                let free_flow = on.length(map) / on.speed_limit(map);
+                total += sim.get_analytics().car_delay(on, free_flow, sim.time());
+            }
+        }
+        total
+    }
+}
+
+// Every valid signal one change away from this one
+pub fn neighbors(signal: &ControlTrafficSignal) -> Vec<(String, ControlTrafficSignal)> {
+    let mut results = Vec::new();
+    for idx in 0..signal.phases.len() {
+        let duration = signal.phases[idx].duration;
+        if duration + STEP <= MAX_PHASE {
+            let mut longer = signal.clone();
+            longer.phases[idx].duration = duration + STEP;
+            results.push((
+                format!("phase {} {} longer", idx + 1, STEP.minimal_tostring()),
+                longer,
+            ));
+        }
+
+        // The duration of an actuated phase is its maximum green, so it can't go below the
+        // minimum.
+        let floor = match signal.phases[idx].phase_type {
+            PhaseType::Fixed => MIN_PHASE,
+            PhaseType::Actuated { min_green, .. } => MIN_PHASE.max(min_green),
+        };
+        if duration - STEP >= floor {
+            let mut shorter = signal.clone();
+            shorter.phases[idx].duration = duration - STEP;
+            results.push((
+                format!("phase {} {} shorter", idx + 1, STEP.minimal_tostring()),
+                shorter,
+            ));
+        }
+
+        if idx + 1 < signal.phases.len() {
+            let mut swapped = signal.clone();
+            swapped.phases.swap(idx, idx + 1);
+            results.push((format!("swap phases {} and {}", idx + 1, idx + 2), swapped));
+        }
+    }
+    results
+        .into_iter()
+        .filter(|(_, s)| s.clone().validate().is_ok())
+        .collect()
+}
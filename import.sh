@@ -63,6 +63,11 @@ for poly in `ls data/polygons/`; do
 	fi
 done
 
+# The tests check that both input formats produce the same map.
+if [ ! -f data/input/montlake.osm.pbf ]; then
+	osmconvert data/input/montlake.osm -o=data/input/montlake.osm.pbf
+fi
+
 if [ ! -f data/shapes/blockface.bin ]; then
 	# From http://data-seattlecitygis.opendata.arcgis.com/datasets/blockface
 	get_if_needed https://opendata.arcgis.com/datasets/a1458ad1abca41869b81f7c0db0cd777_0.kml data/input/blockface.kml;
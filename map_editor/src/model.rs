@@ -373,9 +373,10 @@ impl Model {
     pub fn swap_lanes(&mut self, id: OriginalRoad, prerender: &Prerender) {
         self.road_deleted(id);
 
+        let driving_side = self.map.driving_side;
         let (mut lanes, osm_tags) = {
             let r = self.map.roads.get_mut(&id).unwrap();
-            (r.get_spec(), &mut r.osm_tags)
+            (r.get_spec(driving_side), &mut r.osm_tags)
         };
         mem::swap(&mut lanes.fwd, &mut lanes.back);
         osm_tags.insert(osm::SYNTHETIC_LANES.to_string(), lanes.to_string());
@@ -538,7 +539,7 @@ impl Model {
             r.synthetic() && r.osm_tags.get(osm::NAME) == Some(&"Streety McStreetFace".to_string());
         let lanes_unknown = r.osm_tags.contains_key(osm::INFERRED_PARKING)
             || r.osm_tags.contains_key(osm::INFERRED_SIDEWALKS);
-        let spec = r.get_spec();
+        let spec = r.get_spec(self.map.driving_side);
         let center_pts = PolyLine::new(r.center_points.clone());
 
         let mut obj = Object::blank(ID::Road(id));
@@ -546,8 +547,9 @@ impl Model {
         for (idx, lt) in spec.fwd.iter().enumerate() {
             obj.push(
                 Model::lt_to_color(*lt, unset, lanes_unknown),
-                center_pts
-                    .shift_right(LANE_THICKNESS * (0.5 + (idx as f64)))
+                self.map
+                    .driving_side
+                    .right_shift(&center_pts, LANE_THICKNESS * (0.5 + (idx as f64)))
                     .unwrap()
                     .make_polygons(LANE_THICKNESS),
             );
@@ -561,9 +563,12 @@ impl Model {
         for (idx, lt) in spec.back.iter().enumerate() {
             obj.push(
                 Model::lt_to_color(*lt, unset, lanes_unknown),
-                center_pts
-                    .reversed()
-                    .shift_right(LANE_THICKNESS * (0.5 + (idx as f64)))
+                self.map
+                    .driving_side
+                    .right_shift(
+                        &center_pts.reversed(),
+                        LANE_THICKNESS * (0.5 + (idx as f64)),
+                    )
                     .unwrap()
                     .make_polygons(LANE_THICKNESS),
             );
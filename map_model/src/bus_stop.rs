@@ -1,4 +1,5 @@
 use crate::{LaneID, Position};
+use geom::Duration;
 use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 
@@ -43,6 +44,89 @@ impl BusStop {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BusRoute {
     pub id: BusRouteID,
+    // Shared by both directions of the same GTFS route
     pub name: String,
+    // GTFS direction_id 0. Each direction of a route is its own BusRoute.
+    pub forwards: bool,
+    // Buses loop around these forever, unless they're following the schedule.
     pub stops: Vec<BusStopID>,
+    // Sorted by departure from the first stop. Empty if there's no timetable for this route.
+    pub schedule: Vec<BusRun>,
+    // Of every vehicle serving this route, guessed from the GTFS route type
+    pub capacity: BusCapacity,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BusCapacity {
+    pub seated: usize,
+    pub standing: usize,
+}
+
+impl BusCapacity {
+    // A typical 40-foot bus
+    pub const STANDARD: BusCapacity = BusCapacity {
+        seated: 38,
+        standing: 32,
+    };
+
+    // Passenger ferries
+    pub const FERRY: BusCapacity = BusCapacity {
+        seated: 200,
+        standing: 50,
+    };
+
+    // Aerial trams and gondolas
+    pub const GONDOLA: BusCapacity = BusCapacity {
+        seated: 8,
+        standing: 0,
+    };
+
+    pub fn total(self) -> usize {
+        self.seated + self.standing
+    }
+}
+
+// One scheduled trip of a bus along part of a route.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BusRun {
+    // The scheduled departure from some of the stops, in order. The bus starts at the first and
+    // goes out of service after the last.
+    pub stop_times: Vec<(BusStopID, Duration)>,
+}
+
+impl BusRoute {
+    // Tells apart the two directions of the same GTFS route.
+    pub fn full_name(&self) -> String {
+        if self.forwards {
+            self.name.clone()
+        } else {
+            format!("{} (reverse)", self.name)
+        }
+    }
+
+    // Returns the index of the run's first stop, and how many stops the bus visits along the way,
+    // including the first and last. Stops the timetable skips are still visited. None if the run
+    // uses a stop that isn't on the route.
+    pub fn run_span(&self, run: &BusRun) -> Option<(usize, usize)> {
+        if run
+            .stop_times
+            .iter()
+            .any(|(stop, _)| !self.stops.contains(stop))
+        {
+            return None;
+        }
+        let first = self.stops.iter().position(|s| *s == run.stop_times[0].0)?;
+        let mut idx = first;
+        let mut num_stops = 1;
+        for (stop, _) in run.stop_times.iter().skip(1) {
+            loop {
+                idx = (idx + 1) % self.stops.len();
+                num_stops += 1;
+                if self.stops[idx] == *stop {
+                    break;
+                }
+            }
+        }
+        Some((first, num_stops))
+    }
 }
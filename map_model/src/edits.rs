@@ -3,6 +3,7 @@ use crate::{
     RoadID, TurnID,
 };
 use abstutil::{retain_btreemap, retain_btreeset, Timer};
+use geom::Duration;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -11,8 +12,14 @@ pub struct MapEdits {
     pub(crate) map_name: String,
     pub edits_name: String,
     pub commands: Vec<EditCmd>,
+    // Only in effect during part of the day. The simulation switches these on and off as it runs,
+    // applying them after all of the regular commands.
+    pub timed_commands: Vec<TimedEditCmd>,
+    // Indices into timed_commands that're currently applied to the map
+    #[serde(skip_serializing, skip_deserializing)]
+    pub active_timed_commands: BTreeSet<usize>,
 
-    // Derived from commands, kept up to date by update_derived
+    // Derived from commands and the active timed commands, kept up to date by update_derived
     pub original_lts: BTreeMap<LaneID, LaneType>,
     pub reversed_lanes: BTreeSet<LaneID>,
     pub changed_intersections: BTreeSet<IntersectionID>,
@@ -42,6 +49,14 @@ pub enum EditCmd {
     UncloseIntersection(IntersectionID, IntersectionType),
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TimedEditCmd {
+    pub cmd: EditCmd,
+    // Active from start (inclusive) to end (exclusive)
+    pub start: Duration,
+    pub end: Duration,
+}
+
 pub struct EditEffects {
     pub changed_lanes: BTreeSet<LaneID>,
     pub changed_roads: BTreeSet<RoadID>,
@@ -57,6 +72,8 @@ impl MapEdits {
             // Something has to fill this out later
             edits_name: "no_edits".to_string(),
             commands: Vec::new(),
+            timed_commands: Vec::new(),
+            active_timed_commands: BTreeSet::new(),
 
             original_lts: BTreeMap::new(),
             reversed_lanes: BTreeSet::new(),
@@ -66,7 +83,25 @@ impl MapEdits {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.edits_name == "no_edits" && self.commands.is_empty()
+        self.edits_name == "no_edits" && self.commands.is_empty() && self.timed_commands.is_empty()
+    }
+
+    // Everything actually applied to the map right now, in order.
+    pub fn applied_commands(&self) -> Vec<&EditCmd> {
+        let mut cmds: Vec<&EditCmd> = self.commands.iter().collect();
+        for idx in &self.active_timed_commands {
+            cmds.push(&self.timed_commands[*idx].cmd);
+        }
+        cmds
+    }
+
+    pub fn timed_commands_active_at(&self, time: Duration) -> BTreeSet<usize> {
+        self.timed_commands
+            .iter()
+            .enumerate()
+            .filter(|(_, timed)| timed.start <= time && time < timed.end)
+            .map(|(idx, _)| idx)
+            .collect()
     }
 
     pub fn load(map_name: &str, edits_name: &str, timer: &mut Timer) -> MapEdits {
@@ -82,7 +117,11 @@ impl MapEdits {
 
     // TODO Version these
     pub(crate) fn save(&mut self, map: &Map) {
-        self.compress(map);
+        // compress reads the current map, so it would make any active timed edits permanent.
+        // Which ones are active isn't saved, so the commands are fine as they are.
+        if self.active_timed_commands.is_empty() {
+            self.compress(map);
+        }
 
         assert!(self.dirty);
         assert_ne!(self.edits_name, "no_edits");
@@ -91,7 +130,7 @@ impl MapEdits {
     }
 
     pub fn original_it(&self, i: IntersectionID) -> IntersectionType {
-        for cmd in &self.commands {
+        for cmd in self.applied_commands() {
             if let EditCmd::CloseIntersection { id, orig_it } = cmd {
                 if *id == i {
                     return *orig_it;
@@ -109,7 +148,7 @@ impl MapEdits {
         let mut changed_traffic_signals = BTreeSet::new();
         let mut closed_intersections = BTreeSet::new();
 
-        for cmd in &self.commands {
+        for cmd in self.applied_commands() {
             match cmd {
                 EditCmd::ChangeLaneType { id, orig_lt, .. } => {
                     if !orig_lts.contains_key(id) {
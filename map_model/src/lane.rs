@@ -1,7 +1,6 @@
 use crate::pathfind;
 use crate::{
-    osm, BuildingID, BusStopID, DirectedRoadID, IntersectionID, Map, PathConstraints, Road, RoadID,
-    TurnType,
+    BuildingID, BusStopID, DirectedRoadID, IntersectionID, Map, PathConstraints, RoadID, TurnType,
 };
 use geom::{Angle, Distance, Line, PolyLine, Pt2D};
 use serde_derive::{Deserialize, Serialize};
@@ -76,10 +75,14 @@ pub struct Lane {
     pub parent: RoadID,
     pub lane_type: LaneType,
     pub lane_center_pts: PolyLine,
+    pub width: Distance,
 
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
 
+    // From OSM turn:lanes tags, describing the turns allowed when arriving at this intersection.
+    pub allowed_turns: Option<(IntersectionID, BTreeSet<TurnType>)>,
+
     // Sorted by distance of the front path
     pub building_paths: Vec<BuildingID>,
     pub bus_stops: Vec<BusStopID>,
@@ -199,51 +202,18 @@ impl Lane {
         }
     }
 
-    pub fn get_turn_restrictions(&self, road: &Road) -> Option<BTreeSet<TurnType>> {
-        if !self.is_driving() {
+    // The lane may have been reversed or changed to a different type since the restrictions were
+    // parsed; then they no longer apply.
+    pub fn get_turn_restrictions(&self) -> Option<&BTreeSet<TurnType>> {
+        if !self.is_driving() && !self.is_bus() {
             return None;
         }
-
-        let (dir, offset) = road.dir_and_offset(self.id);
-        let all = if dir && road.osm_tags.contains_key(osm::ENDPT_FWD) {
-            road.osm_tags
-                .get("turn:lanes:forward")
-                .or_else(|| road.osm_tags.get("turn:lanes"))?
-        } else if !dir && road.osm_tags.contains_key(osm::ENDPT_BACK) {
-            road.osm_tags.get("turn:lanes:backward")?
+        let (i, turns) = self.allowed_turns.as_ref()?;
+        if *i == self.dst_i {
+            Some(turns)
         } else {
-            return None;
-        };
-        let parts: Vec<&str> = all.split('|').collect();
-        // TODO Verify the number of lanes matches up
-        let part = parts.get(offset)?;
-        if part == &"none" {
-            return None;
+            None
         }
-        Some(
-            part.split(';')
-                .flat_map(|s| match s {
-                    "left" => vec![TurnType::Left],
-                    "right" => vec![TurnType::Right],
-                    // TODO What is blank supposed to mean? From few observed cases, same as through
-                    "through" | "" => vec![
-                        TurnType::Straight,
-                        TurnType::LaneChangeLeft,
-                        TurnType::LaneChangeRight,
-                    ],
-                    // TODO Check this more carefully
-                    "slight_right" | "slight right" | "merge_to_right" => vec![
-                        TurnType::Straight,
-                        TurnType::LaneChangeRight,
-                        TurnType::Right,
-                    ],
-                    "slight_left" | "slight left" | "merge_to_left" => {
-                        vec![TurnType::Straight, TurnType::LaneChangeLeft, TurnType::Left]
-                    }
-                    _ => panic!("What's turn restriction {}?", s),
-                })
-                .collect(),
-        )
     }
 
     pub fn get_max_cost(&self, constraints: PathConstraints, map: &Map) -> usize {
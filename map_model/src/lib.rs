@@ -10,6 +10,7 @@ mod map;
 mod neighborhood;
 pub mod osm;
 mod pathfind;
+mod rail;
 pub mod raw;
 mod road;
 mod stop_signs;
@@ -19,17 +20,21 @@ mod turn;
 
 pub use crate::area::{Area, AreaID, AreaType};
 pub use crate::building::{Building, BuildingID, FrontPath, OffstreetParking};
-pub use crate::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
-pub use crate::edits::{EditCmd, EditEffects, MapEdits};
+pub use crate::bus_stop::{BusCapacity, BusRoute, BusRouteID, BusRun, BusStop, BusStopID};
+pub use crate::edits::{EditCmd, EditEffects, MapEdits, TimedEditCmd};
 pub use crate::intersection::{Intersection, IntersectionID, IntersectionType};
 pub use crate::lane::{Lane, LaneID, LaneType, PARKING_SPOT_LENGTH};
-pub use crate::make::RoadSpec;
+pub use crate::make::{
+    get_lane_widths, get_turn_lanes, ActuatedTiming, Approach, Movement, RoadSpec, TimingPhase,
+    TimingSheet,
+};
 pub use crate::map::Map;
 pub use crate::neighborhood::{FullNeighborhoodInfo, Neighborhood, NeighborhoodBuilder};
-pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
-pub use crate::road::{DirectedRoadID, Road, RoadID};
+pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep, TransitRide};
+pub use crate::rail::{RailLine, RailLineID, Station, StationID, Track, TrainRun};
+pub use crate::road::{get_speed_limits, DirectedRoadID, Road, RoadID};
 pub use crate::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::traffic_signals::{ControlTrafficSignal, Phase};
+pub use crate::traffic_signals::{ControlTrafficSignal, Phase, PhaseType, SignalPlan};
 pub use crate::traversable::{Position, Traversable};
 pub use crate::turn::{Turn, TurnGroup, TurnGroupID, TurnID, TurnPriority, TurnType};
 use abstutil::Cloneable;
@@ -1,10 +1,10 @@
 use crate::make::sidewalk_finder::find_sidewalk_points;
 use crate::{
-    BusRoute, BusRouteID, BusStop, BusStopID, LaneID, LaneType, Map, PathConstraints, PathRequest,
-    Position,
+    BusCapacity, BusRoute, BusRouteID, BusRun, BusStop, BusStopID, LaneID, LaneType, Map,
+    PathConstraints, PathRequest, Position,
 };
 use abstutil::{MultiMap, Timer};
-use geom::{Bounds, Distance, GPSBounds, HashablePt2D, Pt2D};
+use geom::{Bounds, Distance, Duration, GPSBounds, HashablePt2D, Pt2D};
 use gtfs;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
@@ -17,16 +17,12 @@ pub fn make_bus_stops(
 ) -> (BTreeMap<BusStopID, BusStop>, Vec<BusRoute>) {
     timer.start("make bus stops");
     let mut bus_stop_pts: HashSet<HashablePt2D> = HashSet::new();
-    let mut route_lookups: HashMap<String, Vec<HashablePt2D>> = HashMap::new();
     for route in bus_routes {
-        for gps in &route.stops {
-            if let Some(pt) = Pt2D::from_gps(*gps, gps_bounds) {
-                let hash_pt = pt.to_hashable();
-                bus_stop_pts.insert(hash_pt);
-                route_lookups
-                    .entry(route.name.clone())
-                    .or_insert_with(Vec::new)
-                    .push(hash_pt);
+        for dir in &route.directions {
+            for stop in &dir.stops {
+                if let Some(pt) = Pt2D::from_gps(stop.pt, gps_bounds) {
+                    bus_stop_pts.insert(pt.to_hashable());
+                }
             }
         }
     }
@@ -78,27 +74,64 @@ pub fn make_bus_stops(
         }
     }
 
+    // Each direction of a GTFS route becomes its own BusRoute, so buses don't try to serve both
+    // directions in one loop.
     let mut routes: Vec<BusRoute> = Vec::new();
     for route in bus_routes {
-        let route_name = route.name.to_string();
-        let stops: Vec<BusStopID> = route_lookups
-            .remove(&route_name)
-            .unwrap_or_else(Vec::new)
-            .into_iter()
-            .filter_map(|pt| point_to_stop_id.get(&pt))
-            .cloned()
-            .collect();
-        let id = BusRouteID(routes.len());
-        routes.push(BusRoute {
-            id,
-            name: route_name.to_string(),
-            stops,
-        });
+        for dir in &route.directions {
+            let stops: Vec<BusStopID> = dir
+                .stops
+                .iter()
+                .filter_map(|stop| {
+                    let pt = Pt2D::from_gps(stop.pt, gps_bounds)?;
+                    point_to_stop_id.get(&pt.to_hashable()).cloned()
+                })
+                .collect();
+
+            let mut schedule: Vec<BusRun> = Vec::new();
+            for trip in &dir.trips {
+                for run in trip.runs() {
+                    // Only keep the part of the run inside this map
+                    let stop_times: Vec<(BusStopID, Duration)> = run
+                        .into_iter()
+                        .filter_map(|st| {
+                            let pt = Pt2D::from_gps(st.stop.pt, gps_bounds)?;
+                            let id = point_to_stop_id.get(&pt.to_hashable())?;
+                            Some((*id, st.departure))
+                        })
+                        .collect();
+                    if stop_times.len() >= 2 {
+                        schedule.push(BusRun { stop_times });
+                    }
+                }
+            }
+            schedule.sort_by_key(|run| run.stop_times[0].1);
+
+            let id = BusRouteID(routes.len());
+            routes.push(BusRoute {
+                id,
+                name: route.name.to_string(),
+                forwards: dir.forwards,
+                stops,
+                schedule,
+                capacity: capacity_for(&route.route_type),
+            });
+        }
     }
     timer.stop("make bus stops");
     (bus_stops, routes)
 }
 
+// GTFS doesn't say exactly what vehicles serve a route, just what kind of route it is. This
+// understands the basic and the extended route types.
+fn capacity_for(route_type: &gtfs::RouteType) -> BusCapacity {
+    match route_type {
+        gtfs::RouteType::Other(4) | gtfs::RouteType::Other(1000..=1299) => BusCapacity::FERRY,
+        gtfs::RouteType::Other(6) | gtfs::RouteType::Other(1300..=1399) => BusCapacity::GONDOLA,
+        _ => BusCapacity::STANDARD,
+    }
+}
+
 pub fn fix_bus_route(map: &Map, r: &mut BusRoute) -> bool {
     // Trim out stops if needed; map borders sometimes mean some paths don't work.
     let mut stops = Vec::new();
@@ -120,6 +153,15 @@ pub fn fix_bus_route(map: &Map, r: &mut BusRoute) -> bool {
         stops.pop();
     }
     r.stops = stops;
+
+    // Runs can only use the remaining stops.
+    let stops = &r.stops;
+    for run in &mut r.schedule {
+        run.stop_times.retain(|(stop, _)| stops.contains(stop));
+    }
+    r.schedule.retain(|run| run.stop_times.len() >= 2);
+    r.schedule.sort_by_key(|run| run.stop_times[0].1);
+
     r.stops.len() >= 2
 }
 
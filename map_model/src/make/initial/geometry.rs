@@ -32,10 +32,10 @@ pub fn intersection_polygon(
 
             let (line, width_normal, width_reverse) = if r.src_i == i.id {
                 road_endpts.push(r.trimmed_center_pts.first_pt());
-                (r.trimmed_center_pts.reversed(), r.back_width, r.fwd_width)
+                (r.trimmed_center_pts.reversed(), r.left_width, r.right_width)
             } else if r.dst_i == i.id {
                 road_endpts.push(r.trimmed_center_pts.last_pt());
-                (r.trimmed_center_pts.clone(), r.fwd_width, r.back_width)
+                (r.trimmed_center_pts.clone(), r.right_width, r.left_width)
             } else {
                 panic!("Incident road {} doesn't have an endpoint at {}", id, i.id);
             };
@@ -241,26 +241,26 @@ fn generalized_trim_back(
         if r.dst_i == i {
             endpoints.push(
                 r.trimmed_center_pts
-                    .shift_right(r.fwd_width)
+                    .shift_right(r.right_width)
                     .with_context(timer, format!("main polygon endpoints from {}", r.id))
                     .last_pt(),
             );
             endpoints.push(
                 r.trimmed_center_pts
-                    .shift_left(r.back_width)
+                    .shift_left(r.left_width)
                     .with_context(timer, format!("main polygon endpoints from {}", r.id))
                     .last_pt(),
             );
         } else {
             endpoints.push(
                 r.trimmed_center_pts
-                    .shift_left(r.back_width)
+                    .shift_left(r.left_width)
                     .with_context(timer, format!("main polygon endpoints from {}", r.id))
                     .first_pt(),
             );
             endpoints.push(
                 r.trimmed_center_pts
-                    .shift_right(r.fwd_width)
+                    .shift_right(r.right_width)
                     .with_context(timer, format!("main polygon endpoints from {}", r.id))
                     .first_pt(),
             );
@@ -1,10 +1,15 @@
-use crate::{osm, LaneType};
+use crate::raw::DrivingSide;
+use crate::{osm, LaneType, TurnType, LANE_THICKNESS};
+use geom::Distance;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::{fmt, iter};
 
 // (original direction, reversed direction)
-pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Vec<LaneType>) {
+pub fn get_lane_types(
+    osm_tags: &BTreeMap<String, String>,
+    driving_side: DrivingSide,
+) -> (Vec<LaneType>, Vec<LaneType>) {
     if let Some(s) = osm_tags.get(osm::SYNTHETIC_LANES) {
         if let Some(spec) = RoadSpec::parse(s.to_string()) {
             return (spec.fwd, spec.back);
@@ -17,10 +22,17 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
     if osm_tags.get("junction") == Some(&"roundabout".to_string()) {
         return (vec![LaneType::Driving, LaneType::Sidewalk], Vec::new());
     }
-    if osm_tags.get(osm::HIGHWAY) == Some(&"footway".to_string()) {
-        return (vec![LaneType::Sidewalk], Vec::new());
+    if let Some(lanes) = off_street_lanes(osm_tags) {
+        return lanes;
     }
 
+    // Tags like sidewalk=right refer to the physical side of the way. When driving on the left, the
+    // forwards lanes are on the left side.
+    let (fwd_side_name, back_side_name) = match driving_side {
+        DrivingSide::Right => ("right", "left"),
+        DrivingSide::Left => ("left", "right"),
+    };
+
     // TODO Reversible roads should be handled differently?
     let oneway = osm_tags.get("oneway") == Some(&"yes".to_string())
         || osm_tags.get("oneway") == Some(&"reversible".to_string());
@@ -103,10 +115,10 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
             back_side.push(LaneType::Biking);
         }
     } else {
-        if osm_tags.get("cycleway:right") == Some(&"lane".to_string()) {
+        if osm_tags.get(&format!("cycleway:{}", fwd_side_name)) == Some(&"lane".to_string()) {
             fwd_side.push(LaneType::Biking);
         }
-        if osm_tags.get("cycleway:left") == Some(&"lane".to_string()) {
+        if osm_tags.get(&format!("cycleway:{}", back_side_name)) == Some(&"lane".to_string()) {
             back_side.push(LaneType::Biking);
         }
     }
@@ -116,9 +128,9 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
             || value == Some(&"diagonal".to_string())
             || value == Some(&"perpendicular".to_string())
     }
-    let parking_lane_fwd = has_parking(osm_tags.get(osm::PARKING_RIGHT))
+    let parking_lane_fwd = has_parking(osm_tags.get(&format!("parking:lane:{}", fwd_side_name)))
         || has_parking(osm_tags.get(osm::PARKING_BOTH));
-    let parking_lane_back = has_parking(osm_tags.get(osm::PARKING_LEFT))
+    let parking_lane_back = has_parking(osm_tags.get(&format!("parking:lane:{}", back_side_name)))
         || has_parking(osm_tags.get(osm::PARKING_BOTH));
     if parking_lane_fwd {
         fwd_side.push(LaneType::Parking);
@@ -127,21 +139,249 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
         back_side.push(LaneType::Parking);
     }
 
-    // TODO Need to snap separate sidewalks to ways. Until then, just do this.
-    if osm_tags.get(osm::SIDEWALK) == Some(&"both".to_string())
-        || osm_tags.get(osm::SIDEWALK) == Some(&"separate".to_string())
-    {
-        fwd_side.push(LaneType::Sidewalk);
-        back_side.push(LaneType::Sidewalk);
-    } else if osm_tags.get(osm::SIDEWALK) == Some(&"right".to_string()) {
+    // Sidewalks mapped as separate ways aren't imported (see convert_osm), so a road pointing at
+    // one with "separate" gets a sidewalk of its own on that side.
+    let sidewalk = |side: &str| -> bool {
+        let value = osm_tags.get(osm::SIDEWALK).map(|v| v.as_str());
+        value == Some("both")
+            || value == Some("separate")
+            || value == Some(side)
+            || ["both", side].iter().any(|key| {
+                let value = osm_tags
+                    .get(&format!("sidewalk:{}", key))
+                    .map(|v| v.as_str());
+                value == Some("yes") || value == Some("separate")
+            })
+    };
+    if sidewalk(fwd_side_name) {
         fwd_side.push(LaneType::Sidewalk);
-    } else if osm_tags.get(osm::SIDEWALK) == Some(&"left".to_string()) {
+    }
+    if sidewalk(back_side_name) {
         back_side.push(LaneType::Sidewalk);
     }
 
     (fwd_side, back_side)
 }
 
+// Widths in the same order as the lane types. A road's total width is spread over everything except
+// the sidewalks, then width:lanes, width:lanes:forward, and width:lanes:backward override the
+// driving and bus lanes. Anything unspecified gets LANE_THICKNESS.
+pub fn get_lane_widths(
+    osm_tags: &BTreeMap<String, String>,
+    driving_side: DrivingSide,
+    fwd: &[LaneType],
+    back: &[LaneType],
+) -> (Vec<Distance>, Vec<Distance>) {
+    let mut fwd_widths = vec![LANE_THICKNESS; fwd.len()];
+    let mut back_widths = vec![LANE_THICKNESS; back.len()];
+
+    if let Some(total) = osm_tags.get(osm::WIDTH).and_then(|w| osm::parse_width(w)) {
+        // Paths without a carriageway only have sidewalks; the width covers them instead.
+        let only_sidewalks = fwd.iter().chain(back).all(|lt| *lt == LaneType::Sidewalk);
+        let covered = |lt: &LaneType| only_sidewalks || *lt != LaneType::Sidewalk;
+        let num = fwd.iter().chain(back).filter(|lt| covered(*lt)).count();
+        let each = clamp_lane_width(total / (num as f64));
+        for (w, lt) in fwd_widths.iter_mut().zip(fwd) {
+            if covered(lt) {
+                *w = each;
+            }
+        }
+        for (w, lt) in back_widths.iter_mut().zip(back) {
+            if covered(lt) {
+                *w = each;
+            }
+        }
+    }
+
+    let fwd_order: Vec<(bool, usize)> = motor_lanes_in_travel_order(fwd, driving_side)
+        .into_iter()
+        .map(|i| (true, i))
+        .collect();
+    let back_order: Vec<(bool, usize)> = motor_lanes_in_travel_order(back, driving_side)
+        .into_iter()
+        .map(|i| (false, i))
+        .collect();
+    // width:lanes describes the whole way, left to right facing forwards.
+    let whole_way: Vec<(bool, usize)> = match driving_side {
+        DrivingSide::Right => back_order.iter().rev().chain(&fwd_order).cloned().collect(),
+        DrivingSide::Left => fwd_order
+            .iter()
+            .chain(back_order.iter().rev())
+            .cloned()
+            .collect(),
+    };
+
+    for (key, order) in vec![
+        ("width:lanes", whole_way),
+        ("width:lanes:forward", fwd_order),
+        ("width:lanes:backward", back_order),
+    ] {
+        if let Some(value) = osm_tags.get(key) {
+            let values: Vec<&str> = value.split('|').collect();
+            if values.len() != order.len() {
+                continue;
+            }
+            for ((is_fwd, idx), width) in order.into_iter().zip(values) {
+                if let Some(w) = osm::parse_width(width) {
+                    if is_fwd {
+                        fwd_widths[idx] = clamp_lane_width(w);
+                    } else {
+                        back_widths[idx] = clamp_lane_width(w);
+                    }
+                }
+            }
+        }
+    }
+
+    (fwd_widths, back_widths)
+}
+
+// Don't let bad tags produce absurd geometry.
+fn clamp_lane_width(width: Distance) -> Distance {
+    width.max(Distance::meters(1.0)).min(Distance::meters(6.0))
+}
+
+// Allowed turns for each lane, in the same order as the lane types. turn:lanes tags only describe
+// the end of the OSM way, so roads in the middle of a split way don't get any. A lane without
+// restrictions is None.
+pub fn get_turn_lanes(
+    osm_tags: &BTreeMap<String, String>,
+    driving_side: DrivingSide,
+    fwd: &[LaneType],
+    back: &[LaneType],
+) -> (
+    Vec<Option<BTreeSet<TurnType>>>,
+    Vec<Option<BTreeSet<TurnType>>>,
+) {
+    let mut fwd_turns = vec![None; fwd.len()];
+    let mut back_turns = vec![None; back.len()];
+
+    if osm_tags.contains_key(osm::ENDPT_FWD) {
+        if let Some(value) = osm_tags
+            .get("turn:lanes:forward")
+            .or_else(|| osm_tags.get("turn:lanes"))
+        {
+            parse_turn_lanes(value, driving_side, fwd, &mut fwd_turns);
+        }
+    }
+    if osm_tags.contains_key(osm::ENDPT_BACK) {
+        if let Some(value) = osm_tags.get("turn:lanes:backward") {
+            parse_turn_lanes(value, driving_side, back, &mut back_turns);
+        }
+    }
+
+    (fwd_turns, back_turns)
+}
+
+fn parse_turn_lanes(
+    value: &str,
+    driving_side: DrivingSide,
+    types: &[LaneType],
+    turns: &mut Vec<Option<BTreeSet<TurnType>>>,
+) {
+    let order = motor_lanes_in_travel_order(types, driving_side);
+    let parts: Vec<&str> = value.split('|').collect();
+    // If the tag disagrees with the lanes we inferred, don't guess which lanes it meant.
+    if parts.len() != order.len() {
+        return;
+    }
+    for (idx, part) in order.into_iter().zip(parts) {
+        if part == "none" {
+            continue;
+        }
+        let allowed: BTreeSet<TurnType> = part
+            .split(';')
+            .flat_map(|s| match s.trim() {
+                "left" | "sharp_left" => vec![TurnType::Left],
+                "right" | "sharp_right" => vec![TurnType::Right],
+                // TODO What is blank supposed to mean? From few observed cases, same as through
+                "through" | "" => vec![
+                    TurnType::Straight,
+                    TurnType::LaneChangeLeft,
+                    TurnType::LaneChangeRight,
+                ],
+                // TODO Check this more carefully
+                "slight_right" | "slight right" | "merge_to_right" => vec![
+                    TurnType::Straight,
+                    TurnType::LaneChangeRight,
+                    TurnType::Right,
+                ],
+                "slight_left" | "slight left" | "merge_to_left" => {
+                    vec![TurnType::Straight, TurnType::LaneChangeLeft, TurnType::Left]
+                }
+                // U-turns cross the center line.
+                "reverse" => match driving_side {
+                    DrivingSide::Right => vec![TurnType::Left],
+                    DrivingSide::Left => vec![TurnType::Right],
+                },
+                // Unknown values don't restrict anything.
+                _ => Vec::new(),
+            })
+            .collect();
+        if !allowed.is_empty() {
+            turns[idx] = Some(allowed);
+        }
+    }
+}
+
+// OSM lists per-lane values left to right, facing the direction of travel, and only counts lanes
+// for motor vehicles. The lane types go from the center out, which is the same order only when
+// driving on the right. Returns indices into types.
+fn motor_lanes_in_travel_order(types: &[LaneType], driving_side: DrivingSide) -> Vec<usize> {
+    let mut indices: Vec<usize> = types
+        .iter()
+        .enumerate()
+        .filter(|(_, lt)| **lt == LaneType::Driving || **lt == LaneType::Bus)
+        .map(|(idx, _)| idx)
+        .collect();
+    if driving_side == DrivingSide::Left {
+        indices.reverse();
+    }
+    indices
+}
+
+// Footways, cycleways, and other paths without any cars. Pedestrians get a sidewalk on both sides,
+// so these connect to the rest of the walking network just like a normal road's sidewalks.
+fn off_street_lanes(osm_tags: &BTreeMap<String, String>) -> Option<(Vec<LaneType>, Vec<LaneType>)> {
+    fn allowed(value: Option<&String>) -> bool {
+        value == Some(&"yes".to_string())
+            || value == Some(&"designated".to_string())
+            || value == Some(&"permissive".to_string())
+    }
+    let not_banned = |key: &str| osm_tags.get(key) != Some(&"no".to_string());
+
+    let (bikes, peds) = match osm_tags.get(osm::HIGHWAY)?.as_ref() {
+        "footway" | "pedestrian" => (allowed(osm_tags.get("bicycle")), true),
+        "steps" => (false, true),
+        "cycleway" => (true, allowed(osm_tags.get("foot"))),
+        // Shared by everybody, unless tagged otherwise
+        "path" => {
+            let bikes = not_banned("bicycle");
+            // Don't wind up with no lanes at all
+            (bikes, !bikes || not_banned("foot"))
+        }
+        _ => {
+            return None;
+        }
+    };
+
+    let mut fwd_side = Vec::new();
+    let mut back_side = Vec::new();
+    if bikes {
+        fwd_side.push(LaneType::Biking);
+        if osm_tags.get("oneway") != Some(&"yes".to_string())
+            && osm_tags.get("oneway:bicycle") != Some(&"yes".to_string())
+        {
+            back_side.push(LaneType::Biking);
+        }
+    }
+    if peds {
+        fwd_side.push(LaneType::Sidewalk);
+        back_side.push(LaneType::Sidewalk);
+    }
+    Some((fwd_side, back_side))
+}
+
 // This is a convenient way for map_editor to plumb instructions here.
 #[derive(Serialize, Deserialize)]
 pub struct RoadSpec {
@@ -2,8 +2,8 @@ mod geometry;
 pub mod lane_specs;
 
 pub use self::geometry::intersection_polygon;
-use crate::raw::{OriginalIntersection, OriginalRoad, RawMap, RawRoad};
-use crate::{IntersectionType, LaneType, LANE_THICKNESS};
+use crate::raw::{DrivingSide, OriginalIntersection, OriginalRoad, RawMap, RawRoad};
+use crate::{IntersectionType, LaneType, TurnType};
 use abstutil::Timer;
 use geom::{Bounds, Distance, PolyLine, Pt2D};
 use std::collections::{BTreeMap, BTreeSet};
@@ -23,23 +23,28 @@ pub struct Road {
     pub dst_i: OriginalIntersection,
     pub original_center_pts: PolyLine,
     pub trimmed_center_pts: PolyLine,
-    pub fwd_width: Distance,
-    pub back_width: Distance,
+    // Physical width on each side of the center line, facing along the original points
+    pub right_width: Distance,
+    pub left_width: Distance,
     pub lane_specs: Vec<LaneSpec>,
 }
 
 impl Road {
-    pub fn new(id: OriginalRoad, r: &RawRoad) -> Road {
-        let lane_specs = get_lane_specs(&r.osm_tags);
+    pub fn new(id: OriginalRoad, r: &RawRoad, driving_side: DrivingSide) -> Road {
+        let lane_specs = get_lane_specs(&r.osm_tags, driving_side);
         let mut fwd_width = Distance::ZERO;
         let mut back_width = Distance::ZERO;
         for l in &lane_specs {
             if l.reverse_pts {
-                back_width += LANE_THICKNESS;
+                back_width += l.width;
             } else {
-                fwd_width += LANE_THICKNESS;
+                fwd_width += l.width;
             }
         }
+        let (right_width, left_width) = match driving_side {
+            DrivingSide::Right => (fwd_width, back_width),
+            DrivingSide::Left => (back_width, fwd_width),
+        };
 
         let center_pts = PolyLine::new(r.center_points.clone());
         Road {
@@ -48,8 +53,8 @@ impl Road {
             dst_i: id.i2,
             original_center_pts: center_pts.clone(),
             trimmed_center_pts: center_pts,
-            fwd_width,
-            back_width,
+            right_width,
+            left_width,
             lane_specs,
         }
     }
@@ -92,7 +97,7 @@ impl InitialMap {
             m.intersections.get_mut(&id.i1).unwrap().roads.insert(*id);
             m.intersections.get_mut(&id.i2).unwrap().roads.insert(*id);
 
-            m.roads.insert(*id, Road::new(*id, r));
+            m.roads.insert(*id, Road::new(*id, r, raw.driving_side));
         }
 
         timer.start_iter("find each intersection polygon", m.intersections.len());
@@ -109,22 +114,39 @@ impl InitialMap {
 pub struct LaneSpec {
     pub lane_type: LaneType,
     pub reverse_pts: bool,
+    pub width: Distance,
+    pub allowed_turns: Option<BTreeSet<TurnType>>,
 }
 
-pub fn get_lane_specs(osm_tags: &BTreeMap<String, String>) -> Vec<LaneSpec> {
-    let (side1_types, side2_types) = lane_specs::get_lane_types(osm_tags);
+pub fn get_lane_specs(
+    osm_tags: &BTreeMap<String, String>,
+    driving_side: DrivingSide,
+) -> Vec<LaneSpec> {
+    let (side1_types, side2_types) = lane_specs::get_lane_types(osm_tags, driving_side);
+    let (side1_widths, side2_widths) =
+        lane_specs::get_lane_widths(osm_tags, driving_side, &side1_types, &side2_types);
+    let (side1_turns, side2_turns) =
+        lane_specs::get_turn_lanes(osm_tags, driving_side, &side1_types, &side2_types);
 
     let mut specs: Vec<LaneSpec> = Vec::new();
-    for lane_type in side1_types {
+    for ((lane_type, width), allowed_turns) in
+        side1_types.into_iter().zip(side1_widths).zip(side1_turns)
+    {
         specs.push(LaneSpec {
             lane_type,
             reverse_pts: false,
+            width,
+            allowed_turns,
         });
     }
-    for lane_type in side2_types {
+    for ((lane_type, width), allowed_turns) in
+        side2_types.into_iter().zip(side2_widths).zip(side2_turns)
+    {
         specs.push(LaneSpec {
             lane_type,
             reverse_pts: true,
+            width,
+            allowed_turns,
         });
     }
     if specs.is_empty() {
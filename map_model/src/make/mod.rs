@@ -1,12 +1,18 @@
 mod buildings;
 mod bus_stops;
 pub mod initial;
+mod rail;
 mod remove_disconnected;
 mod sidewalk_finder;
+mod signal_timing;
 mod turns;
 
 pub use self::buildings::make_all_buildings;
 pub use self::bus_stops::{fix_bus_route, make_bus_stops};
-pub use self::initial::lane_specs::{get_lane_types, RoadSpec};
+pub use self::initial::lane_specs::{get_lane_types, get_lane_widths, get_turn_lanes, RoadSpec};
+pub use self::rail::make_rail_network;
 pub use self::remove_disconnected::remove_disconnected_roads;
+pub use self::signal_timing::{
+    load_signal_timing, ActuatedTiming, Approach, Movement, TimingPhase, TimingSheet,
+};
 pub use self::turns::make_all_turns;
@@ -0,0 +1,203 @@
+use crate::make::sidewalk_finder::find_sidewalk_points;
+use crate::{Map, RailLine, RailLineID, Station, StationID, Track, TrainRun};
+use abstutil::Timer;
+use geom::{Bounds, Distance, Duration, GPSBounds, HashablePt2D, PolyLine, Pt2D, Speed};
+use gtfs;
+use std::collections::{BTreeMap, HashMap};
+
+// Stations can be further from the street than bus stops, especially when they're underground.
+const MAX_DIST_TO_SIDEWALK: Distance = Distance::const_meters(50.0);
+// Only used when the timetable doesn't say how long it takes between two stations.
+const DEFAULT_TRAIN_SPEED: Speed = Speed::const_meters_per_second(15.0);
+
+pub fn make_rail_network(
+    map: &Map,
+    rail_routes: &Vec<gtfs::Route>,
+    gps_bounds: &GPSBounds,
+    bounds: &Bounds,
+    timer: &mut Timer,
+) -> (Vec<Station>, Vec<RailLine>) {
+    timer.start("make rail network");
+    // Keyed by GTFS stop ID
+    let mut platforms: BTreeMap<String, (String, HashablePt2D)> = BTreeMap::new();
+    for route in rail_routes {
+        for stop in route.all_stops() {
+            if let Some(pt) = Pt2D::from_gps(stop.pt, gps_bounds) {
+                platforms.insert(stop.id.clone(), (stop.name.clone(), pt.to_hashable()));
+            }
+        }
+    }
+    let sidewalk_pts = find_sidewalk_points(
+        bounds,
+        platforms.values().map(|(_, pt)| *pt).collect(),
+        map.all_lanes(),
+        MAX_DIST_TO_SIDEWALK,
+        timer,
+    );
+
+    let mut stations: Vec<Station> = Vec::new();
+    let mut stop_to_station: HashMap<String, StationID> = HashMap::new();
+    for (stop_id, (name, pt)) in platforms {
+        if let Some(pos) = sidewalk_pts.get(&pt) {
+            let id = StationID(stations.len());
+            stations.push(Station {
+                id,
+                name,
+                pt: pt.to_pt2d(),
+                sidewalk_pos: *pos,
+            });
+            stop_to_station.insert(stop_id, id);
+        } else {
+            timer.warn(format!(
+                "Station {} ({}) isn't close to any sidewalk",
+                name, stop_id
+            ));
+        }
+    }
+
+    let mut lines: Vec<RailLine> = Vec::new();
+    for route in rail_routes {
+        if let Some(mut line) = make_line(route, &stations, &stop_to_station, gps_bounds) {
+            line.id = RailLineID(lines.len());
+            lines.push(line);
+        } else {
+            timer.warn(format!("Skipping rail line {}", route.name));
+        }
+    }
+    timer.stop("make rail network");
+    (stations, lines)
+}
+
+fn make_line(
+    route: &gtfs::Route,
+    stations: &Vec<Station>,
+    stop_to_station: &HashMap<String, StationID>,
+    gps_bounds: &GPSBounds,
+) -> Option<RailLine> {
+    let mut station_ids: Vec<StationID> = Vec::new();
+    // For each direction, where its stations start in the loop, and the stations
+    let mut directions: Vec<(usize, Vec<StationID>)> = Vec::new();
+    let mut shapes: Vec<Vec<Pt2D>> = Vec::new();
+    let mut travel_times: HashMap<(StationID, StationID), Duration> = HashMap::new();
+    for dir in &route.directions {
+        let start = station_ids.len();
+        let mut dir_stations = Vec::new();
+        for stop in &dir.stops {
+            if let Some(id) = stop_to_station.get(&stop.id) {
+                // Both directions might share the platform at the end of the line.
+                if station_ids.last() != Some(id) {
+                    station_ids.push(*id);
+                    dir_stations.push(*id);
+                }
+            }
+        }
+        directions.push((start, dir_stations));
+        shapes.push(
+            dir.shape
+                .iter()
+                .filter_map(|pt| Pt2D::from_gps(*pt, gps_bounds))
+                .collect(),
+        );
+
+        // The trip visiting the most stations is the best guess of how long each hop takes.
+        if let Some(trip) = dir.trips.iter().max_by_key(|t| t.stop_times.len()) {
+            let times: Vec<(StationID, Duration, Duration)> = trip
+                .stop_times
+                .iter()
+                .filter_map(|st| {
+                    let id = stop_to_station.get(&st.stop.id)?;
+                    Some((*id, st.arrival, st.departure))
+                })
+                .collect();
+            for pair in times.windows(2) {
+                if pair[1].1 > pair[0].2 {
+                    travel_times.insert((pair[0].0, pair[1].0), pair[1].1 - pair[0].2);
+                }
+            }
+        }
+    }
+    if station_ids.len() > 1 && station_ids.last() == station_ids.first() {
+        station_ids.pop();
+    }
+    if station_ids.len() < 2 {
+        return None;
+    }
+
+    let mut tracks: Vec<Track> = Vec::new();
+    for idx in 0..station_ids.len() {
+        let next_idx = (idx + 1) % station_ids.len();
+        let (s1, s2) = (station_ids[idx], station_ids[next_idx]);
+        // Only follow a shape between stations in the same direction. Turning around at the ends
+        // of the line is just a straight line.
+        let shape = directions
+            .iter()
+            .zip(shapes.iter())
+            .find(|((start, dir_stations), _)| {
+                idx >= *start && next_idx > idx && next_idx < start + dir_stations.len()
+            })
+            .map(|(_, shape)| shape);
+        let geom = track_geom(shape, stations[s1.0].pt, stations[s2.0].pt)?;
+        let travel_time = travel_times
+            .get(&(s1, s2))
+            .cloned()
+            .unwrap_or_else(|| geom.length() / DEFAULT_TRAIN_SPEED);
+        tracks.push(Track { geom, travel_time });
+    }
+
+    let mut schedule: Vec<TrainRun> = Vec::new();
+    for (dir, (start, dir_stations)) in route.directions.iter().zip(directions.iter()) {
+        for trip in &dir.trips {
+            for run in trip.runs() {
+                // Only keep the part of the run inside this map
+                let mut first: Option<(usize, Duration)> = None;
+                let mut last = 0;
+                for st in run {
+                    if let Some(id) = stop_to_station.get(&st.stop.id) {
+                        if let Some(pos) = dir_stations.iter().skip(last).position(|s| s == id) {
+                            last += pos;
+                            if first.is_none() {
+                                first = Some((last, st.departure));
+                            }
+                        }
+                    }
+                }
+                if let Some((first, departure)) = first {
+                    if last > first {
+                        schedule.push(TrainRun {
+                            first_station: start + first,
+                            departure,
+                            num_stations: last - first + 1,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    schedule.sort_by_key(|run| run.departure);
+
+    Some(RailLine {
+        id: RailLineID(0),
+        name: route.name.clone(),
+        stations: station_ids,
+        tracks,
+        schedule,
+    })
+}
+
+// Follow the shape between two stations if possible, otherwise just go straight.
+fn track_geom(shape: Option<&Vec<Pt2D>>, pt1: Pt2D, pt2: Pt2D) -> Option<PolyLine> {
+    if let Some(shape) = shape {
+        let closest = |pt: Pt2D| (0..shape.len()).min_by_key(|i| shape[*i].dist_to(pt));
+        if let (Some(i1), Some(i2)) = (closest(pt1), closest(pt2)) {
+            if i1 + 1 < i2 {
+                let mut pts = vec![pt1];
+                pts.extend(shape[i1 + 1..i2].iter().cloned());
+                pts.push(pt2);
+                if let Some(pl) = PolyLine::maybe_new(pts) {
+                    return Some(pl);
+                }
+            }
+        }
+    }
+    PolyLine::maybe_new(vec![pt1, pt2])
+}
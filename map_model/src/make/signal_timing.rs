@@ -0,0 +1,254 @@
+use crate::raw::DrivingSide;
+use crate::{
+    ControlTrafficSignal, IntersectionID, IntersectionType, Map, Phase, PhaseType, RoadID,
+    TurnGroup, TurnGroupID, TurnType,
+};
+use abstutil::Timer;
+use geom::Duration;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Timing published by a city for one signalized intersection. Everything is keyed by OSM IDs, so
+// the same sheets work across imports. See docs/new_city.md for the format.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TimingSheet {
+    pub osm_node_id: i64,
+    pub offset: Duration,
+    pub phases: Vec<TimingPhase>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TimingPhase {
+    // For actuated phases, this is the maximum green time.
+    pub duration: Duration,
+    pub protected: Vec<Movement>,
+    pub permitted: Vec<Movement>,
+    // Crosswalks across these approaches get a walk signal.
+    pub crosswalks: Vec<Approach>,
+    // If this is missing, the phase has a fixed duration.
+    pub actuated: Option<ActuatedTiming>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ActuatedTiming {
+    pub min_green: Duration,
+    pub gap: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Movement {
+    pub from: Approach,
+    pub to: Approach,
+}
+
+// One road touching the intersection
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Approach {
+    pub osm_way_id: i64,
+    // When an OSM way continues through the intersection, both pieces have the same ID. Then this
+    // says which piece: "north", "south", "east", or "west" of the intersection.
+    pub side: Option<String>,
+}
+
+// Matches the map's timing sheets (if there are any) to intersections, keeping only the ones that
+// produce a valid signal. Everything else is reported as a warning.
+pub fn load_signal_timing(map: &Map, timer: &mut Timer) -> BTreeMap<IntersectionID, TimingSheet> {
+    let mut results = BTreeMap::new();
+    let path = abstutil::path_signal_timing(map.get_name());
+    if !std::path::Path::new(&path).exists() {
+        return results;
+    }
+    let sheets: Vec<TimingSheet> = abstutil::read_json(&path, timer)
+        .unwrap_or_else(|err| panic!("Couldn't read timing sheets from {}: {}", path, err));
+
+    let mut osm_node_to_id: BTreeMap<i64, IntersectionID> = BTreeMap::new();
+    for i in map.all_intersections() {
+        osm_node_to_id.insert(i.orig_id.osm_node_id, i.id);
+    }
+
+    let num_sheets = sheets.len();
+    let mut problems = Vec::new();
+    for sheet in sheets {
+        let id = if let Some(id) = osm_node_to_id.get(&sheet.osm_node_id) {
+            *id
+        } else {
+            problems.push(format!(
+                "Timing sheet for OSM node {} doesn't match any intersection",
+                sheet.osm_node_id
+            ));
+            continue;
+        };
+        if map.get_i(id).intersection_type != IntersectionType::TrafficSignal {
+            problems.push(format!(
+                "Timing sheet for OSM node {} matches {}, which isn't a traffic signal",
+                sheet.osm_node_id, id
+            ));
+            continue;
+        }
+        if results.contains_key(&id) {
+            problems.push(format!(
+                "Multiple timing sheets for OSM node {}",
+                sheet.osm_node_id
+            ));
+            continue;
+        }
+        match sheet.to_signal(map, id) {
+            Ok(_) => {
+                results.insert(id, sheet);
+            }
+            Err(err) => {
+                problems.push(format!(
+                    "Timing sheet for OSM node {} ({}) doesn't work: {}",
+                    sheet.osm_node_id, id, err
+                ));
+            }
+        }
+    }
+
+    timer.note(format!(
+        "Imported {} of {} timing sheets from {}",
+        results.len(),
+        num_sheets,
+        path
+    ));
+    for problem in problems {
+        timer.warn(problem);
+    }
+    results
+}
+
+impl TimingSheet {
+    pub fn to_signal(&self, map: &Map, i: IntersectionID) -> Result<ControlTrafficSignal, String> {
+        let turn_groups = TurnGroup::for_i(i, map);
+
+        let mut phases = Vec::new();
+        for (idx, spec) in self.phases.iter().enumerate() {
+            let mut phase = Phase::new();
+            phase.duration = spec.duration;
+            if let Some(ref actuated) = spec.actuated {
+                phase.phase_type = PhaseType::Actuated {
+                    min_green: actuated.min_green,
+                    gap: actuated.gap,
+                };
+            }
+            for (movements, protected) in vec![(&spec.protected, true), (&spec.permitted, false)] {
+                for m in movements {
+                    let g = TurnGroupID {
+                        from: find_road(map, i, &m.from)?,
+                        to: find_road(map, i, &m.to)?,
+                        crosswalk: None,
+                    };
+                    if !turn_groups.contains_key(&g) {
+                        return Err(format!(
+                            "phase {} has a movement from way {} to way {}, but that turn doesn't exist",
+                            idx + 1,
+                            m.from.osm_way_id,
+                            m.to.osm_way_id
+                        ));
+                    }
+                    if protected {
+                        phase.protected_groups.insert(g);
+                    } else {
+                        phase.yield_groups.insert(g);
+                    }
+                }
+            }
+            for approach in &spec.crosswalks {
+                let r = find_road(map, i, approach)?;
+                let crosswalks: Vec<TurnGroupID> = turn_groups
+                    .keys()
+                    .filter(|g| g.crosswalk.is_some() && g.from == r)
+                    .cloned()
+                    .collect();
+                if crosswalks.is_empty() {
+                    return Err(format!(
+                        "phase {} has a crosswalk across way {}, but there isn't one",
+                        idx + 1,
+                        approach.osm_way_id
+                    ));
+                }
+                phase.protected_groups.extend(crosswalks);
+            }
+            phases.push(phase);
+        }
+
+        // Sheets usually leave out movements that can always go safely, like right turns and
+        // crosswalks parallel to the main movements. Fill those in, but nothing else; a through
+        // or far-side movement missing from the sheet is missing from the real signal too.
+        let near_turn = match map.get_driving_side() {
+            DrivingSide::Right => TurnType::Right,
+            DrivingSide::Left => TurnType::Left,
+        };
+        for phase in phases.iter_mut() {
+            for (id, g) in &turn_groups {
+                if (g.turn_type == near_turn || g.turn_type == TurnType::Crosswalk)
+                    && !phase.yield_groups.contains(id)
+                    && phase.could_be_protected(*id, &turn_groups)
+                {
+                    phase.protected_groups.insert(*id);
+                }
+            }
+        }
+
+        ControlTrafficSignal {
+            id: i,
+            phases,
+            offset: self.offset,
+            plans: Vec::new(),
+            turn_groups,
+        }
+        .validate()
+    }
+}
+
+fn find_road(map: &Map, i: IntersectionID, approach: &Approach) -> Result<RoadID, String> {
+    let candidates: Vec<RoadID> = map
+        .get_i(i)
+        .roads
+        .iter()
+        .filter(|r| map.get_r(**r).orig_id.osm_way_id == approach.osm_way_id)
+        .cloned()
+        .collect();
+    if candidates.is_empty() {
+        return Err(format!(
+            "way {} doesn't touch the intersection",
+            approach.osm_way_id
+        ));
+    }
+    if candidates.len() == 1 {
+        return Ok(candidates[0]);
+    }
+
+    let side = approach.side.as_ref().ok_or_else(|| {
+        format!(
+            "way {} continues through the intersection, so the approach needs a side",
+            approach.osm_way_id
+        )
+    })?;
+    // Remember y points south.
+    let center = map.get_i(i).polygon.center();
+    let score = |r: RoadID| -> Result<f64, String> {
+        let road = map.get_r(r);
+        let other = if road.src_i == i {
+            road.dst_i
+        } else {
+            road.src_i
+        };
+        let pt = map.get_i(other).polygon.center();
+        match side.as_str() {
+            "north" => Ok(center.y() - pt.y()),
+            "south" => Ok(pt.y() - center.y()),
+            "east" => Ok(pt.x() - center.x()),
+            "west" => Ok(center.x() - pt.x()),
+            _ => Err(format!("unknown side {}", side)),
+        }
+    };
+    let mut best: Option<(RoadID, f64)> = None;
+    for r in candidates {
+        let s = score(r)?;
+        if best.map(|(_, best_score)| s > best_score).unwrap_or(true) {
+            best = Some((r, s));
+        }
+    }
+    Ok(best.unwrap().0)
+}
@@ -1,4 +1,4 @@
-use crate::raw::RestrictionType;
+use crate::raw::{DrivingSide, RestrictionType};
 use crate::{
     Intersection, IntersectionID, Lane, LaneID, LaneType, Road, RoadID, Turn, TurnID, TurnType,
     LANE_THICKNESS,
@@ -11,6 +11,7 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 // TODO Add proper warnings when the geometry is too small to handle.
 
 pub fn make_all_turns(
+    driving_side: DrivingSide,
     i: &Intersection,
     roads: &Vec<Road>,
     lanes: &Vec<Lane>,
@@ -19,8 +20,8 @@ pub fn make_all_turns(
     assert!(!i.is_border());
 
     let mut raw_turns: Vec<Turn> = Vec::new();
-    raw_turns.extend(make_vehicle_turns(i, roads, lanes, timer));
-    raw_turns.extend(make_walking_turns(i, roads, lanes, timer));
+    raw_turns.extend(make_vehicle_turns(driving_side, i, roads, lanes, timer));
+    raw_turns.extend(make_walking_turns(driving_side, i, roads, lanes, timer));
     let unique_turns = ensure_unique(raw_turns);
 
     let mut final_turns: Vec<Turn> = Vec::new();
@@ -30,7 +31,7 @@ pub fn make_all_turns(
             continue;
         }
 
-        if is_turn_allowed(&turn, roads, lanes) {
+        if is_turn_allowed(&turn, lanes) {
             final_turns.push(turn);
         } else {
             filtered_turns
@@ -98,11 +99,30 @@ fn ensure_unique(turns: Vec<Turn>) -> Vec<Turn> {
 }
 
 fn make_vehicle_turns(
+    driving_side: DrivingSide,
     i: &Intersection,
     all_roads: &Vec<Road>,
     lanes: &Vec<Lane>,
     timer: &mut Timer,
 ) -> Vec<Turn> {
+    // The near side turn doesn't cross oncoming traffic; the far side turn does. Lanes are
+    // numbered from the center line outwards, so moving to a higher lane index means moving
+    // towards the curb.
+    let (near_turn, far_turn, towards_curb, away_from_curb) = match driving_side {
+        DrivingSide::Right => (
+            TurnType::Right,
+            TurnType::Left,
+            TurnType::LaneChangeRight,
+            TurnType::LaneChangeLeft,
+        ),
+        DrivingSide::Left => (
+            TurnType::Left,
+            TurnType::Right,
+            TurnType::LaneChangeLeft,
+            TurnType::LaneChangeRight,
+        ),
+    };
+
     let sorted_roads: Vec<&Road> = i
         .get_roads_sorted_by_incoming_angle(all_roads)
         .iter()
@@ -162,13 +182,13 @@ fn make_vehicle_turns(
                 let angle2 = lanes[outgoing[0].0].first_line().angle();
 
                 let type_from_angle = TurnType::from_angles(angle1, angle2);
-                let tt = if type_from_angle == TurnType::Right {
+                let tt = if type_from_angle == near_turn {
                     // This one's fragile, based on angles. Really we care that there aren't roads
                     // between the two.
                     if wraparound_get(&sorted_roads, (idx1 as isize) - 1).id == r2.id
                         || wraparound_get(&sorted_roads, (idx1 as isize) + 1).id == r2.id
                     {
-                        TurnType::Right
+                        near_turn
                     } else {
                         TurnType::Straight
                     }
@@ -211,9 +231,9 @@ fn make_vehicle_turns(
                                 }
                                 if let Some(mut t) = make_vehicle_turn(lanes, i.id, l1, *l2, tt) {
                                     if idx1 < idx2 {
-                                        t.turn_type = TurnType::LaneChangeRight;
+                                        t.turn_type = towards_curb;
                                     } else if idx1 > idx2 {
-                                        t.turn_type = TurnType::LaneChangeLeft;
+                                        t.turn_type = away_from_curb;
                                     }
                                     result.push(Some(t));
                                 }
@@ -221,7 +241,8 @@ fn make_vehicle_turns(
                         }
                         all_incoming_lanes_covered = true;
                     }
-                    TurnType::Right => {
+                    // The outermost lane makes the near side turn
+                    _ if tt == near_turn => {
                         for (idx, l1) in incoming.iter().enumerate() {
                             for l2 in &outgoing {
                                 let turn = make_vehicle_turn(lanes, i.id, *l1, *l2, tt);
@@ -233,7 +254,8 @@ fn make_vehicle_turns(
                             }
                         }
                     }
-                    TurnType::Left => {
+                    // And the innermost lane makes the far side turn
+                    _ if tt == far_turn => {
                         for (idx, l1) in incoming.iter().enumerate() {
                             for l2 in &outgoing {
                                 let turn = make_vehicle_turn(lanes, i.id, *l1, *l2, tt);
@@ -291,11 +313,19 @@ fn make_vehicle_turns_for_dead_end(
 }
 
 fn make_walking_turns(
+    driving_side: DrivingSide,
     i: &Intersection,
     all_roads: &Vec<Road>,
     lanes: &Vec<Lane>,
     timer: &mut Timer,
 ) -> Vec<Turn> {
+    // The incoming sidewalk of a road shares a corner with the outgoing sidewalk of the road next
+    // to it. Which neighbor depends on the side of the road the incoming lanes are on.
+    let step: isize = match driving_side {
+        DrivingSide::Right => -1,
+        DrivingSide::Left => 1,
+    };
+
     let roads: Vec<&Road> = i
         .get_roads_sorted_by_incoming_angle(all_roads)
         .into_iter()
@@ -312,10 +342,10 @@ fn make_walking_turns(
             if let Some(l1) = get_sidewalk(lanes, roads[idx1].incoming_lanes(i.id)) {
                 if let Some(l2) = get_sidewalk(
                     lanes,
-                    abstutil::wraparound_get(&roads, (idx1 as isize) - 1).outgoing_lanes(i.id),
+                    abstutil::wraparound_get(&roads, (idx1 as isize) + step).outgoing_lanes(i.id),
                 ) {
                     if l1.last_pt() != l2.first_pt() {
-                        let geom = make_shared_sidewalk_corner(i, l1, l2, timer);
+                        let geom = make_shared_sidewalk_corner(driving_side, i, l1, l2, timer);
                         result.push(Turn {
                             id: turn_id(i.id, l1.id, l2.id),
                             turn_type: TurnType::SharedSidewalkCorner,
@@ -339,7 +369,7 @@ fn make_walking_turns(
     if roads.len() == 1 {
         if let Some(l1) = get_sidewalk(lanes, roads[0].incoming_lanes(i.id)) {
             if let Some(l2) = get_sidewalk(lanes, roads[0].outgoing_lanes(i.id)) {
-                let geom = make_shared_sidewalk_corner(i, l1, l2, timer);
+                let geom = make_shared_sidewalk_corner(driving_side, i, l1, l2, timer);
                 result.push(Turn {
                     id: turn_id(i.id, l1.id, l2.id),
                     turn_type: TurnType::SharedSidewalkCorner,
@@ -363,17 +393,17 @@ fn make_walking_turns(
         if let Some(l1) = get_sidewalk(lanes, roads[idx1].incoming_lanes(i.id)) {
             // Make the crosswalk to the other side
             if let Some(l2) = get_sidewalk(lanes, roads[idx1].outgoing_lanes(i.id)) {
-                result.extend(make_crosswalks(i.id, l1, l2));
+                result.extend(make_crosswalks(driving_side, i.id, l1, l2));
             }
 
             // Find the shared corner
-            // TODO -1 and not +1 is brittle... must be the angle sorting
+            // TODO The direction of step is brittle... must be the angle sorting
             if let Some(l2) = get_sidewalk(
                 lanes,
-                abstutil::wraparound_get(&roads, (idx1 as isize) - 1).outgoing_lanes(i.id),
+                abstutil::wraparound_get(&roads, (idx1 as isize) + step).outgoing_lanes(i.id),
             ) {
                 if l1.last_pt() != l2.first_pt() {
-                    let geom = make_shared_sidewalk_corner(i, l1, l2, timer);
+                    let geom = make_shared_sidewalk_corner(driving_side, i, l1, l2, timer);
                     result.push(Turn {
                         id: turn_id(i.id, l1.id, l2.id),
                         turn_type: TurnType::SharedSidewalkCorner,
@@ -391,31 +421,34 @@ fn make_walking_turns(
                 }
             } else if let Some(l2) = get_sidewalk(
                 lanes,
-                abstutil::wraparound_get(&roads, (idx1 as isize) - 1).incoming_lanes(i.id),
+                abstutil::wraparound_get(&roads, (idx1 as isize) + step).incoming_lanes(i.id),
             ) {
                 // Adjacent road is missing a sidewalk on the near side, but has one on the far
                 // side
-                result.extend(make_crosswalks(i.id, l1, l2));
+                result.extend(make_crosswalks(driving_side, i.id, l1, l2));
             } else {
                 // We may need to add a crosswalk over this intermediate road that has no
                 // sidewalks at all. There might be a few in the way -- think highway onramps.
                 // TODO Refactor and loop until we find something to connect it to?
                 if let Some(l2) = get_sidewalk(
                     lanes,
-                    abstutil::wraparound_get(&roads, (idx1 as isize) - 2).outgoing_lanes(i.id),
+                    abstutil::wraparound_get(&roads, (idx1 as isize) + 2 * step)
+                        .outgoing_lanes(i.id),
                 ) {
-                    result.extend(make_crosswalks(i.id, l1, l2));
+                    result.extend(make_crosswalks(driving_side, i.id, l1, l2));
                 } else if let Some(l2) = get_sidewalk(
                     lanes,
-                    abstutil::wraparound_get(&roads, (idx1 as isize) - 2).incoming_lanes(i.id),
+                    abstutil::wraparound_get(&roads, (idx1 as isize) + 2 * step)
+                        .incoming_lanes(i.id),
                 ) {
-                    result.extend(make_crosswalks(i.id, l1, l2));
+                    result.extend(make_crosswalks(driving_side, i.id, l1, l2));
                 } else if roads.len() > 3 {
                     if let Some(l2) = get_sidewalk(
                         lanes,
-                        abstutil::wraparound_get(&roads, (idx1 as isize) - 3).outgoing_lanes(i.id),
+                        abstutil::wraparound_get(&roads, (idx1 as isize) + 3 * step)
+                            .outgoing_lanes(i.id),
                     ) {
-                        result.extend(make_crosswalks(i.id, l1, l2));
+                        result.extend(make_crosswalks(driving_side, i.id, l1, l2));
                     }
                 }
             }
@@ -425,18 +458,27 @@ fn make_walking_turns(
     result
 }
 
-fn make_crosswalks(i: IntersectionID, l1: &Lane, l2: &Lane) -> Vec<Turn> {
+fn make_crosswalks(
+    driving_side: DrivingSide,
+    i: IntersectionID,
+    l1: &Lane,
+    l2: &Lane,
+) -> Vec<Turn> {
     let l1_pt = l1.endpoint(i);
     let l2_pt = l2.endpoint(i);
     if l1_pt == l2_pt {
         return Vec::new();
     }
     // TODO Not sure this is always right.
-    let direction = if (l1.dst_i == i) == (l2.dst_i == i) {
+    let mut direction = if (l1.dst_i == i) == (l2.dst_i == i) {
         -1.0
     } else {
         1.0
     };
+    // Everything's mirrored when driving on the left
+    if driving_side == DrivingSide::Left {
+        direction *= -1.0;
+    }
     // Jut out a bit into the intersection, cross over, then jut back in.
     let line = Line::new(l1_pt, l2_pt).shift_either_direction(direction * LANE_THICKNESS / 2.0);
     let geom_fwds = PolyLine::new(vec![l1_pt, line.pt1(), line.pt2(), l2_pt]);
@@ -525,6 +567,7 @@ fn make_degenerate_crosswalks(
 }
 
 fn make_shared_sidewalk_corner(
+    driving_side: DrivingSide,
     i: &Intersection,
     l1: &Lane,
     l2: &Lane,
@@ -533,16 +576,31 @@ fn make_shared_sidewalk_corner(
     let baseline = PolyLine::new(vec![l1.last_pt(), l2.first_pt()]);
 
     // Find all of the points on the intersection polygon between the two sidewalks.
-    let corner1 = l1.last_line().shift_right(LANE_THICKNESS / 2.0).pt2();
-    let corner2 = l2.first_line().shift_right(LANE_THICKNESS / 2.0).pt1();
+    let corner1 = driving_side
+        .right_shift_line(&l1.last_line(), l1.width / 2.0)
+        .pt2();
+    let corner2 = driving_side
+        .right_shift_line(&l2.first_line(), l2.width / 2.0)
+        .pt1();
 
     // The order of the points here seems backwards, but it's because we scan from corner2
     // to corner1 below.
     let mut pts_between = vec![l2.first_pt()];
-    // Intersection polygons are constructed in clockwise order, so do corner2 to corner1.
-    if let Some(pts) =
-        Pt2D::find_pts_between(&i.polygon.points(), corner2, corner1, Distance::meters(0.5))
-    {
+    // Intersection polygons are constructed in clockwise order, so do corner2 to corner1. When
+    // driving on the left, the corner goes the other way around the polygon.
+    let pts = match driving_side {
+        DrivingSide::Right => {
+            Pt2D::find_pts_between(&i.polygon.points(), corner2, corner1, Distance::meters(0.5))
+        }
+        DrivingSide::Left => {
+            Pt2D::find_pts_between(&i.polygon.points(), corner1, corner2, Distance::meters(0.5))
+                .map(|mut pts| {
+                    pts.reverse();
+                    pts
+                })
+        }
+    };
+    if let Some(pts) = pts {
         let mut deduped = pts.clone();
         deduped.dedup();
         if deduped.len() >= 2 {
@@ -552,8 +610,8 @@ fn make_shared_sidewalk_corner(
             }
 
             pts_between.extend(
-                PolyLine::new(deduped)
-                    .shift_right(LANE_THICKNESS / 2.0)
+                driving_side
+                    .right_shift(&PolyLine::new(deduped), l1.width / 2.0)
                     .with_context(
                         timer,
                         format!("SharedSidewalkCorner between {} and {}", l1.id, l2.id),
@@ -680,10 +738,8 @@ fn from_pt(pt: Point2d<f64>) -> Pt2D {
     Pt2D::new(pt.x, pt.y)
 }
 
-fn is_turn_allowed(turn: &Turn, roads: &Vec<Road>, lanes: &Vec<Lane>) -> bool {
-    let l = &lanes[turn.id.src.0];
-    let r = &roads[l.parent.0];
-    if let Some(types) = l.get_turn_restrictions(r) {
+fn is_turn_allowed(turn: &Turn, lanes: &Vec<Lane>) -> bool {
+    if let Some(types) = lanes[turn.id.src.0].get_turn_restrictions() {
         types.contains(&turn.turn_type)
     } else {
         true
@@ -1,13 +1,15 @@
 use crate::pathfind::Pathfinder;
-use crate::raw::{OriginalIntersection, OriginalRoad, RawMap};
+use crate::raw::{DrivingSide, OriginalIntersection, OriginalRoad, RawMap};
+use crate::road::get_speed_limits;
 use crate::{
     connectivity, make, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop,
     BusStopID, ControlStopSign, ControlTrafficSignal, EditCmd, EditEffects, Intersection,
     IntersectionID, IntersectionType, Lane, LaneID, LaneType, MapEdits, Path, PathConstraints,
-    PathRequest, Position, Road, RoadID, Turn, TurnID, LANE_THICKNESS,
+    PathRequest, Position, RailLine, RailLineID, Road, RoadID, Station, StationID, TimingSheet,
+    TransitRide, Traversable, Turn, TurnID,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer};
-use geom::{Bounds, Distance, GPSBounds, Polygon, Pt2D};
+use geom::{Bounds, Distance, Duration, GPSBounds, Polygon, Pt2D};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 use std::io;
@@ -29,15 +31,24 @@ pub struct Map {
     )]
     bus_stops: BTreeMap<BusStopID, BusStop>,
     bus_routes: Vec<BusRoute>,
+    stations: Vec<Station>,
+    rail_lines: Vec<RailLine>,
     areas: Vec<Area>,
     boundary_polygon: Polygon,
 
     // Note that border nodes belong in neither!
     stop_signs: BTreeMap<IntersectionID, ControlStopSign>,
     traffic_signals: BTreeMap<IntersectionID, ControlTrafficSignal>,
+    // Imported timing, used instead of the heuristics whenever a signal is reset
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    signal_timing: BTreeMap<IntersectionID, TimingSheet>,
 
     gps_bounds: GPSBounds,
     bounds: Bounds,
+    driving_side: DrivingSide,
 
     turn_lookup: Vec<TurnID>,
     // TODO Argh, hack, initialization order is hard!
@@ -69,6 +80,8 @@ impl Map {
             buildings: Vec::new(),
             bus_stops: BTreeMap::new(),
             bus_routes: Vec::new(),
+            stations: Vec::new(),
+            rail_lines: Vec::new(),
             areas: Vec::new(),
             boundary_polygon: Polygon::new(&vec![
                 Pt2D::new(0.0, 0.0),
@@ -77,8 +90,10 @@ impl Map {
             ]),
             stop_signs: BTreeMap::new(),
             traffic_signals: BTreeMap::new(),
+            signal_timing: BTreeMap::new(),
             gps_bounds: GPSBounds::new(),
             bounds: Bounds::new(),
+            driving_side: DrivingSide::Right,
             turn_lookup: Vec::new(),
             pathfinder: None,
             pathfinder_dirty: false,
@@ -100,6 +115,7 @@ impl Map {
 
         timer.start("finalize Map");
 
+        m.signal_timing = make::load_signal_timing(&m, timer);
         // TODO Can probably move this into make_half_map.
         {
             let mut stop_signs: BTreeMap<IntersectionID, ControlStopSign> = BTreeMap::new();
@@ -165,6 +181,13 @@ impl Map {
             }
         }
 
+        {
+            let (stations, lines) =
+                make::make_rail_network(&m, &raw.rail_routes, &m.gps_bounds, &m.bounds, timer);
+            m.stations = stations;
+            m.rail_lines = lines;
+        }
+
         timer.start("setup rest of Pathfinder (walking with transit)");
         let mut pathfinder = m.pathfinder.take().unwrap();
         pathfinder.setup_walking_with_transit(&m);
@@ -286,6 +309,10 @@ impl Map {
         &self.traffic_signals[&id]
     }
 
+    pub fn get_signal_timing(&self, id: IntersectionID) -> Option<&TimingSheet> {
+        self.signal_timing.get(&id)
+    }
+
     pub fn lookup_turn_by_idx(&self, idx: usize) -> Option<TurnID> {
         self.turn_lookup.get(idx).cloned()
     }
@@ -411,6 +438,10 @@ impl Map {
         &self.bounds
     }
 
+    pub fn get_driving_side(&self) -> DrivingSide {
+        self.driving_side
+    }
+
     pub fn get_name(&self) -> &String {
         &self.name
     }
@@ -431,8 +462,13 @@ impl Map {
         &self.bus_routes
     }
 
+    // Routes going both ways have a BusRoute per direction. This finds the forwards one if it
+    // exists.
     pub fn get_bus_route(&self, name: &str) -> Option<&BusRoute> {
-        self.bus_routes.iter().find(|r| r.name == name)
+        self.bus_routes
+            .iter()
+            .find(|r| r.name == name && r.forwards)
+            .or_else(|| self.bus_routes.iter().find(|r| r.name == name))
     }
 
     pub fn get_routes_serving_stop(&self, stop: BusStopID) -> Vec<&BusRoute> {
@@ -445,6 +481,29 @@ impl Map {
         routes
     }
 
+    pub fn all_stations(&self) -> &Vec<Station> {
+        &self.stations
+    }
+
+    pub fn get_station(&self, id: StationID) -> &Station {
+        &self.stations[id.0]
+    }
+
+    pub fn get_rl(&self, line: RailLineID) -> &RailLine {
+        &self.rail_lines[line.0]
+    }
+
+    pub fn get_all_rail_lines(&self) -> &Vec<RailLine> {
+        &self.rail_lines
+    }
+
+    pub fn get_lines_serving_station(&self, station: StationID) -> Vec<&RailLine> {
+        self.rail_lines
+            .iter()
+            .filter(|l| l.stations.contains(&station))
+            .collect()
+    }
+
     pub fn building_to_road(&self, id: BuildingID) -> &Road {
         self.get_parent(self.get_b(id).sidewalk())
     }
@@ -488,55 +547,33 @@ impl Map {
     // blackholes" -- if there are no free spots on that lane, then the roads force cars to a
     // border.
     pub fn find_driving_lane_near_building(&self, b: BuildingID) -> LaneID {
-        if let Ok(l) = self.find_closest_lane(self.get_b(b).sidewalk(), vec![LaneType::Driving]) {
-            return self.get_l(l).parking_blackhole.unwrap_or(l);
-        }
-
-        let mut roads_queue: VecDeque<RoadID> = VecDeque::new();
-        let mut visited: HashSet<RoadID> = HashSet::new();
-        {
-            let start = self.building_to_road(b).id;
-            roads_queue.push_back(start);
-            visited.insert(start);
-        }
-
-        loop {
-            if roads_queue.is_empty() {
-                panic!(
-                    "Giving up looking for a driving lane near {}, searched {} roads: {:?}",
-                    b,
-                    visited.len(),
-                    visited
-                );
-            }
-            let r = self.get_r(roads_queue.pop_front().unwrap());
-
-            for (lane, lane_type) in r
-                .children_forwards
-                .iter()
-                .chain(r.children_backwards.iter())
-            {
-                if *lane_type == LaneType::Driving {
-                    return self.get_l(*lane).parking_blackhole.unwrap_or(*lane);
-                }
-            }
+        self.maybe_find_driving_lane_near_building(b)
+            .unwrap_or_else(|| panic!("Giving up looking for a driving lane near {}", b))
+    }
 
-            for next_r in self.get_next_roads(r.id).into_iter() {
-                if !visited.contains(&next_r) {
-                    roads_queue.push_back(next_r);
-                    visited.insert(next_r);
-                }
-            }
-        }
+    // None if no road reachable from the building has a driving lane.
+    pub fn maybe_find_driving_lane_near_building(&self, b: BuildingID) -> Option<LaneID> {
+        self.maybe_find_lane_near_building(b, vec![LaneType::Driving])
     }
 
     // TODO Refactor and also use a different blackhole measure
     pub fn find_biking_lane_near_building(&self, b: BuildingID) -> LaneID {
+        self.maybe_find_biking_lane_near_building(b)
+            .unwrap_or_else(|| panic!("Giving up looking for a biking or driving lane near {}", b))
+    }
+
+    pub fn maybe_find_biking_lane_near_building(&self, b: BuildingID) -> Option<LaneID> {
         if let Ok(l) = self.find_closest_lane(self.get_b(b).sidewalk(), vec![LaneType::Biking]) {
-            return self.get_l(l).parking_blackhole.unwrap_or(l);
+            return Some(self.get_l(l).parking_blackhole.unwrap_or(l));
         }
-        if let Ok(l) = self.find_closest_lane(self.get_b(b).sidewalk(), vec![LaneType::Driving]) {
-            return self.get_l(l).parking_blackhole.unwrap_or(l);
+        self.maybe_find_lane_near_building(b, vec![LaneType::Biking, LaneType::Driving])
+    }
+
+    // Tries the building's own road first, then searches outwards. The first lane of any of the
+    // types wins.
+    fn maybe_find_lane_near_building(&self, b: BuildingID, types: Vec<LaneType>) -> Option<LaneID> {
+        if let Ok(l) = self.find_closest_lane(self.get_b(b).sidewalk(), types.clone()) {
+            return Some(self.get_l(l).parking_blackhole.unwrap_or(l));
         }
 
         let mut roads_queue: VecDeque<RoadID> = VecDeque::new();
@@ -547,27 +584,15 @@ impl Map {
             visited.insert(start);
         }
 
-        loop {
-            if roads_queue.is_empty() {
-                panic!(
-                    "Giving up looking for a biking or driving lane near {}, searched {} roads: {:?}",
-                    b,
-                    visited.len(),
-                    visited
-                );
-            }
-            let r = self.get_r(roads_queue.pop_front().unwrap());
-
+        while let Some(r) = roads_queue.pop_front() {
+            let r = self.get_r(r);
             for (lane, lane_type) in r
                 .children_forwards
                 .iter()
                 .chain(r.children_backwards.iter())
             {
-                if *lane_type == LaneType::Biking {
-                    return self.get_l(*lane).parking_blackhole.unwrap_or(*lane);
-                }
-                if *lane_type == LaneType::Driving {
-                    return self.get_l(*lane).parking_blackhole.unwrap_or(*lane);
+                if types.contains(lane_type) {
+                    return Some(self.get_l(*lane).parking_blackhole.unwrap_or(*lane));
                 }
             }
 
@@ -578,6 +603,7 @@ impl Map {
                 }
             }
         }
+        None
     }
 
     pub fn get_boundary_polygon(&self) -> &Polygon {
@@ -589,11 +615,7 @@ impl Map {
         self.pathfinder.as_ref().unwrap().pathfind(req, self)
     }
 
-    pub fn should_use_transit(
-        &self,
-        start: Position,
-        end: Position,
-    ) -> Option<(BusStopID, BusStopID, BusRouteID)> {
+    pub fn should_use_transit(&self, start: Position, end: Position) -> Option<Vec<TransitRide>> {
         self.pathfinder
             .as_ref()
             .unwrap()
@@ -619,23 +641,13 @@ impl Map {
 
     // new_edits assumed to be valid. Returns actual lanes that changed, roads changed, turns
     // deleted, turns added, intersections modified. Doesn't update pathfinding yet.
-    pub fn apply_edits(
-        &mut self,
-        mut new_edits: MapEdits,
-        timer: &mut Timer,
-    ) -> (
-        BTreeSet<LaneID>,
-        BTreeSet<RoadID>,
-        BTreeSet<TurnID>,
-        BTreeSet<TurnID>,
-        BTreeSet<IntersectionID>,
-    ) {
+    pub fn apply_edits(&mut self, mut new_edits: MapEdits, timer: &mut Timer) -> EditEffects {
         // TODO More efficient ways to do this: given two sets of edits, produce a smaller diff.
         // Simplest strategy: Remove common prefix.
         let mut effects = EditEffects::new();
 
         // First undo all existing edits.
-        let mut undo = std::mem::replace(&mut self.edits.commands, Vec::new());
+        let mut undo: Vec<EditCmd> = self.edits.applied_commands().into_iter().cloned().collect();
         undo.reverse();
         let mut undid = 0;
         for cmd in &undo {
@@ -647,16 +659,13 @@ impl Map {
 
         // Apply new edits.
         let mut applied = 0;
-        for cmd in &new_edits.commands {
+        let apply: Vec<EditCmd> = new_edits.applied_commands().into_iter().cloned().collect();
+        for cmd in &apply {
             if cmd.apply(&mut effects, self, timer) {
                 applied += 1;
             }
         }
-        timer.note(format!(
-            "Applied {} / {} new edits",
-            applied,
-            new_edits.commands.len()
-        ));
+        timer.note(format!("Applied {} / {} new edits", applied, apply.len()));
 
         // Might need to update bus stops.
         for id in &effects.changed_roads {
@@ -676,19 +685,29 @@ impl Map {
         new_edits.update_derived(self, timer);
         self.edits = new_edits;
         self.pathfinder_dirty = true;
-        (
-            effects.changed_lanes,
-            // TODO We just care about contraflow roads here
-            effects.changed_roads,
-            effects.deleted_turns,
-            // Some of these might've been added, then later deleted.
-            effects
-                .added_turns
-                .into_iter()
-                .filter(|t| self.turns.contains_key(t))
-                .collect(),
-            effects.changed_intersections,
-        )
+        // Some of these might've been added, then later deleted.
+        let turns = &self.turns;
+        effects.added_turns.retain(|t| turns.contains_key(t));
+        effects
+    }
+
+    // Cars will route using these times, instead of assuming free-flow speed everywhere. Lanes and
+    // turns without a time still use the speed limit. An empty map goes back to free-flow.
+    pub fn set_driving_travel_times(
+        &mut self,
+        travel_times: BTreeMap<Traversable, Duration>,
+        timer: &mut Timer,
+    ) {
+        assert!(!self.pathfinder_dirty);
+        timer.start("apply travel times to car pathfinding");
+        let mut pathfinder = self.pathfinder.take().unwrap();
+        pathfinder.set_driving_travel_times(self, travel_times);
+        self.pathfinder = Some(pathfinder);
+        timer.stop("apply travel times to car pathfinding");
+    }
+
+    pub fn get_driving_travel_times(&self) -> &BTreeMap<Traversable, Duration> {
+        self.pathfinder.as_ref().unwrap().get_driving_travel_times()
     }
 
     pub fn recalculate_pathfinding_after_edits(&mut self, timer: &mut Timer) {
@@ -729,12 +748,16 @@ fn make_half_map(
         buildings: Vec::new(),
         bus_stops: BTreeMap::new(),
         bus_routes: Vec::new(),
+        stations: Vec::new(),
+        rail_lines: Vec::new(),
         areas: Vec::new(),
         boundary_polygon: raw.boundary_polygon.clone(),
         stop_signs: BTreeMap::new(),
         traffic_signals: BTreeMap::new(),
+        signal_timing: BTreeMap::new(),
         gps_bounds,
         bounds,
+        driving_side: raw.driving_side,
         turn_lookup: Vec::new(),
         pathfinder: None,
         pathfinder_dirty: false,
@@ -776,6 +799,7 @@ fn make_half_map(
         let i1 = intersection_id_mapping[&r.src_i];
         let i2 = intersection_id_mapping[&r.dst_i];
 
+        let (speed_limit_fwd, speed_limit_back) = get_speed_limits(&raw.roads[&r.id].osm_tags);
         let mut road = Road {
             id: road_id,
             osm_tags: raw.roads[&r.id].osm_tags.clone(),
@@ -790,8 +814,13 @@ fn make_half_map(
             center_pts: r.trimmed_center_pts.clone(),
             src_i: i1,
             dst_i: i2,
+            speed_limit_fwd,
+            speed_limit_back,
         };
 
+        // How far out from the center line the next lane on each side starts
+        let mut width_fwd = Distance::ZERO;
+        let mut width_back = Distance::ZERO;
         for lane in &r.lane_specs {
             let id = LaneID(map.lanes.len());
 
@@ -801,25 +830,25 @@ fn make_half_map(
 
             let (unshifted_pts, offset) = if lane.reverse_pts {
                 road.children_backwards.push((id, lane.lane_type));
-                (
-                    road.center_pts.reversed(),
-                    road.children_backwards.len() - 1,
-                )
+                width_back += lane.width;
+                (road.center_pts.reversed(), width_back)
             } else {
                 road.children_forwards.push((id, lane.lane_type));
-                (road.center_pts.clone(), road.children_forwards.len() - 1)
+                width_fwd += lane.width;
+                (road.center_pts.clone(), width_fwd)
             };
             // TODO probably different behavior for oneways
             // TODO need to factor in yellow center lines (but what's the right thing to even do?
-            // Reverse points for British-style driving on the left
-            let width = LANE_THICKNESS * (0.5 + (offset as f64));
-            let lane_center_pts = unshifted_pts
-                .shift_right(width)
+            let lane_center_pts = raw
+                .driving_side
+                .right_shift(&unshifted_pts, offset - lane.width / 2.0)
                 .with_context(timer, format!("shift for {}", id));
 
             map.lanes.push(Lane {
                 id,
                 lane_center_pts,
+                width: lane.width,
+                allowed_turns: lane.allowed_turns.clone().map(|turns| (dst_i, turns)),
                 src_i,
                 dst_i,
                 lane_type: lane.lane_type,
@@ -860,7 +889,7 @@ fn make_half_map(
             continue;
         }
 
-        for t in make::make_all_turns(i, &map.roads, &map.lanes, timer) {
+        for t in make::make_all_turns(map.driving_side, i, &map.roads, &map.lanes, timer) {
             assert!(!map.turns.contains_key(&t.id));
             i.turns.push(t.id);
             map.turns.insert(t.id, t);
@@ -1135,7 +1164,7 @@ fn recalculate_turns(
         return;
     }
 
-    for t in make::make_all_turns(i, &map.roads, &map.lanes, timer) {
+    for t in make::make_all_turns(map.driving_side, i, &map.roads, &map.lanes, timer) {
         effects.added_turns.insert(t.id);
         i.turns.push(t.id);
         if let Some(_existing_t) = old_turns.iter().find(|turn| turn.id == t.id) {
@@ -1,3 +1,5 @@
+use geom::{Distance, Speed};
+
 // These are common OSM keys. Keys used in just one or two places don't really need to be defined
 // here.
 
@@ -5,6 +7,7 @@
 pub const NAME: &str = "name";
 pub const HIGHWAY: &str = "highway";
 pub const MAXSPEED: &str = "maxspeed";
+pub const WIDTH: &str = "width";
 pub const PARKING_RIGHT: &str = "parking:lane:right";
 pub const PARKING_LEFT: &str = "parking:lane:left";
 pub const PARKING_BOTH: &str = "parking:lane:both";
@@ -30,3 +33,125 @@ pub const SYNTHETIC_LANES: &str = "abst:synthetic_lanes";
 // Any roads might have these.
 pub const INFERRED_PARKING: &str = "abst:parking_inferred";
 pub const INFERRED_SIDEWALKS: &str = "abst:sidewalks_inferred";
+
+// Parses a maxspeed value. Plain numbers are km/h, following OSM convention. Values that don't
+// describe a fixed limit, like "signals", "variable", or "none", return None.
+pub fn parse_speed(value: &str) -> Option<Speed> {
+    // Multiple values are sometimes given, like "50;30". Just use the first.
+    let value = value.split(';').next().unwrap().trim();
+    if value == "walk" {
+        return Some(Speed::km_per_hour(7.0));
+    }
+    if let Some(speed) = parse_speed_zone(value) {
+        return Some(speed);
+    }
+
+    let (num, unit) = split_number(value)?;
+    let speed = match unit {
+        "" | "km/h" | "kmh" | "kph" => Speed::km_per_hour(num),
+        "mph" => Speed::miles_per_hour(num),
+        "knots" => Speed::knots(num),
+        _ => {
+            return None;
+        }
+    };
+    Some(speed)
+}
+
+// Implicit limits like "DE:urban" or "GB:nsl_single". Only the common zones are handled; see
+// https://wiki.openstreetmap.org/wiki/Default_speed_limits.
+fn parse_speed_zone(value: &str) -> Option<Speed> {
+    let mut parts = value.splitn(2, ':');
+    let country = parts.next()?;
+    let zone = parts.next()?;
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let imperial = country == "GB" || country == "US";
+
+    // Explicit zones, like "DE:zone30" or "DE:zone:30"
+    if zone.starts_with("zone") {
+        let (num, unit) = split_number(zone.trim_start_matches("zone").trim_start_matches(':'))?;
+        if !unit.is_empty() {
+            return None;
+        }
+        return Some(if imperial {
+            Speed::miles_per_hour(num)
+        } else {
+            Speed::km_per_hour(num)
+        });
+    }
+
+    let mph = match (country, zone) {
+        ("GB", "urban") => Some(30.0),
+        ("GB", "nsl_single") => Some(60.0),
+        ("GB", "nsl_dual") | ("GB", "motorway") => Some(70.0),
+        _ => None,
+    };
+    if let Some(mph) = mph {
+        return Some(Speed::miles_per_hour(mph));
+    }
+
+    let kmh = match (country, zone) {
+        ("RU", "urban") => 60.0,
+        (_, "urban") => 50.0,
+        ("DE", "rural") | ("AT", "rural") => 100.0,
+        ("FR", "rural") | ("CH", "rural") | ("NL", "rural") => 80.0,
+        (_, "rural") => 90.0,
+        // There's no limit on much of the Autobahn; use the advisory speed.
+        ("DE", "motorway") => 130.0,
+        ("FR", "motorway") | ("AT", "motorway") | ("IT", "motorway") => 130.0,
+        ("RU", "motorway") => 110.0,
+        ("NL", "motorway") => 100.0,
+        (_, "motorway") => 120.0,
+        (_, "living_street") => 7.0,
+        (_, "bicycle_road") => 30.0,
+        _ => {
+            return None;
+        }
+    };
+    Some(Speed::km_per_hour(kmh))
+}
+
+// Parses a width, like "3.5", "3.5 m", "12 ft", or "10'6\"". Plain numbers are meters.
+pub fn parse_width(value: &str) -> Option<Distance> {
+    let value = value.trim();
+    let width = if let Some(idx) = value.find('\'') {
+        let feet = value[0..idx].trim().parse::<f64>().ok()?;
+        let rest = value[idx + 1..].trim().trim_end_matches('"').trim();
+        let inches = if rest.is_empty() {
+            0.0
+        } else {
+            rest.parse::<f64>().ok()?
+        };
+        Distance::feet(feet) + Distance::inches(inches)
+    } else {
+        let (num, unit) = split_number(value)?;
+        match unit {
+            "" | "m" => Distance::meters(num),
+            "cm" => Distance::meters(num / 100.0),
+            "ft" | "feet" => Distance::feet(num),
+            _ => {
+                return None;
+            }
+        }
+    };
+    if width > Distance::ZERO {
+        Some(width)
+    } else {
+        None
+    }
+}
+
+// Splits "50 mph" into (50.0, "mph"). The number must be positive.
+fn split_number(value: &str) -> Option<(f64, &str)> {
+    let value = value.trim();
+    let idx = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or_else(|| value.len());
+    let num = value[0..idx].parse::<f64>().ok()?;
+    if num <= 0.0 || !num.is_finite() {
+        return None;
+    }
+    Some((num, value[idx..].trim()))
+}
@@ -1,11 +1,13 @@
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::{
-    Lane, LaneID, LaneType, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID,
+    Lane, LaneID, LaneType, Map, Path, PathConstraints, PathRequest, PathStep, Traversable, Turn,
+    TurnID,
 };
 use fast_paths::{FastGraph, InputGraph, PathCalculator};
-use geom::Distance;
+use geom::{Distance, Duration};
 use serde_derive::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use thread_local::ThreadLocal;
 
 #[derive(Serialize, Deserialize)]
@@ -14,6 +16,9 @@ pub struct VehiclePathfinder {
     #[serde(deserialize_with = "deserialize_nodemap")]
     nodes: NodeMap<LaneID>,
     constraints: PathConstraints,
+    // Observed instead of free-flow times, for some lanes and turns. Only used for cars.
+    #[serde(skip_serializing, skip_deserializing)]
+    travel_times: BTreeMap<Traversable, Duration>,
 
     #[serde(skip_serializing, skip_deserializing)]
     path_calc: ThreadLocal<RefCell<PathCalculator>>,
@@ -32,7 +37,7 @@ impl VehiclePathfinder {
         for l in map.all_lanes() {
             nodes.get_or_insert(l.id);
         }
-        let input_graph = make_input_graph(map, &nodes, constraints);
+        let input_graph = make_input_graph(map, &nodes, constraints, &BTreeMap::new());
 
         // All VehiclePathfinders have the same nodes (lanes), so if we're not the first being
         // built, seed from the node ordering.
@@ -47,6 +52,7 @@ impl VehiclePathfinder {
             graph,
             nodes,
             constraints,
+            travel_times: BTreeMap::new(),
             path_calc: ThreadLocal::new(),
         }
     }
@@ -91,16 +97,28 @@ impl VehiclePathfinder {
         // ordering.
         // TODO Make sure the result of this is deterministic and equivalent to computing from
         // scratch.
-        let input_graph = make_input_graph(map, &self.nodes, self.constraints);
+        let input_graph = make_input_graph(map, &self.nodes, self.constraints, &self.travel_times);
         let node_ordering = self.graph.get_node_ordering();
         self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
     }
+
+    pub fn set_travel_times(&mut self, map: &Map, travel_times: BTreeMap<Traversable, Duration>) {
+        assert_eq!(self.constraints, PathConstraints::Car);
+        self.travel_times = travel_times;
+        // Same as applying edits; the graph's shape doesn't change, just the weights.
+        self.apply_edits(map);
+    }
+
+    pub fn get_travel_times(&self) -> &BTreeMap<Traversable, Duration> {
+        &self.travel_times
+    }
 }
 
 fn make_input_graph(
     map: &Map,
     nodes: &NodeMap<LaneID>,
     constraints: PathConstraints,
+    travel_times: &BTreeMap<Traversable, Duration>,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
     let num_lanes = map.all_lanes().len();
@@ -110,11 +128,12 @@ fn make_input_graph(
         if constraints.can_use(l, map) {
             for turn in map.get_turns_for(l.id, constraints) {
                 any = true;
-                input_graph.add_edge(
-                    from,
-                    nodes.get(turn.id.dst),
-                    cost(l, turn, constraints, map),
-                );
+                let weight = if travel_times.is_empty() {
+                    cost(l, turn, constraints, map)
+                } else {
+                    experienced_cost(l, turn, travel_times, map)
+                };
+                input_graph.add_edge(from, nodes.get(turn.id.dst), weight);
             }
         }
         // The nodes in the graph MUST exactly be all of the lanes, so we can reuse node
@@ -136,8 +155,8 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
     match constraints {
         PathConstraints::Car => {
             // Prefer slightly longer route on faster roads
-            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit();
-            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit();
+            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit(lane.id);
+            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit(turn.id.dst);
             (t1 + t2).inner_seconds().round() as usize
         }
         PathConstraints::Bike => {
@@ -163,8 +182,8 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
         }
         PathConstraints::Bus => {
             // Like Car, but prefer bus lanes.
-            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit();
-            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit();
+            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit(lane.id);
+            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit(turn.id.dst);
             let lt_penalty = if lane.is_bus() {
                 1.0
             } else {
@@ -177,6 +196,26 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
     }
 }
 
+// Like the cost for cars, but preferring observed travel times when there are any
+fn experienced_cost(
+    lane: &Lane,
+    turn: &Turn,
+    travel_times: &BTreeMap<Traversable, Duration>,
+    map: &Map,
+) -> usize {
+    let t1 = travel_times
+        .get(&Traversable::Lane(lane.id))
+        .cloned()
+        .unwrap_or_else(|| lane.length() / map.get_r(lane.parent).get_speed_limit(lane.id));
+    let t2 = travel_times
+        .get(&Traversable::Turn(turn.id))
+        .cloned()
+        .unwrap_or_else(|| {
+            turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit(turn.id.dst)
+        });
+    (t1 + t2).inner_seconds().round() as usize
+}
+
 fn check_bike_route(path: &Path, map: &Map) {
     let steps: Vec<PathStep> = path.get_steps().iter().cloned().collect();
     for pair in steps.windows(2) {
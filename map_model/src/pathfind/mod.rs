@@ -5,13 +5,12 @@ mod walking;
 pub use self::driving::cost;
 use self::driving::VehiclePathfinder;
 use self::walking::SidewalkPathfinder;
-use crate::{
-    osm, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, Position, Traversable, TurnID,
-};
+pub use self::walking::TransitRide;
+use crate::{osm, Lane, LaneID, LaneType, Map, Position, Traversable, TurnID};
 use abstutil::Timer;
-use geom::{Distance, PolyLine};
+use geom::{Distance, Duration, PolyLine};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -170,6 +169,33 @@ impl Path {
         self.total_length += self.steps[idx].as_traversable().length(map);
     }
 
+    // After map edits, the first upcoming step that doesn't exist anymore or can't be used with
+    // these constraints. The current step is skipped; the caller decides if it can be finished.
+    pub fn first_broken_step(&self, constraints: PathConstraints, map: &Map) -> Option<usize> {
+        self.steps
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, step)| match step {
+                PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
+                    !constraints.can_use(map.get_l(*l), map)
+                }
+                PathStep::Turn(t) => map.maybe_get_t(*t).is_none(),
+            })
+            .map(|(idx, _)| idx)
+    }
+
+    // Keeps the current step and swaps out everything after it. Also trusting the caller to
+    // connect the new steps and end in the same place as before.
+    pub fn replace_remaining_steps(&mut self, steps: Vec<PathStep>, map: &Map) {
+        for step in self.steps.drain(1..) {
+            self.total_length -= step.as_traversable().length(map);
+        }
+        for step in steps {
+            self.add(step, map);
+        }
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -439,13 +465,25 @@ impl Pathfinder {
         map: &Map,
         start: Position,
         end: Position,
-    ) -> Option<(BusStopID, BusStopID, BusRouteID)> {
+    ) -> Option<Vec<TransitRide>> {
         self.walking_with_transit_graph
             .as_ref()
             .unwrap()
             .should_use_transit(map, start, end)
     }
 
+    pub fn set_driving_travel_times(
+        &mut self,
+        map: &Map,
+        travel_times: BTreeMap<Traversable, Duration>,
+    ) {
+        self.car_graph.set_travel_times(map, travel_times);
+    }
+
+    pub fn get_driving_travel_times(&self) -> &BTreeMap<Traversable, Duration> {
+        self.car_graph.get_travel_times()
+    }
+
     pub fn apply_edits(&mut self, map: &Map, timer: &mut Timer) {
         timer.start("apply edits to car pathfinding");
         self.car_graph.apply_edits(map);
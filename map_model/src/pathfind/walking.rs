@@ -1,7 +1,7 @@
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::{
     BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID, LaneType, Map, Path,
-    PathRequest, PathStep, Position,
+    PathRequest, PathStep, Position, RailLineID, StationID,
 };
 use fast_paths::{FastGraph, InputGraph, PathCalculator};
 use geom::Distance;
@@ -20,11 +20,24 @@ pub struct SidewalkPathfinder {
     path_calc: ThreadLocal<RefCell<PathCalculator>>,
 }
 
+// Pedestrians can walk at most this far (as the crow flies) to transfer between a train and a bus.
+const MAX_TRANSFER_DIST: Distance = Distance::const_meters(200.0);
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 enum Node {
     // Direction determined later
     Cross(DirectedRoadID),
-    RideBus(BusStopID),
+    RideBus(BusRouteID, BusStopID),
+    RideTrain(RailLineID, StationID),
+}
+
+// One vehicle ridden during a trip using transit. Pedestrians walk between consecutive rides,
+// except when transferring between trains at the same station.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TransitRide {
+    // Board at the first stop, get off at the second
+    Bus(BusRouteID, BusStopID, BusStopID),
+    Train(RailLineID, StationID, StationID),
 }
 
 impl SidewalkPathfinder {
@@ -37,9 +50,16 @@ impl SidewalkPathfinder {
             }
         }
         if use_transit {
-            // Add a node for each bus stop.
-            for stop in map.all_bus_stops().values() {
-                nodes.get_or_insert(Node::RideBus(stop.id));
+            // Add a node for each stop of each route, so switching vehicles is explicit.
+            for route in map.get_all_bus_routes() {
+                for stop in &route.stops {
+                    nodes.get_or_insert(Node::RideBus(route.id, *stop));
+                }
+            }
+            for line in map.get_all_rail_lines() {
+                for station in &line.stations {
+                    nodes.get_or_insert(Node::RideTrain(line.id, *station));
+                }
             }
         }
 
@@ -53,8 +73,8 @@ impl SidewalkPathfinder {
     }
 
     pub fn apply_edits(&mut self, map: &Map) {
-        // The NodeMap is all sidewalks and bus stops -- it won't change. So we can also reuse the
-        // node ordering.
+        // The NodeMap is all sidewalks, bus stops, and stations -- it won't change. So we can also
+        // reuse the node ordering.
         let input_graph = make_input_graph(map, &self.nodes, self.use_transit);
         let node_ordering = self.graph.get_node_ordering();
         self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
@@ -109,11 +129,11 @@ impl SidewalkPathfinder {
         for pair in path.windows(2) {
             let lane1 = match pair[0] {
                 Node::Cross(dr) => map.get_l(get_sidewalk(dr, map)),
-                Node::RideBus(_) => unreachable!(),
+                Node::RideBus(_, _) | Node::RideTrain(_, _) => unreachable!(),
             };
             let l2 = match pair[1] {
                 Node::Cross(dr) => get_sidewalk(dr, map),
-                Node::RideBus(_) => unreachable!(),
+                Node::RideBus(_, _) | Node::RideTrain(_, _) => unreachable!(),
             };
 
             let fwd_t = map.get_turn_between(lane1.id, l2, lane1.dst_i);
@@ -138,7 +158,7 @@ impl SidewalkPathfinder {
         // Don't end a path in a turn; sim layer breaks.
         let last_lane = match path.last().unwrap() {
             Node::Cross(dr) => map.get_l(get_sidewalk(*dr, map)),
-            Node::RideBus(_) => unreachable!(),
+            Node::RideBus(_, _) | Node::RideTrain(_, _) => unreachable!(),
         };
         if Some(last_lane.src_i) == current_i {
             steps.push(PathStep::Lane(last_lane.id));
@@ -156,13 +176,13 @@ impl SidewalkPathfinder {
         ))
     }
 
-    // Attempt the pathfinding and see if we should ride a bus.
+    // Attempt the pathfinding and see if we should ride any buses or trains.
     pub fn should_use_transit(
         &self,
         map: &Map,
         start: Position,
         end: Position,
-    ) -> Option<(BusStopID, BusStopID, BusRouteID)> {
+    ) -> Option<Vec<TransitRide>> {
         // TODO maybe_get is a temporaryish hack -- some sidewalks are actually totally
         // disconnected, so there's no node for them. Just fail the pathfinding. Really this is a
         // bug in turn creation though.
@@ -172,26 +192,54 @@ impl SidewalkPathfinder {
             self.nodes.maybe_get(lane_to_node(end.lane(), map))?,
         )?;
 
-        let mut nodes = self.nodes.translate(&raw_path);
-        let mut first_stop = None;
-        for n in &nodes {
-            if let Node::RideBus(stop) = n {
-                first_stop = Some(*stop);
-                break;
+        // Split the path into the stretches spent on each vehicle.
+        let mut rides = Vec::new();
+        // (where the vehicle was boarded, the latest stop)
+        let mut current: Option<(Node, Node)> = None;
+        for n in self.nodes.translate(&raw_path) {
+            if let Some((first, _)) = current {
+                let same_vehicle = match (first, n) {
+                    (Node::RideBus(r1, _), Node::RideBus(r2, _)) => r1 == r2,
+                    (Node::RideTrain(l1, _), Node::RideTrain(l2, _)) => l1 == l2,
+                    _ => false,
+                };
+                if same_vehicle {
+                    current = Some((first, n));
+                    continue;
+                }
             }
-        }
-        let first_stop = first_stop?;
-        let possible_routes = map.get_routes_serving_stop(first_stop);
-
-        nodes.reverse();
-        for n in nodes {
-            if let Node::RideBus(stop2) = n {
-                if let Some(route) = possible_routes.iter().find(|r| r.stops.contains(&stop2)) {
-                    return Some((first_stop, stop2, route.id));
+            // Transferring might pass through a stop without riding anything from it.
+            if let Some(ride) = current.take().and_then(|(n1, n2)| make_ride(n1, n2)) {
+                rides.push(ride);
+            }
+            match n {
+                Node::RideBus(_, _) | Node::RideTrain(_, _) => {
+                    current = Some((n, n));
                 }
+                Node::Cross(_) => {}
             }
         }
-        unreachable!()
+        if let Some(ride) = current.and_then(|(n1, n2)| make_ride(n1, n2)) {
+            rides.push(ride);
+        }
+
+        if rides.is_empty() {
+            None
+        } else {
+            Some(rides)
+        }
+    }
+}
+
+fn make_ride(board: Node, alight: Node) -> Option<TransitRide> {
+    match (board, alight) {
+        (Node::RideBus(route, stop1), Node::RideBus(_, stop2)) if stop1 != stop2 => {
+            Some(TransitRide::Bus(route, stop1, stop2))
+        }
+        (Node::RideTrain(line, station1), Node::RideTrain(_, station2)) if station1 != station2 => {
+            Some(TransitRide::Train(line, station1, station2))
+        }
+        _ => None,
     }
 }
 
@@ -234,11 +282,25 @@ fn make_input_graph(map: &Map, nodes: &NodeMap<Node>, use_transit: bool) -> Inpu
     if use_transit {
         // Addd a "free" cost of 1 (fast_paths ignores 0-weight edges) for moving between the stop
         // and sidewalk.
-        for stop in map.all_bus_stops().values() {
-            let cross_lane = nodes.get(lane_to_node(stop.sidewalk_pos.lane(), map));
-            let ride_bus = nodes.get(Node::RideBus(stop.id));
-            input_graph.add_edge(cross_lane, ride_bus, 1);
-            input_graph.add_edge(ride_bus, cross_lane, 1);
+        for route in map.get_all_bus_routes() {
+            for stop in &route.stops {
+                let cross_lane =
+                    nodes.get(lane_to_node(map.get_bs(*stop).sidewalk_pos.lane(), map));
+                let ride_bus = nodes.get(Node::RideBus(route.id, *stop));
+                input_graph.add_edge(cross_lane, ride_bus, 1);
+                input_graph.add_edge(ride_bus, cross_lane, 1);
+            }
+        }
+        for line in map.get_all_rail_lines() {
+            for station in &line.stations {
+                let cross_lane = nodes.get(lane_to_node(
+                    map.get_station(*station).sidewalk_pos.lane(),
+                    map,
+                ));
+                let ride_train = nodes.get(Node::RideTrain(line.id, *station));
+                input_graph.add_edge(cross_lane, ride_train, 1);
+                input_graph.add_edge(ride_train, cross_lane, 1);
+            }
         }
 
         // Connect each adjacent stop along a route, again with a "free" cost.
@@ -254,12 +316,54 @@ fn make_input_graph(map: &Map, nodes: &NodeMap<Node>, use_transit: bool) -> Inpu
                     )))
             {
                 input_graph.add_edge(
-                    nodes.get(Node::RideBus(*stop1)),
-                    nodes.get(Node::RideBus(*stop2)),
+                    nodes.get(Node::RideBus(route.id, *stop1)),
+                    nodes.get(Node::RideBus(route.id, *stop2)),
+                    1,
+                );
+            }
+        }
+        for line in map.get_all_rail_lines() {
+            for (idx, station) in line.stations.iter().enumerate() {
+                input_graph.add_edge(
+                    nodes.get(Node::RideTrain(line.id, *station)),
+                    nodes.get(Node::RideTrain(
+                        line.id,
+                        line.stations[line.next_station_idx(idx)],
+                    )),
                     1,
                 );
             }
         }
+
+        // Transfers without leaving the platform, or walking a short way to a bus stop.
+        for line1 in map.get_all_rail_lines() {
+            for station in &line1.stations {
+                let ride_train = nodes.get(Node::RideTrain(line1.id, *station));
+                for line2 in map.get_lines_serving_station(*station) {
+                    if line1.id != line2.id {
+                        input_graph.add_edge(
+                            ride_train,
+                            nodes.get(Node::RideTrain(line2.id, *station)),
+                            1,
+                        );
+                    }
+                }
+
+                let pt = map.get_station(*station).pt;
+                for route in map.get_all_bus_routes() {
+                    for stop in &route.stops {
+                        let dist = map.get_bs(*stop).sidewalk_pos.pt(map).dist_to(pt);
+                        if dist > MAX_TRANSFER_DIST {
+                            continue;
+                        }
+                        let dist_cm = ((dist.inner_meters() * 100.0).round() as usize).max(1);
+                        let ride_bus = nodes.get(Node::RideBus(route.id, *stop));
+                        input_graph.add_edge(ride_train, ride_bus, dist_cm);
+                        input_graph.add_edge(ride_bus, ride_train, dist_cm);
+                    }
+                }
+            }
+        }
     }
     input_graph.freeze();
     input_graph
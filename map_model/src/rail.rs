@@ -0,0 +1,69 @@
+use crate::Position;
+use geom::{Duration, PolyLine, Pt2D};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StationID(pub usize);
+
+impl fmt::Display for StationID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StationID({0})", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RailLineID(pub usize);
+
+impl fmt::Display for RailLineID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RailLineID({0})", self.0)
+    }
+}
+
+// One platform of a light rail, subway, or train station. Several lines can share it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Station {
+    pub id: StationID,
+    pub name: String,
+    // Where the platform really is. It might be underground or elevated.
+    pub pt: Pt2D,
+    // Where pedestrians enter and leave the station
+    pub sidewalk_pos: Position,
+}
+
+// The rail network is grade-separated; tracks don't follow or interact with any lanes.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RailLine {
+    pub id: RailLineID,
+    pub name: String,
+    // Like buses, trains loop around these, going back to the first after the last.
+    pub stations: Vec<StationID>,
+    // tracks[i] goes from stations[i] to the next station.
+    pub tracks: Vec<Track>,
+    // Sorted by departure from the first station. Empty if there's no timetable for this line.
+    pub schedule: Vec<TrainRun>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Track {
+    pub geom: PolyLine,
+    // Scheduled time between departing one station and arriving at the next
+    pub travel_time: Duration,
+}
+
+// One scheduled trip of a train along part of a line.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrainRun {
+    // Index into the line's stations
+    pub first_station: usize,
+    pub departure: Duration,
+    // Including the first and last
+    pub num_stations: usize,
+}
+
+impl RailLine {
+    pub fn next_station_idx(&self, idx: usize) -> usize {
+        (idx + 1) % self.stations.len()
+    }
+}
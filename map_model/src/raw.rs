@@ -1,7 +1,7 @@
 use crate::make::get_lane_types;
 use crate::{osm, AreaType, IntersectionType, OffstreetParking, RoadSpec};
-use abstutil::{deserialize_btreemap, retain_btreemap, serialize_btreemap, Error, Timer};
-use geom::{Distance, GPSBounds, Polygon, Pt2D};
+use abstutil::{deserialize_btreemap, retain_btreemap, serialize_btreemap, Error, Timer, Warn};
+use geom::{Distance, GPSBounds, Line, PolyLine, Polygon, Pt2D};
 use gtfs::Route;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
@@ -14,10 +14,13 @@ pub struct RawMap {
     pub intersections: BTreeMap<OriginalIntersection, RawIntersection>,
     pub buildings: BTreeMap<OriginalBuilding, RawBuilding>,
     pub bus_routes: Vec<Route>,
+    // Light rail, subways, and trains
+    pub rail_routes: Vec<Route>,
     pub areas: Vec<RawArea>,
 
     pub boundary_polygon: Polygon,
     pub gps_bounds: GPSBounds,
+    pub driving_side: DrivingSide,
 }
 
 // A way to refer to roads across many maps.
@@ -79,6 +82,7 @@ impl RawMap {
             intersections: BTreeMap::new(),
             buildings: BTreeMap::new(),
             bus_routes: Vec::new(),
+            rail_routes: Vec::new(),
             areas: Vec::new(),
             // Some nonsense thing
             boundary_polygon: Polygon::rectangle(
@@ -87,6 +91,7 @@ impl RawMap {
                 Distance::meters(1.0),
             ),
             gps_bounds: GPSBounds::new(),
+            driving_side: DrivingSide::Right,
         }
     }
 
@@ -312,7 +317,10 @@ impl RawMap {
         };
         let mut roads = BTreeMap::new();
         for r in &i.roads {
-            roads.insert(*r, initial::Road::new(*r, &self.roads[r]));
+            roads.insert(
+                *r,
+                initial::Road::new(*r, &self.roads[r], self.driving_side),
+            );
         }
 
         let (i_pts, debug) = initial::intersection_polygon(&i, &mut roads, timer);
@@ -322,16 +330,16 @@ impl RawMap {
                 .values()
                 .map(|r| {
                     // A little of get_thick_polyline
-                    let pl = if r.fwd_width >= r.back_width {
+                    let pl = if r.right_width >= r.left_width {
                         r.trimmed_center_pts
-                            .shift_right((r.fwd_width - r.back_width) / 2.0)
+                            .shift_right((r.right_width - r.left_width) / 2.0)
                             .unwrap()
                     } else {
                         r.trimmed_center_pts
-                            .shift_left((r.back_width - r.fwd_width) / 2.0)
+                            .shift_left((r.left_width - r.right_width) / 2.0)
                             .unwrap()
                     };
-                    pl.make_polygons(r.fwd_width + r.back_width)
+                    pl.make_polygons(r.right_width + r.left_width)
                 })
                 .collect(),
             debug,
@@ -519,8 +527,8 @@ pub struct RawRoad {
 }
 
 impl RawRoad {
-    pub fn get_spec(&self) -> RoadSpec {
-        let (fwd, back) = get_lane_types(&self.osm_tags);
+    pub fn get_spec(&self, driving_side: DrivingSide) -> RoadSpec {
+        let (fwd, back) = get_lane_types(&self.osm_tags, driving_side);
         RoadSpec { fwd, back }
     }
 
@@ -579,6 +587,46 @@ impl RestrictionType {
     }
 }
 
+// Which side of the road vehicles drive on. This is a property of the whole map.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DrivingSide {
+    Right,
+    Left,
+}
+
+// "Right" and "left" in these methods are from the perspective of driving on the right; they
+// flip when driving on the left. Lanes going forwards are on the right of the center line, and
+// the outermost lane is the rightmost.
+impl DrivingSide {
+    pub fn right_shift(self, pl: &PolyLine, width: Distance) -> Warn<PolyLine> {
+        match self {
+            DrivingSide::Right => pl.shift_right(width),
+            DrivingSide::Left => pl.shift_left(width),
+        }
+    }
+
+    pub fn left_shift(self, pl: &PolyLine, width: Distance) -> Warn<PolyLine> {
+        match self {
+            DrivingSide::Right => pl.shift_left(width),
+            DrivingSide::Left => pl.shift_right(width),
+        }
+    }
+
+    pub fn right_shift_line(self, line: &Line, width: Distance) -> Line {
+        match self {
+            DrivingSide::Right => line.shift_right(width),
+            DrivingSide::Left => line.shift_left(width),
+        }
+    }
+
+    pub fn left_shift_line(self, line: &Line, width: Distance) -> Line {
+        match self {
+            DrivingSide::Right => line.shift_left(width),
+            DrivingSide::Left => line.shift_right(width),
+        }
+    }
+}
+
 // Directives from the map_editor crate to apply to the RawMap layer.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MapFixes {
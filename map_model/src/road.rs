@@ -1,7 +1,5 @@
 use crate::raw::{OriginalRoad, RestrictionType};
-use crate::{
-    osm, BusStopID, IntersectionID, LaneID, LaneType, Map, PathConstraints, LANE_THICKNESS,
-};
+use crate::{osm, BusStopID, IntersectionID, LaneID, LaneType, Map, PathConstraints};
 use abstutil::{Error, Warn};
 use geom::{Distance, PolyLine, Polygon, Speed};
 use serde_derive::{Deserialize, Serialize};
@@ -115,6 +113,10 @@ pub struct Road {
     pub center_pts: PolyLine,
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
+
+    // Parsed from OSM once during map construction
+    pub speed_limit_fwd: Speed,
+    pub speed_limit_back: Speed,
 }
 
 impl Road {
@@ -153,6 +155,29 @@ impl Road {
         panic!("{} doesn't contain {}", self.id, lane);
     }
 
+    // The total width of every lane going one direction
+    pub fn width(&self, fwds: bool, map: &Map) -> Distance {
+        let lanes = if fwds {
+            &self.children_forwards
+        } else {
+            &self.children_backwards
+        };
+        lanes.iter().map(|(l, _)| map.get_l(*l).width).sum()
+    }
+
+    // lane must belong to this road. How far the center of the lane is from the road's center
+    // line, on its own side.
+    pub fn offset_from_center(&self, lane: LaneID, map: &Map) -> Distance {
+        let (fwds, idx) = self.dir_and_offset(lane);
+        let lanes = if fwds {
+            &self.children_forwards
+        } else {
+            &self.children_backwards
+        };
+        let before: Distance = lanes[0..idx].iter().map(|(l, _)| map.get_l(*l).width).sum();
+        before + map.get_l(lane).width / 2.0
+    }
+
     pub fn parking_to_driving(&self, parking: LaneID) -> Option<LaneID> {
         // TODO Crossing bike/bus lanes means higher layers of sim should know to block these off
         // when parking/unparking
@@ -211,23 +236,13 @@ impl Road {
         }
     }
 
-    pub fn get_speed_limit(&self) -> Speed {
-        // TODO Should probably cache this
-        if let Some(limit) = self.osm_tags.get(osm::MAXSPEED) {
-            // TODO handle other units
-            if limit.ends_with(" mph") {
-                if let Ok(mph) = limit[0..limit.len() - 4].parse::<f64>() {
-                    return Speed::miles_per_hour(mph);
-                }
-            }
-        }
-
-        if self.osm_tags.get(osm::HIGHWAY) == Some(&"primary".to_string())
-            || self.osm_tags.get(osm::HIGHWAY) == Some(&"secondary".to_string())
-        {
-            return Speed::miles_per_hour(40.0);
+    // lane must belong to this road
+    pub fn get_speed_limit(&self, lane: LaneID) -> Speed {
+        if self.is_forwards(lane) {
+            self.speed_limit_fwd
+        } else {
+            self.speed_limit_back
         }
-        Speed::miles_per_hour(20.0)
     }
 
     pub fn get_zorder(&self) -> isize {
@@ -320,23 +335,24 @@ impl Road {
         search.iter().find(|(_, t)| lt == *t).map(|(id, _)| *id)
     }
 
-    pub fn get_thick_polyline(&self) -> Warn<(PolyLine, Distance)> {
-        let width_right = (self.children_forwards.len() as f64) * LANE_THICKNESS;
-        let width_left = (self.children_backwards.len() as f64) * LANE_THICKNESS;
-        let total_width = width_right + width_left;
-        if width_right >= width_left {
-            self.center_pts
-                .shift_right((width_right - width_left) / 2.0)
+    pub fn get_thick_polyline(&self, map: &Map) -> Warn<(PolyLine, Distance)> {
+        let width_fwd = self.width(true, map);
+        let width_back = self.width(false, map);
+        let total_width = width_fwd + width_back;
+        let driving_side = map.get_driving_side();
+        if width_fwd >= width_back {
+            driving_side
+                .right_shift(&self.center_pts, (width_fwd - width_back) / 2.0)
                 .map(|pl| (pl, total_width))
         } else {
-            self.center_pts
-                .shift_left((width_left - width_right) / 2.0)
+            driving_side
+                .left_shift(&self.center_pts, (width_back - width_fwd) / 2.0)
                 .map(|pl| (pl, total_width))
         }
     }
 
-    pub fn get_thick_polygon(&self) -> Warn<Polygon> {
-        self.get_thick_polyline()
+    pub fn get_thick_polygon(&self, map: &Map) -> Warn<Polygon> {
+        self.get_thick_polyline(map)
             .map(|(pl, width)| pl.make_polygons(width))
     }
 
@@ -386,8 +402,14 @@ impl Road {
                 "tertiary_link" => 9,
 
                 "residential" => 5,
+                "living_street" => 3,
+                "service" => 2,
 
+                "cycleway" => 1,
                 "footway" => 1,
+                "path" => 1,
+                "pedestrian" => 1,
+                "steps" => 1,
 
                 "unclassified" => 0,
                 "road" => 0,
@@ -407,3 +429,26 @@ impl Road {
         stops
     }
 }
+
+// Returns the (forwards, backwards) speed limits. maxspeed:forward and maxspeed:backward override
+// maxspeed. Without any of these, zone tags like "maxspeed:type=DE:urban" or the road class decide.
+pub fn get_speed_limits(osm_tags: &BTreeMap<String, String>) -> (Speed, Speed) {
+    let parse = |key: &str| osm_tags.get(key).and_then(|v| osm::parse_speed(v));
+    let default = parse(osm::MAXSPEED)
+        .or_else(|| parse("maxspeed:type"))
+        .or_else(|| parse("source:maxspeed"))
+        .unwrap_or_else(|| default_speed_limit(osm_tags));
+    (
+        parse("maxspeed:forward").unwrap_or(default),
+        parse("maxspeed:backward").unwrap_or(default),
+    )
+}
+
+fn default_speed_limit(osm_tags: &BTreeMap<String, String>) -> Speed {
+    if osm_tags.get(osm::HIGHWAY) == Some(&"primary".to_string())
+        || osm_tags.get(osm::HIGHWAY) == Some(&"secondary".to_string())
+    {
+        return Speed::miles_per_hour(40.0);
+    }
+    Speed::miles_per_hour(20.0)
+}
@@ -1,3 +1,4 @@
+use crate::raw::DrivingSide;
 use crate::{IntersectionID, Map, RoadID, TurnGroup, TurnGroupID, TurnID, TurnPriority, TurnType};
 use abstutil::{deserialize_btreemap, retain_btreeset, serialize_btreemap, Timer};
 use geom::Duration;
@@ -9,6 +10,9 @@ pub struct ControlTrafficSignal {
     pub id: IntersectionID,
     pub phases: Vec<Phase>,
     pub offset: Duration,
+    // Replace the phases and offset above during parts of the day. Whenever no plan is scheduled,
+    // the phases and offset above apply.
+    pub plans: Vec<SignalPlan>,
 
     #[serde(
         serialize_with = "serialize_btreemap",
@@ -17,15 +21,49 @@ pub struct ControlTrafficSignal {
     pub turn_groups: BTreeMap<TurnGroupID, TurnGroup>,
 }
 
+// When the scheduled plan changes, the phase running at that moment finishes first. Then the new
+// plan picks up wherever its own offset puts it, so coordinated signals stay coordinated.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SignalPlan {
+    // Time of day, from start (inclusive) to end (exclusive). Plans can't overlap.
+    pub start: Duration,
+    pub end: Duration,
+    pub phases: Vec<Phase>,
+    pub offset: Duration,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Phase {
     pub protected_groups: BTreeSet<TurnGroupID>,
     pub yield_groups: BTreeSet<TurnGroupID>,
+    // For actuated phases, this is the maximum green time.
     pub duration: Duration,
+    pub phase_type: PhaseType,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum PhaseType {
+    // Always lasts exactly the phase's duration.
+    Fixed,
+    // Lasts at least min_green. After that, the phase is extended as long as new agents keep
+    // arriving for it less than gap apart, up to the phase's duration. If nobody is waiting for
+    // the phase when it would start, it's skipped.
+    Actuated { min_green: Duration, gap: Duration },
 }
 
 impl ControlTrafficSignal {
     pub fn new(map: &Map, id: IntersectionID, timer: &mut Timer) -> ControlTrafficSignal {
+        // Edits can change the turns, so imported timing might not fit anymore.
+        if let Some(sheet) = map.get_signal_timing(id) {
+            match sheet.to_signal(map, id) {
+                Ok(ts) => {
+                    return ts;
+                }
+                Err(err) => {
+                    timer.warn(format!("Imported timing for {} doesn't fit: {}", id, err));
+                }
+            }
+        }
         let mut policies = ControlTrafficSignal::get_possible_policies(map, id);
         if policies.len() == 1 {
             timer.warn(format!("Falling back to greedy_assignment for {}", id));
@@ -67,28 +105,149 @@ impl ControlTrafficSignal {
         results
     }
 
-    pub fn current_phase_and_remaining_time(&self, now: Duration) -> (usize, &Phase, Duration) {
-        let mut cycle_length = Duration::ZERO;
-        for p in &self.phases {
-            cycle_length += p.duration;
+    // Any actuated phase means the sim has to track the current phase, since it depends on
+    // demand.
+    pub fn is_actuated(&self) -> bool {
+        self.phases
+            .iter()
+            .chain(self.plans.iter().flat_map(|plan| plan.phases.iter()))
+            .any(|p| match p.phase_type {
+                PhaseType::Actuated { .. } => true,
+                PhaseType::Fixed => false,
+            })
+    }
+
+    // The plan scheduled for this time of day, or None for the default phases and offset.
+    pub fn scheduled_plan(&self, now: Duration) -> Option<usize> {
+        let time = time_of_day(now);
+        self.plans
+            .iter()
+            .position(|plan| plan.start <= time && time < plan.end)
+    }
+
+    pub fn get_phases(&self, plan: Option<usize>) -> &Vec<Phase> {
+        match plan {
+            Some(idx) => &self.plans[idx].phases,
+            None => &self.phases,
         }
+    }
 
-        let mut now_offset = (now + self.offset) % cycle_length;
-        for (idx, p) in self.phases.iter().enumerate() {
-            if now_offset < p.duration {
-                return (idx, p, p.duration - now_offset);
+    pub fn get_phases_mut(&mut self, plan: Option<usize>) -> &mut Vec<Phase> {
+        match plan {
+            Some(idx) => &mut self.plans[idx].phases,
+            None => &mut self.phases,
+        }
+    }
+
+    pub fn get_offset(&self, plan: Option<usize>) -> Duration {
+        match plan {
+            Some(idx) => self.plans[idx].offset,
+            None => self.offset,
+        }
+    }
+
+    pub fn set_offset(&mut self, plan: Option<usize>, offset: Duration) {
+        match plan {
+            Some(idx) => {
+                self.plans[idx].offset = offset;
+            }
+            None => {
+                self.offset = offset;
+            }
+        }
+    }
+
+    pub fn turn_to_group(&self, t: TurnID) -> TurnGroupID {
+        // TODO Cache this?
+        self.turn_groups
+            .values()
+            .find(|g| g.members.contains(&t))
+            .map(|g| g.id)
+            .unwrap()
+    }
+
+    // Only correct for fixed-time signals; the sim tracks the current phase of actuated signals.
+    pub fn current_phase_and_remaining_time(&self, now: Duration) -> (usize, &Phase, Duration) {
+        let (plan, idx, remaining) = self.current_plan_and_phase(now);
+        (idx, &self.get_phases(plan)[idx], remaining)
+    }
+
+    // Like scheduled_plan, but the old plan is still in effect while it finishes its last phase.
+    // Only correct for fixed-time signals.
+    pub fn plan_in_effect(&self, now: Duration) -> Option<usize> {
+        self.current_plan_and_phase(now).0
+    }
+
+    fn current_plan_and_phase(&self, now: Duration) -> (Option<usize>, usize, Duration) {
+        let plan = self.scheduled_plan(now);
+        let time = time_of_day(now);
+        // When did the schedule last change? If nothing changed yet today, it was midnight,
+        // unless the simulation only just started.
+        let day = Duration::minutes(24 * 60);
+        if let Some(boundary) = self
+            .plans
+            .iter()
+            .flat_map(|p| vec![p.start, p.end])
+            .filter(|t| *t > Duration::ZERO && *t <= time)
+            .max()
+            .or_else(|| {
+                if now >= day {
+                    Some(Duration::ZERO)
+                } else {
+                    None
+                }
+            })
+        {
+            let changed_at = now - (time - boundary);
+            // Right before midnight, whatever ran until the end of the previous day was in effect.
+            let prev_end = if boundary == Duration::ZERO {
+                day
             } else {
-                now_offset -= p.duration;
+                boundary
+            };
+            let prev = self.plans.iter().position(|p| p.end == prev_end);
+            if prev != plan {
+                let (idx, remaining) =
+                    fixed_phase_at(self.get_phases(prev), self.get_offset(prev), changed_at);
+                if now < changed_at + remaining {
+                    return (prev, idx, changed_at + remaining - now);
+                }
             }
         }
-        unreachable!()
+        let (idx, remaining) = fixed_phase_at(self.get_phases(plan), self.get_offset(plan), now);
+        (plan, idx, remaining)
     }
 
     pub fn validate(self) -> Result<ControlTrafficSignal, String> {
+        let mut plans: Vec<&SignalPlan> = self.plans.iter().collect();
+        plans.sort_by_key(|p| p.start);
+        for (idx, plan) in plans.iter().enumerate() {
+            if plan.start >= plan.end || plan.end > Duration::minutes(24 * 60) {
+                return Err(format!(
+                    "Traffic signal {} has a plan from {} to {}",
+                    self.id, plan.start, plan.end
+                ));
+            }
+            if idx > 0 && plans[idx - 1].end > plan.start {
+                return Err(format!(
+                    "Traffic signal {} has plans overlapping at {}",
+                    self.id, plan.start
+                ));
+            }
+        }
+
+        self.validate_phases(&self.phases)?;
+        for plan in &self.plans {
+            self.validate_phases(&plan.phases)?;
+        }
+        Ok(self)
+    }
+
+    fn validate_phases(&self, phases: &Vec<Phase>) -> Result<(), String> {
         // Does the assignment cover the correct set of groups?
         let expected_groups: BTreeSet<TurnGroupID> = self.turn_groups.keys().cloned().collect();
         let mut actual_groups: BTreeSet<TurnGroupID> = BTreeSet::new();
-        for phase in &self.phases {
+        for phase in phases {
             actual_groups.extend(phase.protected_groups.iter());
             actual_groups.extend(phase.yield_groups.iter());
         }
@@ -107,7 +266,7 @@ impl ControlTrafficSignal {
             ));
         }
 
-        for phase in &self.phases {
+        for phase in phases {
             // Do any of the priority groups in one phase conflict?
             for g1 in phase.protected_groups.iter().map(|g| &self.turn_groups[g]) {
                 for g2 in phase.protected_groups.iter().map(|g| &self.turn_groups[g]) {
@@ -124,9 +283,21 @@ impl ControlTrafficSignal {
             for g in phase.yield_groups.iter().map(|g| &self.turn_groups[g]) {
                 assert!(g.turn_type != TurnType::Crosswalk);
             }
+
+            if let PhaseType::Actuated { min_green, gap } = phase.phase_type {
+                if min_green <= Duration::ZERO
+                    || min_green > phase.duration
+                    || gap <= Duration::ZERO
+                {
+                    return Err(format!(
+                        "Traffic signal {} has an actuated phase with min green {}, gap {}, and max green {}",
+                        self.id, min_green, gap, phase.duration
+                    ));
+                }
+            }
         }
 
-        Ok(self)
+        Ok(())
     }
 
     fn greedy_assignment(map: &Map, intersection: IntersectionID) -> ControlTrafficSignal {
@@ -164,6 +335,7 @@ impl ControlTrafficSignal {
             id: intersection,
             phases,
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups,
         };
         // This must succeed
@@ -192,6 +364,7 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
         ts.validate().ok()
@@ -239,6 +412,7 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups,
         };
         ts.validate().ok()
@@ -282,6 +456,7 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
         ts.validate().ok()
@@ -324,6 +499,7 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
         ts.validate().ok()
@@ -377,6 +553,7 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups: TurnGroup::for_i(i, map),
         };
         ts.validate().ok()
@@ -403,6 +580,7 @@ impl ControlTrafficSignal {
             id: i,
             phases: vec![all_walk, all_yield],
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups,
         };
         // This must succeed
@@ -440,14 +618,15 @@ impl ControlTrafficSignal {
             id: i,
             phases,
             offset: Duration::ZERO,
+            plans: Vec::new(),
             turn_groups,
         };
         ts.validate().ok()
     }
 
-    pub fn convert_to_ped_scramble(&mut self, map: &Map) {
+    pub fn convert_to_ped_scramble(&mut self, plan: Option<usize>, map: &Map) {
         // Remove Crosswalk groups from existing phases.
-        let mut replaced = std::mem::replace(&mut self.phases, Vec::new());
+        let mut replaced = std::mem::replace(self.get_phases_mut(plan), Vec::new());
         for phase in replaced.iter_mut() {
             // Crosswalks are only in protected_groups.
             retain_btreeset(&mut phase.protected_groups, |g| {
@@ -466,7 +645,6 @@ impl ControlTrafficSignal {
                 phase.yield_groups.remove(&g);
             }
         }
-        self.phases = replaced;
 
         let mut phase = Phase::new();
         for g in self.turn_groups.values() {
@@ -474,7 +652,8 @@ impl ControlTrafficSignal {
                 phase.edit_group(g, TurnPriority::Protected, &self.turn_groups, map);
             }
         }
-        self.phases.push(phase);
+        replaced.push(phase);
+        *self.get_phases_mut(plan) = replaced;
     }
 }
 
@@ -484,6 +663,7 @@ impl Phase {
             protected_groups: BTreeSet::new(),
             yield_groups: BTreeSet::new(),
             duration: Duration::seconds(30.0),
+            phase_type: PhaseType::Fixed,
         }
     }
 
@@ -502,14 +682,7 @@ impl Phase {
     }
 
     pub fn get_priority_of_turn(&self, t: TurnID, parent: &ControlTrafficSignal) -> TurnPriority {
-        // TODO Cache this?
-        let g = parent
-            .turn_groups
-            .values()
-            .find(|g| g.members.contains(&t))
-            .map(|g| g.id)
-            .unwrap();
-        self.get_priority_of_group(g)
+        self.get_priority_of_group(parent.turn_to_group(t))
     }
 
     pub fn get_priority_of_group(&self, g: TurnGroupID) -> TurnPriority {
@@ -566,6 +739,8 @@ fn expand_all_phases(phases: &mut Vec<Phase>, turn_groups: &BTreeMap<TurnGroupID
 const PROTECTED: bool = true;
 const YIELD: bool = false;
 
+// The templates are written for driving on the right: right turns are the easy ones that don't
+// cross oncoming traffic. When driving on the left, that's true of left turns instead.
 fn make_phases(
     map: &Map,
     i: IntersectionID,
@@ -579,6 +754,11 @@ fn make_phases(
         let mut phase = Phase::new();
 
         for (roads, turn_type, protected) in specs.into_iter() {
+            let turn_type = match (map.get_driving_side(), turn_type) {
+                (DrivingSide::Left, TurnType::Right) => TurnType::Left,
+                (DrivingSide::Left, TurnType::Left) => TurnType::Right,
+                _ => turn_type,
+            };
             for group in turn_groups.values() {
                 if !roads.contains(&group.id.from) || turn_type != group.turn_type {
                     continue;
@@ -607,3 +787,25 @@ fn make_phases(
 
     phases
 }
+
+fn time_of_day(now: Duration) -> Duration {
+    now % Duration::minutes(24 * 60)
+}
+
+// The current phase of a fixed-time cycle, and how much longer it lasts.
+fn fixed_phase_at(phases: &Vec<Phase>, offset: Duration, now: Duration) -> (usize, Duration) {
+    let mut cycle_length = Duration::ZERO;
+    for p in phases {
+        cycle_length += p.duration;
+    }
+
+    let mut now_offset = (now + offset) % cycle_length;
+    for (idx, p) in phases.iter().enumerate() {
+        if now_offset < p.duration {
+            return (idx, p.duration - now_offset);
+        } else {
+            now_offset -= p.duration;
+        }
+    }
+    unreachable!()
+}
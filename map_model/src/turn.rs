@@ -1,4 +1,4 @@
-use crate::{IntersectionID, LaneID, Map, RoadID, LANE_THICKNESS};
+use crate::{IntersectionID, LaneID, Map, RoadID};
 use abstutil::MultiMap;
 use geom::{Angle, Distance, PolyLine, Pt2D};
 use serde_derive::{Deserialize, Serialize};
@@ -235,13 +235,23 @@ impl TurnGroup {
         offsets.dedup();
         // TODO This breaks if the group is non-contiguous. Add a rightmost bike lane that gets a
         // crazy left turn.
-        let offset = if offsets.len() % 2 == 0 {
-            // Middle of two lanes
-            (offsets[offsets.len() / 2] as f64) - 0.5
+        let lanes = if dir {
+            &r.children_forwards
         } else {
-            offsets[offsets.len() / 2] as f64
+            &r.children_backwards
         };
-        let pl = pl.shift_right(LANE_THICKNESS * (0.5 + offset)).unwrap();
+        let width_until = |offset: usize| -> Distance {
+            lanes[0..offset]
+                .iter()
+                .map(|(l, _)| map.get_l(*l).width)
+                .sum()
+        };
+        let start = width_until(offsets[0]);
+        let end = width_until(*offsets.last().unwrap() + 1);
+        let pl = map
+            .get_driving_side()
+            .right_shift(&pl, (start + end) / 2.0)
+            .unwrap();
         let pl = if self
             .id
             .crosswalk
@@ -252,8 +262,7 @@ impl TurnGroup {
         } else {
             pl.reversed()
         };
-        let width = LANE_THICKNESS * ((*offsets.last().unwrap() - offsets[0] + 1) as f64);
-        (pl, width)
+        (pl, end - start)
     }
 
     pub fn conflicts_with(&self, other: &TurnGroup) -> bool {
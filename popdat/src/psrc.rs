@@ -2,6 +2,8 @@ use abstutil::{prettyprint_usize, skip_fail, FileWithProgress, Timer};
 use geom::{Distance, Duration, FindClosest, LonLat, Pt2D};
 use map_model::Map;
 use serde_derive::{Deserialize, Serialize};
+// Scenarios need this too
+pub use sim::Purpose;
 use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
@@ -17,6 +19,11 @@ pub struct Trip {
     pub purpose: (Purpose, Purpose),
     pub trip_time: Duration,
     pub trip_dist: Distance,
+
+    // (household, person within the household)
+    pub person: (usize, usize),
+    // Order of the trip within the person's day
+    pub seq: usize,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -40,21 +47,6 @@ pub enum Mode {
     Transit,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
-pub enum Purpose {
-    Home,
-    Work,
-    School,
-    Escort,
-    PersonalBusiness,
-    Shopping,
-    Meal,
-    Social,
-    Recreation,
-    Medical,
-    ParkAndRideTransfer,
-}
-
 pub fn import_trips(
     parcels_path: &str,
     trips_path: &str,
@@ -97,6 +89,14 @@ pub fn import_trips(
         // travdist
         let trip_dist = Distance::miles(rec[24].parse::<f64>()?);
 
+        // hhno and pno
+        let person = (
+            rec[10].trim_end_matches(".0").parse::<usize>()?,
+            rec[18].trim_end_matches(".0").parse::<usize>()?,
+        );
+        // tsvid
+        let seq = rec[19].trim_end_matches(".0").parse::<usize>()?;
+
         trips.push(Trip {
             from,
             to,
@@ -105,6 +105,8 @@ pub fn import_trips(
             mode,
             trip_time,
             trip_dist,
+            person,
+            seq,
         });
     }
     done(timer);
@@ -3,7 +3,9 @@ use crate::PopDat;
 use abstutil::Timer;
 use geom::{Distance, Duration, LonLat, Polygon, Pt2D};
 use map_model::{BuildingID, IntersectionID, Map, PathConstraints, Position};
-use sim::{DrivingGoal, Scenario, SidewalkSpot, SpawnTrip, TripSpec};
+use sim::{
+    DrivingGoal, PersonID, PersonSpec, PersonTrip, Scenario, SidewalkSpot, SpawnTrip, TripSpec,
+};
 use std::collections::{BTreeMap, HashMap};
 
 #[derive(Clone, Debug)]
@@ -16,6 +18,10 @@ pub struct Trip {
     // These are an upper bound when TripEndpt::Border is involved.
     pub trip_time: Duration,
     pub trip_dist: Distance,
+    // (household, person within the household)
+    pub person: (usize, usize),
+    // Order of the trip within the person's day
+    pub seq: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -94,17 +100,8 @@ impl Trip {
             Mode::Transit => {
                 let start = self.from.start_sidewalk_spot(map);
                 let goal = self.to.end_sidewalk_spot(map);
-                if let Some((stop1, stop2, route)) =
-                    map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
-                {
-                    Some(SpawnTrip::UsingTransit(
-                        self.depart_at,
-                        start,
-                        goal,
-                        route,
-                        stop1,
-                        stop2,
-                    ))
+                if let Some(rides) = map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos) {
+                    Some(SpawnTrip::UsingTransit(self.depart_at, start, goal, rides))
                 } else {
                     //timer.warn(format!("{:?} not actually using transit, because pathfinding didn't find any useful route", trip));
                     Some(SpawnTrip::JustWalking(self.depart_at, start, goal))
@@ -250,6 +247,8 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
             mode: trip.mode,
             trip_time: trip.trip_time,
             trip_dist: trip.trip_dist,
+            person: trip.person,
+            seq: trip.seq,
         };
 
         match (&trip.from, &trip.to) {
@@ -281,15 +280,70 @@ pub fn clip_trips(map: &Map, timer: &mut Timer) -> (Vec<Trip>, HashMap<BuildingI
 pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
     let (trips, _) = clip_trips(map, timer);
     // TODO Don't clone trips for parallelize
-    let individ_trips = timer
-        .parallelize("turn PSRC trips into SpawnTrips", trips.clone(), |trip| {
-            trip.to_spawn_trip(map)
-        })
-        .into_iter()
-        .flatten()
-        .collect();
+    let spawn_trips = timer.parallelize("turn PSRC trips into SpawnTrips", trips.clone(), |trip| {
+        trip.to_spawn_trip(map)
+    });
+
+    let mut trips_per_person: BTreeMap<(usize, usize), Vec<(Trip, SpawnTrip)>> = BTreeMap::new();
+    for (trip, maybe_spawn) in trips.into_iter().zip(spawn_trips) {
+        if let Some(spawn) = maybe_spawn {
+            trips_per_person
+                .entry(trip.person)
+                .or_insert_with(Vec::new)
+                .push((trip, spawn));
+        }
+    }
+
+    let mut people = Vec::new();
+    let mut individ_trips = Vec::new();
+    let mut leftover_trips = Vec::new();
+    for (_, mut trips) in trips_per_person {
+        trips.sort_by_key(|(trip, _)| (trip.depart_at, trip.seq));
+        // Somebody whose day doesn't start at a building in the map (or who never goes home
+        // within it) just gets independent trips.
+        let home = trips
+            .iter()
+            .find_map(|(trip, _)| match trip.from {
+                TripEndpt::Building(b) if trip.purpose.0 == Purpose::Home => Some(b),
+                _ => None,
+            })
+            .or_else(|| match trips[0].0.from {
+                TripEndpt::Building(b) => Some(b),
+                TripEndpt::Border(_, _) => None,
+            });
+        let home = if let Some(b) = home {
+            b
+        } else {
+            for (trip, spawn) in trips {
+                leftover_trips.push(trip);
+                individ_trips.push(spawn);
+            }
+            continue;
+        };
+
+        let drives = trips.iter().any(|(trip, _)| {
+            trip.mode == Mode::Drive
+                && match trip.from {
+                    TripEndpt::Building(_) => true,
+                    TripEndpt::Border(_, _) => false,
+                }
+        });
+        people.push(PersonSpec {
+            id: PersonID(people.len()),
+            home,
+            num_cars: if drives { 1 } else { 0 },
+            trips: trips
+                .into_iter()
+                .map(|(trip, spawn)| PersonTrip {
+                    trip: spawn,
+                    purpose: trip.purpose.1,
+                })
+                .collect(),
+        });
+    }
 
-    // How many parked cars do we need to spawn near each building?
+    // How many parked cars do we need to spawn near each building? People bring their own, so
+    // this is only for the leftover trips.
     // TODO This assumes trips are instantaneous. At runtime, somebody might try to use a parked
     // car from a building, but one hasn't been delivered yet.
     let mut individ_parked_cars = BTreeMap::new();
@@ -298,7 +352,8 @@ pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
         individ_parked_cars.insert(b.id, 0);
         avail_per_bldg.insert(b.id, 0);
     }
-    for trip in trips {
+    leftover_trips.sort_by_key(|trip| trip.depart_at);
+    for trip in leftover_trips {
         if trip.mode != Mode::Drive {
             continue;
         }
@@ -323,5 +378,7 @@ pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
         border_spawn_over_time: Vec::new(),
         individ_trips,
         individ_parked_cars,
+        people,
+        mode_choice: None,
     }
 }
@@ -1,8 +1,8 @@
-use crate::{AgentID, CarID, Event, TripID, TripMode, VehicleType};
-use abstutil::Counter;
+use crate::{AgentID, CarID, Event, PedestrianID, TripID, TripMode, VehicleType};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use derivative::Derivative;
 use geom::{Duration, DurationHistogram};
-use map_model::{BusRouteID, BusStopID, IntersectionID, Map, RoadID, Traversable};
+use map_model::{BusRouteID, BusStopID, IntersectionID, LaneID, Map, RoadID, Traversable};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
 
@@ -14,11 +14,41 @@ pub struct Analytics {
     #[serde(skip_serializing, skip_deserializing)]
     pub(crate) test_expectations: VecDeque<Event>,
     pub bus_arrivals: Vec<(Duration, CarID, BusRouteID, BusStopID)>,
+    pub bus_departures: Vec<(Duration, CarID, BusRouteID, BusStopID)>,
+    // Which scheduled run each bus is serving, as an index into the route's schedule
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub bus_runs: BTreeMap<CarID, usize>,
     #[serde(skip_serializing, skip_deserializing)]
     pub total_bus_passengers: Counter<BusRouteID>,
+    // Boarding time, route, stop, how long the passenger waited at the stop
+    pub bus_passenger_waits: Vec<(Duration, BusRouteID, BusStopID, Duration)>,
+    // Time, route, stop. Each time a full bus leaves a passenger behind.
+    pub bus_denied_boardings: Vec<(Duration, BusRouteID, BusStopID)>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    peds_waiting_for_bus: BTreeMap<PedestrianID, (BusStopID, Duration)>,
     // TODO Hack: No TripMode means aborted
     // Finish time, ID, mode (or None as aborted), trip duration
     pub finished_trips: Vec<(Duration, TripID, Option<TripMode>, Duration)>,
+    // Departure time and mode, for every trip whose mode was picked by a ModeChoice model
+    pub mode_choices: Vec<(Duration, TripMode)>,
+
+    // For feeding back into routing. Only cars, not bikes or buses, and not the partial lanes
+    // where a car starts or stops.
+    // Total time spent and number of cars
+    #[serde(skip_serializing, skip_deserializing)]
+    car_travel_times: BTreeMap<Traversable, (Duration, usize)>,
+    // Where each car is now, and when it got there
+    #[serde(skip_serializing, skip_deserializing)]
+    cars_entered: BTreeMap<CarID, (Traversable, Duration)>,
+    // Every lane each car drove on, over all of its trips
+    #[serde(skip_serializing, skip_deserializing)]
+    pub car_routes: BTreeMap<CarID, Vec<LaneID>>,
 }
 
 #[derive(Serialize, Deserialize, Derivative)]
@@ -43,8 +73,17 @@ impl Analytics {
             },
             test_expectations: VecDeque::new(),
             bus_arrivals: Vec::new(),
+            bus_departures: Vec::new(),
+            bus_runs: BTreeMap::new(),
             total_bus_passengers: Counter::new(),
+            bus_passenger_waits: Vec::new(),
+            bus_denied_boardings: Vec::new(),
+            peds_waiting_for_bus: BTreeMap::new(),
             finished_trips: Vec::new(),
+            mode_choices: Vec::new(),
+            car_travel_times: BTreeMap::new(),
+            cars_entered: BTreeMap::new(),
+            car_routes: BTreeMap::new(),
         }
     }
 
@@ -63,6 +102,22 @@ impl Analytics {
                 },
             };
 
+            if let AgentID::Car(c) = a {
+                if c.1 == VehicleType::Car {
+                    if let Some((from, since)) = self.cars_entered.insert(c, (to, time)) {
+                        let entry = self
+                            .car_travel_times
+                            .entry(from)
+                            .or_insert((Duration::ZERO, 0));
+                        entry.0 += time - since;
+                        entry.1 += 1;
+                    }
+                    if let Traversable::Lane(l) = to {
+                        self.car_routes.entry(c).or_insert_with(Vec::new).push(l);
+                    }
+                }
+            }
+
             match to {
                 Traversable::Lane(l) => {
                     let r = map.get_l(l).parent;
@@ -82,20 +137,43 @@ impl Analytics {
             };
         }
 
+        // Don't count the time between trips
+        if let Event::CarReachedParkingSpot(car, _) | Event::CarOrBikeReachedBorder(car, _) = ev {
+            self.cars_entered.remove(&car);
+        }
+
         // Test expectations
         if !self.test_expectations.is_empty() && &ev == self.test_expectations.front().unwrap() {
             println!("At {}, met expectation {:?}", time, ev);
             self.test_expectations.pop_front();
         }
 
-        // Bus arrivals
+        // Bus arrivals and departures
+        if let Event::BusStartedRun(bus, _, run) = ev {
+            self.bus_runs.insert(bus, run);
+        }
         if let Event::BusArrivedAtStop(bus, route, stop) = ev {
             self.bus_arrivals.push((time, bus, route, stop));
         }
+        if let Event::BusDepartedFromStop(bus, route, stop) = ev {
+            self.bus_departures.push((time, bus, route, stop));
+        }
 
         // Bus passengers
-        if let Event::PedEntersBus(_, _, route) = ev {
+        if let Event::PedReachedBusStop(ped, stop) = ev {
+            self.peds_waiting_for_bus.insert(ped, (stop, time));
+        }
+        if let Event::PedEntersBus(ped, _, route) = ev {
             self.total_bus_passengers.inc(route);
+            if let Some((stop, since)) = self.peds_waiting_for_bus.remove(&ped) {
+                self.bus_passenger_waits
+                    .push((time, route, stop, time - since));
+            }
+        }
+        if let Event::PedDeniedBoarding(ped, _, route) = ev {
+            if let Some((stop, _)) = self.peds_waiting_for_bus.get(&ped) {
+                self.bus_denied_boardings.push((time, route, *stop));
+            }
         }
 
         // Finished trips
@@ -151,6 +229,38 @@ impl Analytics {
         (all, num_aborted, per_mode)
     }
 
+    // The average time cars took to cross each lane and turn
+    pub fn car_travel_times(&self) -> BTreeMap<Traversable, Duration> {
+        self.car_travel_times
+            .iter()
+            .map(|(t, (total, count))| (*t, *total * (1.0 / (*count as f64))))
+            .collect()
+    }
+
+    // How much longer than free-flow cars have spent crossing this lane or turn, including the cars
+    // still on it
+    pub fn car_delay(&self, on: Traversable, free_flow: Duration, now: Duration) -> Duration {
+        let mut delay = Duration::ZERO;
+        if let Some((total, count)) = self.car_travel_times.get(&on) {
+            delay += (*total - free_flow * (*count as f64)).max(Duration::ZERO);
+        }
+        for (at, since) in self.cars_entered.values() {
+            if *at == on {
+                delay += (now - *since - free_flow).max(Duration::ZERO);
+            }
+        }
+        delay
+    }
+
+    // How many trips a ModeChoice model sent each way
+    pub fn mode_split(&self) -> BTreeMap<TripMode, usize> {
+        let mut split = BTreeMap::new();
+        for (_, mode) in &self.mode_choices {
+            *split.entry(*mode).or_insert(0) += 1;
+        }
+        split
+    }
+
     pub fn bus_arrivals(
         &self,
         now: Duration,
@@ -211,6 +321,95 @@ impl Analytics {
         delays_to_stop
     }
 
+    // Every stop of the route that a bus has reached so far.
+    pub fn bus_stop_stats(
+        &self,
+        now: Duration,
+        r: BusRouteID,
+        map: &Map,
+    ) -> BTreeMap<BusStopID, BusStopStats> {
+        let route = map.get_br(r);
+        let mut stats: BTreeMap<BusStopID, BusStopStats> = BTreeMap::new();
+
+        let mut last_arrival: BTreeMap<BusStopID, Duration> = BTreeMap::new();
+        let mut arrivals_per_bus: BTreeMap<CarID, Vec<(Duration, BusStopID)>> = BTreeMap::new();
+        for (t, car, route_id, stop) in &self.bus_arrivals {
+            if *t > now {
+                break;
+            }
+            if *route_id != r {
+                continue;
+            }
+            let s = stats.entry(*stop).or_insert_with(BusStopStats::new);
+            if let Some(prev) = last_arrival.insert(*stop, *t) {
+                s.headways.push((*t, *t - prev));
+            }
+            arrivals_per_bus
+                .entry(*car)
+                .or_insert_with(Vec::new)
+                .push((*t, *stop));
+        }
+
+        // For each bus following the schedule, the index of the next stop time in its run
+        let mut next_scheduled: BTreeMap<CarID, usize> = BTreeMap::new();
+        for (t, car, route_id, stop) in &self.bus_departures {
+            if *t > now {
+                break;
+            }
+            if *route_id != r {
+                continue;
+            }
+            // The bus must've most recently arrived at this stop
+            let (arrived, _) = arrivals_per_bus[car]
+                .iter()
+                .rev()
+                .find(|(t1, bs)| t1 <= t && bs == stop)
+                .unwrap();
+            let s = stats.get_mut(stop).unwrap();
+            s.dwell_times.push((*t, *t - *arrived));
+
+            // The timetable has departure times. Walk through the run in order, since a route
+            // might visit the same stop twice, and the timetable might skip stops.
+            if let Some(run) = self.bus_runs.get(car) {
+                let idx = next_scheduled.entry(*car).or_insert(0);
+                if let Some((bs, scheduled)) = route.schedule[*run].stop_times.get(*idx) {
+                    if bs == stop {
+                        s.schedule_deviation.push((*t, *t - *scheduled));
+                        *idx += 1;
+                    }
+                }
+            }
+        }
+
+        for (t, route_id, stop, wait) in &self.bus_passenger_waits {
+            if *t > now {
+                break;
+            }
+            if *route_id == r {
+                stats
+                    .entry(*stop)
+                    .or_insert_with(BusStopStats::new)
+                    .passenger_waits
+                    .push((*t, *wait));
+            }
+        }
+
+        for (t, route_id, stop) in &self.bus_denied_boardings {
+            if *t > now {
+                break;
+            }
+            if *route_id == r {
+                stats
+                    .entry(*stop)
+                    .or_insert_with(BusStopStats::new)
+                    .denied_boardings
+                    .push(*t);
+            }
+        }
+
+        stats
+    }
+
     // Slightly misleading -- TripMode::Transit means buses, not pedestrians taking transit
     pub fn throughput_road(
         &self,
@@ -271,3 +470,75 @@ impl Analytics {
         per_mode
     }
 }
+
+pub struct BusStopStats {
+    // (departure time, actual minus scheduled departure). Positive means late. Only buses
+    // following the schedule count. Buses go out of service at their last stop without departing,
+    // so that stop never counts.
+    pub schedule_deviation: Vec<(Duration, Duration)>,
+    // (arrival time, time since the previous bus on the route arrived)
+    pub headways: Vec<(Duration, Duration)>,
+    // (departure time, how long the bus stayed)
+    pub dwell_times: Vec<(Duration, Duration)>,
+    // (boarding time, how long the passenger waited)
+    pub passenger_waits: Vec<(Duration, Duration)>,
+    // When a full bus left somebody behind
+    pub denied_boardings: Vec<Duration>,
+}
+
+impl BusStopStats {
+    fn new() -> BusStopStats {
+        BusStopStats {
+            schedule_deviation: Vec::new(),
+            headways: Vec::new(),
+            dwell_times: Vec::new(),
+            passenger_waits: Vec::new(),
+            denied_boardings: Vec::new(),
+        }
+    }
+
+    // The coefficient of variation of the headways. 0 means perfectly regular service; bunched
+    // buses make this grow. None if there aren't enough arrivals yet.
+    pub fn headway_irregularity(&self) -> Option<f64> {
+        if self.headways.len() < 2 {
+            return None;
+        }
+        let n = self.headways.len() as f64;
+        let mean = self
+            .headways
+            .iter()
+            .map(|(_, dt)| dt.inner_seconds())
+            .sum::<f64>()
+            / n;
+        if mean == 0.0 {
+            return None;
+        }
+        let variance = self
+            .headways
+            .iter()
+            .map(|(_, dt)| (dt.inner_seconds() - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        Some(variance.sqrt() / mean)
+    }
+
+    pub fn avg_schedule_deviation(&self) -> Option<Duration> {
+        avg(&self.schedule_deviation)
+    }
+
+    pub fn avg_dwell_time(&self) -> Option<Duration> {
+        avg(&self.dwell_times)
+    }
+
+    pub fn avg_passenger_wait(&self) -> Option<Duration> {
+        avg(&self.passenger_waits)
+    }
+}
+
+fn avg(list: &[(Duration, Duration)]) -> Option<Duration> {
+    if list.is_empty() {
+        return None;
+    }
+    let total: f64 = list.iter().map(|(_, dt)| dt.inner_seconds()).sum();
+    Some(Duration::seconds(total / (list.len() as f64)))
+}
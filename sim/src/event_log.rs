@@ -0,0 +1,128 @@
+use crate::Event;
+use geom::Duration;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventLogFormat {
+    // Newline-delimited JSON, one LoggedEvent per line
+    Json,
+    // Consecutive bincode-encoded LoggedEvents
+    Binary,
+}
+
+#[derive(Clone, Debug)]
+pub struct EventLogOptions {
+    pub path: String,
+    pub format: EventLogFormat,
+    // Names of the Event variants to record, like "PedEntersBus". Empty means everything.
+    pub only: BTreeSet<String>,
+}
+
+impl EventLogOptions {
+    // Files ending in .bin get the binary format; anything else is JSON.
+    pub fn new(path: String) -> EventLogOptions {
+        let format = if path.ends_with(".bin") {
+            EventLogFormat::Binary
+        } else {
+            EventLogFormat::Json
+        };
+        EventLogOptions {
+            path,
+            format,
+            only: BTreeSet::new(),
+        }
+    }
+
+    // Only record these event types. A typo would silently log nothing, so unknown names are an
+    // error.
+    pub fn only_record(&mut self, names: Vec<String>) -> Result<(), String> {
+        for name in &names {
+            if !Event::ALL_NAMES.contains(&name.as_str()) {
+                return Err(format!(
+                    "unknown event type {}; try one of {}",
+                    name,
+                    Event::ALL_NAMES.join(", ")
+                ));
+            }
+        }
+        self.only = names.into_iter().collect();
+        Ok(())
+    }
+
+    // Every sim truncates its log, so sims sharing options need their own files. foo.json becomes
+    // foo_suffix.json.
+    pub fn with_suffix(&self, suffix: &str) -> EventLogOptions {
+        let path = Path::new(&self.path);
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        let suffix = suffix.replace(' ', "_");
+        let name = match path.extension() {
+            Some(ext) => format!("{}_{}.{}", stem, suffix, ext.to_string_lossy()),
+            None => format!("{}_{}", stem, suffix),
+        };
+        EventLogOptions {
+            path: path.with_file_name(name).to_string_lossy().to_string(),
+            format: self.format,
+            only: self.only.clone(),
+        }
+    }
+}
+
+// What's written to the log for every event
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LoggedEvent {
+    pub time: Duration,
+    pub event_type: String,
+    pub event: Event,
+}
+
+// Writes events to a file as the sim produces them, replacing anything already there. This isn't
+// part of the savestate; a sim loaded from one doesn't log anything.
+pub(crate) struct EventLog {
+    format: EventLogFormat,
+    only: BTreeSet<String>,
+    file: BufWriter<File>,
+}
+
+impl EventLog {
+    pub fn new(opts: &EventLogOptions) -> EventLog {
+        if let Some(parent) = Path::new(&opts.path).parent() {
+            std::fs::create_dir_all(parent).expect("Creating parent dir failed");
+        }
+        let file = File::create(&opts.path)
+            .unwrap_or_else(|err| panic!("Couldn't open event log {}: {}", opts.path, err));
+        println!("Logging events to {}", opts.path);
+        EventLog {
+            format: opts.format,
+            only: opts.only.clone(),
+            file: BufWriter::new(file),
+        }
+    }
+
+    pub fn record(&mut self, time: Duration, ev: &Event) {
+        let event_type = ev.name();
+        if !self.only.is_empty() && !self.only.contains(event_type) {
+            return;
+        }
+        let logged = LoggedEvent {
+            time,
+            event_type: event_type.to_string(),
+            event: ev.clone(),
+        };
+        let result = match self.format {
+            EventLogFormat::Json => serde_json::to_writer(&mut self.file, &logged)
+                .map_err(std::io::Error::from)
+                .and_then(|_| self.file.write_all(b"\n")),
+            EventLogFormat::Binary => bincode::serialize_into(&mut self.file, &logged)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err)),
+        };
+        result.expect("Writing to event log failed");
+    }
+
+    pub fn flush(&mut self) {
+        self.file.flush().expect("Flushing event log failed");
+    }
+}
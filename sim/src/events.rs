@@ -1,27 +1,83 @@
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, TripID, TripMode};
+use crate::{AgentID, CarID, ParkingSpot, PedestrianID, TrainID, TripID, TripMode};
 use geom::Duration;
-use map_model::{BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Traversable};
+use map_model::{
+    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, RailLineID, StationID, Traversable,
+};
 use serde_derive::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarOrBikeReachedBorder(CarID, IntersectionID),
 
+    // The index of the route's scheduled run
+    BusStartedRun(CarID, BusRouteID, usize),
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
     BusDepartedFromStop(CarID, BusRouteID, BusStopID),
+    TrainArrivedAtStation(TrainID, RailLineID, StationID),
+    TrainDepartedFromStation(TrainID, RailLineID, StationID),
 
     PedReachedParkingSpot(PedestrianID, ParkingSpot),
     PedReachedBuilding(PedestrianID, BuildingID),
     PedReachedBorder(PedestrianID, IntersectionID),
     PedReachedBusStop(PedestrianID, BusStopID),
     PedEntersBus(PedestrianID, CarID, BusRouteID),
+    // The bus was full
+    PedDeniedBoarding(PedestrianID, CarID, BusRouteID),
     PedLeavesBus(PedestrianID, CarID, BusRouteID),
+    PedReachedStation(PedestrianID, StationID),
+    PedEntersTrain(PedestrianID, TrainID, RailLineID),
+    PedLeavesTrain(PedestrianID, TrainID, RailLineID),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    // An informed driver changed their path to avoid this lane
+    CarRerouted(CarID, LaneID),
 
     AgentEntersTraversable(AgentID, Traversable),
 
     TripFinished(TripID, TripMode, Duration),
     TripAborted(TripID),
 }
+
+// Lists every variant once, so name() and ALL_NAMES can't disagree. A missing variant makes the
+// match in name() non-exhaustive.
+macro_rules! event_names {
+    ($($variant:ident),* $(,)?) => {
+        impl Event {
+            // Every value name() can return
+            pub const ALL_NAMES: &'static [&'static str] = &[$(stringify!($variant)),*];
+
+            // Matches the name of the variant
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Event::$variant(..) => stringify!($variant),)*
+                }
+            }
+        }
+    };
+}
+
+event_names!(
+    CarReachedParkingSpot,
+    CarOrBikeReachedBorder,
+    BusStartedRun,
+    BusArrivedAtStop,
+    BusDepartedFromStop,
+    TrainArrivedAtStation,
+    TrainDepartedFromStation,
+    PedReachedParkingSpot,
+    PedReachedBuilding,
+    PedReachedBorder,
+    PedReachedBusStop,
+    PedEntersBus,
+    PedDeniedBoarding,
+    PedLeavesBus,
+    PedReachedStation,
+    PedEntersTrain,
+    PedLeavesTrain,
+    BikeStoppedAtSidewalk,
+    CarRerouted,
+    AgentEntersTraversable,
+    TripFinished,
+    TripAborted,
+);
@@ -1,39 +1,50 @@
 mod analytics;
+mod event_log;
 mod events;
 mod make;
 mod mechanics;
+mod people;
+mod rail;
 mod render;
 mod router;
 mod scheduler;
 mod sim;
+mod trajectories;
 mod transit;
 mod trips;
 
-pub use self::analytics::Analytics;
+pub use self::analytics::{Analytics, BusStopStats};
+pub(crate) use self::event_log::EventLog;
+pub use self::event_log::{EventLogFormat, EventLogOptions, LoggedEvent};
 pub use self::events::Event;
 pub use self::make::{
-    ABTest, BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SimFlags,
-    SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
+    ABTest, BorderSpawnOverTime, ModeChoice, OriginDestination, Scenario, ScenarioProblem,
+    SeedParkedCars, SimFlags, SpawnOverTime, SpawnTrip, TripEstimate, TripSpawner, TripSpec,
 };
+pub use self::mechanics::SpeedProfile;
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
+pub(crate) use self::people::PeopleState;
+pub use self::people::{PersonSpec, PersonTrip, Purpose};
+pub(crate) use self::rail::RailSimState;
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{Sim, SimOptions};
+pub use self::trajectories::{Trajectory, TrajectoryRecorder, TrajectorySample};
 pub(crate) use self::transit::TransitSimState;
 pub use self::trips::{FinishedTrips, TripEnd, TripMode, TripStart, TripStatus};
 pub use self::trips::{TripCount, TripResult};
 pub(crate) use self::trips::{TripLeg, TripManager};
 pub use crate::render::{
     AgentMetadata, CarStatus, DontDrawAgents, DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput,
-    GetDrawAgents, PedCrowdLocation, UnzoomedAgent,
+    DrawTrainInput, GetDrawAgents, PedCrowdLocation, UnzoomedAgent,
 };
 use abstutil::Cloneable;
 use geom::{Distance, Duration, Pt2D, Speed};
 use map_model::{
     BuildingID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, Path, PathConstraints,
-    Position,
+    Position, StationID,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -81,6 +92,16 @@ impl fmt::Display for PedestrianID {
     }
 }
 
+// Trains run on the rail network, never on lanes, so they're not cars.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TrainID(pub usize);
+
+impl fmt::Display for TrainID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TrainID({0})", self.0)
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
 pub enum AgentID {
     Car(CarID),
@@ -114,6 +135,15 @@ impl fmt::Display for TripID {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PersonID(pub usize);
+
+impl fmt::Display for PersonID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PersonID({0})", self.0)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
 pub enum VehicleType {
     Car,
@@ -129,6 +159,23 @@ impl VehicleType {
             VehicleType::Bike => PathConstraints::Bike,
         }
     }
+
+    // These next two are only used for kinematic driving. In m/s^2.
+    pub fn max_accel(self) -> f64 {
+        match self {
+            VehicleType::Car => 2.5,
+            VehicleType::Bus => 1.2,
+            VehicleType::Bike => 1.0,
+        }
+    }
+
+    pub fn max_decel(self) -> f64 {
+        match self {
+            VehicleType::Car => 3.5,
+            VehicleType::Bus => 2.0,
+            VehicleType::Bike => 2.0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -207,15 +254,21 @@ impl DrivingGoal {
     }
 
     pub fn goal_pos(&self, constraints: PathConstraints, map: &Map) -> Position {
+        self.maybe_goal_pos(constraints, map)
+            .unwrap_or_else(|| panic!("No lane to drive to for {:?}", self))
+    }
+
+    // None if there's no lane anywhere near the building to park or stop on.
+    pub fn maybe_goal_pos(&self, constraints: PathConstraints, map: &Map) -> Option<Position> {
         let lane = match self {
             DrivingGoal::ParkNear(b) => match constraints {
-                PathConstraints::Car => map.find_driving_lane_near_building(*b),
-                PathConstraints::Bike => map.find_biking_lane_near_building(*b),
+                PathConstraints::Car => map.maybe_find_driving_lane_near_building(*b)?,
+                PathConstraints::Bike => map.maybe_find_biking_lane_near_building(*b)?,
                 PathConstraints::Bus | PathConstraints::Pedestrian => unreachable!(),
             },
             DrivingGoal::Border(_, l) => *l,
         };
-        Position::new(lane, map.get_l(lane).length())
+        Some(Position::new(lane, map.get_l(lane).length()))
     }
 
     pub(crate) fn make_router(&self, path: Path, map: &Map, vt: VehicleType) -> Router {
@@ -307,6 +360,13 @@ impl SidewalkSpot {
         }
     }
 
+    pub fn station(station: StationID, map: &Map) -> SidewalkSpot {
+        SidewalkSpot {
+            sidewalk_pos: map.get_station(station).sidewalk_pos,
+            connection: SidewalkPOI::Station(station),
+        }
+    }
+
     // Recall sidewalks are bidirectional.
     pub fn start_at_border(i: IntersectionID, map: &Map) -> Option<SidewalkSpot> {
         let lanes = map
@@ -374,6 +434,7 @@ pub enum SidewalkPOI {
     DeferredParkingSpot(BuildingID, DrivingGoal),
     Building(BuildingID),
     BusStop(BusStopID),
+    Station(StationID),
     Border(IntersectionID),
     // The equivalent position on the nearest driving/bike lane
     BikeRack(Position),
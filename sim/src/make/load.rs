@@ -1,4 +1,4 @@
-use crate::{Scenario, Sim, SimOptions};
+use crate::{EventLogOptions, Scenario, Sim, SimOptions};
 use abstutil::CmdArgs;
 use geom::Duration;
 use map_model::{Map, MapEdits};
@@ -29,6 +29,19 @@ impl SimFlags {
                 use_freeform_policy_everywhere: args.enabled("--freeform_policy"),
                 disable_block_the_box: args.enabled("--disable_block_the_box"),
                 recalc_lanechanging: !args.enabled("--dont_recalc_lc"),
+                kinematic_driving: args.enabled("--kinematic_driving"),
+                event_log: args.optional("--event_log").map(|path| {
+                    let mut opts = EventLogOptions::new(path);
+                    if let Some(only) = args.optional("--event_log_only") {
+                        opts.only_record(only.split(',').map(|x| x.to_string()).collect())
+                            .unwrap_or_else(|err| panic!("Bad --event_log_only: {}", err));
+                    }
+                    opts
+                }),
+                record_trajectories: args.optional_parse("--record_trajectories", Duration::parse),
+                informed_drivers: args
+                    .optional_parse("--informed_drivers", |s| s.parse::<f64>())
+                    .unwrap_or(0.0),
             },
         }
     }
@@ -1,11 +1,15 @@
 mod a_b_test;
 mod load;
+mod mode_choice;
 mod scenario;
 mod spawner;
+mod validate;
 
 pub use self::a_b_test::ABTest;
 pub use self::load::SimFlags;
+pub use self::mode_choice::{ModeChoice, TripEstimate};
 pub use self::scenario::{
     BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SpawnOverTime, SpawnTrip,
 };
 pub use self::spawner::{TripSpawner, TripSpec};
+pub use self::validate::ScenarioProblem;
@@ -0,0 +1,282 @@
+use crate::make::validate::ride_endpoints;
+use crate::{DrivingGoal, SidewalkPOI, SidewalkSpot, SpawnTrip, TripMode, MAX_CAR_LENGTH};
+use abstutil::Timer;
+use geom::{Distance, Duration, Speed};
+use map_model::{BuildingID, Map, PathConstraints, PathRequest, Position, TransitRide};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde_derive::{Deserialize, Serialize};
+
+// Rough speeds for estimating travel times. These don't need to match the simulation exactly.
+const WALKING_SPEED: Speed = Speed::const_meters_per_second(1.1);
+const BIKING_SPEED: Speed = Speed::const_meters_per_second(4.0);
+const BUS_SPEED: Speed = Speed::const_meters_per_second(6.0);
+const TRAIN_SPEED: Speed = Speed::const_meters_per_second(12.0);
+// Per ride, on average
+const TRANSIT_WAIT: Duration = Duration::const_seconds(300.0);
+
+// A multinomial logit model. Each mode that can make a trip costs its estimated travel time (in
+// minutes) times a weight, plus a flat penalty. A mode is picked with probability proportional to
+// exp(-cost), so map edits that change travel times shift demand between modes.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ModeChoice {
+    // The cost of one minute traveling each way
+    pub walk_per_minute: f64,
+    pub bike_per_minute: f64,
+    pub drive_per_minute: f64,
+    pub transit_per_minute: f64,
+    // Everything travel time doesn't capture: effort, parking, fares...
+    pub bike_constant: f64,
+    pub drive_constant: f64,
+    pub transit_constant: f64,
+}
+
+// Estimated travel times for every mode that can make one trip. Driving is included whenever
+// there's a route; whether the person has a car there is only decided when choosing.
+#[derive(Clone)]
+pub struct TripEstimate {
+    times: Vec<(TripMode, Duration)>,
+    // For transit, if that's possible
+    rides: Option<Vec<TransitRide>>,
+}
+
+impl TripEstimate {
+    pub fn times(&self, can_drive: bool) -> Vec<(TripMode, Duration)> {
+        self.times
+            .iter()
+            .filter(|(mode, _)| can_drive || *mode != TripMode::Drive)
+            .cloned()
+            .collect()
+    }
+}
+
+impl ModeChoice {
+    pub fn default_logit() -> ModeChoice {
+        ModeChoice {
+            walk_per_minute: 0.15,
+            bike_per_minute: 0.12,
+            drive_per_minute: 0.08,
+            transit_per_minute: 0.1,
+            bike_constant: 1.0,
+            drive_constant: 1.5,
+            transit_constant: 1.0,
+        }
+    }
+
+    // Only modes that can make the trip are returned. Driving assumes a car is parked near the
+    // start.
+    pub fn estimate_times(
+        from: BuildingID,
+        to: BuildingID,
+        can_drive: bool,
+        map: &Map,
+    ) -> Vec<(TripMode, Duration)> {
+        estimate(from, to, map).times(can_drive)
+    }
+
+    // Each estimate needs a few pathfinds, so do all of the trips at once.
+    pub fn estimate_trips(
+        trips: Vec<(BuildingID, BuildingID)>,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> Vec<TripEstimate> {
+        timer.parallelize(
+            "estimate travel times for mode choice",
+            trips,
+            |(from, to)| estimate(from, to, map),
+        )
+    }
+
+    // None for trips that rechoose() leaves alone.
+    pub fn estimate_spawn_trips(
+        trips: Vec<&SpawnTrip>,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> Vec<Option<TripEstimate>> {
+        let endpoints: Vec<Option<(BuildingID, BuildingID)>> = trips
+            .into_iter()
+            .map(|t| t.bldg_endpoints().filter(|(from, to)| from != to))
+            .collect();
+        let mut estimates =
+            ModeChoice::estimate_trips(endpoints.iter().filter_map(|x| *x).collect(), map, timer)
+                .into_iter();
+        endpoints
+            .into_iter()
+            .map(|x| x.map(|_| estimates.next().unwrap()))
+            .collect()
+    }
+
+    pub fn probabilities(&self, times: &[(TripMode, Duration)]) -> Vec<(TripMode, f64)> {
+        let utilities: Vec<f64> = times
+            .iter()
+            .map(|(mode, time)| {
+                let minutes = time.inner_seconds() / 60.0;
+                -match mode {
+                    TripMode::Walk => self.walk_per_minute * minutes,
+                    TripMode::Bike => self.bike_per_minute * minutes + self.bike_constant,
+                    TripMode::Drive => self.drive_per_minute * minutes + self.drive_constant,
+                    TripMode::Transit => self.transit_per_minute * minutes + self.transit_constant,
+                }
+            })
+            .collect();
+        // Subtract the best utility, so exp() doesn't overflow for long trips.
+        let best = utilities
+            .iter()
+            .cloned()
+            .fold(std::f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = utilities.iter().map(|u| (u - best).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        times
+            .iter()
+            .zip(weights)
+            .map(|((mode, _), w)| (*mode, w / total))
+            .collect()
+    }
+
+    // Picks how to get between two buildings, using an estimate for that trip. Driving trips will
+    // use some car parked near the start. None if there's no way to make the trip at all.
+    pub fn choose(
+        &self,
+        depart: Duration,
+        from: BuildingID,
+        to: BuildingID,
+        estimate: &TripEstimate,
+        can_drive: bool,
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Option<(TripMode, SpawnTrip)> {
+        let times = estimate.times(can_drive);
+        if times.is_empty() {
+            return None;
+        }
+
+        let mut choice = times[0].0;
+        let mut roll = rng.gen_range(0.0, 1.0);
+        for (mode, prob) in self.probabilities(&times) {
+            choice = mode;
+            if roll < prob {
+                break;
+            }
+            roll -= prob;
+        }
+
+        let start = SidewalkSpot::building(from, map);
+        let goal = SidewalkSpot::building(to, map);
+        let trip = match choice {
+            TripMode::Walk => SpawnTrip::JustWalking(depart, start, goal),
+            TripMode::Bike => SpawnTrip::UsingBike(depart, start, DrivingGoal::ParkNear(to)),
+            TripMode::Drive => {
+                SpawnTrip::MaybeUsingParkedCar(depart, from, DrivingGoal::ParkNear(to))
+            }
+            TripMode::Transit => {
+                SpawnTrip::UsingTransit(depart, start, goal, estimate.rides.clone().unwrap())
+            }
+        };
+        Some((choice, trip))
+    }
+
+    // Picks a new mode for a trip between two buildings, keeping the departure time. Trips
+    // starting or ending at a border keep their mode and give None.
+    pub fn rechoose(
+        &self,
+        trip: &SpawnTrip,
+        estimate: &TripEstimate,
+        can_drive: bool,
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Option<(TripMode, SpawnTrip)> {
+        let (from, to) = trip.bldg_endpoints()?;
+        if from == to {
+            return None;
+        }
+        self.choose(trip.depart(), from, to, estimate, can_drive, map, rng)
+    }
+}
+
+fn estimate(from: BuildingID, to: BuildingID, map: &Map) -> TripEstimate {
+    let mut times = Vec::new();
+    let start = SidewalkSpot::building(from, map).sidewalk_pos;
+    let goal = SidewalkSpot::building(to, map).sidewalk_pos;
+
+    if let Some(dist) = path_length(start, goal, PathConstraints::Pedestrian, map) {
+        times.push((TripMode::Walk, dist / WALKING_SPEED));
+    }
+
+    // Same checks as SpawnOverTime uses for bikes
+    let end_sidewalk = map.get_b(to).sidewalk();
+    if start.lane() != end_sidewalk
+        && map
+            .get_parent(end_sidewalk)
+            .sidewalk_to_bike(end_sidewalk)
+            .is_some()
+    {
+        if let Some(SidewalkPOI::BikeRack(pos)) =
+            SidewalkSpot::bike_from_bike_rack(start.lane(), map).map(|spot| spot.connection)
+        {
+            if let Some(dist) = DrivingGoal::ParkNear(to)
+                .maybe_goal_pos(PathConstraints::Bike, map)
+                .and_then(|end| path_length(pos, end, PathConstraints::Bike, map))
+            {
+                times.push((TripMode::Bike, dist / BIKING_SPEED));
+            }
+        }
+    }
+
+    // Buildings without a driving lane nearby just can't be driven from or to.
+    if let (Some(start_lane), Some(end)) = (
+        map.maybe_find_driving_lane_near_building(from),
+        DrivingGoal::ParkNear(to).maybe_goal_pos(PathConstraints::Car, map),
+    ) {
+        let req = PathRequest {
+            start: Position::new(
+                start_lane,
+                MAX_CAR_LENGTH.min(map.get_l(start_lane).length()),
+            ),
+            end,
+            constraints: PathConstraints::Car,
+        };
+        if let Some(path) = map.pathfind(req) {
+            // Free-flow time, at the speed limit everywhere
+            let mut time = Duration::ZERO;
+            for step in path.get_steps() {
+                let t = step.as_traversable();
+                time += t.length(map) / t.speed_limit(map);
+            }
+            times.push((TripMode::Drive, time));
+        }
+    }
+
+    let rides = map.should_use_transit(start, goal);
+    if let Some(ref rides) = rides {
+        let mut time = Duration::ZERO;
+        let mut at = start;
+        for ride in rides {
+            let (board, alight) = ride_endpoints(ride, map);
+            time += at.pt(map).dist_to(board.pt(map)) / WALKING_SPEED;
+            let speed = match ride {
+                TransitRide::Bus(_, _, _) => BUS_SPEED,
+                TransitRide::Train(_, _, _) => TRAIN_SPEED,
+            };
+            time += TRANSIT_WAIT + board.pt(map).dist_to(alight.pt(map)) / speed;
+            at = alight;
+        }
+        time += at.pt(map).dist_to(goal.pt(map)) / WALKING_SPEED;
+        times.push((TripMode::Transit, time));
+    }
+
+    TripEstimate { times, rides }
+}
+
+fn path_length(
+    start: Position,
+    end: Position,
+    constraints: PathConstraints,
+    map: &Map,
+) -> Option<Distance> {
+    map.pathfind(PathRequest {
+        start,
+        end,
+        constraints,
+    })
+    .map(|path| path.total_length())
+}
@@ -1,12 +1,12 @@
 use crate::{
-    CarID, DrivingGoal, ParkingSpot, SidewalkSpot, Sim, TripSpec, VehicleSpec, VehicleType,
-    BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    CarID, DrivingGoal, ModeChoice, ParkingSpot, PersonSpec, SidewalkPOI, SidewalkSpot, Sim,
+    TripMode, TripSpec, VehicleSpec, VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
 };
 use abstutil::{fork_rng, prettyprint_usize, Timer, WeightedUsizeChoice};
 use geom::{Distance, Duration, Speed};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, DirectedRoadID, FullNeighborhoodInfo, Map, PathConstraints,
-    Position, RoadID,
+    BuildingID, DirectedRoadID, FullNeighborhoodInfo, Map, PathConstraints, Position, RoadID,
+    TransitRide,
 };
 use rand::seq::SliceRandom;
 use rand::Rng;
@@ -20,6 +20,7 @@ pub struct Scenario {
     pub map_name: String,
 
     // Higher-level ways of specifying stuff
+    // Trains too
     pub seed_buses: bool,
     pub seed_parked_cars: Vec<SeedParkedCars>,
     pub spawn_over_time: Vec<SpawnOverTime>,
@@ -28,6 +29,12 @@ pub struct Scenario {
     // Much more detailed
     pub individ_trips: Vec<SpawnTrip>,
     pub individ_parked_cars: BTreeMap<BuildingID, usize>,
+    // Each person's trips happen in order, reusing their own cars
+    pub people: Vec<PersonSpec>,
+
+    // If present, the mode of every trip between two buildings is picked when the scenario is
+    // instantiated, based on the current map. Otherwise modes are fixed by the scenario.
+    pub mode_choice: Option<ModeChoice>,
 }
 
 // SpawnOverTime and BorderSpawnOverTime should be kept separate. Agents in SpawnOverTime pick
@@ -81,6 +88,7 @@ impl Scenario {
                 prettyprint_usize(self.border_spawn_over_time.len())
             ),
             format!("{} SpawnTrip", prettyprint_usize(self.individ_trips.len())),
+            format!("{} people", prettyprint_usize(self.people.len())),
         ]
     }
 
@@ -94,6 +102,9 @@ impl Scenario {
             for route in map.get_all_bus_routes() {
                 sim.seed_bus_route(route, map, timer);
             }
+            for line in map.get_all_rail_lines() {
+                sim.seed_rail_line(line);
+            }
         }
 
         timer.start("load full neighborhood info");
@@ -124,6 +135,24 @@ impl Scenario {
                 panic!("Neighborhood {} isn't defined", s.start_from_neighborhood);
             }
 
+            // The model ignores percent_biking and percent_use_transit. Trips to a border still
+            // use those.
+            if let (Some(model), OriginDestination::Neighborhood(ref n)) =
+                (self.mode_choice.as_ref(), &s.goal)
+            {
+                s.spawn_chosen_agents(
+                    model,
+                    n,
+                    rng,
+                    sim,
+                    &mut reserved_cars,
+                    &neighborhoods,
+                    map,
+                    timer,
+                );
+                continue;
+            }
+
             timer.start_iter("SpawnOverTime each agent", s.num_agents);
             for _ in 0..s.num_agents {
                 timer.next();
@@ -147,11 +176,36 @@ impl Scenario {
         }
         individ_parked_cars.shuffle(rng);
         seed_individ_parked_cars(individ_parked_cars, sim, map, rng, timer);
+        seed_people(
+            &self.people,
+            self.mode_choice.as_ref(),
+            sim,
+            map,
+            rng,
+            timer,
+        );
 
+        let estimates = if self.mode_choice.is_some() {
+            ModeChoice::estimate_spawn_trips(self.individ_trips.iter().collect(), map, timer)
+        } else {
+            Vec::new()
+        };
         timer.start_iter("SpawnTrip", self.individ_trips.len());
-        for t in &self.individ_trips {
+        for (idx, t) in self.individ_trips.iter().enumerate() {
             timer.next();
-            let (depart, spec) = t.clone().to_trip_spec(rng);
+            let mut trip = t.clone();
+            if let (Some(model), Some(Some(estimate))) = (&self.mode_choice, estimates.get(idx)) {
+                // Only people who were going to drive have a car waiting for them.
+                let can_drive = match t {
+                    SpawnTrip::MaybeUsingParkedCar(_, _, _) => true,
+                    _ => false,
+                };
+                if let Some((mode, new_trip)) = model.rechoose(t, estimate, can_drive, map, rng) {
+                    sim.record_mode_choice(new_trip.depart(), mode);
+                    trip = new_trip;
+                }
+            }
+            let (depart, spec) = trip.to_trip_spec(rng);
             sim.schedule_trip(depart, spec, map);
         }
 
@@ -206,6 +260,8 @@ impl Scenario {
                 .collect(),
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            people: Vec::new(),
+            mode_choice: None,
         };
         for i in map.all_outgoing_borders() {
             s.spawn_over_time.push(SpawnOverTime {
@@ -231,6 +287,8 @@ impl Scenario {
             border_spawn_over_time: Vec::new(),
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            people: Vec::new(),
+            mode_choice: None,
         }
     }
 
@@ -258,6 +316,8 @@ impl Scenario {
             border_spawn_over_time: Vec::new(),
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            people: Vec::new(),
+            mode_choice: None,
         }
     }
 
@@ -307,6 +367,79 @@ impl Scenario {
 }
 
 impl SpawnOverTime {
+    // Estimating travel times needs a few pathfinds per agent, so pick everyone's endpoints first
+    // and estimate them all at once.
+    fn spawn_chosen_agents(
+        &self,
+        model: &ModeChoice,
+        goal_neighborhood: &str,
+        rng: &mut XorShiftRng,
+        sim: &mut Sim,
+        reserved_cars: &mut HashSet<CarID>,
+        neighborhoods: &HashMap<String, FullNeighborhoodInfo>,
+        map: &Map,
+        timer: &mut Timer,
+    ) {
+        let mut agents = Vec::new();
+        for _ in 0..self.num_agents {
+            let spawn_time = rand_time(rng, self.start_time, self.stop_time);
+            let from_bldg = *neighborhoods[&self.start_from_neighborhood]
+                .buildings
+                .choose(rng)
+                .unwrap();
+            let to_bldg = *neighborhoods[goal_neighborhood]
+                .buildings
+                .choose(rng)
+                .unwrap();
+            if from_bldg == to_bldg {
+                timer.warn("Skipping trip between same two buildings".to_string());
+                continue;
+            }
+            agents.push((spawn_time, from_bldg, to_bldg));
+        }
+        let estimates = ModeChoice::estimate_trips(
+            agents.iter().map(|(_, from, to)| (*from, *to)).collect(),
+            map,
+            timer,
+        );
+
+        timer.start_iter("SpawnOverTime each agent", agents.len());
+        for ((spawn_time, from_bldg, to_bldg), estimate) in agents.into_iter().zip(estimates) {
+            timer.next();
+            let parked_car = sim
+                .get_parked_cars_by_owner(from_bldg)
+                .into_iter()
+                .find(|p| !reserved_cars.contains(&p.vehicle.id))
+                .map(|p| (p.vehicle.id, p.spot));
+            if let Some((mode, trip)) = model.choose(
+                spawn_time,
+                from_bldg,
+                to_bldg,
+                &estimate,
+                parked_car.is_some(),
+                map,
+                rng,
+            ) {
+                sim.record_mode_choice(spawn_time, mode);
+                let spec = if mode == TripMode::Drive {
+                    let (car, spot) = parked_car.unwrap();
+                    reserved_cars.insert(car);
+                    TripSpec::UsingParkedCar {
+                        start: SidewalkSpot::building(from_bldg, map),
+                        spot,
+                        goal: DrivingGoal::ParkNear(to_bldg),
+                        ped_speed: Scenario::rand_ped_speed(rng),
+                    }
+                } else {
+                    trip.to_trip_spec(rng).1
+                };
+                sim.schedule_trip(spawn_time, spec, map);
+            } else {
+                timer.warn(format!("No way to get from {} to {}", from_bldg, to_bldg));
+            }
+        }
+    }
+
     fn spawn_agent(
         &self,
         rng: &mut XorShiftRng,
@@ -397,16 +530,14 @@ impl SpawnOverTime {
             if rng.gen_bool(self.percent_use_transit) {
                 // TODO This throws away some work. It also sequentially does expensive
                 // work right here.
-                if let Some((stop1, stop2, route)) =
+                if let Some(rides) =
                     map.should_use_transit(start_spot.sidewalk_pos, goal.sidewalk_pos)
                 {
                     sim.schedule_trip(
                         spawn_time,
                         TripSpec::UsingTransit {
                             start: start_spot,
-                            route,
-                            stop1,
-                            stop2,
+                            rides,
                             goal,
                             ped_speed: Scenario::rand_ped_speed(rng),
                         },
@@ -463,16 +594,14 @@ impl BorderSpawnOverTime {
                 if rng.gen_bool(self.percent_use_transit) {
                     // TODO This throws away some work. It also sequentially does expensive
                     // work right here.
-                    if let Some((stop1, stop2, route)) =
+                    if let Some(rides) =
                         map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
                     {
                         sim.schedule_trip(
                             spawn_time,
                             TripSpec::UsingTransit {
                                 start: start.clone(),
-                                route,
-                                stop1,
-                                stop2,
+                                rides,
                                 goal,
                                 ped_speed: Scenario::rand_ped_speed(rng),
                             },
@@ -715,20 +844,7 @@ fn seed_individ_parked_cars(
     base_rng: &mut XorShiftRng,
     timer: &mut Timer,
 ) {
-    let mut open_spots_per_road: BTreeMap<RoadID, Vec<ParkingSpot>> = BTreeMap::new();
-    for spot in sim.get_all_parking_spots().1 {
-        let r = match spot {
-            ParkingSpot::Onstreet(l, _) => map.get_l(l).parent,
-            ParkingSpot::Offstreet(b, _) => map.get_l(map.get_b(b).sidewalk()).parent,
-        };
-        open_spots_per_road
-            .entry(r)
-            .or_insert_with(Vec::new)
-            .push(spot);
-    }
-    for spots in open_spots_per_road.values_mut() {
-        spots.shuffle(base_rng);
-    }
+    let mut open_spots_per_road = open_spots_per_road(sim, map, base_rng);
     let all_roads = map
         .all_roads()
         .iter()
@@ -757,6 +873,110 @@ fn seed_individ_parked_cars(
     }
 }
 
+// Each person's cars start parked near their home.
+fn seed_people(
+    people: &[PersonSpec],
+    mode_choice: Option<&ModeChoice>,
+    sim: &mut Sim,
+    map: &Map,
+    base_rng: &mut XorShiftRng,
+    timer: &mut Timer,
+) {
+    let mut open_spots_per_road = open_spots_per_road(sim, map, base_rng);
+    let all_roads = map
+        .all_roads()
+        .iter()
+        .map(|r| r.id)
+        .collect::<BTreeSet<_>>();
+
+    let mut estimates = if mode_choice.is_some() {
+        ModeChoice::estimate_spawn_trips(
+            people
+                .iter()
+                .flat_map(|p| p.trips.iter().map(|t| &t.trip))
+                .collect(),
+            map,
+            timer,
+        )
+    } else {
+        Vec::new()
+    }
+    .into_iter();
+
+    timer.start_iter("seed people", people.len());
+    for person in people {
+        timer.next();
+        let mut car_spots = Vec::new();
+        for _ in 0..person.num_cars {
+            if let Some(spot) = find_spot_near_building(
+                person.home,
+                &mut open_spots_per_road,
+                &all_roads,
+                map,
+                timer,
+            ) {
+                car_spots.push((Scenario::rand_car(base_rng), spot));
+            } else {
+                timer.warn(format!("Not enough room to park {}'s car", person.id));
+            }
+        }
+
+        // People can only drive from wherever they left their car.
+        let mut car_at = if car_spots.is_empty() {
+            None
+        } else {
+            Some(person.home)
+        };
+        let mut trips = Vec::new();
+        for t in &person.trips {
+            let mut trip = t.trip.clone();
+            if let (Some(model), Some(Some(estimate))) = (mode_choice, estimates.next()) {
+                let can_drive =
+                    car_at.is_some() && trip.bldg_endpoints().map(|(from, _)| from) == car_at;
+                if let Some((mode, new_trip)) =
+                    model.rechoose(&trip, &estimate, can_drive, map, base_rng)
+                {
+                    sim.record_mode_choice(new_trip.depart(), mode);
+                    trip = new_trip;
+                }
+            }
+            if let SpawnTrip::MaybeUsingParkedCar(_, from, ref goal) = trip {
+                if car_at == Some(from) {
+                    car_at = match goal {
+                        DrivingGoal::ParkNear(b) => Some(*b),
+                        DrivingGoal::Border(_, _) => None,
+                    };
+                }
+            }
+            let (depart, spec) = trip.to_trip_spec(base_rng);
+            trips.push((depart, t.purpose, spec));
+        }
+        sim.add_person(person.id, person.home, car_spots, trips, map);
+    }
+}
+
+fn open_spots_per_road(
+    sim: &Sim,
+    map: &Map,
+    base_rng: &mut XorShiftRng,
+) -> BTreeMap<RoadID, Vec<ParkingSpot>> {
+    let mut open_spots_per_road: BTreeMap<RoadID, Vec<ParkingSpot>> = BTreeMap::new();
+    for spot in sim.get_all_parking_spots().1 {
+        let r = match spot {
+            ParkingSpot::Onstreet(l, _) => map.get_l(l).parent,
+            ParkingSpot::Offstreet(b, _) => map.get_l(map.get_b(b).sidewalk()).parent,
+        };
+        open_spots_per_road
+            .entry(r)
+            .or_insert_with(Vec::new)
+            .push(spot);
+    }
+    for spots in open_spots_per_road.values_mut() {
+        spots.shuffle(base_rng);
+    }
+    open_spots_per_road
+}
+
 // Pick a parking spot for this building. If the building's road has a free spot, use it. If not,
 // start BFSing out from the road in a deterministic way until finding a nearby road with an open
 // spot.
@@ -820,17 +1040,58 @@ pub enum SpawnTrip {
     MaybeUsingParkedCar(Duration, BuildingID, DrivingGoal),
     UsingBike(Duration, SidewalkSpot, DrivingGoal),
     JustWalking(Duration, SidewalkSpot, SidewalkSpot),
-    UsingTransit(
-        Duration,
-        SidewalkSpot,
-        SidewalkSpot,
-        BusRouteID,
-        BusStopID,
-        BusStopID,
-    ),
+    UsingTransit(Duration, SidewalkSpot, SidewalkSpot, Vec<TransitRide>),
 }
 
 impl SpawnTrip {
+    pub fn depart(&self) -> Duration {
+        match self {
+            SpawnTrip::CarAppearing { depart, .. } => *depart,
+            SpawnTrip::MaybeUsingParkedCar(depart, _, _)
+            | SpawnTrip::UsingBike(depart, _, _)
+            | SpawnTrip::JustWalking(depart, _, _)
+            | SpawnTrip::UsingTransit(depart, _, _, _) => *depart,
+        }
+    }
+
+    // None if the trip starts or ends somewhere besides a building
+    pub fn bldg_endpoints(&self) -> Option<(BuildingID, BuildingID)> {
+        let from = match self {
+            SpawnTrip::CarAppearing { .. } => {
+                return None;
+            }
+            SpawnTrip::MaybeUsingParkedCar(_, b, _) => *b,
+            SpawnTrip::UsingBike(_, start, _)
+            | SpawnTrip::JustWalking(_, start, _)
+            | SpawnTrip::UsingTransit(_, start, _, _) => match start.connection {
+                SidewalkPOI::Building(b) => b,
+                _ => {
+                    return None;
+                }
+            },
+        };
+        let to = match self {
+            SpawnTrip::CarAppearing { .. } => unreachable!(),
+            SpawnTrip::MaybeUsingParkedCar(_, _, goal) | SpawnTrip::UsingBike(_, _, goal) => {
+                match goal {
+                    DrivingGoal::ParkNear(b) => *b,
+                    DrivingGoal::Border(_, _) => {
+                        return None;
+                    }
+                }
+            }
+            SpawnTrip::JustWalking(_, _, goal) | SpawnTrip::UsingTransit(_, _, goal, _) => {
+                match goal.connection {
+                    SidewalkPOI::Building(b) => b,
+                    _ => {
+                        return None;
+                    }
+                }
+            }
+        };
+        Some((from, to))
+    }
+
     // (departure time, spec)
     pub fn to_trip_spec(self, rng: &mut XorShiftRng) -> (Duration, TripSpec) {
         match self {
@@ -878,14 +1139,12 @@ impl SpawnTrip {
                     ped_speed: Scenario::rand_ped_speed(rng),
                 },
             ),
-            SpawnTrip::UsingTransit(depart, start, goal, route, stop1, stop2) => (
+            SpawnTrip::UsingTransit(depart, start, goal, rides) => (
                 depart,
                 TripSpec::UsingTransit {
                     start,
                     goal,
-                    route,
-                    stop1,
-                    stop2,
+                    rides,
                     ped_speed: Scenario::rand_ped_speed(rng),
                 },
             ),
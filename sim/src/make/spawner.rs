@@ -1,11 +1,11 @@
 use crate::{
     CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, ParkingSimState, ParkingSpot,
-    PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
+    PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
     VehicleSpec, MAX_CAR_LENGTH,
 };
 use abstutil::Timer;
 use geom::{Duration, Speed, EPSILON_DIST};
-use map_model::{BuildingID, BusRouteID, BusStopID, Map, PathConstraints, PathRequest, Position};
+use map_model::{BuildingID, Map, Path, PathConstraints, PathRequest, Position, TransitRide};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 
@@ -43,9 +43,8 @@ pub enum TripSpec {
     UsingTransit {
         start: SidewalkSpot,
         goal: SidewalkSpot,
-        route: BusRouteID,
-        stop1: BusStopID,
-        stop2: BusStopID,
+        // Walk between each of these, unless transferring between trains at one station
+        rides: Vec<TransitRide>,
         ped_speed: Speed,
     },
 }
@@ -53,9 +52,18 @@ pub enum TripSpec {
 #[derive(Serialize, Deserialize, PartialEq)]
 pub struct TripSpawner {
     parked_cars_claimed: BTreeSet<CarID>,
-    trips: Vec<(Duration, Option<PedestrianID>, Option<CarID>, TripSpec)>,
+    trips: Vec<PendingTrip>,
 }
 
+// (start time, pedestrian, vehicle, what to do, who's doing it)
+type PendingTrip = (
+    Duration,
+    Option<PedestrianID>,
+    Option<CarID>,
+    TripSpec,
+    Option<PersonID>,
+);
+
 impl TripSpawner {
     pub fn new() -> TripSpawner {
         TripSpawner {
@@ -64,15 +72,17 @@ impl TripSpawner {
         }
     }
 
+    // Returns false if the trip was skipped.
     pub fn schedule_trip(
         &mut self,
         start_time: Duration,
         ped_id: Option<PedestrianID>,
         car_id: Option<CarID>,
         spec: TripSpec,
+        person: Option<PersonID>,
         map: &Map,
         parking: &ParkingSimState,
-    ) {
+    ) -> bool {
         // TODO We'll want to repeat this validation when we spawn stuff later for a second leg...
         match &spec {
             TripSpec::CarAppearing {
@@ -137,7 +147,7 @@ impl TripSpawner {
                         "Can't start biking from {}; no biking or driving lane nearby?",
                         start.sidewalk_pos.lane()
                     );
-                    return;
+                    return false;
                 }
                 if let DrivingGoal::ParkNear(b) = goal {
                     let last_lane = goal.goal_pos(PathConstraints::Bike, map).lane();
@@ -151,7 +161,7 @@ impl TripSpawner {
                             "Can't fulfill {:?} for a bike trip; no sidewalk near {}",
                             goal, last_lane
                         );
-                        return;
+                        return false;
                     }
                     // A bike trip going from one lane to the same lane should... just walk.
                     if start.sidewalk_pos.lane() == map.get_b(*b).sidewalk() {
@@ -168,21 +178,23 @@ impl TripSpawner {
                                 goal: SidewalkSpot::building(*b, map),
                                 ped_speed: *ped_speed,
                             },
+                            person,
                         ));
-                        return;
+                        return true;
                     }
                 }
             }
             TripSpec::UsingTransit { .. } => {}
         };
 
-        self.trips.push((start_time, ped_id, car_id, spec));
+        self.trips.push((start_time, ped_id, car_id, spec, person));
+        true
     }
 
     pub fn spawn_all(
         &mut self,
         map: &Map,
-        parking: &ParkingSimState,
+        parking: &mut ParkingSimState,
         trips: &mut TripManager,
         scheduler: &mut Scheduler,
         timer: &mut Timer,
@@ -197,271 +209,350 @@ impl TripSpawner {
             },
         );
         timer.start_iter("spawn trips", paths.len());
-        for ((start_time, ped_id, car_id, spec), req, maybe_path) in paths {
+        for (tuple, req, maybe_path) in paths {
             timer.next();
-            match spec {
-                TripSpec::CarAppearing {
-                    start_pos,
-                    vehicle_spec,
-                    goal,
-                    ped_speed,
-                } => {
-                    // Assumption: If a car is appearing at a border and driving to a building,
-                    // then it's owned by that building. Otherwise we wind up with endless waves of
-                    // parked cars that're never reused.
-                    let owner = if let DrivingGoal::ParkNear(b) = goal {
-                        Some(b)
-                    } else {
-                        None
-                    };
-                    let vehicle = vehicle_spec.make(car_id.unwrap(), owner);
-                    let mut legs = vec![TripLeg::Drive(vehicle.clone(), goal.clone())];
-                    if let DrivingGoal::ParkNear(b) = goal {
+            if let Err(err) = self.spawn_trip(
+                tuple,
+                req,
+                maybe_path,
+                map,
+                parking,
+                trips,
+                scheduler,
+                retry_if_no_room,
+            ) {
+                timer.warn(err);
+            }
+        }
+
+        timer.start("finalize spawned trips");
+        scheduler.finalize_batch();
+        timer.stop("finalize spawned trips");
+    }
+
+    // For the few trips scheduled while the sim is running, like somebody's next trip of the day.
+    // Problems are reported like everything else happening mid-simulation.
+    pub fn spawn_during_sim(
+        &mut self,
+        now: Duration,
+        map: &Map,
+        parking: &mut ParkingSimState,
+        trips: &mut TripManager,
+        scheduler: &mut Scheduler,
+    ) {
+        for tuple in std::mem::replace(&mut self.trips, Vec::new()) {
+            let req = tuple.3.get_pathfinding_request(map, parking);
+            let maybe_path = map.pathfind(req.clone());
+            if let Err(err) =
+                self.spawn_trip(tuple, req, maybe_path, map, parking, trips, scheduler, true)
+            {
+                println!("WARNING: At {}, {}", now, err);
+            }
+        }
+        scheduler.finalize_batch();
+    }
+
+    // If the trip can't start, it's aborted and the reason is returned.
+    fn spawn_trip(
+        &mut self,
+        (start_time, ped_id, car_id, spec, person): PendingTrip,
+        req: PathRequest,
+        maybe_path: Option<Path>,
+        map: &Map,
+        parking: &mut ParkingSimState,
+        trips: &mut TripManager,
+        scheduler: &mut Scheduler,
+        retry_if_no_room: bool,
+    ) -> Result<(), String> {
+        match spec {
+            TripSpec::CarAppearing {
+                start_pos,
+                vehicle_spec,
+                goal,
+                ped_speed,
+            } => {
+                // Assumption: If a car is appearing at a border and driving to a building,
+                // then it's owned by that building. Otherwise we wind up with endless waves of
+                // parked cars that're never reused.
+                let owner = if let DrivingGoal::ParkNear(b) = goal {
+                    Some(b)
+                } else {
+                    None
+                };
+                let vehicle = vehicle_spec.make(car_id.unwrap(), owner);
+                let mut legs = vec![TripLeg::Drive(vehicle.clone(), goal.clone())];
+                if let DrivingGoal::ParkNear(b) = goal {
+                    legs.push(TripLeg::Walk(
+                        ped_id.unwrap(),
+                        ped_speed,
+                        SidewalkSpot::building(b, map),
+                    ));
+                }
+                let trip_start = TripStart::Border(map.get_l(start_pos.lane()).src_i);
+                let trip = trips.new_trip(start_time, trip_start, legs, person);
+                if let Some(path) = maybe_path {
+                    let router = goal.make_router(path, map, vehicle.vehicle_type);
+                    scheduler.quick_push(
+                        start_time,
+                        Command::SpawnCar(
+                            CreateCar::for_appearing(vehicle, start_pos, router, trip),
+                            retry_if_no_room,
+                        ),
+                    );
+                } else {
+                    trips.abort_trip_failed_start(trip);
+                    return Err(format!(
+                        "CarAppearing trip couldn't find the first path {}",
+                        req
+                    ));
+                }
+            }
+            TripSpec::UsingParkedCar {
+                start,
+                spot,
+                goal,
+                ped_speed,
+            } => {
+                let vehicle = parking.get_car_at_spot(spot).unwrap().vehicle.clone();
+                // People might start from anywhere they left their own car, not just home.
+                let start_bldg = match start.connection {
+                    SidewalkPOI::Building(b) => b,
+                    _ => unreachable!(),
+                };
+                if person.is_none() {
+                    assert_eq!(vehicle.owner, Some(start_bldg));
+                }
+                // From here on, parking keeps track of who's using the car.
+                self.parked_cars_claimed.remove(&vehicle.id);
+
+                let parking_spot = SidewalkSpot::parking_spot(spot, map, parking);
+
+                let mut legs = vec![
+                    TripLeg::Walk(ped_id.unwrap(), ped_speed, parking_spot.clone()),
+                    TripLeg::Drive(vehicle.clone(), goal.clone()),
+                ];
+                match goal {
+                    DrivingGoal::ParkNear(b) => {
                         legs.push(TripLeg::Walk(
                             ped_id.unwrap(),
                             ped_speed,
                             SidewalkSpot::building(b, map),
                         ));
                     }
-                    let trip_start = TripStart::Border(map.get_l(start_pos.lane()).src_i);
-                    let trip = trips.new_trip(start_time, trip_start, legs);
-                    if let Some(path) = maybe_path {
-                        let router = goal.make_router(path, map, vehicle.vehicle_type);
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnCar(
-                                CreateCar::for_appearing(vehicle, start_pos, router, trip),
-                                retry_if_no_room,
-                            ),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "CarAppearing trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
-                    }
+                    DrivingGoal::Border(_, _) => {}
                 }
-                TripSpec::UsingParkedCar {
-                    start,
-                    spot,
-                    goal,
-                    ped_speed,
-                } => {
-                    let vehicle = &parking.get_car_at_spot(spot).unwrap().vehicle;
-                    match start.connection {
-                        SidewalkPOI::Building(b) => assert_eq!(vehicle.owner, Some(b)),
-                        _ => unreachable!(),
-                    };
+                let trip = trips.new_trip(start_time, TripStart::Bldg(start_bldg), legs, person);
 
-                    let parking_spot = SidewalkSpot::parking_spot(spot, map, parking);
+                if let Some(path) = maybe_path {
+                    // Until the driver reaches the car, nobody else can take it -- not a
+                    // housemate looking for a car, or anybody's next trip.
+                    parking.reserve_parked_car(vehicle.id);
+                    scheduler.quick_push(
+                        start_time,
+                        Command::SpawnPed(CreatePedestrian {
+                            id: ped_id.unwrap(),
+                            speed: ped_speed,
+                            start,
+                            goal: parking_spot,
+                            path,
+                            trip,
+                        }),
+                    );
+                } else {
+                    trips.abort_trip_failed_start(trip);
+                    return Err(format!(
+                        "UsingParkedCar trip couldn't find the first path {}",
+                        req
+                    ));
+                }
+            }
+            TripSpec::MaybeUsingParkedCar {
+                start_bldg,
+                goal,
+                ped_speed,
+            } => {
+                let walk_to = SidewalkSpot::deferred_parking_spot(start_bldg, goal, map);
+                // Can't add TripLeg::Drive, because we don't know the vehicle yet! Plumb along
+                // the DrivingGoal, so we can expand the trip later.
+                let legs = vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone())];
+                let trip = trips.new_trip(start_time, TripStart::Bldg(start_bldg), legs, person);
 
-                    let mut legs = vec![
-                        TripLeg::Walk(ped_id.unwrap(), ped_speed, parking_spot.clone()),
-                        TripLeg::Drive(vehicle.clone(), goal.clone()),
-                    ];
-                    match goal {
-                        DrivingGoal::ParkNear(b) => {
-                            legs.push(TripLeg::Walk(
-                                ped_id.unwrap(),
-                                ped_speed,
-                                SidewalkSpot::building(b, map),
-                            ));
+                scheduler.quick_push(
+                    start_time,
+                    Command::SpawnPed(CreatePedestrian {
+                        id: ped_id.unwrap(),
+                        speed: ped_speed,
+                        start: SidewalkSpot::building(start_bldg, map),
+                        goal: walk_to,
+                        // This is guaranteed to work, and is junk anyway.
+                        path: maybe_path.unwrap(),
+                        trip,
+                    }),
+                );
+            }
+            TripSpec::JustWalking {
+                start,
+                goal,
+                ped_speed,
+            } => {
+                let trip = trips.new_trip(
+                    start_time,
+                    match start.connection {
+                        SidewalkPOI::Building(b) => TripStart::Bldg(b),
+                        SidewalkPOI::SuddenlyAppear => {
+                            TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
                         }
-                        DrivingGoal::Border(_, _) => {}
-                    }
-                    let trip =
-                        trips.new_trip(start_time, TripStart::Bldg(vehicle.owner.unwrap()), legs);
+                        SidewalkPOI::Border(i) => TripStart::Border(i),
+                        _ => unreachable!(),
+                    },
+                    vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, goal.clone())],
+                    person,
+                );
 
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal: parking_spot,
-                                path,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "UsingParkedCar trip couldn't find the first path {}",
-                            req
+                if let Some(path) = maybe_path {
+                    scheduler.quick_push(
+                        start_time,
+                        Command::SpawnPed(CreatePedestrian {
+                            id: ped_id.unwrap(),
+                            speed: ped_speed,
+                            start,
+                            goal,
+                            path,
+                            trip,
+                        }),
+                    );
+                } else {
+                    trips.abort_trip_failed_start(trip);
+                    return Err(format!(
+                        "JustWalking trip couldn't find the first path {}",
+                        req
+                    ));
+                }
+            }
+            TripSpec::UsingBike {
+                start,
+                vehicle,
+                goal,
+                ped_speed,
+            } => {
+                let walk_to =
+                    SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map).unwrap();
+                let mut legs = vec![
+                    TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
+                    TripLeg::Drive(vehicle.make(car_id.unwrap(), None), goal.clone()),
+                ];
+                match goal {
+                    DrivingGoal::ParkNear(b) => {
+                        legs.push(TripLeg::Walk(
+                            ped_id.unwrap(),
+                            ped_speed,
+                            SidewalkSpot::building(b, map),
                         ));
-                        trips.abort_trip_failed_start(trip);
                     }
-                }
-                TripSpec::MaybeUsingParkedCar {
-                    start_bldg,
-                    goal,
-                    ped_speed,
-                } => {
-                    let walk_to = SidewalkSpot::deferred_parking_spot(start_bldg, goal, map);
-                    // Can't add TripLeg::Drive, because we don't know the vehicle yet! Plumb along
-                    // the DrivingGoal, so we can expand the trip later.
-                    let legs = vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone())];
-                    let trip = trips.new_trip(start_time, TripStart::Bldg(start_bldg), legs);
+                    DrivingGoal::Border(_, _) => {}
+                };
+                let trip = trips.new_trip(
+                    start_time,
+                    match start.connection {
+                        SidewalkPOI::Building(b) => TripStart::Bldg(b),
+                        SidewalkPOI::SuddenlyAppear => {
+                            TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
+                        }
+                        SidewalkPOI::Border(i) => TripStart::Border(i),
+                        _ => unreachable!(),
+                    },
+                    legs,
+                    person,
+                );
 
+                if let Some(path) = maybe_path {
                     scheduler.quick_push(
                         start_time,
                         Command::SpawnPed(CreatePedestrian {
                             id: ped_id.unwrap(),
                             speed: ped_speed,
-                            start: SidewalkSpot::building(start_bldg, map),
+                            start,
                             goal: walk_to,
-                            // This is guaranteed to work, and is junk anyway.
-                            path: maybe_path.unwrap(),
+                            path,
                             trip,
                         }),
                     );
+                } else {
+                    trips.abort_trip_failed_start(trip);
+                    return Err(format!(
+                        "UsingBike trip couldn't find the first path {}",
+                        req
+                    ));
                 }
-                TripSpec::JustWalking {
-                    start,
-                    goal,
-                    ped_speed,
-                } => {
-                    let trip = trips.new_trip(
-                        start_time,
-                        match start.connection {
-                            SidewalkPOI::Building(b) => TripStart::Bldg(b),
-                            SidewalkPOI::SuddenlyAppear => {
-                                TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
-                            }
-                            SidewalkPOI::Border(i) => TripStart::Border(i),
-                            _ => unreachable!(),
-                        },
-                        vec![TripLeg::Walk(ped_id.unwrap(), ped_speed, goal.clone())],
-                    );
-
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal,
-                                path,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "JustWalking trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
+            }
+            TripSpec::UsingTransit {
+                start,
+                rides,
+                goal,
+                ped_speed,
+            } => {
+                let ped = ped_id.unwrap();
+                let walk_to = boarding_spot(&rides[0], map);
+                let mut legs = Vec::new();
+                let mut last_station = None;
+                for ride in rides {
+                    // Transfers between trains at the same station happen on the platform,
+                    // with no walking in between.
+                    let on_platform = match ride {
+                        TransitRide::Train(_, station1, _) => last_station == Some(station1),
+                        TransitRide::Bus(_, _, _) => false,
+                    };
+                    if !on_platform {
+                        legs.push(TripLeg::Walk(ped, ped_speed, boarding_spot(&ride, map)));
                     }
+                    last_station = match ride {
+                        TransitRide::Train(_, _, station2) => Some(station2),
+                        TransitRide::Bus(_, _, _) => None,
+                    };
+                    legs.push(match ride {
+                        TransitRide::Bus(route, _, stop2) => TripLeg::RideBus(ped, route, stop2),
+                        TransitRide::Train(line, _, station2) => {
+                            TripLeg::RideTrain(ped, line, station2)
+                        }
+                    });
                 }
-                TripSpec::UsingBike {
-                    start,
-                    vehicle,
-                    goal,
-                    ped_speed,
-                } => {
-                    let walk_to =
-                        SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map).unwrap();
-                    let mut legs = vec![
-                        TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
-                        TripLeg::Drive(vehicle.make(car_id.unwrap(), None), goal.clone()),
-                    ];
-                    match goal {
-                        DrivingGoal::ParkNear(b) => {
-                            legs.push(TripLeg::Walk(
-                                ped_id.unwrap(),
-                                ped_speed,
-                                SidewalkSpot::building(b, map),
-                            ));
+                legs.push(TripLeg::Walk(ped, ped_speed, goal));
+                let trip = trips.new_trip(
+                    start_time,
+                    match start.connection {
+                        SidewalkPOI::Building(b) => TripStart::Bldg(b),
+                        SidewalkPOI::SuddenlyAppear => {
+                            TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
                         }
-                        DrivingGoal::Border(_, _) => {}
-                    };
-                    let trip = trips.new_trip(
-                        start_time,
-                        match start.connection {
-                            SidewalkPOI::Building(b) => TripStart::Bldg(b),
-                            SidewalkPOI::SuddenlyAppear => {
-                                TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
-                            }
-                            SidewalkPOI::Border(i) => TripStart::Border(i),
-                            _ => unreachable!(),
-                        },
-                        legs,
-                    );
+                        SidewalkPOI::Border(i) => TripStart::Border(i),
+                        _ => unreachable!(),
+                    },
+                    legs,
+                    person,
+                );
 
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal: walk_to,
-                                path,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "UsingBike trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
-                    }
-                }
-                TripSpec::UsingTransit {
-                    start,
-                    route,
-                    stop1,
-                    stop2,
-                    goal,
-                    ped_speed,
-                } => {
-                    let walk_to = SidewalkSpot::bus_stop(stop1, map);
-                    let trip = trips.new_trip(
+                if let Some(path) = maybe_path {
+                    scheduler.quick_push(
                         start_time,
-                        match start.connection {
-                            SidewalkPOI::Building(b) => TripStart::Bldg(b),
-                            SidewalkPOI::SuddenlyAppear => {
-                                TripStart::Border(map.get_l(start.sidewalk_pos.lane()).src_i)
-                            }
-                            SidewalkPOI::Border(i) => TripStart::Border(i),
-                            _ => unreachable!(),
-                        },
-                        vec![
-                            TripLeg::Walk(ped_id.unwrap(), ped_speed, walk_to.clone()),
-                            TripLeg::RideBus(ped_id.unwrap(), route, stop2),
-                            TripLeg::Walk(ped_id.unwrap(), ped_speed, goal),
-                        ],
+                        Command::SpawnPed(CreatePedestrian {
+                            id: ped_id.unwrap(),
+                            speed: ped_speed,
+                            start,
+                            goal: walk_to,
+                            path,
+                            trip,
+                        }),
                     );
-
-                    if let Some(path) = maybe_path {
-                        scheduler.quick_push(
-                            start_time,
-                            Command::SpawnPed(CreatePedestrian {
-                                id: ped_id.unwrap(),
-                                speed: ped_speed,
-                                start,
-                                goal: walk_to,
-                                path,
-                                trip,
-                            }),
-                        );
-                    } else {
-                        timer.warn(format!(
-                            "UsingTransit trip couldn't find the first path {}",
-                            req
-                        ));
-                        trips.abort_trip_failed_start(trip);
-                    }
+                } else {
+                    trips.abort_trip_failed_start(trip);
+                    return Err(format!(
+                        "UsingTransit trip couldn't find the first path {}",
+                        req
+                    ));
                 }
             }
         }
-
-        timer.start("finalize spawned trips");
-        scheduler.finalize_batch();
-        timer.stop("finalize spawned trips");
+        Ok(())
     }
 
     pub fn is_done(&self) -> bool {
@@ -533,11 +624,18 @@ impl TripSpec {
                     .sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             },
-            TripSpec::UsingTransit { start, stop1, .. } => PathRequest {
+            TripSpec::UsingTransit { start, rides, .. } => PathRequest {
                 start: start.sidewalk_pos,
-                end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
+                end: boarding_spot(&rides[0], map).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             },
         }
     }
 }
+
+fn boarding_spot(ride: &TransitRide, map: &Map) -> SidewalkSpot {
+    match ride {
+        TransitRide::Bus(_, stop1, _) => SidewalkSpot::bus_stop(*stop1, map),
+        TransitRide::Train(_, station1, _) => SidewalkSpot::station(*station1, map),
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{
+    DrivingGoal, OriginDestination, ParkingSimState, Scenario, SidewalkPOI, SidewalkSpot,
+    SpawnTrip, BIKE_LENGTH, MAX_CAR_LENGTH,
+};
+use abstutil::{prettyprint_usize, Timer, WeightedUsizeChoice};
+use map_model::{
+    FullNeighborhoodInfo, Map, PathConstraints, PathRequest, Position, RoadID, TransitRide,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+// Something that'd make Scenario::instantiate skip agents, warn, or crash. The first String is
+// always where in the scenario the problem is, like "border_spawn_over_time[3]".
+#[derive(Debug)]
+pub enum ScenarioProblem {
+    MissingNeighborhood(String, String),
+    EmptyNeighborhood(String, String),
+    // The reason the origin or destination doesn't exist
+    UnresolvableEndpoint(String, String),
+    ImpossiblePath(String, PathRequest),
+    // Cars wanted, spots available
+    ParkingShortfall(String, usize, usize),
+}
+
+impl fmt::Display for ScenarioProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioProblem::MissingNeighborhood(src, n) => {
+                write!(f, "{}: neighborhood {} isn't defined", src, n)
+            }
+            ScenarioProblem::EmptyNeighborhood(src, n) => {
+                write!(f, "{}: neighborhood {} has no buildings", src, n)
+            }
+            ScenarioProblem::UnresolvableEndpoint(src, why) => write!(f, "{}: {}", src, why),
+            ScenarioProblem::ImpossiblePath(src, req) => write!(f, "{}: no path for {}", src, req),
+            ScenarioProblem::ParkingShortfall(src, wanted, available) => write!(
+                f,
+                "{}: about {} parked cars wanted, but only {} spots",
+                src,
+                prettyprint_usize(*wanted),
+                prettyprint_usize(*available)
+            ),
+        }
+    }
+}
+
+impl Scenario {
+    // Doesn't touch a Sim, so this is cheap enough to run before a long simulation. Only the
+    // deterministic parts are checked; neighborhood-to-neighborhood trips pick random buildings.
+    pub fn validate(&self, map: &Map, timer: &mut Timer) -> Vec<ScenarioProblem> {
+        timer.start(&format!("validate {}", self.scenario_name));
+        let mut problems = Vec::new();
+
+        timer.start("load full neighborhood info");
+        let neighborhoods = FullNeighborhoodInfo::load_all(map);
+        timer.stop("load full neighborhood info");
+        let parking = ParkingSimState::new(map, timer);
+
+        for (idx, s) in self.seed_parked_cars.iter().enumerate() {
+            let src = format!("seed_parked_cars[{}]", idx);
+            if let Some(n) =
+                check_neighborhood(&src, &s.neighborhood, &neighborhoods, &mut problems)
+            {
+                let available = free_spots(&n.roads, &parking, map);
+                let wanted =
+                    (n.buildings.len() as f64 * expected(&s.cars_per_building)).round() as usize;
+                if wanted > available {
+                    problems.push(ScenarioProblem::ParkingShortfall(src, wanted, available));
+                }
+            }
+        }
+
+        for (idx, s) in self.spawn_over_time.iter().enumerate() {
+            let src = format!("spawn_over_time[{}]", idx);
+            check_neighborhood(
+                &src,
+                &s.start_from_neighborhood,
+                &neighborhoods,
+                &mut problems,
+            );
+            match s.goal {
+                OriginDestination::Neighborhood(ref n) => {
+                    check_neighborhood(&src, n, &neighborhoods, &mut problems);
+                }
+                OriginDestination::EndOfRoad(dr) => {
+                    // Agents fall back to walking, so only that's required.
+                    if SidewalkSpot::end_at_border(dr.dst_i(map), map).is_none() {
+                        problems.push(ScenarioProblem::UnresolvableEndpoint(
+                            src,
+                            format!("can't end at {} without a sidewalk", dr),
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (idx, s) in self.border_spawn_over_time.iter().enumerate() {
+            let src = format!("border_spawn_over_time[{}]", idx);
+            if let OriginDestination::Neighborhood(ref n) = s.goal {
+                check_neighborhood(&src, n, &neighborhoods, &mut problems);
+            }
+
+            if s.num_peds > 0 {
+                match SidewalkSpot::start_at_border(s.start_from_border.src_i(map), map) {
+                    Some(start) => {
+                        if let OriginDestination::EndOfRoad(dr) = s.goal {
+                            match SidewalkSpot::end_at_border(dr.dst_i(map), map) {
+                                Some(goal) => check_path(
+                                    &src,
+                                    start.sidewalk_pos,
+                                    goal.sidewalk_pos,
+                                    PathConstraints::Pedestrian,
+                                    map,
+                                    &mut problems,
+                                ),
+                                None => problems.push(ScenarioProblem::UnresolvableEndpoint(
+                                    src.clone(),
+                                    format!("pedestrians can't end at {} without a sidewalk", dr),
+                                )),
+                            }
+                        }
+                    }
+                    None => problems.push(ScenarioProblem::UnresolvableEndpoint(
+                        src.clone(),
+                        format!(
+                            "pedestrians can't start at {} without a sidewalk",
+                            s.start_from_border
+                        ),
+                    )),
+                }
+            }
+
+            for (num, constraints, length) in vec![
+                (s.num_cars, PathConstraints::Car, MAX_CAR_LENGTH),
+                (s.num_bikes, PathConstraints::Bike, BIKE_LENGTH),
+            ] {
+                if num == 0 {
+                    continue;
+                }
+                let lanes = s.start_from_border.lanes(constraints, map);
+                if lanes.is_empty() || map.get_l(lanes[0]).length() < length {
+                    problems.push(ScenarioProblem::UnresolvableEndpoint(
+                        src.clone(),
+                        format!(
+                            "no room to start a {:?} at {}",
+                            constraints, s.start_from_border
+                        ),
+                    ));
+                    continue;
+                }
+                if let OriginDestination::EndOfRoad(dr) = s.goal {
+                    match DrivingGoal::end_at_border(dr, constraints, map) {
+                        Some(goal) => check_path(
+                            &src,
+                            Position::new(lanes[0], length),
+                            goal.goal_pos(constraints, map),
+                            constraints,
+                            map,
+                            &mut problems,
+                        ),
+                        None => problems.push(ScenarioProblem::UnresolvableEndpoint(
+                            src.clone(),
+                            format!("a {:?} can't end at {}", constraints, dr),
+                        )),
+                    }
+                }
+            }
+        }
+
+        if !self.individ_parked_cars.is_empty() {
+            let wanted = self.individ_parked_cars.values().sum();
+            let available = parking.get_all_parking_spots().1.len();
+            if wanted > available {
+                problems.push(ScenarioProblem::ParkingShortfall(
+                    "individ_parked_cars".to_string(),
+                    wanted,
+                    available,
+                ));
+            }
+        }
+
+        let mut all_trips: Vec<(String, &SpawnTrip)> = self
+            .individ_trips
+            .iter()
+            .enumerate()
+            .map(|(idx, trip)| (format!("individ_trips[{}]", idx), trip))
+            .collect();
+        for (idx1, person) in self.people.iter().enumerate() {
+            for (idx2, t) in person.trips.iter().enumerate() {
+                all_trips.push((format!("people[{}].trips[{}]", idx1, idx2), &t.trip));
+            }
+        }
+
+        timer.start_iter("check individual trips", all_trips.len());
+        for (src, trip) in all_trips {
+            timer.next();
+            match trip {
+                SpawnTrip::CarAppearing {
+                    start,
+                    goal,
+                    is_bike,
+                    ..
+                } => {
+                    let constraints = if *is_bike {
+                        PathConstraints::Bike
+                    } else {
+                        PathConstraints::Car
+                    };
+                    if let Some(end) = goal_pos(&src, goal, constraints, map, &mut problems) {
+                        check_path(&src, *start, end, constraints, map, &mut problems);
+                    }
+                }
+                SpawnTrip::MaybeUsingParkedCar(_, b, goal) => {
+                    // Where the car is isn't known yet, so just start driving from the building.
+                    let start = match map.maybe_find_driving_lane_near_building(*b) {
+                        Some(l) => {
+                            // Short lanes near the building are fine; the car is parked somewhere
+                            // else anyway.
+                            Position::new(l, MAX_CAR_LENGTH.min(map.get_l(l).length()))
+                        }
+                        None => {
+                            problems.push(ScenarioProblem::UnresolvableEndpoint(
+                                src,
+                                format!("no driving lane near {} to start from", b),
+                            ));
+                            continue;
+                        }
+                    };
+                    let constraints = PathConstraints::Car;
+                    if let Some(end) = goal_pos(&src, goal, constraints, map, &mut problems) {
+                        check_path(&src, start, end, constraints, map, &mut problems);
+                    }
+                }
+                SpawnTrip::UsingBike(_, start, goal) => {
+                    match SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map) {
+                        Some(rack) => {
+                            if let SidewalkPOI::BikeRack(pos) = rack.connection {
+                                let constraints = PathConstraints::Bike;
+                                if let Some(end) =
+                                    goal_pos(&src, goal, constraints, map, &mut problems)
+                                {
+                                    check_path(&src, pos, end, constraints, map, &mut problems);
+                                }
+                            }
+                        }
+                        None => problems.push(ScenarioProblem::UnresolvableEndpoint(
+                            src,
+                            format!("can't start biking from {}", start.sidewalk_pos.lane()),
+                        )),
+                    }
+                }
+                SpawnTrip::JustWalking(_, start, goal) => {
+                    check_path(
+                        &src,
+                        start.sidewalk_pos,
+                        goal.sidewalk_pos,
+                        PathConstraints::Pedestrian,
+                        map,
+                        &mut problems,
+                    );
+                }
+                SpawnTrip::UsingTransit(_, start, goal, rides) => {
+                    if rides.is_empty() {
+                        problems.push(ScenarioProblem::UnresolvableEndpoint(
+                            src,
+                            "transit trip without any rides".to_string(),
+                        ));
+                        continue;
+                    }
+                    let (board, _) = ride_endpoints(&rides[0], map);
+                    let (_, alight) = ride_endpoints(rides.last().unwrap(), map);
+                    check_path(
+                        &src,
+                        start.sidewalk_pos,
+                        board,
+                        PathConstraints::Pedestrian,
+                        map,
+                        &mut problems,
+                    );
+                    check_path(
+                        &src,
+                        alight,
+                        goal.sidewalk_pos,
+                        PathConstraints::Pedestrian,
+                        map,
+                        &mut problems,
+                    );
+                }
+            }
+        }
+
+        timer.stop(&format!("validate {}", self.scenario_name));
+        problems
+    }
+}
+
+fn check_neighborhood<'a>(
+    src: &str,
+    name: &str,
+    neighborhoods: &'a HashMap<String, FullNeighborhoodInfo>,
+    problems: &mut Vec<ScenarioProblem>,
+) -> Option<&'a FullNeighborhoodInfo> {
+    match neighborhoods.get(name) {
+        Some(n) => {
+            if n.buildings.is_empty() {
+                problems.push(ScenarioProblem::EmptyNeighborhood(
+                    src.to_string(),
+                    name.to_string(),
+                ));
+                return None;
+            }
+            Some(n)
+        }
+        None => {
+            problems.push(ScenarioProblem::MissingNeighborhood(
+                src.to_string(),
+                name.to_string(),
+            ));
+            None
+        }
+    }
+}
+
+fn check_path(
+    src: &str,
+    start: Position,
+    end: Position,
+    constraints: PathConstraints,
+    map: &Map,
+    problems: &mut Vec<ScenarioProblem>,
+) {
+    let req = PathRequest {
+        start,
+        end,
+        constraints,
+    };
+    if map.pathfind(req.clone()).is_none() {
+        problems.push(ScenarioProblem::ImpossiblePath(src.to_string(), req));
+    }
+}
+
+// Like DrivingGoal::goal_pos, but a problem instead of a crash when there's nowhere to go
+fn goal_pos(
+    src: &str,
+    goal: &DrivingGoal,
+    constraints: PathConstraints,
+    map: &Map,
+    problems: &mut Vec<ScenarioProblem>,
+) -> Option<Position> {
+    let pos = goal.maybe_goal_pos(constraints, map);
+    if pos.is_none() {
+        problems.push(ScenarioProblem::UnresolvableEndpoint(
+            src.to_string(),
+            format!("no lane to drive to for {:?}", goal),
+        ));
+    }
+    pos
+}
+
+// Same as seed_parked_cars: every free spot along the neighborhood's roads
+fn free_spots(roads: &BTreeSet<RoadID>, parking: &ParkingSimState, map: &Map) -> usize {
+    let mut total = 0;
+    for r in roads {
+        let r = map.get_r(*r);
+        for (lane, _) in r
+            .children_forwards
+            .iter()
+            .chain(r.children_backwards.iter())
+        {
+            total += parking.get_free_spots(*lane).len();
+        }
+    }
+    total
+}
+
+// The average number of cars per building
+fn expected(choice: &WeightedUsizeChoice) -> f64 {
+    let total: usize = choice.weights.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let sum: usize = choice
+        .weights
+        .iter()
+        .enumerate()
+        .map(|(cars, weight)| cars * weight)
+        .sum();
+    (sum as f64) / (total as f64)
+}
+
+// Where a rider gets on and off
+pub(crate) fn ride_endpoints(ride: &TransitRide, map: &Map) -> (Position, Position) {
+    match ride {
+        TransitRide::Bus(_, stop1, stop2) => (
+            map.get_bs(*stop1).sidewalk_pos,
+            map.get_bs(*stop2).sidewalk_pos,
+        ),
+        TransitRide::Train(_, station1, station2) => (
+            map.get_station(*station1).sidewalk_pos,
+            map.get_station(*station2).sidewalk_pos,
+        ),
+    }
+}
@@ -2,8 +2,8 @@ use crate::{
     AgentMetadata, CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, Router, TimeInterval,
     TransitSimState, TripID, Vehicle, VehicleType,
 };
-use geom::{Distance, Duration, PolyLine};
-use map_model::{Map, Traversable, LANE_THICKNESS};
+use geom::{Distance, Duration, PolyLine, Speed};
+use map_model::{Map, Traversable, TurnPriority};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
@@ -15,6 +15,9 @@ pub struct Car {
     pub trip: TripID,
     pub blocked_since: Option<Duration>,
     pub started_at: Duration,
+    // Only tracked with kinematic driving; None means cars cross everything at constant speed.
+    // This is the speed carried out of the last Crossing state into WaitingToAdvance.
+    pub speed: Option<Speed>,
 
     // In reverse order -- most recently left is first. The sum length of these must be >=
     // vehicle.length.
@@ -29,30 +32,158 @@ impl Car {
         start_time: Duration,
         map: &Map,
     ) -> CarState {
-        let dist_int = DistanceInterval::new_driving(
-            start_dist,
-            if self.router.last_step() {
-                self.router.get_end_dist()
-            } else {
-                self.router.head().length(map)
-            },
-        );
+        let dist_int = self.dist_int_to_end(start_dist, map);
+        if self.speed.is_some() {
+            let stop_at_end = self.must_stop_at_end(start_dist, start_time, map);
+            return self.kinematic_crossing_state(dist_int, start_time, stop_at_end, map);
+        }
         self.crossing_state_with_end_dist(dist_int, start_time, map)
     }
 
+    // Only with kinematic driving: brake to a stop at stop_dist, behind a stopped leader, instead
+    // of going all the way to the end.
+    pub fn crossing_state_stopping_at(
+        &self,
+        start_dist: Distance,
+        stop_dist: Distance,
+        start_time: Duration,
+        map: &Map,
+    ) -> CarState {
+        let end = self.end_dist(map).min(stop_dist).max(start_dist);
+        self.kinematic_crossing_state(
+            DistanceInterval::new_driving(start_dist, end),
+            start_time,
+            true,
+            map,
+        )
+    }
+
+    // Only with kinematic driving: cross the whole step, but slow to end_speed by the end, so
+    // there's room to brake for a stopped leader on the next step.
+    pub fn crossing_state_slowing_to(
+        &self,
+        start_dist: Distance,
+        end_speed: Speed,
+        start_time: Duration,
+        map: &Map,
+    ) -> CarState {
+        let dist_int = self.dist_int_to_end(start_dist, map);
+        let cruise_speed = self.cruise_speed(map);
+        let end_speed = if self.must_stop_at_end(start_dist, start_time, map) {
+            Speed::ZERO
+        } else {
+            end_speed.min(cruise_speed)
+        };
+        let profile = SpeedProfile::new(
+            dist_int.length(),
+            self.current_speed(start_time),
+            cruise_speed,
+            end_speed,
+            self.vehicle.vehicle_type,
+        );
+        CarState::Crossing(
+            TimeInterval::new(start_time, start_time + profile.total_time()),
+            dist_int,
+            Some(profile),
+        )
+    }
+
+    // Always at constant speed, even with kinematic driving. Callers use this for optimistic
+    // estimates.
     pub fn crossing_state_with_end_dist(
         &self,
         dist_int: DistanceInterval,
         start_time: Duration,
         map: &Map,
     ) -> CarState {
-        let on = self.router.head();
-        let mut speed = on.speed_limit(map);
+        let dt = (dist_int.end - dist_int.start) / self.cruise_speed(map);
+        CarState::Crossing(
+            TimeInterval::new(start_time, start_time + dt),
+            dist_int,
+            None,
+        )
+    }
+
+    fn dist_int_to_end(&self, start_dist: Distance, map: &Map) -> DistanceInterval {
+        DistanceInterval::new_driving(start_dist, self.end_dist(map))
+    }
+
+    // Where the car is headed on the current step, if nothing's in the way
+    pub fn end_dist(&self, map: &Map) -> Distance {
+        if self.router.last_step() {
+            self.router.get_end_dist()
+        } else {
+            self.router.head().length(map)
+        }
+    }
+
+    fn cruise_speed(&self, map: &Map) -> Speed {
+        let mut speed = self.router.head().speed_limit(map);
         if let Some(s) = self.vehicle.max_speed {
             speed = speed.min(s);
         }
-        let dt = (dist_int.end - dist_int.start) / speed;
-        CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
+        speed
+    }
+
+    fn kinematic_crossing_state(
+        &self,
+        dist_int: DistanceInterval,
+        start_time: Duration,
+        stop_at_end: bool,
+        map: &Map,
+    ) -> CarState {
+        let cruise_speed = self.cruise_speed(map);
+        let profile = SpeedProfile::new(
+            dist_int.length(),
+            self.current_speed(start_time),
+            cruise_speed,
+            if stop_at_end {
+                Speed::ZERO
+            } else {
+                cruise_speed
+            },
+            self.vehicle.vehicle_type,
+        );
+        CarState::Crossing(
+            TimeInterval::new(start_time, start_time + profile.total_time()),
+            dist_int,
+            Some(profile),
+        )
+    }
+
+    // Only meaningful with kinematic driving.
+    pub fn current_speed(&self, now: Duration) -> Speed {
+        match self.state {
+            CarState::Crossing(ref time_int, _, Some(ref profile)) => {
+                profile.speed_after(now - time_int.start)
+            }
+            CarState::WaitingToAdvance => self.speed.unwrap_or(Speed::ZERO),
+            _ => Speed::ZERO,
+        }
+    }
+
+    // Will the car have to brake to a stop by the end of the current step? This only looks at the
+    // map, not other agents. Callers brake for queued leaders separately.
+    fn must_stop_at_end(&self, start_dist: Distance, start_time: Duration, map: &Map) -> bool {
+        if self.router.last_step() {
+            return true;
+        }
+        let (l, t) = match (self.router.head(), self.router.maybe_next()) {
+            (Traversable::Lane(l), Some(Traversable::Turn(t))) => (l, t),
+            _ => {
+                return false;
+            }
+        };
+        if let Some(signal) = map.maybe_get_traffic_signal(t.parent) {
+            // Roughly when will we reach the intersection? Don't coast through on yellow.
+            let eta = start_time + (map.get_l(l).length() - start_dist) / self.cruise_speed(map);
+            let (_, phase, _) = signal.current_phase_and_remaining_time(eta);
+            phase.get_priority_of_turn(t, signal) != TurnPriority::Protected
+        } else if let Some(sign) = map.maybe_get_stop_sign(t.parent) {
+            sign.get_priority(t, map) != TurnPriority::Protected
+        } else {
+            false
+        }
     }
 
     pub fn get_draw_car(
@@ -109,18 +240,22 @@ impl Car {
                 };
                 match spot {
                     ParkingSpot::Onstreet(parking_l, _) => {
-                        let width = LANE_THICKNESS * percent_time;
                         let driving_l = self.router.head().as_lane();
                         let parent = map.get_parent(driving_l);
-                        // Is the parking lane to the left or right of the driving lane?
+                        let driving_offset = parent.offset_from_center(driving_l, map);
+                        let parking_offset = parent.offset_from_center(*parking_l, map);
+                        // Is the parking lane to the left or right of the driving lane? Lanes
+                        // can have different widths, so use the real distance between centers.
                         let shift = if parent.dir_and_offset(driving_l).0
                             == parent.dir_and_offset(*parking_l).0
                         {
-                            width
+                            (parking_offset - driving_offset) * percent_time
                         } else {
-                            -width
+                            (driving_offset + parking_offset) * -percent_time
                         };
-                        raw_body.shift_right(shift).unwrap()
+                        map.get_driving_side()
+                            .right_shift(&raw_body, shift)
+                            .unwrap()
                     }
                     ParkingSpot::Offstreet(b, _) => {
                         // Append the car's polyline on the street with the driveway
@@ -156,7 +291,7 @@ impl Car {
             status: match self.state {
                 CarState::Queued => CarStatus::Moving,
                 CarState::WaitingToAdvance => CarStatus::Moving,
-                CarState::Crossing(_, _) => CarStatus::Moving,
+                CarState::Crossing(_, _, _) => CarStatus::Moving,
                 // Eh they're technically moving, but this is a bit easier to spot
                 CarState::Unparking(_, _, _) => CarStatus::Parked,
                 CarState::Parking(_, _, _) => CarStatus::Parked,
@@ -194,7 +329,8 @@ impl Car {
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum CarState {
-    Crossing(TimeInterval, DistanceInterval),
+    // The profile is only present with kinematic driving; otherwise the speed is constant.
+    Crossing(TimeInterval, DistanceInterval, Option<SpeedProfile>),
     Queued,
     WaitingToAdvance,
     // Where's the front of the car while this is happening?
@@ -206,7 +342,7 @@ pub enum CarState {
 impl CarState {
     pub fn get_end_time(&self) -> Duration {
         match self {
-            CarState::Crossing(ref time_int, _) => time_int.end,
+            CarState::Crossing(ref time_int, _, _) => time_int.end,
             CarState::Queued => unreachable!(),
             CarState::WaitingToAdvance => unreachable!(),
             CarState::Unparking(_, _, ref time_int) => time_int.end,
@@ -215,3 +351,153 @@ impl CarState {
         }
     }
 }
+
+// With kinematic driving, a car accelerates from start_speed towards cruise_speed, maybe cruises,
+// then brakes to end_speed by the end of the interval. If it starts faster than cruise_speed (like
+// when entering a slower road), the first phase brakes instead. Speeds are in m/s and rates in
+// m/s^2. The rates only exceed the vehicle's limits when there isn't room to stop in time.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SpeedProfile {
+    start_speed: f64,
+    peak_speed: f64,
+    end_speed: f64,
+    // Negative when the first phase brakes
+    accel: f64,
+    decel: f64,
+    accel_time: Duration,
+    cruise_time: Duration,
+    decel_time: Duration,
+}
+
+impl SpeedProfile {
+    pub fn new(
+        dist: Distance,
+        start_speed: Speed,
+        cruise_speed: Speed,
+        end_speed: Speed,
+        vehicle_type: VehicleType,
+    ) -> SpeedProfile {
+        let d = dist.inner_meters();
+        let v_max = cruise_speed.inner_meters_per_second();
+        let v0 = start_speed.inner_meters_per_second();
+        let mut v1 = end_speed.inner_meters_per_second().min(v_max);
+        let accel = vehicle_type.max_accel();
+        let mut decel = vehicle_type.max_decel();
+
+        if d <= 0.0 {
+            return SpeedProfile {
+                start_speed: v0,
+                peak_speed: v0,
+                end_speed: v0,
+                accel,
+                decel,
+                accel_time: Duration::ZERO,
+                cruise_time: Duration::ZERO,
+                decel_time: Duration::ZERO,
+            };
+        }
+
+        // Not enough room to get up to the desired end speed
+        v1 = v1.min((v0 * v0 + 2.0 * accel * d).sqrt());
+        // Not enough room to brake comfortably, so brake harder, all the way from the start.
+        if v0 > v1 && (v0 * v0 - v1 * v1) / (2.0 * decel) >= d {
+            decel = (v0 * v0 - v1 * v1) / (2.0 * d);
+            return SpeedProfile {
+                start_speed: v0,
+                peak_speed: v0,
+                end_speed: v1,
+                accel,
+                decel,
+                accel_time: Duration::ZERO,
+                cruise_time: Duration::ZERO,
+                decel_time: Duration::seconds((v0 - v1) / decel),
+            };
+        }
+
+        // Slow down to the speed limit first.
+        if v0 > v_max {
+            let slow_dist = (v0 * v0 - v_max * v_max) / (2.0 * decel);
+            let stop_dist = (v_max * v_max - v1 * v1) / (2.0 * decel);
+            return SpeedProfile {
+                start_speed: v0,
+                peak_speed: v_max,
+                end_speed: v1,
+                accel: -decel,
+                decel,
+                accel_time: Duration::seconds((v0 - v_max) / decel),
+                cruise_time: Duration::seconds((d - slow_dist - stop_dist).max(0.0) / v_max),
+                decel_time: Duration::seconds((v_max - v1) / decel),
+            };
+        }
+
+        // Accelerate as long as possible, then brake just in time.
+        let peak = ((2.0 * accel * decel * d + decel * v0 * v0 + accel * v1 * v1)
+            / (accel + decel))
+            .sqrt()
+            .min(v_max)
+            .max(v0)
+            .max(v1);
+
+        let accel_dist = (peak * peak - v0 * v0) / (2.0 * accel);
+        let decel_dist = (peak * peak - v1 * v1) / (2.0 * decel);
+        let cruise_dist = (d - accel_dist - decel_dist).max(0.0);
+        SpeedProfile {
+            start_speed: v0,
+            peak_speed: peak,
+            end_speed: v1,
+            accel,
+            decel,
+            accel_time: Duration::seconds((peak - v0) / accel),
+            cruise_time: if peak > 0.0 {
+                Duration::seconds(cruise_dist / peak)
+            } else {
+                Duration::ZERO
+            },
+            decel_time: Duration::seconds((peak - v1) / decel),
+        }
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.accel_time + self.cruise_time + self.decel_time
+    }
+
+    // How far along the DistanceInterval after some time?
+    pub fn lerp(&self, dist_int: &DistanceInterval, elapsed: Duration) -> Distance {
+        if elapsed >= self.total_time() {
+            return dist_int.end;
+        }
+        let t = elapsed.max(Duration::ZERO).inner_seconds();
+        let t1 = self.accel_time.inner_seconds();
+        let t2 = self.cruise_time.inner_seconds();
+        let accel_dist = self.start_speed * t1 + 0.5 * self.accel * t1 * t1;
+
+        let d = if t < t1 {
+            self.start_speed * t + 0.5 * self.accel * t * t
+        } else if t < t1 + t2 {
+            accel_dist + self.peak_speed * (t - t1)
+        } else {
+            let dt = t - t1 - t2;
+            accel_dist + self.peak_speed * t2 + self.peak_speed * dt - 0.5 * self.decel * dt * dt
+        };
+        (dist_int.start + Distance::meters(d)).min(dist_int.end)
+    }
+
+    pub fn speed_after(&self, elapsed: Duration) -> Speed {
+        let t = elapsed.max(Duration::ZERO).inner_seconds();
+        let t1 = self.accel_time.inner_seconds();
+        let t2 = self.cruise_time.inner_seconds();
+        let v = if t < t1 {
+            self.start_speed + self.accel * t
+        } else if t < t1 + t2 {
+            self.peak_speed
+        } else {
+            (self.peak_speed - self.decel * (t - t1 - t2)).max(self.end_speed)
+        };
+        Speed::meters_per_second(v.max(0.0))
+    }
+
+    // The worst-case rates this profile uses, in m/s^2
+    pub fn max_rates(&self) -> (f64, f64) {
+        (self.accel.max(0.0), self.decel.max(-self.accel))
+    }
+}
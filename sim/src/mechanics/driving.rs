@@ -2,19 +2,18 @@ use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
     ActionAtEnd, AgentID, AgentMetadata, CarID, Command, CreateCar, DistanceInterval, DrawCarInput,
-    Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval,
-    TransitSimState, TripManager, TripPositions, UnzoomedAgent, WalkingSimState,
+    Event, IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, Scheduler, TimeInterval,
+    TransitSimState, TripManager, TripPositions, UnzoomedAgent, VehicleType, WalkingSimState,
     FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::{Distance, Duration, PolyLine};
+use geom::{Distance, Duration, PolyLine, Speed};
 use map_model::{BuildingID, LaneID, Map, Path, PathStep, Traversable};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet, VecDeque};
 
 const TIME_TO_UNPARK: Duration = Duration::const_seconds(10.0);
 const TIME_TO_PARK: Duration = Duration::const_seconds(15.0);
-const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
 
 // TODO Do something else.
 pub(crate) const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
@@ -35,15 +34,24 @@ pub struct DrivingSimState {
     events: Vec<Event>,
 
     recalc_lanechanging: bool,
+    kinematic_driving: bool,
+    informed_drivers: f64,
 }
 
 impl DrivingSimState {
-    pub fn new(map: &Map, recalc_lanechanging: bool) -> DrivingSimState {
+    pub fn new(
+        map: &Map,
+        recalc_lanechanging: bool,
+        kinematic_driving: bool,
+        informed_drivers: f64,
+    ) -> DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
             queues: BTreeMap::new(),
             events: Vec::new(),
             recalc_lanechanging,
+            kinematic_driving,
+            informed_drivers,
         };
 
         for l in map.all_lanes() {
@@ -84,15 +92,26 @@ impl DrivingSimState {
             &self.cars,
             &self.queues,
         ) {
+            let mut router = params.router;
+            if params.vehicle.vehicle_type == VehicleType::Car
+                && is_informed(params.vehicle.id, self.informed_drivers)
+            {
+                router.make_informed();
+            }
             let mut car = Car {
                 vehicle: params.vehicle,
-                router: params.router,
+                router,
                 // Temporary
                 state: CarState::Queued,
                 last_steps: VecDeque::new(),
                 blocked_since: None,
                 started_at: now,
                 trip: params.trip,
+                speed: if self.kinematic_driving {
+                    Some(Speed::ZERO)
+                } else {
+                    None
+                },
             };
             if let Some(p) = params.maybe_parked_car {
                 car.state = CarState::Unparking(
@@ -178,6 +197,15 @@ impl DrivingSimState {
             }
         };
 
+        if self.kinematic_driving {
+            if let Some(state) = self.resume_after_stopping_short(id, now, map) {
+                let car = self.cars.get_mut(&id).unwrap();
+                car.state = state;
+                scheduler.push(car.state.get_end_time(), Command::UpdateCar(id));
+                return;
+            }
+        }
+
         if !need_distances {
             // We need to mutate two different cars in one case. To avoid fighting the borrow
             // checker, temporarily move one of them out of the BTreeMap.
@@ -238,7 +266,10 @@ impl DrivingSimState {
         scheduler: &mut Scheduler,
     ) -> bool {
         match car.state {
-            CarState::Crossing(_, _) => {
+            CarState::Crossing(_, _, _) => {
+                if car.speed.is_some() {
+                    car.speed = Some(car.current_speed(now));
+                }
                 car.state = CarState::Queued;
                 car.blocked_since = Some(now);
                 if car.router.last_step() {
@@ -268,6 +299,12 @@ impl DrivingSimState {
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             }
             CarState::Idling(dist, _) => {
+                // Wait for passengers who boarded while the bus was stopped
+                if let Some(dwell) = transit.extra_dwell(car.vehicle.id) {
+                    car.state = CarState::Idling(dist, TimeInterval::new(now, now + dwell));
+                    scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                    return false;
+                }
                 car.router = transit.bus_departed_from_stop(car.vehicle.id);
                 car.state = car.crossing_state(dist, now, map);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -302,7 +339,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could jump
                         // forwards here; the leader is still in front of them.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::Idling(_, _) => {}
@@ -333,7 +370,11 @@ impl DrivingSimState {
                             &car,
                         )),
                     ) {
-                        // Don't schedule a retry here.
+                        // Don't schedule a retry here. By the time we do go, we'll be starting
+                        // from a stop.
+                        if car.speed.is_some() {
+                            car.speed = Some(Speed::ZERO);
+                        }
                         return false;
                     }
                 }
@@ -347,8 +388,17 @@ impl DrivingSimState {
                 // We do NOT need to update the follower. If they were Queued, they'll remain that
                 // way, until laggy_head is None.
 
-                let last_step = car.router.advance(&car.vehicle, parking, map);
-                car.state = car.crossing_state(Distance::ZERO, now, map);
+                let (last_step, avoided) =
+                    car.router
+                        .advance(&car.vehicle, parking, &self.queues, now, map);
+                if let Some(l) = avoided {
+                    self.events.push(Event::CarRerouted(car.vehicle.id, l));
+                }
+                car.state = if self.kinematic_driving {
+                    self.kinematic_crossing_state(&car, goto, now, map)
+                } else {
+                    car.crossing_state(Distance::ZERO, now, map)
+                };
                 car.blocked_since = None;
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                 self.events.push(Event::AgentEntersTraversable(
@@ -414,7 +464,7 @@ impl DrivingSimState {
         // Just two cases here. In all cases, we leave the Queued state.
         car.blocked_since = None;
         match car.state {
-            CarState::Crossing(_, _)
+            CarState::Crossing(_, _, _)
             | CarState::Unparking(_, _, _)
             | CarState::Idling(_, _)
             | CarState::WaitingToAdvance => unreachable!(),
@@ -453,21 +503,20 @@ impl DrivingSimState {
                         trips.bike_reached_end(now, car.vehicle.id, bike_rack, map, scheduler);
                     }
                     Some(ActionAtEnd::BusAtStop) => {
-                        transit.bus_arrived_at_stop(
+                        if let Some(dwell) = transit.bus_arrived_at_stop(
                             now,
                             car.vehicle.id,
                             trips,
                             walking,
                             scheduler,
                             map,
-                        );
-                        car.state = CarState::Idling(
-                            our_dist,
-                            TimeInterval::new(now, now + TIME_TO_WAIT_AT_STOP),
-                        );
-                        scheduler
-                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                        return true;
+                        ) {
+                            car.state =
+                                CarState::Idling(our_dist, TimeInterval::new(now, now + dwell));
+                            scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            return true;
+                        }
                     }
                     None => {
                         scheduler.push(
@@ -480,7 +529,7 @@ impl DrivingSimState {
                         /*
                         // If this car wasn't blocked at all, when would it reach its goal?
                         let ideal_end_time = match car.crossing_state(our_dist, now, map) {
-                            CarState::Crossing(time_int, _) => time_int.end,
+                            CarState::Crossing(time_int, _, _) => time_int.end,
                             _ => unreachable!(),
                         };
                         if ideal_end_time == now {
@@ -542,6 +591,89 @@ impl DrivingSimState {
         scheduler.cancel(Command::UpdateCar(c));
     }
 
+    // After live map edits, cars partly on a lane or turn that can't hold vehicles anymore
+    // vanish, and everybody else with a broken path ahead tries to find another way. Cars and
+    // buses that can't find one vanish too. Returns the vanished cars with the spot they were
+    // parking in (if it still exists), and how many cars rerouted.
+    pub fn handle_live_edits(
+        &mut self,
+        now: Duration,
+        map: &Map,
+        parking: &ParkingSimState,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) -> (Vec<(CarID, Option<ParkingSpot>)>, usize) {
+        let mut killed = Vec::new();
+        let mut rerouted = 0;
+        let ids: Vec<CarID> = self.cars.keys().cloned().collect();
+        for id in ids {
+            let car = self.cars.get_mut(&id).unwrap();
+            let stuck = queue_obsolete(car.router.head(), map)
+                || car.last_steps.iter().any(|on| queue_obsolete(*on, map))
+                || match car.state {
+                    CarState::Parking(_, spot, _) => !parking.spot_exists(spot),
+                    _ => false,
+                };
+            if !stuck {
+                let broken = car
+                    .router
+                    .get_path()
+                    .first_broken_step(car.vehicle.vehicle_type.to_constraints(), map)
+                    .is_some();
+                let old_next = car.router.maybe_next();
+                if car.router.reroute_after_edits(&car.vehicle, map) {
+                    if broken {
+                        rerouted += 1;
+                    }
+                    // They might've already asked for the old turn
+                    if car.router.maybe_next() != old_next {
+                        if let Some(Traversable::Turn(t)) = old_next {
+                            intersections.cancel_request(AgentID::Car(id), t);
+                        }
+                        if let CarState::WaitingToAdvance = car.state {
+                            scheduler.update(now, Command::UpdateCar(id));
+                        }
+                    }
+                    continue;
+                }
+            }
+            let spot = match car.state {
+                CarState::Parking(_, spot, _) if parking.spot_exists(spot) => Some(spot),
+                _ => None,
+            };
+            self.kill_stuck_car(id, now, map, scheduler, intersections);
+            killed.push((id, spot));
+        }
+
+        // Queues for lanes and turns that're gone should be empty by now.
+        let obsolete: Vec<Traversable> = self
+            .queues
+            .keys()
+            .filter(|on| queue_obsolete(**on, map))
+            .cloned()
+            .collect();
+        for on in obsolete {
+            let q = &self.queues[&on];
+            if q.cars.is_empty() && q.laggy_head.is_none() {
+                self.queues.remove(&on);
+            }
+        }
+        for l in map.all_lanes() {
+            let on = Traversable::Lane(l.id);
+            if l.lane_type.is_for_moving_vehicles() && !self.queues.contains_key(&on) {
+                self.queues.insert(on, Queue::new(on, map));
+            }
+        }
+        for t in map.all_turns().values() {
+            let on = Traversable::Turn(t.id);
+            if !t.between_sidewalks() && !self.queues.contains_key(&on) {
+                self.queues.insert(on, Queue::new(on, map));
+            }
+        }
+
+        (killed, rerouted)
+    }
+
     fn delete_car(
         &mut self,
         car: &mut Car,
@@ -578,7 +710,7 @@ impl DrivingSimState {
             // lead car's back is still sticking out. Need to still be bound by them, even
             // though they don't exist! If the leader just parked, then we're fine.
             match follower.state {
-                CarState::Queued | CarState::Crossing(_, _) => {
+                CarState::Queued | CarState::Crossing(_, _, _) => {
                     // If the follower was still Crossing, they might not've been blocked
                     // by leader yet. In that case, recalculating their Crossing state is a
                     // no-op. But if they were blocked, then this will prevent them from
@@ -699,6 +831,9 @@ impl DrivingSimState {
                                 // The follower has been smoothly following while the laggy head gets out
                                 // of the way. So immediately promote them to WaitingToAdvance.
                                 follower.state = CarState::WaitingToAdvance;
+                                if follower.speed.is_some() {
+                                    follower.speed = Some(Speed::ZERO);
+                                }
                                 if self.recalc_lanechanging {
                                     follower
                                         .router
@@ -710,7 +845,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could jump
                         // forwards here; the leader vanished from the end of the traversable.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::Idling(_, _) => {}
@@ -722,6 +857,84 @@ impl DrivingSimState {
         }
     }
 
+    // With kinematic driving, a car entering a step brakes for the back of the line of cars
+    // behind anybody stopped there, at its usual rate. If the step is a turn, it also leaves room
+    // to brake for a line starting on the next lane.
+    fn kinematic_crossing_state(
+        &self,
+        car: &Car,
+        goto: Traversable,
+        now: Duration,
+        map: &Map,
+    ) -> CarState {
+        if let Some(stop) = self.stopping_point(goto, None, now) {
+            return car.crossing_state_stopping_at(Distance::ZERO, stop, now, map);
+        }
+        if let (Traversable::Turn(_), Some(next)) = (goto, car.router.maybe_next()) {
+            if let Some(stop) = self.stopping_point(next, None, now) {
+                let decel = car.vehicle.vehicle_type.max_decel();
+                let end_speed =
+                    Speed::meters_per_second((2.0 * decel * stop.inner_meters()).sqrt());
+                return car.crossing_state_slowing_to(Distance::ZERO, end_speed, now, map);
+            }
+        }
+        car.crossing_state(Distance::ZERO, now, map)
+    }
+
+    // If anybody's stopped on this queue, where should a car joining the back stop? Leaves room
+    // for everyone between the stopped car and the back, since they'll stop there too. If
+    // behind_idx is set, only looks at cars ahead of that position in the queue.
+    fn stopping_point(
+        &self,
+        on: Traversable,
+        behind_idx: Option<usize>,
+        now: Duration,
+    ) -> Option<Distance> {
+        let queue = &self.queues[&on];
+        let ahead = behind_idx.unwrap_or_else(|| queue.cars.len());
+        if ahead == 0 {
+            return None;
+        }
+        let stopped_idx =
+            queue
+                .cars
+                .iter()
+                .take(ahead)
+                .rposition(|c| match self.cars[c].state {
+                    CarState::Crossing(_, _, _) => false,
+                    _ => true,
+                })?;
+        let dists = queue.get_car_positions(now, &self.cars, &self.queues);
+        let mut stop = dists[stopped_idx].1;
+        for (c, _) in &dists[stopped_idx..ahead] {
+            stop = stop - self.cars[c].vehicle.length - FOLLOWING_DISTANCE;
+        }
+        Some(stop.max(Distance::ZERO))
+    }
+
+    // With kinematic driving, a car might brake to a stop behind a stopped leader, short of the
+    // end of its step. If the leader's moving again by then, keep going.
+    fn resume_after_stopping_short(&self, id: CarID, now: Duration, map: &Map) -> Option<CarState> {
+        let car = &self.cars[&id];
+        let stopped_at = match car.state {
+            CarState::Crossing(_, ref dist_int, Some(_)) => dist_int.end,
+            _ => {
+                return None;
+            }
+        };
+        if stopped_at >= car.end_dist(map) {
+            return None;
+        }
+        let on = car.router.head();
+        let idx = self.queues[&on].cars.iter().position(|c| *c == id).unwrap();
+        match self.stopping_point(on, Some(idx), now) {
+            // Still stuck, so just wait in the queue like usual.
+            Some(stop) if stop <= stopped_at => None,
+            Some(stop) => Some(car.crossing_state_stopping_at(stopped_at, stop, now, map)),
+            None => Some(car.crossing_state(stopped_at, now, map)),
+        }
+    }
+
     pub fn get_unzoomed_agents(&self, now: Duration, map: &Map) -> Vec<UnzoomedAgent> {
         let mut result = Vec::new();
 
@@ -949,3 +1162,16 @@ impl DrivingSimState {
         std::mem::replace(&mut self.events, Vec::new())
     }
 }
+
+// Spreads informed drivers evenly over car IDs, so the same cars are informed in every run.
+// True if a lane or turn can't have a queue of vehicles anymore.
+fn queue_obsolete(on: Traversable, map: &Map) -> bool {
+    match on {
+        Traversable::Lane(l) => !map.get_l(l).lane_type.is_for_moving_vehicles(),
+        Traversable::Turn(t) => map.maybe_get_t(t).is_none(),
+    }
+}
+
+fn is_informed(id: CarID, fraction: f64) -> bool {
+    ((id.0 as f64) * 0.618_033_988_75).fract() < fraction
+}
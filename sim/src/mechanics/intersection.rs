@@ -5,8 +5,8 @@ use abstutil::{deserialize_btreemap, serialize_btreemap};
 use derivative::Derivative;
 use geom::{Duration, DurationHistogram};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, TurnID, TurnPriority,
-    TurnType,
+    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, Phase, PhaseType, TurnID,
+    TurnPriority, TurnType,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet};
@@ -35,6 +35,22 @@ struct State {
     #[derivative(PartialEq = "ignore")]
     #[serde(skip_serializing, skip_deserializing)]
     delays: DurationHistogram,
+    // The exact sum of everything in delays
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    total_delay: Duration,
+    // Only for actuated traffic signals. Fixed-time signals are a function of the time.
+    signal: Option<SignalState>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+struct SignalState {
+    // None is the default plan
+    plan: Option<usize>,
+    current_phase: usize,
+    phase_started: Duration,
+    // The last time somebody started waiting for a turn in the current phase
+    last_arrival: Duration,
 }
 
 impl IntersectionSimState {
@@ -57,6 +73,20 @@ impl IntersectionSimState {
                     accepted: BTreeSet::new(),
                     waiting: BTreeMap::new(),
                     delays: DurationHistogram::new(),
+                    total_delay: Duration::ZERO,
+                    signal: if i.is_traffic_signal()
+                        && !use_freeform_policy_everywhere
+                        && map.get_traffic_signal(i.id).is_actuated()
+                    {
+                        Some(SignalState {
+                            plan: map.get_traffic_signal(i.id).scheduled_plan(Duration::ZERO),
+                            current_phase: 0,
+                            phase_started: Duration::ZERO,
+                            last_arrival: Duration::ZERO,
+                        })
+                    } else {
+                        None
+                    },
                 },
             );
             if i.is_traffic_signal() && !use_freeform_policy_everywhere {
@@ -83,7 +113,12 @@ impl IntersectionSimState {
     ) {
         let state = self.state.get_mut(&turn.parent).unwrap();
         assert!(state.accepted.remove(&Request { agent, turn }));
-        if map.get_t(turn).turn_type != TurnType::SharedSidewalkCorner {
+        // The turn might've just been deleted by live map edits.
+        if map
+            .maybe_get_t(turn)
+            .map(|t| t.turn_type != TurnType::SharedSidewalkCorner)
+            .unwrap_or(true)
+        {
             self.wakeup_waiting(now, turn.parent, scheduler, map);
         }
     }
@@ -133,7 +168,7 @@ impl IntersectionSimState {
                 protected.push(req);
             }
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(i) {
-            let (_, phase, _) = signal.current_phase_and_remaining_time(now);
+            let (_, phase, _) = self.state[&i].current_phase(signal, now);
             for (req, _) in all {
                 match phase.get_priority_of_turn(req.turn, signal) {
                     TurnPriority::Protected => {
@@ -175,19 +210,90 @@ impl IntersectionSimState {
         }
     }
 
+    // After live map edits. Requests to use turns that don't exist anymore are dropped, and
+    // changed intersections start their new control from scratch. Agents already doing a deleted
+    // turn must be removed separately.
+    pub fn handle_live_edits(
+        &mut self,
+        now: Duration,
+        changed: &BTreeSet<IntersectionID>,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        for state in self.state.values_mut() {
+            state
+                .waiting
+                .retain(|req, _| map.maybe_get_t(req.turn).is_some());
+        }
+
+        for id in changed {
+            let i = map.get_i(*id);
+            let signal = i.is_traffic_signal() && !self.use_freeform_policy_everywhere;
+            self.state.get_mut(id).unwrap().signal =
+                if signal && map.get_traffic_signal(*id).is_actuated() {
+                    Some(SignalState {
+                        plan: map.get_traffic_signal(*id).scheduled_plan(now),
+                        current_phase: 0,
+                        phase_started: now,
+                        last_arrival: now,
+                    })
+                } else {
+                    None
+                };
+            // Whatever was scheduled was for the old control.
+            scheduler.cancel(Command::UpdateIntersection(*id));
+            if signal {
+                self.update_intersection(now, *id, map, scheduler);
+            } else {
+                self.wakeup_waiting(now, *id, scheduler, map);
+            }
+        }
+    }
+
     // This is only triggered for traffic signals.
     pub fn update_intersection(
-        &self,
+        &mut self,
         now: Duration,
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
     ) {
+        let signal = map.get_traffic_signal(id);
+        let next_check = if self.state[&id].signal.is_some() {
+            self.state
+                .get_mut(&id)
+                .unwrap()
+                .update_actuated_signal(signal, now, map)
+        } else {
+            let (_, _, remaining) = signal.current_phase_and_remaining_time(now);
+            now + remaining
+        };
         self.wakeup_waiting(now, id, scheduler, map);
-        let (_, _, remaining) = map
-            .get_traffic_signal(id)
-            .current_phase_and_remaining_time(now);
-        scheduler.push(now + remaining, Command::UpdateIntersection(id));
+        scheduler.push(next_check, Command::UpdateIntersection(id));
+    }
+
+    // For actuated signals, this depends on the demand seen so far.
+    pub fn current_signal_phase<'a>(
+        &self,
+        id: IntersectionID,
+        now: Duration,
+        map: &'a Map,
+    ) -> (usize, &'a Phase, Duration) {
+        self.state[&id].current_phase(map.get_traffic_signal(id), now)
+    }
+
+    // Which of the signal's plans is running, with None meaning the default.
+    pub fn current_signal_plan(
+        &self,
+        id: IntersectionID,
+        now: Duration,
+        map: &Map,
+    ) -> Option<usize> {
+        if let Some(ref st) = self.state[&id].signal {
+            st.plan
+        } else {
+            map.get_traffic_signal(id).plan_in_effect(now)
+        }
     }
 
     // For cars: The head car calls this when they're at the end of the lane WaitingToAdvance. If
@@ -210,6 +316,9 @@ impl IntersectionSimState {
         //let debug = turn.parent == IntersectionID(64);
         let req = Request { agent, turn };
         let state = self.state.get_mut(&turn.parent).unwrap();
+        if !state.waiting.contains_key(&req) {
+            state.new_arrival(turn, now, map);
+        }
         state.waiting.entry(req.clone()).or_insert(now);
 
         let allowed = if self.use_freeform_policy_everywhere {
@@ -239,7 +348,9 @@ impl IntersectionSimState {
         }
 
         assert!(!state.any_accepted_conflict_with(turn, map));
-        state.delays.add(now - state.waiting.remove(&req).unwrap());
+        let delay = now - state.waiting.remove(&req).unwrap();
+        state.delays.add(delay);
+        state.total_delay += delay;
         state.accepted.insert(req);
         /*if debug {
             println!("{}: {} going!", now, agent)
@@ -270,9 +381,118 @@ impl IntersectionSimState {
     pub fn get_intersection_delays(&self, id: IntersectionID) -> &DurationHistogram {
         &self.state[&id].delays
     }
+
+    // Includes agents still waiting, so gridlock doesn't look like an improvement.
+    pub fn get_total_delay(&self, id: IntersectionID, now: Duration) -> Duration {
+        let state = &self.state[&id];
+        let mut total = state.total_delay;
+        for since in state.waiting.values() {
+            total += now - *since;
+        }
+        total
+    }
 }
 
 impl State {
+    fn current_phase<'a>(
+        &self,
+        signal: &'a ControlTrafficSignal,
+        now: Duration,
+    ) -> (usize, &'a Phase, Duration) {
+        if let Some(ref st) = self.signal {
+            let phase = &signal.get_phases(st.plan)[st.current_phase];
+            (
+                st.current_phase,
+                phase,
+                (st.phase_started + phase.duration - now).max(Duration::ZERO),
+            )
+        } else {
+            signal.current_phase_and_remaining_time(now)
+        }
+    }
+
+    fn new_arrival(&mut self, turn: TurnID, now: Duration, map: &Map) {
+        if self.signal.is_none() || map.get_t(turn).turn_type == TurnType::SharedSidewalkCorner {
+            return;
+        }
+        let signal = map.get_traffic_signal(self.id);
+        let st = self.signal.as_mut().unwrap();
+        if signal.get_phases(st.plan)[st.current_phase].get_priority_of_turn(turn, signal)
+            != TurnPriority::Banned
+        {
+            st.last_arrival = now;
+        }
+    }
+
+    fn has_demand(&self, phase: &Phase, signal: &ControlTrafficSignal, map: &Map) -> bool {
+        self.waiting.keys().any(|req| {
+            map.get_t(req.turn).turn_type != TurnType::SharedSidewalkCorner
+                && phase.get_priority_of_turn(req.turn, signal) != TurnPriority::Banned
+        })
+    }
+
+    // Maybe switch phases. Returns the time to check again.
+    fn update_actuated_signal(
+        &mut self,
+        signal: &ControlTrafficSignal,
+        now: Duration,
+        map: &Map,
+    ) -> Duration {
+        let st = self.signal.clone().unwrap();
+        let phase = &signal.get_phases(st.plan)[st.current_phase];
+        let elapsed = now - st.phase_started;
+        match phase.phase_type {
+            PhaseType::Fixed => {
+                if elapsed < phase.duration {
+                    return st.phase_started + phase.duration;
+                }
+            }
+            PhaseType::Actuated { min_green, gap } => {
+                if elapsed < min_green {
+                    return st.phase_started + min_green;
+                }
+                // Extend the green while agents keep arriving
+                if elapsed < phase.duration && now - st.last_arrival < gap {
+                    return (st.last_arrival + gap).min(st.phase_started + phase.duration);
+                }
+            }
+        }
+
+        // Skip actuated phases that nobody's waiting for. If nobody's waiting for anything, rest
+        // in the current phase. If the scheduled plan changed, start the new one from the top.
+        let plan = signal.scheduled_plan(now);
+        let phases = signal.get_phases(plan);
+        let (start, first_offset) = if plan == st.plan {
+            (st.current_phase, 1)
+        } else {
+            (0, 0)
+        };
+        let mut next = start;
+        for offset in first_offset..first_offset + phases.len() {
+            let idx = (start + offset) % phases.len();
+            let skip = match phases[idx].phase_type {
+                PhaseType::Fixed => false,
+                PhaseType::Actuated { .. } => !self.has_demand(&phases[idx], signal, map),
+            };
+            if !skip {
+                next = idx;
+                break;
+            }
+        }
+
+        self.signal = Some(SignalState {
+            plan,
+            current_phase: next,
+            phase_started: now,
+            last_arrival: now,
+        });
+        let phase = &phases[next];
+        match phase.phase_type {
+            PhaseType::Fixed => now + phase.duration,
+            PhaseType::Actuated { min_green, .. } => now + min_green,
+        }
+    }
+
     fn any_accepted_conflict_with(&self, t: TurnID, map: &Map) -> bool {
         let turn = map.get_t(t);
         self.accepted
@@ -349,7 +569,7 @@ impl State {
             return true;
         }
 
-        let (_, phase, remaining_phase_time) = signal.current_phase_and_remaining_time(now);
+        let (_, phase, remaining_phase_time) = self.current_phase(signal, now);
 
         // Can't go at all this phase.
         if phase.get_priority_of_turn(new_req.turn, signal) == TurnPriority::Banned {
@@ -5,6 +5,7 @@ mod parking;
 mod queue;
 mod walking;
 
+pub use self::car::SpeedProfile;
 pub use self::driving::DrivingSimState;
 pub use self::intersection::IntersectionSimState;
 pub use self::parking::ParkingSimState;
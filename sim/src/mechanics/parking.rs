@@ -112,6 +112,11 @@ impl ParkingSimState {
         self.reserved_spots.insert(spot);
     }
 
+    // For cars removed while they were parking
+    pub fn unreserve_spot(&mut self, spot: ParkingSpot) {
+        self.reserved_spots.remove(&spot);
+    }
+
     pub fn remove_parked_car(&mut self, p: ParkedCar) {
         self.parked_cars
             .remove(&p.vehicle.id)
@@ -145,6 +150,13 @@ impl ParkingSimState {
         None
     }
 
+    // For a trip that already knows which car it'll use. Removing the car or returning it lifts the
+    // reservation.
+    pub fn reserve_parked_car(&mut self, id: CarID) {
+        assert!(self.parked_cars.contains_key(&id));
+        assert!(self.dynamically_reserved_cars.insert(id));
+    }
+
     pub fn dynamically_return_car(&mut self, p: ParkedCar) {
         self.dynamically_reserved_cars.remove(&p.vehicle.id);
     }
@@ -206,7 +218,96 @@ impl ParkingSimState {
     }
 
     pub fn is_free(&self, spot: ParkingSpot) -> bool {
-        !self.occupants.contains_key(&spot) && !self.reserved_spots.contains(&spot)
+        self.spot_exists(spot)
+            && !self.occupants.contains_key(&spot)
+            && !self.reserved_spots.contains(&spot)
+    }
+
+    // Live map edits can make on-street spots vanish.
+    pub fn spot_exists(&self, spot: ParkingSpot) -> bool {
+        match spot {
+            ParkingSpot::Onstreet(l, idx) => self
+                .onstreet_lanes
+                .get(&l)
+                .map(|lane| idx < lane.spot_dist_along.len())
+                .unwrap_or(false),
+            ParkingSpot::Offstreet(b, idx) => {
+                idx < self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0)
+            }
+        }
+    }
+
+    // After live map edits, parking lanes might've appeared or vanished. Cars parked on a spot
+    // that's gone move to a free spot along the same driving lane, or disappear if there isn't
+    // one. Returns the vanished spots that were occupied or reserved, so anybody headed there can
+    // give up.
+    pub fn handle_live_edits(&mut self, map: &Map, timer: &mut Timer) -> BTreeSet<ParkingSpot> {
+        let old_driving_lanes: BTreeMap<LaneID, LaneID> = self
+            .onstreet_lanes
+            .iter()
+            .map(|(l, lane)| (*l, lane.driving_lane))
+            .collect();
+        self.onstreet_lanes.clear();
+        self.driving_to_parking_lanes = MultiMap::new();
+        for l in map.all_lanes() {
+            if let Some(lane) = ParkingLane::new(l, map, timer) {
+                self.driving_to_parking_lanes
+                    .insert(lane.driving_lane, l.id);
+                self.onstreet_lanes.insert(lane.parking_lane, lane);
+            }
+        }
+
+        let mut vanished = BTreeSet::new();
+        for spot in self.reserved_spots.clone() {
+            if !self.spot_exists(spot) {
+                self.reserved_spots.remove(&spot);
+                vanished.insert(spot);
+            }
+        }
+
+        let evicted: Vec<ParkedCar> = self
+            .parked_cars
+            .values()
+            .filter(|p| !self.spot_exists(p.spot))
+            .cloned()
+            .collect();
+        let mut moved = 0;
+        for p in &evicted {
+            vanished.insert(p.spot);
+            self.remove_parked_car(p.clone());
+            let old_lane = match p.spot {
+                ParkingSpot::Onstreet(l, _) => l,
+                ParkingSpot::Offstreet(_, _) => unreachable!(),
+            };
+            if let Some((spot, _)) = self.get_first_free_spot(
+                Position::new(old_driving_lanes[&old_lane], Distance::ZERO),
+                &p.vehicle,
+                map,
+            ) {
+                self.reserve_spot(spot);
+                self.add_parked_car(ParkedCar {
+                    vehicle: p.vehicle.clone(),
+                    spot,
+                });
+                moved += 1;
+            }
+        }
+        if !evicted.is_empty() {
+            timer.note(format!(
+                "{} parked cars lost their spot. {} moved nearby, the rest vanished",
+                evicted.len(),
+                moved
+            ));
+        }
+        vanished
+    }
+
+    // None if the car isn't parked, or somebody's already on their way to it
+    pub fn lookup_parked_car(&self, id: CarID) -> Option<&ParkedCar> {
+        if self.dynamically_reserved_cars.contains(&id) {
+            return None;
+        }
+        self.parked_cars.get(&id)
     }
 
     pub fn get_car_at_spot(&self, spot: ParkingSpot) -> Option<&ParkedCar> {
@@ -1,14 +1,17 @@
 use crate::{
     AgentID, AgentMetadata, Command, CreatePedestrian, DistanceInterval, DrawPedCrowdInput,
     DrawPedestrianInput, Event, IntersectionSimState, ParkingSimState, ParkingSpot,
-    PedCrowdLocation, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TimeInterval,
-    TransitSimState, TripID, TripManager, TripPositions, UnzoomedAgent,
+    PedCrowdLocation, PedestrianID, RailSimState, Scheduler, SidewalkPOI, SidewalkSpot,
+    TimeInterval, TransitSimState, TripID, TripManager, TripPositions, UnzoomedAgent,
 };
 use abstutil::{deserialize_multimap, serialize_multimap, MultiMap};
 use geom::{Distance, Duration, Line, PolyLine, Speed};
-use map_model::{BuildingID, BusRouteID, Map, Path, PathStep, Traversable, LANE_THICKNESS};
+use map_model::{
+    BuildingID, BusRouteID, Map, Path, PathConstraints, PathStep, RailLineID, Traversable,
+    LANE_THICKNESS,
+};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 const TIME_TO_START_BIKING: Duration = Duration::const_seconds(30.0);
 const TIME_TO_FINISH_BIKING: Duration = Duration::const_seconds(45.0);
@@ -111,6 +114,7 @@ impl WalkingSimState {
         scheduler: &mut Scheduler,
         trips: &mut TripManager,
         transit: &mut TransitSimState,
+        rail: &mut RailSimState,
     ) {
         let mut ped = self.peds.get_mut(&id).unwrap();
         match ped.state {
@@ -147,6 +151,18 @@ impl WalkingSimState {
                                 self.peds.remove(&id);
                             }
                         }
+                        SidewalkPOI::Station(station) => {
+                            if let Some(line) =
+                                trips.ped_reached_station(ped.id, station, map, rail)
+                            {
+                                ped.state = PedState::WaitingForTrain(line);
+                                ped.blocked_since = Some(now);
+                            } else {
+                                self.peds_per_traversable
+                                    .remove(ped.path.current_step().as_traversable(), ped.id);
+                                self.peds.remove(&id);
+                            }
+                        }
                         SidewalkPOI::Border(i) => {
                             self.peds_per_traversable
                                 .remove(ped.path.current_step().as_traversable(), ped.id);
@@ -228,14 +244,68 @@ impl WalkingSimState {
                 ped.state = ped.crossing_state(spot.sidewalk_pos.dist_along(), now, map);
                 scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
-            PedState::WaitingForBus(_) => unreachable!(),
+            PedState::WaitingForBus(_) | PedState::WaitingForTrain(_) => unreachable!(),
+        }
+    }
+
+    // After live map edits, pedestrians whose path or destination can't be used anymore give up
+    // and vanish. Anybody waiting for transit doesn't need their path anymore. Returns the
+    // removed pedestrians and where they were headed.
+    pub fn handle_live_edits(
+        &mut self,
+        now: Duration,
+        vanished_spots: &BTreeSet<ParkingSpot>,
+        map: &Map,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) -> Vec<(PedestrianID, SidewalkSpot)> {
+        let broken: Vec<PedestrianID> = self
+            .peds
+            .values()
+            .filter(|ped| match ped.state {
+                PedState::WaitingForBus(_) | PedState::WaitingForTrain(_) => false,
+                _ => {
+                    let spot_gone = match ped.goal.connection {
+                        SidewalkPOI::ParkingSpot(spot) => vanished_spots.contains(&spot),
+                        _ => false,
+                    };
+                    let on_deleted_turn = match ped.path.current_step() {
+                        PathStep::Turn(t) => map.maybe_get_t(t).is_none(),
+                        _ => false,
+                    };
+                    spot_gone
+                        || on_deleted_turn
+                        || ped
+                            .path
+                            .first_broken_step(PathConstraints::Pedestrian, map)
+                            .is_some()
+                }
+            })
+            .map(|ped| ped.id)
+            .collect();
+
+        let mut removed = Vec::new();
+        for id in broken {
+            let ped = self.peds.remove(&id).unwrap();
+            let agent = AgentID::Pedestrian(id);
+            let on = ped.path.current_step();
+            self.peds_per_traversable.remove(on.as_traversable(), id);
+            scheduler.cancel(Command::UpdatePed(id));
+            if let PedState::WaitingToTurn(_) = ped.state {
+                intersections.cancel_request(agent, ped.path.next_step().as_turn());
+            }
+            if let PathStep::Turn(t) = on {
+                intersections.turn_finished(now, agent, t, scheduler, map);
+            }
+            removed.push((id, ped.goal));
         }
+        removed
     }
 
-    pub fn ped_boarded_bus(&mut self, id: PedestrianID) {
+    pub fn ped_boarded_transit(&mut self, id: PedestrianID) {
         let ped = self.peds.remove(&id).unwrap();
         match ped.state {
-            PedState::WaitingForBus(_) => {
+            PedState::WaitingForBus(_) | PedState::WaitingForTrain(_) => {
                 self.peds_per_traversable
                     .remove(ped.path.current_step().as_traversable(), id);
             }
@@ -273,8 +343,14 @@ impl WalkingSimState {
                 (now - p.started_at).minimal_tostring()
             ),
         ];
-        if let PedState::WaitingForBus(r) = p.state {
-            lines.push(format!("Waiting for bus {}", map.get_br(r).name));
+        match p.state {
+            PedState::WaitingForBus(r) => {
+                lines.push(format!("Waiting for bus {}", map.get_br(r).name));
+            }
+            PedState::WaitingForTrain(l) => {
+                lines.push(format!("Waiting for train {}", map.get_rl(l).name));
+            }
+            _ => {}
         }
         lines
     }
@@ -364,7 +440,8 @@ impl WalkingSimState {
                 }
                 PedState::StartingToBike(_, _, _)
                 | PedState::FinishingBiking(_, _, _)
-                | PedState::WaitingForBus(_) => {
+                | PedState::WaitingForBus(_)
+                | PedState::WaitingForTrain(_) => {
                     // The backwards half of the sidewalk is closer to the road.
                     backwards.push((*id, dist));
                 }
@@ -462,7 +539,9 @@ impl Pedestrian {
             PedState::EnteringBuilding(b, _) => map.get_b(b).front_path.sidewalk.dist_along(),
             PedState::StartingToBike(ref spot, _, _) => spot.sidewalk_pos.dist_along(),
             PedState::FinishingBiking(ref spot, _, _) => spot.sidewalk_pos.dist_along(),
-            PedState::WaitingForBus(_) => self.goal.sidewalk_pos.dist_along(),
+            PedState::WaitingForBus(_) | PedState::WaitingForTrain(_) => {
+                self.goal.sidewalk_pos.dist_along()
+            }
         }
     }
 
@@ -523,7 +602,7 @@ impl Pedestrian {
             PedState::FinishingBiking(_, ref line, ref time_int) => {
                 (line.percent_along(time_int.percent(now)), line.angle())
             }
-            PedState::WaitingForBus(_) => {
+            PedState::WaitingForBus(_) | PedState::WaitingForTrain(_) => {
                 let (pt, angle) = self.goal.sidewalk_pos.pt_and_angle(map);
                 // Face the road
                 (pt, angle.rotate_degs(90.0))
@@ -613,6 +692,7 @@ enum PedState {
     StartingToBike(SidewalkSpot, Line, TimeInterval),
     FinishingBiking(SidewalkSpot, Line, TimeInterval),
     WaitingForBus(BusRouteID),
+    WaitingForTrain(RailLineID),
 }
 
 impl PedState {
@@ -624,7 +704,7 @@ impl PedState {
             PedState::EnteringBuilding(_, ref time_int) => time_int.end,
             PedState::StartingToBike(_, _, ref time_int) => time_int.end,
             PedState::FinishingBiking(_, _, ref time_int) => time_int.end,
-            PedState::WaitingForBus(_) => unreachable!(),
+            PedState::WaitingForBus(_) | PedState::WaitingForTrain(_) => unreachable!(),
         }
     }
 }
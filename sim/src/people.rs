@@ -0,0 +1,172 @@
+use crate::{CarID, DrivingGoal, ParkingSimState, PersonID, SidewalkSpot, SpawnTrip, TripSpec};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::Duration;
+use map_model::{BuildingID, Map};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+// Why somebody goes somewhere. These come from the PSRC travel survey.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    Home,
+    Work,
+    School,
+    Escort,
+    PersonalBusiness,
+    Shopping,
+    Meal,
+    Social,
+    Recreation,
+    Medical,
+    ParkAndRideTransfer,
+}
+
+// How a Scenario describes somebody's day.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PersonSpec {
+    pub id: PersonID,
+    pub home: BuildingID,
+    // These start the day parked near home.
+    pub num_cars: usize,
+    // In order. Each trip only starts after the previous one is done.
+    pub trips: Vec<PersonTrip>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PersonTrip {
+    // The departure time is the earliest the trip starts; it'll be later if the previous trip
+    // runs long.
+    pub trip: SpawnTrip,
+    // What the person does at the destination
+    pub purpose: Purpose,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Person {
+    cars: Vec<CarID>,
+    // What's left of the day. (earliest departure, purpose, trip)
+    remaining: VecDeque<(Duration, Purpose, TripSpec)>,
+    // Why the person is taking the trip in progress
+    current: Option<Purpose>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct PeopleState {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    people: BTreeMap<PersonID, Person>,
+}
+
+impl PeopleState {
+    pub fn new() -> PeopleState {
+        PeopleState {
+            people: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_person(
+        &mut self,
+        id: PersonID,
+        cars: Vec<CarID>,
+        trips: Vec<(Duration, Purpose, TripSpec)>,
+    ) {
+        assert!(!self.people.contains_key(&id));
+        self.people.insert(
+            id,
+            Person {
+                cars,
+                remaining: trips.into_iter().collect(),
+                current: None,
+            },
+        );
+    }
+
+    // Pops the person's next trip, if they have one. Trips that'd use a parked car use the
+    // person's own car, wherever they last left it. If it isn't parked anywhere, they walk instead
+    // of borrowing somebody else's car.
+    pub fn next_trip(
+        &mut self,
+        id: PersonID,
+        map: &Map,
+        parking: &ParkingSimState,
+    ) -> Option<(Duration, Purpose, TripSpec)> {
+        let person = self.people.get_mut(&id).unwrap();
+        assert!(person.current.is_none());
+        loop {
+            let (depart, purpose, spec) = person.remaining.pop_front()?;
+            let spec = match spec {
+                TripSpec::MaybeUsingParkedCar {
+                    start_bldg,
+                    goal,
+                    ped_speed,
+                } => {
+                    let start = SidewalkSpot::building(start_bldg, map);
+                    if let Some(car) = person
+                        .cars
+                        .iter()
+                        .find_map(|c| parking.lookup_parked_car(*c))
+                    {
+                        TripSpec::UsingParkedCar {
+                            start,
+                            spot: car.spot,
+                            goal,
+                            ped_speed,
+                        }
+                    } else {
+                        let walk_to = match &goal {
+                            DrivingGoal::ParkNear(b) => Some(SidewalkSpot::building(*b, map)),
+                            DrivingGoal::Border(i, _) => SidewalkSpot::end_at_border(*i, map),
+                        };
+                        match walk_to {
+                            Some(walk_to) if walk_to != start => {
+                                println!(
+                                    "WARNING: {} has no parked car, so they'll walk to {:?}",
+                                    id, goal
+                                );
+                                TripSpec::JustWalking {
+                                    start,
+                                    goal: walk_to,
+                                    ped_speed,
+                                }
+                            }
+                            _ => {
+                                println!(
+                                    "WARNING: {} has no parked car and can't walk to {:?}, so \
+                                     they're skipping that trip",
+                                    id, goal
+                                );
+                                continue;
+                            }
+                        }
+                    }
+                }
+                x => x,
+            };
+            return Some((depart, purpose, spec));
+        }
+    }
+
+    pub fn trip_started(&mut self, id: PersonID, purpose: Purpose) {
+        self.people.get_mut(&id).unwrap().current = Some(purpose);
+    }
+
+    // Aborted trips count as done too; the person just moves on with the rest of their day.
+    pub fn trip_done(&mut self, id: PersonID) {
+        let person = self.people.get_mut(&id).unwrap();
+        assert!(person.current.is_some());
+        person.current = None;
+    }
+
+    // Why the person is taking their current trip
+    pub fn current_purpose(&self, id: PersonID) -> Option<Purpose> {
+        self.people[&id].current
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.people
+            .values()
+            .all(|p| p.current.is_none() && p.remaining.is_empty())
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    AgentMetadata, Command, DrawTrainInput, Event, PedestrianID, Scheduler, TimeInterval, TrainID,
+    TripManager, WalkingSimState,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, PolyLine, Pt2D};
+use map_model::{Map, RailLineID, StationID};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// These index stations along a line.
+type StationIdx = usize;
+
+// Trains stop this long at every station, no matter how many people get on or off.
+const DWELL_TIME: Duration = Duration::const_seconds(30.0);
+// Just for drawing. A two-car light rail train.
+const TRAIN_LENGTH: Distance = Distance::const_meters(55.0);
+
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Train {
+    id: TrainID,
+    line: RailLineID,
+    // Where does each passenger want to get off?
+    passengers: Vec<(PedestrianID, StationID)>,
+    state: TrainState,
+    // For trains following the schedule, how many more stations to visit (including the one it's
+    // heading to or stopped at) before going out of service.
+    stations_left: Option<usize>,
+    started_at: Duration,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+enum TrainState {
+    // Left this station and is heading to the next one
+    Moving(StationIdx, TimeInterval),
+    AtStation(StationIdx),
+}
+
+// Trains run on their own grade-separated tracks, so unlike buses, they never interact with
+// anything on the roads. They just follow the times given by the line.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct RailSimState {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    trains: BTreeMap<TrainID, Train>,
+    // (ped, waiting at, line, getting off at)
+    peds_waiting: Vec<(PedestrianID, StationID, RailLineID, StationID)>,
+    // Waiting to transfer without leaving the platform, so not in the walking sim
+    on_platform: BTreeSet<PedestrianID>,
+
+    events: Vec<Event>,
+}
+
+impl RailSimState {
+    pub fn new() -> RailSimState {
+        RailSimState {
+            trains: BTreeMap::new(),
+            peds_waiting: Vec::new(),
+            on_platform: BTreeSet::new(),
+            events: Vec::new(),
+        }
+    }
+
+    // The train appears already stopped at its first station.
+    pub fn start_train(
+        &mut self,
+        now: Duration,
+        id: TrainID,
+        line: RailLineID,
+        first_station: StationIdx,
+        // If None, the train never goes out of service.
+        num_stations: Option<usize>,
+        trips: &mut TripManager,
+        walking: &mut WalkingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        self.trains.insert(
+            id,
+            Train {
+                id,
+                line,
+                passengers: Vec::new(),
+                // Arriving will immediately switch this
+                state: TrainState::AtStation(first_station),
+                stations_left: num_stations,
+                started_at: now,
+            },
+        );
+        self.train_arrived(now, id, first_station, trips, walking, scheduler, map);
+    }
+
+    pub fn update_train(
+        &mut self,
+        now: Duration,
+        id: TrainID,
+        trips: &mut TripManager,
+        walking: &mut WalkingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        let train = self.trains.get_mut(&id).unwrap();
+        let line = map.get_rl(train.line);
+        match train.state {
+            TrainState::AtStation(idx) => {
+                self.events.push(Event::TrainDepartedFromStation(
+                    id,
+                    line.id,
+                    line.stations[idx],
+                ));
+                let arrive_at = now + line.tracks[idx].travel_time;
+                train.state = TrainState::Moving(idx, TimeInterval::new(now, arrive_at));
+                scheduler.push(arrive_at, Command::UpdateTrain(id));
+            }
+            TrainState::Moving(idx, _) => {
+                let next_idx = line.next_station_idx(idx);
+                self.train_arrived(now, id, next_idx, trips, walking, scheduler, map);
+            }
+        }
+    }
+
+    fn train_arrived(
+        &mut self,
+        now: Duration,
+        id: TrainID,
+        idx: StationIdx,
+        trips: &mut TripManager,
+        walking: &mut WalkingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        let train = self.trains.get_mut(&id).unwrap();
+        let line = map.get_rl(train.line);
+        let station = line.stations[idx];
+        train.state = TrainState::AtStation(idx);
+        if let Some(ref mut n) = train.stations_left {
+            *n -= 1;
+        }
+        self.events
+            .push(Event::TrainArrivedAtStation(id, line.id, station));
+
+        let mut still_riding = Vec::new();
+        let mut transfers = Vec::new();
+        for (ped, station2) in train.passengers.drain(..) {
+            if station == station2 {
+                self.events.push(Event::PedLeavesTrain(ped, id, line.id));
+                if let Some((line2, station3)) = trips.ped_left_transit(now, ped, map, scheduler) {
+                    transfers.push((ped, line2, station3));
+                }
+            } else {
+                still_riding.push((ped, station2));
+            }
+        }
+        train.passengers = still_riding;
+
+        // Another line's train might already be waiting at the platform.
+        for (ped, line2, station3) in transfers {
+            if !self.ped_waiting_for_train(ped, station, line2, station3, map) {
+                self.on_platform.insert(ped);
+            }
+        }
+        let train = self.trains.get_mut(&id).unwrap();
+
+        if train.stations_left == Some(0) {
+            assert!(train.passengers.is_empty());
+            self.trains.remove(&id);
+            return;
+        }
+
+        let mut still_waiting = Vec::new();
+        for (ped, station1, line_id, station2) in self.peds_waiting.drain(..) {
+            if station == station1 && line_id == line.id && will_reach(train, map, station2) {
+                train.passengers.push((ped, station2));
+                self.events.push(Event::PedEntersTrain(ped, id, line_id));
+                // Transferring pedestrians already started this leg when they got off.
+                if !self.on_platform.remove(&ped) {
+                    trips.ped_boarded_transit(ped, walking);
+                }
+            } else {
+                still_waiting.push((ped, station1, line_id, station2));
+            }
+        }
+        self.peds_waiting = still_waiting;
+
+        scheduler.push(now + DWELL_TIME, Command::UpdateTrain(id));
+    }
+
+    // If true, the pedestrian boarded a train immediately.
+    pub fn ped_waiting_for_train(
+        &mut self,
+        ped: PedestrianID,
+        station1: StationID,
+        line: RailLineID,
+        station2: StationID,
+        map: &Map,
+    ) -> bool {
+        assert!(station1 != station2);
+        for train in self.trains.values_mut() {
+            if let TrainState::AtStation(idx) = train.state {
+                if train.line == line
+                    && map.get_rl(line).stations[idx] == station1
+                    && will_reach(train, map, station2)
+                {
+                    train.passengers.push((ped, station2));
+                    self.events.push(Event::PedEntersTrain(ped, train.id, line));
+                    return true;
+                }
+            }
+        }
+
+        self.peds_waiting.push((ped, station1, line, station2));
+        false
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn get_all_draw_trains(&self, now: Duration, map: &Map) -> Vec<DrawTrainInput> {
+        self.trains
+            .values()
+            .map(|train| DrawTrainInput {
+                id: train.id,
+                line: train.line,
+                body: train.body(now, map),
+                metadata: AgentMetadata {
+                    time_spent_blocked: Duration::ZERO,
+                    percent_dist_crossed: 0.0,
+                    trip_time_so_far: now - train.started_at,
+                    occupying_intersection: false,
+                },
+            })
+            .collect()
+    }
+
+    // Where's the train this pedestrian is riding?
+    pub fn passenger_pos(&self, ped: PedestrianID, now: Duration, map: &Map) -> Option<Pt2D> {
+        self.trains
+            .values()
+            .find(|t| t.passengers.iter().any(|(p, _)| *p == ped))
+            .map(|t| t.pos(now, map))
+    }
+
+    pub fn location_of_trains(
+        &self,
+        line: RailLineID,
+        now: Duration,
+        map: &Map,
+    ) -> Vec<(TrainID, Pt2D)> {
+        self.trains
+            .values()
+            .filter(|t| t.line == line)
+            .map(|t| (t.id, t.pos(now, map)))
+            .collect()
+    }
+}
+
+impl Train {
+    // Starts at the back of the train. A stopped train waits with its back at the station, facing
+    // the next track, and the front of a moving train goes from there to the next station.
+    fn body(&self, now: Duration, map: &Map) -> PolyLine {
+        let line = map.get_rl(self.line);
+        let (track, pct) = match self.state {
+            TrainState::AtStation(idx) => (&line.tracks[idx].geom, 0.0),
+            TrainState::Moving(idx, ref interval) => {
+                (&line.tracks[idx].geom, interval.percent(now))
+            }
+        };
+        let length = TRAIN_LENGTH.min(track.length());
+        let front = (length + (track.length() - length) * pct).min(track.length());
+        track.exact_slice(front - length, front)
+    }
+
+    fn pos(&self, now: Duration, map: &Map) -> Pt2D {
+        let line = map.get_rl(self.line);
+        match self.state {
+            TrainState::AtStation(idx) => map.get_station(line.stations[idx]).pt,
+            TrainState::Moving(idx, ref interval) => {
+                let track = &line.tracks[idx].geom;
+                track.dist_along(track.length() * interval.percent(now)).0
+            }
+        }
+    }
+}
+
+// Will the train stop here before going out of service?
+fn will_reach(train: &Train, map: &Map, station: StationID) -> bool {
+    let idx = match train.state {
+        TrainState::AtStation(idx) => idx,
+        TrainState::Moving(_, _) => unreachable!(),
+    };
+    let stations = &map.get_rl(train.line).stations;
+    match train.stations_left {
+        Some(n) => (1..=n).any(|i| stations[(idx + i) % stations.len()] == station),
+        None => true,
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{CarID, PedestrianID, VehicleType};
+use crate::{CarID, PedestrianID, TrainID, VehicleType};
 use geom::{Angle, Distance, Duration, PolyLine, Pt2D};
-use map_model::{BuildingID, Map, Traversable, TurnID};
+use map_model::{BuildingID, Map, RailLineID, Traversable, TurnID};
 
 // Intermediate structures so that sim and game crates don't have a cyclic dependency.
 #[derive(Clone)]
@@ -49,6 +49,17 @@ pub struct DrawCarInput {
     pub body: PolyLine,
 }
 
+// Trains aren't on any lane, so they're always drawn separately.
+#[derive(Clone)]
+pub struct DrawTrainInput {
+    pub id: TrainID,
+    pub line: RailLineID,
+    pub metadata: AgentMetadata,
+
+    // Starts at the BACK of the train, along the track.
+    pub body: PolyLine,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum CarStatus {
     Moving,
@@ -80,6 +91,7 @@ pub trait GetDrawAgents {
     fn get_all_draw_cars(&self, map: &Map) -> Vec<DrawCarInput>;
     fn get_all_draw_peds(&self, map: &Map) -> Vec<DrawPedestrianInput>;
     fn get_unzoomed_agents(&self, map: &Map) -> Vec<UnzoomedAgent>;
+    fn get_all_draw_trains(&self, map: &Map) -> Vec<DrawTrainInput>;
 }
 
 pub struct DontDrawAgents;
@@ -116,4 +128,7 @@ impl GetDrawAgents for DontDrawAgents {
     fn get_unzoomed_agents(&self, _: &Map) -> Vec<UnzoomedAgent> {
         Vec::new()
     }
+    fn get_all_draw_trains(&self, _: &Map) -> Vec<DrawTrainInput> {
+        Vec::new()
+    }
 }
@@ -1,18 +1,32 @@
 use crate::mechanics::Queue;
-use crate::{ParkingSimState, ParkingSpot, SidewalkSpot, Vehicle};
-use geom::Distance;
+use crate::{ParkingSimState, ParkingSpot, SidewalkSpot, Vehicle, FOLLOWING_DISTANCE};
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathStep, Position,
-    Traversable, TurnID,
+    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
+    Position, Traversable, TurnID,
 };
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, VecDeque};
+
+// When an informed driver estimates how long a lane or turn will take, every car already queued
+// there adds this much.
+const DELAY_PER_QUEUED_CAR: Duration = Duration::const_seconds(2.0);
+// Don't bother switching routes unless it's estimated to save at least this much.
+const MIN_REROUTE_SAVINGS: Duration = Duration::const_seconds(30.0);
+// Looking for a faster path is expensive, so each car only does it this often, no matter how many
+// jams it sees.
+const TIME_BETWEEN_REROUTES: Duration = Duration::const_seconds(60.0);
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Router {
     // Front is always the current step
     path: Path,
     goal: Goal,
+    // Re-plan around congestion when entering each lane
+    informed: bool,
+    // The last time this car looked for a faster path
+    last_reroute: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -52,6 +66,8 @@ impl Router {
         Router {
             path,
             goal: Goal::EndAtBorder { end_dist, i },
+            informed: false,
+            last_reroute: None,
         }
     }
 
@@ -63,6 +79,8 @@ impl Router {
                 spot: None,
                 stuck_end_dist: None,
             },
+            informed: false,
+            last_reroute: None,
         }
     }
 
@@ -70,6 +88,8 @@ impl Router {
         Router {
             path,
             goal: Goal::BikeThenStop { end_dist },
+            informed: false,
+            last_reroute: None,
         }
     }
 
@@ -77,6 +97,18 @@ impl Router {
         Router {
             path,
             goal: Goal::FollowBusRoute { end_dist },
+            informed: false,
+            last_reroute: None,
+        }
+    }
+
+    // Only makes sense for cars headed to a building or border.
+    pub fn make_informed(&mut self) {
+        match self.goal {
+            Goal::ParkNearBuilding { .. } | Goal::EndAtBorder { .. } => {
+                self.informed = true;
+            }
+            Goal::BikeThenStop { .. } | Goal::FollowBusRoute { .. } => unreachable!(),
         }
     }
 
@@ -119,19 +151,29 @@ impl Router {
         &self.path
     }
 
-    // Returns the step just finished
+    // Returns the step just finished, and if the rest of the path changed to avoid a jam, the
+    // lane that was jammed.
     pub fn advance(
         &mut self,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
+        queues: &BTreeMap<Traversable, Queue>,
+        now: Duration,
         map: &Map,
-    ) -> Traversable {
+    ) -> (Traversable, Option<LaneID>) {
         let prev = self.path.shift(map).as_traversable();
         if self.last_step() {
             // Do this to trigger the side-effect of looking for parking.
             self.maybe_handle_end(Distance::ZERO, vehicle, parking, map);
         }
 
+        let mut avoided = None;
+        if self.informed && !self.last_step() {
+            if let Traversable::Lane(_) = self.head() {
+                avoided = self.maybe_reroute(vehicle, queues, now, map);
+            }
+        }
+
         // Sanity check laws haven't been broken
         if let Traversable::Lane(l) = self.head() {
             let lane = map.get_l(l);
@@ -143,7 +185,107 @@ impl Router {
             }
         }
 
-        prev
+        (prev, avoided)
+    }
+
+    // If a lane coming up is too full for this car to fit, look for a faster way to the same
+    // last lane, assuming all the queues stay as they are right now.
+    fn maybe_reroute(
+        &mut self,
+        vehicle: &Vehicle,
+        queues: &BTreeMap<Traversable, Queue>,
+        now: Duration,
+        map: &Map,
+    ) -> Option<LaneID> {
+        let jammed = self.path.get_steps().iter().skip(1).find_map(|step| {
+            if let PathStep::Lane(l) = step {
+                let queue = &queues[&Traversable::Lane(*l)];
+                if queue.reserved_length > Distance::ZERO
+                    && queue.reserved_length + vehicle.length + FOLLOWING_DISTANCE >= queue.geom_len
+                {
+                    return Some(*l);
+                }
+            }
+            None
+        })?;
+
+        let start = self.head().as_lane();
+        let end = self.path.last_step().as_lane();
+        // Some paths loop back around to where they started
+        if start == end {
+            return None;
+        }
+        if let Some(t) = self.last_reroute {
+            if now - t < TIME_BETWEEN_REROUTES {
+                return None;
+            }
+        }
+        self.last_reroute = Some(now);
+        let mut current_cost = Duration::ZERO;
+        for step in self.path.get_steps().iter().skip(1) {
+            current_cost += estimated_time(step.as_traversable(), queues, map);
+        }
+        let (steps, cost) = fastest_path(
+            start,
+            end,
+            vehicle.vehicle_type.to_constraints(),
+            queues,
+            map,
+        )?;
+        if cost + MIN_REROUTE_SAVINGS > current_cost {
+            return None;
+        }
+        self.path.replace_remaining_steps(steps, map);
+        Some(jammed)
+    }
+
+    // After live map edits, find a new way to the same last lane if anything ahead is broken.
+    // The lane right after a turn can't change. False if there's no way to keep going.
+    pub fn reroute_after_edits(&mut self, vehicle: &Vehicle, map: &Map) -> bool {
+        let constraints = vehicle.vehicle_type.to_constraints();
+        let broken = match self.path.first_broken_step(constraints, map) {
+            Some(idx) => idx,
+            None => {
+                return true;
+            }
+        };
+        let num_steps = self.path.get_steps().len();
+        if broken == num_steps - 1 {
+            return false;
+        }
+        let (from, on_turn) = match self.head() {
+            Traversable::Lane(l) => (l, false),
+            Traversable::Turn(t) => {
+                if broken == 1 {
+                    return false;
+                }
+                (t.dst, true)
+            }
+        };
+        let end = self.path.last_step().as_lane();
+        if from == end {
+            return false;
+        }
+        let new_path = match map.pathfind(PathRequest {
+            start: Position::new(from, Distance::ZERO),
+            end: Position::new(end, map.get_l(end).length()),
+            constraints,
+        }) {
+            Some(p) => p,
+            None => {
+                return false;
+            }
+        };
+        let steps = new_path.get_steps().iter().cloned();
+        self.path.replace_remaining_steps(
+            if on_turn {
+                steps.collect()
+            } else {
+                steps.skip(1).collect()
+            },
+            map,
+        );
+        true
     }
 
     // Called when the car is Queued at the last step, or when they initially advance to the last
@@ -306,6 +448,58 @@ impl Router {
     }
 }
 
+fn estimated_time(on: Traversable, queues: &BTreeMap<Traversable, Queue>, map: &Map) -> Duration {
+    let free_flow = on.length(map) / on.speed_limit(map);
+    free_flow + DELAY_PER_QUEUED_CAR * (queues[&on].cars.len() as f64)
+}
+
+// Dijkstra's, weighted by estimated_time. Like path_to_free_parking_spot, the first PathStep is
+// the turn after start. Also returns the estimated cost.
+fn fastest_path(
+    start: LaneID,
+    end: LaneID,
+    constraints: PathConstraints,
+    queues: &BTreeMap<Traversable, Queue>,
+    map: &Map,
+) -> Option<(Vec<PathStep>, Duration)> {
+    let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
+    let mut best: HashMap<LaneID, Duration> = HashMap::new();
+    let mut queue: BinaryHeap<(Reverse<Duration>, LaneID)> = BinaryHeap::new();
+    best.insert(start, Duration::ZERO);
+    queue.push((Reverse(Duration::ZERO), start));
+
+    while let Some((Reverse(cost), current)) = queue.pop() {
+        if current == end {
+            let mut steps = Vec::new();
+            let mut current = current;
+            while current != start {
+                let turn = backrefs[&current];
+                steps.push(PathStep::Lane(current));
+                steps.push(PathStep::Turn(turn));
+                current = turn.src;
+            }
+            steps.reverse();
+            return Some((steps, cost));
+        }
+        if cost > best[&current] {
+            continue;
+        }
+        for turn in map.get_turns_for(current, constraints) {
+            let dst = turn.id.dst;
+            let dst_cost = cost
+                + estimated_time(Traversable::Turn(turn.id), queues, map)
+                + estimated_time(Traversable::Lane(dst), queues, map);
+            if best.get(&dst).map(|c| dst_cost < *c).unwrap_or(true) {
+                best.insert(dst, dst_cost);
+                backrefs.insert(dst, turn.id);
+                queue.push((Reverse(dst_cost), dst));
+            }
+        }
+    }
+
+    None
+}
+
 // Unrealistically assumes the driver has knowledge of currently free parking spots, even if
 // they're far away. Since they don't reserve the spot in advance, somebody else can still beat
 // them there, producing some nice, realistic churn if there's too much contention.
@@ -1,7 +1,7 @@
-use crate::{AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID};
+use crate::{AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, TrainID};
 use derivative::Derivative;
 use geom::{Duration, DurationHistogram};
-use map_model::IntersectionID;
+use map_model::{BusRouteID, IntersectionID, RailLineID};
 use serde_derive::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, BinaryHeap};
@@ -11,12 +11,21 @@ pub enum Command {
     // If true, retry when there's no room to spawn somewhere
     SpawnCar(CreateCar, bool),
     SpawnPed(CreatePedestrian),
+    // The index of the route's scheduled run
+    StartBus(CarID, BusRouteID, usize),
+    // The index of the line's scheduled run, or None to keep looping forever
+    StartTrain(TrainID, RailLineID, Option<usize>),
     UpdateCar(CarID),
     // Distinguish this from UpdateCar to avoid confusing things
     UpdateLaggyHead(CarID),
     UpdatePed(PedestrianID),
+    UpdateTrain(TrainID),
     UpdateIntersection(IntersectionID),
     Savestate(Duration),
+    // The interval between samples
+    RecordTrajectories(Duration),
+    // Some timed map edits start or end now
+    UpdateTimedEdits,
 }
 
 impl Command {
@@ -31,11 +40,16 @@ impl Command {
         match self {
             Command::SpawnCar(ref create, _) => CommandType::Car(create.vehicle.id),
             Command::SpawnPed(ref create) => CommandType::Ped(create.id),
+            Command::StartBus(id, _, _) => CommandType::Car(*id),
+            Command::StartTrain(id, _, _) => CommandType::Train(*id),
             Command::UpdateCar(id) => CommandType::Car(*id),
             Command::UpdateLaggyHead(id) => CommandType::CarLaggyHead(*id),
             Command::UpdatePed(id) => CommandType::Ped(*id),
+            Command::UpdateTrain(id) => CommandType::Train(*id),
             Command::UpdateIntersection(id) => CommandType::Intersection(*id),
             Command::Savestate(_) => CommandType::Savestate,
+            Command::RecordTrajectories(_) => CommandType::RecordTrajectories,
+            Command::UpdateTimedEdits => CommandType::TimedEdits,
         }
     }
 }
@@ -47,8 +61,11 @@ pub enum CommandType {
     Car(CarID),
     CarLaggyHead(CarID),
     Ped(PedestrianID),
+    Train(TrainID),
     Intersection(IntersectionID),
     Savestate,
+    RecordTrajectories,
+    TimedEdits,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq)]
@@ -157,6 +174,11 @@ impl Scheduler {
         self.queued_commands.remove(&cmd.to_type());
     }
 
+    // When this command is scheduled to happen, if at all.
+    pub fn get_time(&self, cmd: &Command) -> Option<Duration> {
+        self.queued_commands.get(&cmd.to_type()).map(|(_, t)| *t)
+    }
+
     // This API is safer than handing out a batch of items at a time, because while processing one
     // item, we might change the priority of other items or add new items. Don't make the caller
     // reconcile those changes -- just keep pulling items from here, one at a time.
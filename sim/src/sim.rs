@@ -1,25 +1,32 @@
 use crate::{
-    AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, FinishedTrips, GetDrawAgents,
-    IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, Router, Scheduler,
-    SidewalkPOI, SidewalkSpot, TransitSimState, TripCount, TripID, TripLeg, TripManager,
-    TripPositions, TripResult, TripSpawner, TripSpec, TripStart, TripStatus, UnzoomedAgent,
-    VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
+    AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, CreatePedestrian, DrawCarInput,
+    DrawPedCrowdInput, DrawPedestrianInput, DrawTrainInput, DrivingGoal, DrivingSimState, Event,
+    EventLog, EventLogOptions, FinishedTrips, GetDrawAgents, IntersectionSimState, ParkedCar,
+    ParkingSimState, ParkingSpot, PedestrianID, PeopleState, PersonID, Purpose, RailSimState,
+    Router, Scheduler, SidewalkPOI, SidewalkSpot, TrainID, TrajectoryRecorder, TransitSimState,
+    TripCount, TripID, TripLeg, TripManager, TripMode, TripPositions, TripResult, TripSpawner,
+    TripSpec, TripStart, TripStatus, UnzoomedAgent, VehicleSpec, VehicleType, WalkingSimState,
+    BUS_LENGTH,
 };
 use abstutil::{elapsed_seconds, Timer};
 use derivative::Derivative;
 use geom::{Distance, Duration, DurationHistogram, PolyLine, Pt2D};
 use map_model::{
-    BuildingID, BusRoute, BusRouteID, IntersectionID, LaneID, Map, Path, PathConstraints,
-    PathRequest, PathStep, Traversable,
+    BuildingID, BusRoute, BusRouteID, EditCmd, EditEffects, IntersectionID, LaneID, LaneType, Map,
+    MapEdits, Path, PathConstraints, PathRequest, PathStep, Phase, RailLine, RailLineID,
+    Traversable,
 };
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::panic;
 use std::time::Instant;
 
 // TODO Do something else.
 const BLIND_RETRY_TO_SPAWN: Duration = Duration::const_seconds(5.0);
+// Driving paths planned this long before a timed edit starts avoid whatever it'll take away.
+const TIMED_EDIT_LOOKAHEAD: Duration = Duration::const_seconds(15.0 * 60.0);
+// Added to the cost of anything a timed edit is about to take away
+const TIMED_EDIT_PENALTY: Duration = Duration::const_seconds(3600.0);
 
 #[derive(Serialize, Deserialize, Derivative)]
 #[derivative(PartialEq)]
@@ -29,12 +36,15 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    rail: RailSimState,
     trips: TripManager,
+    people: PeopleState,
     spawner: TripSpawner,
     scheduler: Scheduler,
     time: Duration,
     car_id_counter: usize,
     ped_id_counter: usize,
+    train_id_counter: usize,
 
     // TODO Reconsider these
     pub(crate) map_name: String,
@@ -44,6 +54,17 @@ pub struct Sim {
     run_name: String,
     #[derivative(PartialEq = "ignore")]
     step_count: usize,
+    // Once the map changes underneath the sim, anything spawning was planned for the old map.
+    edited_live: bool,
+    // Only step_with_timed_edits can switch timed edits on and off.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    stepping_with_timed_edits: bool,
+    // While driving costs on the map steer around upcoming timed edits, the travel times from
+    // before, so the penalties can be lifted again.
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    travel_times_before_timed_edits: Option<BTreeMap<Traversable, Duration>>,
 
     // Lazily computed.
     #[derivative(PartialEq = "ignore")]
@@ -52,6 +73,10 @@ pub struct Sim {
     // TODO Maybe the buffered events in child objects should also have this.
     #[derivative(PartialEq = "ignore")]
     analytics: Analytics,
+    #[derivative(PartialEq = "ignore")]
+    #[serde(skip_serializing, skip_deserializing)]
+    event_log: Option<EventLog>,
+    trajectories: Option<TrajectoryRecorder>,
 }
 
 #[derive(Clone)]
@@ -61,6 +86,14 @@ pub struct SimOptions {
     pub use_freeform_policy_everywhere: bool,
     pub disable_block_the_box: bool,
     pub recalc_lanechanging: bool,
+    // Cars accelerate and brake instead of instantly changing speed.
+    pub kinematic_driving: bool,
+    // Write every event to a file as it happens
+    pub event_log: Option<EventLogOptions>,
+    // Sample every agent's position this often
+    pub record_trajectories: Option<Duration>,
+    // The fraction of cars that re-plan their route around congestion as they go
+    pub informed_drivers: f64,
 }
 
 impl SimOptions {
@@ -71,6 +104,10 @@ impl SimOptions {
             use_freeform_policy_everywhere: false,
             disable_block_the_box: false,
             recalc_lanechanging: true,
+            kinematic_driving: false,
+            event_log: None,
+            record_trajectories: None,
+            informed_drivers: 0.0,
         }
     }
 }
@@ -82,8 +119,19 @@ impl Sim {
         if let Some(d) = opts.savestate_every {
             scheduler.push(d, Command::Savestate(d));
         }
+        if let Some(d) = opts.record_trajectories {
+            scheduler.push(Duration::ZERO, Command::RecordTrajectories(d));
+        }
+        if !map.get_edits().timed_commands.is_empty() {
+            scheduler.push(Duration::ZERO, Command::UpdateTimedEdits);
+        }
         Sim {
-            driving: DrivingSimState::new(map, opts.recalc_lanechanging),
+            driving: DrivingSimState::new(
+                map,
+                opts.recalc_lanechanging,
+                opts.kinematic_driving,
+                opts.informed_drivers,
+            ),
             parking: ParkingSimState::new(map, timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(
@@ -93,21 +141,29 @@ impl Sim {
                 opts.disable_block_the_box,
             ),
             transit: TransitSimState::new(),
+            rail: RailSimState::new(),
             trips: TripManager::new(),
+            people: PeopleState::new(),
             spawner: TripSpawner::new(),
             scheduler,
             time: Duration::ZERO,
             car_id_counter: 0,
             ped_id_counter: 0,
+            train_id_counter: 0,
 
             map_name: map.get_name().to_string(),
             // TODO
             edits_name: "no_edits".to_string(),
             run_name: opts.run_name,
             step_count: 0,
+            edited_live: false,
+            stepping_with_timed_edits: false,
+            travel_times_before_timed_edits: None,
             trip_positions: None,
 
             analytics: Analytics::new(),
+            event_log: opts.event_log.as_ref().map(EventLog::new),
+            trajectories: opts.record_trajectories.map(TrajectoryRecorder::new),
         }
     }
 
@@ -117,6 +173,18 @@ impl Sim {
         spec: TripSpec,
         map: &Map,
     ) -> (Option<PedestrianID>, Option<CarID>) {
+        let (ped_id, car_id, _) = self.schedule_trip_for(start_time, spec, None, map);
+        (ped_id, car_id)
+    }
+
+    // The last value is false if the spawner skipped the trip.
+    fn schedule_trip_for(
+        &mut self,
+        start_time: Duration,
+        spec: TripSpec,
+        person: Option<PersonID>,
+        map: &Map,
+    ) -> (Option<PedestrianID>, Option<CarID>, bool) {
         let (ped_id, car_id) = match spec {
             TripSpec::CarAppearing {
                 ref vehicle_spec,
@@ -152,15 +220,59 @@ impl Sim {
             }
         };
 
-        self.spawner
-            .schedule_trip(start_time, ped_id, car_id, spec, map, &self.parking);
-        (ped_id, car_id)
+        let ok = self.spawner.schedule_trip(
+            start_time,
+            ped_id,
+            car_id,
+            spec,
+            person,
+            map,
+            &self.parking,
+        );
+        (ped_id, car_id, ok)
+    }
+
+    // The person's trips happen one after another, each starting once the previous is done. Their
+    // cars are seeded at the given spots, owned by home. Call spawn_all_trips afterwards.
+    pub fn add_person(
+        &mut self,
+        id: PersonID,
+        home: BuildingID,
+        car_spots: Vec<(VehicleSpec, ParkingSpot)>,
+        trips: Vec<(Duration, Purpose, TripSpec)>,
+        map: &Map,
+    ) {
+        let cars = car_spots
+            .into_iter()
+            .map(|(vehicle, spot)| self.seed_parked_car(vehicle, spot, Some(home)))
+            .collect();
+        self.people.add_person(id, cars, trips);
+        self.start_next_trip(id, map);
+    }
+
+    // Doesn't spawn anything; call spawn_all_trips afterwards.
+    fn start_next_trip(&mut self, id: PersonID, map: &Map) {
+        while let Some((depart, purpose, spec)) = self.people.next_trip(id, map, &self.parking) {
+            let start_time = if depart > self.time {
+                depart
+            } else {
+                self.time
+            };
+            if self.schedule_trip_for(start_time, spec, Some(id), map).2 {
+                self.people.trip_started(id, purpose);
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn record_mode_choice(&mut self, depart: Duration, mode: TripMode) {
+        self.analytics.mode_choices.push((depart, mode));
     }
 
     pub fn spawn_all_trips(&mut self, map: &Map, timer: &mut Timer, retry_if_no_room: bool) {
         self.spawner.spawn_all(
             map,
-            &self.parking,
+            &mut self.parking,
             &mut self.trips,
             &mut self.scheduler,
             timer,
@@ -206,84 +318,146 @@ impl Sim {
         self.parking.get_offstreet_parked_cars(bldg)
     }
 
+    // If the route has a timetable, schedules a bus for every run and returns them in order of
+    // departure. Otherwise, just spawns one bus immediately somewhere along the route.
     pub fn seed_bus_route(&mut self, route: &BusRoute, map: &Map, timer: &mut Timer) -> Vec<CarID> {
-        let mut results: Vec<CarID> = Vec::new();
+        let stops = self.transit.create_empty_route(route, map);
+
+        if !route.schedule.is_empty() {
+            let mut results = Vec::new();
+            for (idx, run) in route.schedule.iter().enumerate() {
+                let first_stop = match route.run_span(run) {
+                    Some((first_stop, _)) => first_stop,
+                    None => {
+                        timer.warn(format!(
+                            "Run {} of {} uses stops not on the route, skipping it",
+                            idx, route.name
+                        ));
+                        continue;
+                    }
+                };
+                let id = CarID(self.car_id_counter, VehicleType::Bus);
+                self.car_id_counter += 1;
+                // Appear early enough to reach the first stop in time for the departure
+                let start =
+                    run.stop_times[0].1 - self.transit.approach_time(route.id, first_stop, map);
+                self.scheduler
+                    .push(start.max(self.time), Command::StartBus(id, route.id, idx));
+                results.push(id);
+            }
+            return results;
+        }
 
         // Try to spawn just ONE bus anywhere.
-        // TODO Be more realistic. One bus per stop is too much, one is too little.
-        for (next_stop_idx, mut path, end_dist) in
-            self.transit.create_empty_route(route, map).into_iter()
-        {
+        for (next_stop_idx, path, end_dist) in stops {
             let id = CarID(self.car_id_counter, VehicleType::Bus);
             self.car_id_counter += 1;
-
-            // For now, no desire for randomness. Caller can pass in list of specs if that ever
-            // changes.
-            let vehicle = VehicleSpec {
-                vehicle_type: VehicleType::Bus,
-                length: BUS_LENGTH,
-                max_speed: None,
+            if self.start_bus(id, route, next_stop_idx, None, path, end_dist, map) {
+                return vec![id];
             }
-            .make(id, None);
+            timer.warn(format!(
+                "Giving up on seeding a bus headed towards stop {} of {} ({})",
+                next_stop_idx, route.name, route.id
+            ));
+        }
+        // TODO Bigger failure
+        timer.warn(format!("Failed to make ANY buses for {}!", route.name));
+        Vec::new()
+    }
+
+    // If the line has a timetable, schedules a train for every run and returns them in order of
+    // departure. Otherwise, just starts one train at the first station that loops forever.
+    pub fn seed_rail_line(&mut self, line: &RailLine) -> Vec<TrainID> {
+        let mut starts: Vec<(Duration, Option<usize>)> = line
+            .schedule
+            .iter()
+            .enumerate()
+            .map(|(idx, run)| (run.departure.max(self.time), Some(idx)))
+            .collect();
+        if starts.is_empty() {
+            starts.push((self.time, None));
+        }
 
-            // TODO The path analytics (total dist, dist crossed so far) will be wrong for the
-            // first round of buses.
-            // Same for this TripStart, though it doesn't matter too much.
-            let trip = self.trips.new_trip(
-                self.time,
-                TripStart::Border(map.get_l(path.current_step().as_lane()).src_i),
-                vec![TripLeg::ServeBusRoute(id, route.id)],
-            );
+        let mut results = Vec::new();
+        for (time, run) in starts {
+            let id = TrainID(self.train_id_counter);
+            self.train_id_counter += 1;
+            self.scheduler
+                .push(time, Command::StartTrain(id, line.id, run));
+            results.push(id);
+        }
+        results
+    }
 
-            loop {
-                if path.is_last_step() {
-                    timer.warn(format!(
-                        "Giving up on seeding a bus headed towards stop {} of {} ({})",
-                        next_stop_idx, route.name, route.id
-                    ));
-                    self.trips.abort_trip_failed_start(trip);
-                    break;
-                }
-                let start_lane = if let PathStep::Lane(l) = path.current_step() {
-                    l
-                } else {
-                    path.shift(map);
-                    continue;
-                };
-                if map.get_l(start_lane).length() < vehicle.length {
-                    path.shift(map);
-                    continue;
-                }
+    // Returns false if there's nowhere along the path to start the bus.
+    fn start_bus(
+        &mut self,
+        id: CarID,
+        route: &BusRoute,
+        next_stop_idx: usize,
+        // (index into the route's schedule, number of stops to visit)
+        run: Option<(usize, usize)>,
+        mut path: Path,
+        end_dist: Distance,
+        map: &Map,
+    ) -> bool {
+        // For now, no desire for randomness. Caller can pass in list of specs if that ever
+        // changes.
+        let vehicle = VehicleSpec {
+            vehicle_type: VehicleType::Bus,
+            length: BUS_LENGTH,
+            max_speed: None,
+        }
+        .make(id, None);
+
+        // TODO The path analytics (total dist, dist crossed so far) will be wrong for the
+        // first round of buses.
+        // Same for this TripStart, though it doesn't matter too much.
+        let trip = self.trips.new_trip(
+            self.time,
+            TripStart::Border(map.get_l(path.current_step().as_lane()).src_i),
+            vec![TripLeg::ServeBusRoute(id, route.id)],
+            None,
+        );
 
-                // Bypass some layers of abstraction that don't make sense for buses.
-                if self.driving.start_car_on_lane(
-                    self.time,
-                    CreateCar {
-                        start_dist: vehicle.length,
-                        vehicle: vehicle.clone(),
-                        router: Router::follow_bus_route(path.clone(), end_dist),
-                        maybe_parked_car: None,
-                        trip,
-                    },
-                    map,
-                    &self.intersections,
-                    &self.parking,
-                    &mut self.scheduler,
-                ) {
-                    self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
-                    self.transit.bus_created(id, route.id, next_stop_idx);
-                    results.push(id);
-                    return results;
-                } else {
-                    path.shift(map);
-                }
+        loop {
+            if path.is_last_step() {
+                self.trips.abort_trip_failed_start(trip);
+                return false;
+            }
+            let start_lane = if let PathStep::Lane(l) = path.current_step() {
+                l
+            } else {
+                path.shift(map);
+                continue;
+            };
+            if map.get_l(start_lane).length() < vehicle.length {
+                path.shift(map);
+                continue;
+            }
+
+            // Bypass some layers of abstraction that don't make sense for buses.
+            if self.driving.start_car_on_lane(
+                self.time,
+                CreateCar {
+                    start_dist: vehicle.length,
+                    vehicle: vehicle.clone(),
+                    router: Router::follow_bus_route(path.clone(), end_dist),
+                    maybe_parked_car: None,
+                    trip,
+                },
+                map,
+                &self.intersections,
+                &self.parking,
+                &mut self.scheduler,
+            ) {
+                self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
+                self.transit.bus_created(id, route.id, next_stop_idx, run);
+                return true;
+            } else {
+                path.shift(map);
             }
         }
-        if results.is_empty() {
-            // TODO Bigger failure
-            timer.warn(format!("Failed to make ANY buses for {}!", route.name));
-        }
-        results
     }
 
     pub fn set_name(&mut self, name: String) {
@@ -347,6 +521,10 @@ impl GetDrawAgents for Sim {
         result.extend(self.walking.get_unzoomed_agents(self.time, map));
         result
     }
+
+    fn get_all_draw_trains(&self, map: &Map) -> Vec<DrawTrainInput> {
+        self.rail.get_all_draw_trains(self.time, map)
+    }
 }
 
 // Running
@@ -372,8 +550,14 @@ impl Sim {
 
             self.time = time;
             match cmd {
-                Command::SpawnCar(create_car, retry_if_no_room) => {
-                    if self.driving.start_car_on_lane(
+                Command::SpawnCar(mut create_car, retry_if_no_room) => {
+                    if self.edited_live && !self.spawn_car_still_valid(&mut create_car, map) {
+                        println!(
+                            "WARNING: At {}, {} can't start after map edits",
+                            self.time, create_car.trip
+                        );
+                        self.trips.abort_trip_failed_start(create_car.trip);
+                    } else if self.driving.start_car_on_lane(
                         self.time,
                         create_car.clone(),
                         map,
@@ -402,7 +586,15 @@ impl Sim {
                     }
                 }
                 Command::SpawnPed(mut create_ped) => {
-                    let ok = if let SidewalkPOI::DeferredParkingSpot(b, driving_goal) =
+                    let still_valid =
+                        !self.edited_live || self.spawn_ped_still_valid(&mut create_ped, map);
+                    let ok = if !still_valid {
+                        println!(
+                            "WARNING: At {}, {} can't start after map edits",
+                            self.time, create_ped.id
+                        );
+                        false
+                    } else if let SidewalkPOI::DeferredParkingSpot(b, driving_goal) =
                         create_ped.goal.connection.clone()
                     {
                         if let Some(parked_car) = self.parking.dynamically_reserve_car(b) {
@@ -491,6 +683,48 @@ impl Sim {
                         self.trips.abort_trip_failed_start(create_ped.trip);
                     }
                 }
+                Command::StartBus(id, route, run) => {
+                    let route = map.get_br(route);
+                    // seed_bus_route skips runs with stops that aren't on the route
+                    let (first_stop, num_stops) = route.run_span(&route.schedule[run]).unwrap();
+                    // Appear somewhere before the first stop, so the bus actually serves it.
+                    let (path, end_dist) = self.transit.path_to_stop(route.id, first_stop);
+                    if !self.start_bus(
+                        id,
+                        route,
+                        first_stop,
+                        Some((run, num_stops)),
+                        path,
+                        end_dist,
+                        map,
+                    ) {
+                        println!(
+                            "WARNING: At {}, no room to start {} on {}. Skipping that run.",
+                            self.time, id, route.name
+                        );
+                    }
+                }
+                Command::StartTrain(id, line, run) => {
+                    let line = map.get_rl(line);
+                    let (first_station, num_stations) = match run {
+                        Some(idx) => {
+                            let run = &line.schedule[idx];
+                            (run.first_station, Some(run.num_stations))
+                        }
+                        None => (0, None),
+                    };
+                    self.rail.start_train(
+                        self.time,
+                        id,
+                        line.id,
+                        first_station,
+                        num_stations,
+                        &mut self.trips,
+                        &mut self.walking,
+                        &mut self.scheduler,
+                        map,
+                    );
+                }
                 Command::UpdateCar(car) => {
                     self.driving.update_car(
                         car,
@@ -523,6 +757,17 @@ impl Sim {
                         &mut self.scheduler,
                         &mut self.trips,
                         &mut self.transit,
+                        &mut self.rail,
+                    );
+                }
+                Command::UpdateTrain(train) => {
+                    self.rail.update_train(
+                        self.time,
+                        train,
+                        &mut self.trips,
+                        &mut self.walking,
+                        &mut self.scheduler,
+                        map,
                     );
                 }
                 Command::UpdateIntersection(i) => {
@@ -535,17 +780,79 @@ impl Sim {
                     assert_eq!(savestate_at, None);
                     savestate_at = Some(self.time);
                 }
+                Command::RecordTrajectories(interval) => {
+                    self.scheduler
+                        .push(self.time + interval, Command::RecordTrajectories(interval));
+                    let mut samples = Vec::new();
+                    for agent in self.trips.active_agents() {
+                        if let (Some(trip), Some(pt)) = (
+                            self.trips.agent_to_trip(agent),
+                            self.canonical_pt_for_agent(agent, map),
+                        ) {
+                            samples.push((trip, agent, pt));
+                        }
+                    }
+                    self.trajectories
+                        .as_mut()
+                        .unwrap()
+                        .record(self.time, samples);
+                }
+                // The map can't change here; step_with_timed_edits does that right after.
+                Command::UpdateTimedEdits => {
+                    let edits = map.get_edits();
+                    if !self.stepping_with_timed_edits
+                        && edits.timed_commands_active_at(self.time) != edits.active_timed_commands
+                    {
+                        panic!(
+                            "Timed map edits change at {}, but step can't change the map. Use \
+                             step_with_timed_edits.",
+                            self.time
+                        );
+                    }
+                    if let Some(t) = next_timed_edit_update(edits, self.time) {
+                        self.scheduler.push(t, Command::UpdateTimedEdits);
+                    }
+                }
             }
 
             // Record events at precisely the time they occur.
             let mut events = Vec::new();
             events.extend(self.trips.collect_events());
             events.extend(self.transit.collect_events());
+            events.extend(self.rail.collect_events());
             events.extend(self.driving.collect_events());
             events.extend(self.walking.collect_events());
+            let mut people_ready = Vec::new();
             for ev in events {
+                match ev {
+                    Event::TripFinished(trip, _, _) | Event::TripAborted(trip) => {
+                        if let Some(person) = self.trips.trip_to_person(trip) {
+                            self.people.trip_done(person);
+                            people_ready.push(person);
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(ref mut log) = self.event_log {
+                    log.record(self.time, &ev);
+                }
                 self.analytics.event(ev, self.time, map);
             }
+            if !people_ready.is_empty() {
+                for person in people_ready {
+                    self.start_next_trip(person, map);
+                }
+                self.spawner.spawn_during_sim(
+                    self.time,
+                    map,
+                    &mut self.parking,
+                    &mut self.trips,
+                    &mut self.scheduler,
+                );
+            }
+        }
+        if let Some(ref mut log) = self.event_log {
+            log.flush();
         }
         if let Some(t) = savestate_at {
             self.time = t;
@@ -556,6 +863,54 @@ impl Sim {
         self.trip_positions = None;
     }
 
+    // Like step, but also switches timed map edits on and off exactly when they're scheduled to.
+    // Pathfinding is recalculated each time, and new driving paths avoid anything that'll be taken
+    // away soon. Plain step refuses to run past a timed edit starting or ending.
+    pub fn step_with_timed_edits(&mut self, map: &mut Map, dt: Duration, timer: &mut Timer) {
+        let target_time = self.time + dt;
+        self.stepping_with_timed_edits = true;
+        while let Some(t) = self
+            .scheduler
+            .get_time(&Command::UpdateTimedEdits)
+            .filter(|t| *t <= target_time)
+        {
+            self.step(map, t - self.time);
+
+            let mut edits = map.get_edits().clone();
+            let active = edits.timed_commands_active_at(self.time);
+            if active != edits.active_timed_commands {
+                edits.active_timed_commands = active;
+                let effects = map.apply_edits(edits, timer);
+                map.recalculate_pathfinding_after_edits(timer);
+                self.handle_live_edits(map, &effects, timer);
+            }
+
+            // Closures add a penalty on top of whatever travel times are already there, like
+            // experienced ones from traffic assignment.
+            let closing = upcoming_timed_closures(map, self.time);
+            if closing.is_empty() {
+                if let Some(before) = self.travel_times_before_timed_edits.take() {
+                    map.set_driving_travel_times(before, timer);
+                }
+            } else {
+                let before = self
+                    .travel_times_before_timed_edits
+                    .get_or_insert_with(|| map.get_driving_travel_times().clone());
+                let mut times = before.clone();
+                for t in closing {
+                    let base = before
+                        .get(&t)
+                        .cloned()
+                        .unwrap_or_else(|| t.length(map) / t.speed_limit(map));
+                    times.insert(t, base + TIMED_EDIT_PENALTY);
+                }
+                map.set_driving_travel_times(times, timer);
+            }
+        }
+        self.step(map, target_time - self.time);
+        self.stepping_with_timed_edits = false;
+    }
+
     pub fn timed_step(&mut self, map: &Map, dt: Duration, timer: &mut Timer) {
         // TODO Ideally print every second or so
         let orig_time = self.time;
@@ -589,6 +944,132 @@ impl Sim {
         }
     }
 
+    // Call after Map::apply_edits and Map::recalculate_pathfinding_after_edits. Agents that can
+    // still reach their goal are rerouted; the rest vanish and their trips are aborted. A bus that
+    // can't continue its route vanishes with its passengers. Returns the number of cars rerouted
+    // and trips aborted.
+    pub fn handle_live_edits(
+        &mut self,
+        map: &Map,
+        effects: &EditEffects,
+        timer: &mut Timer,
+    ) -> (usize, usize) {
+        self.intersections.handle_live_edits(
+            self.time,
+            &effects.changed_intersections,
+            map,
+            &mut self.scheduler,
+        );
+        let vanished_spots = self.parking.handle_live_edits(map, timer);
+
+        let killed_peds = self.walking.handle_live_edits(
+            self.time,
+            &vanished_spots,
+            map,
+            &mut self.intersections,
+            &mut self.scheduler,
+        );
+        for (id, goal) in &killed_peds {
+            self.trips.abort_trip(AgentID::Pedestrian(*id));
+            if let SidewalkPOI::ParkingSpot(spot) = goal.connection {
+                if let Some(p) = self.parking.get_car_at_spot(spot).cloned() {
+                    self.parking.dynamically_return_car(p);
+                }
+            }
+        }
+
+        let (killed_cars, rerouted) = self.driving.handle_live_edits(
+            self.time,
+            map,
+            &self.parking,
+            &mut self.intersections,
+            &mut self.scheduler,
+        );
+        let mut aborted = killed_peds.len();
+        for (id, spot) in &killed_cars {
+            if id.1 == VehicleType::Bus {
+                // Nobody's left to drive the passengers anywhere
+                for ped in self.transit.bus_removed(*id) {
+                    self.trips.abort_trip(AgentID::Pedestrian(ped));
+                    aborted += 1;
+                }
+                self.trips.bus_out_of_service(self.time, *id);
+                continue;
+            }
+            self.trips.abort_trip(AgentID::Car(*id));
+            aborted += 1;
+            if let Some(spot) = spot {
+                self.parking.unreserve_spot(*spot);
+            }
+        }
+
+        self.transit.handle_live_edits(map);
+        self.edited_live = true;
+        self.trip_positions = None;
+        timer.note(format!(
+            "After live edits, {} cars rerouted, {} vehicles and {} pedestrians vanished, {} \
+             trips aborted",
+            rerouted,
+            killed_cars.len(),
+            killed_peds.len(),
+            aborted
+        ));
+        (rerouted, aborted)
+    }
+
+    fn spawn_car_still_valid(&self, create_car: &mut CreateCar, map: &Map) -> bool {
+        let start = create_car.router.head().as_lane();
+        if !create_car
+            .vehicle
+            .vehicle_type
+            .to_constraints()
+            .can_use(map.get_l(start), map)
+        {
+            return false;
+        }
+        if let Some(ref p) = create_car.maybe_parked_car {
+            // Maybe the car was moved off a parking lane that vanished
+            if self.parking.lookup_parked_car(p.vehicle.id).map(|p| p.spot) != Some(p.spot) {
+                return false;
+            }
+        }
+        create_car
+            .router
+            .reroute_after_edits(&create_car.vehicle, map)
+    }
+
+    fn spawn_ped_still_valid(&self, create_ped: &mut CreatePedestrian, map: &Map) -> bool {
+        match create_ped.goal.connection {
+            SidewalkPOI::ParkingSpot(spot) => {
+                if self.parking.get_car_at_spot(spot).is_none() {
+                    return false;
+                }
+            }
+            // The path is calculated when they spawn
+            SidewalkPOI::DeferredParkingSpot(_, _) => {
+                return true;
+            }
+            _ => {}
+        }
+        if create_ped
+            .path
+            .first_broken_step(PathConstraints::Pedestrian, map)
+            .is_none()
+        {
+            return true;
+        }
+        if let Some(path) = map.pathfind(PathRequest {
+            start: create_ped.start.sidewalk_pos,
+            end: create_ped.goal.sidewalk_pos,
+            constraints: PathConstraints::Pedestrian,
+        }) {
+            create_ped.path = path;
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn dump_before_abort(&self) {
         println!(
             "********************************************************************************"
@@ -737,7 +1218,7 @@ impl Sim {
     }
 
     pub fn is_done(&self) -> bool {
-        self.spawner.is_done() && self.trips.is_done()
+        self.spawner.is_done() && self.trips.is_done() && self.people.is_done()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -792,7 +1273,13 @@ impl Sim {
             lines.extend(self.trips.tooltip_lines(AgentID::Car(car)));
             if car.1 == VehicleType::Bus {
                 let passengers = self.transit.get_passengers(car);
-                lines.push(format!("{} passengers riding", passengers.len()));
+                let (seated, standing) = self.transit.get_bus_load(car);
+                lines.push(format!(
+                    "{} passengers riding ({} seated, {} standing)",
+                    passengers.len(),
+                    seated,
+                    standing
+                ));
                 for (id, stop) in passengers {
                     lines.push(format!("- {} till {:?}", id, stop));
                 }
@@ -834,6 +1321,15 @@ impl Sim {
         self.trips.trip_to_agent(id)
     }
 
+    pub fn trip_to_person(&self, id: TripID) -> Option<PersonID> {
+        self.trips.trip_to_person(id)
+    }
+
+    // Why the person is taking their current trip, if they're on one
+    pub fn current_purpose(&self, id: PersonID) -> Option<Purpose> {
+        self.people.current_purpose(id)
+    }
+
     pub fn trip_status(&self, id: TripID) -> TripStatus {
         self.trips.trip_status(id)
     }
@@ -914,7 +1410,16 @@ impl Sim {
                 .parking
                 .canonical_pt(id, map)
                 .or_else(|| Some(self.get_draw_car(id, map)?.body.last_pt())),
-            AgentID::Pedestrian(id) => Some(self.get_draw_ped(id, map)?.pos),
+            AgentID::Pedestrian(id) => {
+                if let Some(ped) = self.get_draw_ped(id, map) {
+                    return Some(ped.pos);
+                }
+                // Riders aren't on the map themselves, so use their vehicle.
+                if let Some(bus) = self.transit.bus_for_passenger(id) {
+                    return self.canonical_pt_for_agent(AgentID::Car(bus), map);
+                }
+                self.rail.passenger_pos(id, self.time, map)
+            }
         }
     }
 
@@ -922,10 +1427,27 @@ impl Sim {
         self.intersections.get_accepted_agents(id)
     }
 
+    // Actuated signals depend on demand, so ask the sim instead of the map.
+    pub fn current_signal_phase<'a>(
+        &self,
+        id: IntersectionID,
+        map: &'a Map,
+    ) -> (usize, &'a Phase, Duration) {
+        self.intersections.current_signal_phase(id, self.time, map)
+    }
+
+    pub fn current_signal_plan(&self, id: IntersectionID, map: &Map) -> Option<usize> {
+        self.intersections.current_signal_plan(id, self.time, map)
+    }
+
     pub fn get_intersection_delays(&self, id: IntersectionID) -> &DurationHistogram {
         self.intersections.get_intersection_delays(id)
     }
 
+    pub fn get_total_intersection_delay(&self, id: IntersectionID) -> Duration {
+        self.intersections.get_total_delay(id, self.time)
+    }
+
     pub fn location_of_buses(&self, route: BusRouteID, map: &Map) -> Vec<(CarID, Pt2D)> {
         let mut results = Vec::new();
         for car in self.transit.buses_for_route(route) {
@@ -938,6 +1460,15 @@ impl Sim {
         results
     }
 
+    pub fn location_of_trains(&self, line: RailLineID, map: &Map) -> Vec<(TrainID, Pt2D)> {
+        self.rail.location_of_trains(line, self.time, map)
+    }
+
+    // Only if SimOptions::record_trajectories was set
+    pub fn get_trajectories(&self) -> Option<&TrajectoryRecorder> {
+        self.trajectories.as_ref()
+    }
+
     pub fn get_analytics(&self) -> &Analytics {
         &self.analytics
     }
@@ -976,3 +1507,43 @@ impl Sim {
         }
     }
 }
+
+// When timed edits start or end, and when driving paths should start avoiding them
+fn next_timed_edit_update(edits: &MapEdits, time: Duration) -> Option<Duration> {
+    edits
+        .timed_commands
+        .iter()
+        .flat_map(|timed| vec![timed.start - TIMED_EDIT_LOOKAHEAD, timed.start, timed.end])
+        .filter(|t| *t > time)
+        .min()
+}
+
+// Lanes and turns that inactive timed edits will soon take away from cars. New paths avoid them
+// with a penalty; cars already using them reroute when the edit starts.
+fn upcoming_timed_closures(map: &Map, now: Duration) -> BTreeSet<Traversable> {
+    let edits = map.get_edits();
+    let mut closing = Vec::new();
+    for (idx, timed) in edits.timed_commands.iter().enumerate() {
+        if edits.active_timed_commands.contains(&idx)
+            || timed.start <= now
+            || timed.start > now + TIMED_EDIT_LOOKAHEAD
+        {
+            continue;
+        }
+        match &timed.cmd {
+            EditCmd::ChangeLaneType { id, lt, .. } if *lt != LaneType::Driving => {
+                closing.push(Traversable::Lane(*id));
+            }
+            EditCmd::ReverseLane { l, .. } => {
+                closing.push(Traversable::Lane(*l));
+            }
+            EditCmd::CloseIntersection { id, .. } => {
+                for t in &map.get_i(*id).turns {
+                    closing.push(Traversable::Turn(*t));
+                }
+            }
+            _ => {}
+        }
+    }
+    closing.into_iter().collect()
+}
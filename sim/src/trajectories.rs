@@ -0,0 +1,134 @@
+use crate::{AgentID, TripID};
+use geom::{Duration, GPSBounds, Pt2D};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Error, Write};
+
+// Periodically samples where every agent is, so movement can be replayed or compared against GPS
+// traces after the sim is done.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct TrajectoryRecorder {
+    interval: Duration,
+    trajectories: BTreeMap<TripID, Trajectory>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct Trajectory {
+    pub trip: TripID,
+    // In order of time. The agent changes when the trip switches modes.
+    pub samples: Vec<TrajectorySample>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TrajectorySample {
+    pub time: Duration,
+    pub agent: AgentID,
+    pub pt: Pt2D,
+}
+
+impl TrajectoryRecorder {
+    pub fn new(interval: Duration) -> TrajectoryRecorder {
+        assert!(interval > Duration::ZERO);
+        TrajectoryRecorder {
+            interval,
+            trajectories: BTreeMap::new(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub(crate) fn record(&mut self, time: Duration, samples: Vec<(TripID, AgentID, Pt2D)>) {
+        for (trip, agent, pt) in samples {
+            self.trajectories
+                .entry(trip)
+                .or_insert_with(|| Trajectory {
+                    trip,
+                    samples: Vec::new(),
+                })
+                .samples
+                .push(TrajectorySample { time, agent, pt });
+        }
+    }
+
+    pub fn get(&self, trip: TripID) -> Option<&Trajectory> {
+        self.trajectories.get(&trip)
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &Trajectory> {
+        self.trajectories.values()
+    }
+
+    // One row per sample: trip, agent, seconds since midnight, longitude, latitude
+    pub fn export_csv(&self, path: &str, gps_bounds: &GPSBounds) -> Result<(), Error> {
+        let mut f = BufWriter::new(File::create(path)?);
+        writeln!(f, "trip,agent,time,longitude,latitude")?;
+        for t in self.trajectories.values() {
+            for s in &t.samples {
+                let gps = s.pt.forcibly_to_gps(gps_bounds);
+                writeln!(
+                    f,
+                    "{},{},{},{},{}",
+                    t.trip.0,
+                    agent_name(s.agent),
+                    s.time.inner_seconds(),
+                    gps.longitude,
+                    gps.latitude
+                )?;
+            }
+        }
+        f.flush()
+    }
+
+    // A FeatureCollection with one LineString per trip. The "times" property lines up with the
+    // coordinates, which is what most animated trip layers expect.
+    pub fn export_geojson(&self, path: &str, gps_bounds: &GPSBounds) -> Result<(), Error> {
+        let mut features = Vec::new();
+        for t in self.trajectories.values() {
+            // A LineString needs at least two points
+            if t.samples.len() < 2 {
+                continue;
+            }
+            let coordinates: Vec<Vec<f64>> = t
+                .samples
+                .iter()
+                .map(|s| {
+                    let gps = s.pt.forcibly_to_gps(gps_bounds);
+                    vec![gps.longitude, gps.latitude]
+                })
+                .collect();
+            let times: Vec<f64> = t.samples.iter().map(|s| s.time.inner_seconds()).collect();
+            let agents: Vec<String> = t.samples.iter().map(|s| agent_name(s.agent)).collect();
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": coordinates,
+                },
+                "properties": {
+                    "trip": t.trip.0,
+                    "times": times,
+                    "agents": agents,
+                },
+            }));
+        }
+        let collection = serde_json::json!({
+            "type": "FeatureCollection",
+            "features": features,
+        });
+
+        let mut f = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut f, &collection)?;
+        f.flush()
+    }
+}
+
+fn agent_name(id: AgentID) -> String {
+    match id {
+        // Distinguish buses, bikes, etc
+        AgentID::Car(c) => format!("{:?}{}", c.1, c.0).to_lowercase(),
+        AgentID::Pedestrian(p) => format!("ped{}", p.0),
+    }
+}
@@ -1,8 +1,11 @@
-use crate::{CarID, Event, PedestrianID, Router, Scheduler, TripManager, WalkingSimState};
+use crate::{
+    CarID, Event, PedestrianID, Router, Scheduler, TripManager, WalkingSimState, BUS_LENGTH,
+};
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration};
 use map_model::{
-    BusRoute, BusRouteID, BusStopID, Map, Path, PathConstraints, PathRequest, Position,
+    BusCapacity, BusRoute, BusRouteID, BusStopID, Map, Path, PathConstraints, PathRequest,
+    PathStep, Position,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -10,6 +13,13 @@ use std::collections::BTreeMap;
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
 
+// Buses always stop at least this long, to open and close the doors.
+const MIN_DWELL_TIME: Duration = Duration::const_seconds(10.0);
+const TIME_PER_ALIGHTING: Duration = Duration::const_seconds(1.5);
+const TIME_PER_BOARDING: Duration = Duration::const_seconds(2.5);
+// Squeezing past standing passengers is slower.
+const TIME_PER_CROWDED_BOARDING: Duration = Duration::const_seconds(4.0);
+
 #[derive(Serialize, Deserialize, PartialEq)]
 struct StopForRoute {
     id: BusStopID,
@@ -22,6 +32,7 @@ struct StopForRoute {
 struct Route {
     stops: Vec<StopForRoute>,
     buses: Vec<CarID>,
+    capacity: BusCapacity,
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -31,6 +42,12 @@ struct Bus {
     // Where does each passenger want to deboard?
     passengers: Vec<(PedestrianID, BusStopID)>,
     state: BusState,
+    // How much longer to stay at the current stop, for passengers who boarded after the bus
+    // arrived
+    extra_dwell: Duration,
+    // For buses following the schedule, how many more stops to visit (including the one it's
+    // driving to or stopped at) before going out of service.
+    stops_left: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq)]
@@ -79,6 +96,7 @@ impl TransitSimState {
 
         let route = Route {
             buses: Vec::new(),
+            capacity: bus_route.capacity,
             stops: bus_route
                 .stops
                 .iter()
@@ -125,8 +143,105 @@ impl TransitSimState {
         stops
     }
 
-    pub fn bus_created(&mut self, bus: CarID, route: BusRouteID, next_stop_idx: StopIdx) {
+    // After live map edits, route between stops again where the old path is broken. Buses already
+    // driving are handled separately.
+    pub fn handle_live_edits(&mut self, map: &Map) {
+        for (id, route) in self.routes.iter_mut() {
+            for idx in 0..route.stops.len() {
+                if route.stops[idx]
+                    .path_to_next_stop
+                    .first_broken_step(PathConstraints::Bus, map)
+                    .is_none()
+                {
+                    continue;
+                }
+                let next = &route.stops[route.stops[idx].next_stop_idx];
+                let (next_id, end) = (next.id, next.driving_pos);
+                let stop = &mut route.stops[idx];
+                if let Some(path) = map.pathfind(PathRequest {
+                    start: stop.driving_pos,
+                    end,
+                    constraints: PathConstraints::Bus,
+                }) {
+                    stop.path_to_next_stop = path;
+                } else {
+                    println!(
+                        "WARNING: after map edits, {} has no route between stops {:?} and {:?}",
+                        id, stop.id, next_id
+                    );
+                }
+            }
+        }
+    }
+
+    // The bus vanished after live map edits. Returns its passengers, who vanish with it.
+    pub fn bus_removed(&mut self, id: CarID) -> Vec<PedestrianID> {
+        let bus = self.buses.remove(&id).unwrap();
+        self.routes
+            .get_mut(&bus.route)
+            .unwrap()
+            .buses
+            .retain(|b| *b != id);
+        bus.passengers.into_iter().map(|(ped, _)| ped).collect()
+    }
+
+    // The path from the stop before this one, and the end distance for this stop.
+    pub fn path_to_stop(&self, route: BusRouteID, stop_idx: StopIdx) -> (Path, Distance) {
+        let stops = &self.routes[&route].stops;
+        let prev_idx = if stop_idx == 0 {
+            stops.len() - 1
+        } else {
+            stop_idx - 1
+        };
+        (
+            stops[prev_idx].path_to_next_stop.clone(),
+            stops[stop_idx].driving_pos.dist_along(),
+        )
+    }
+
+    // Roughly how long a bus starting a run takes to reach the first stop from wherever it appears,
+    // ignoring traffic.
+    pub fn approach_time(&self, route: BusRouteID, stop_idx: StopIdx, map: &Map) -> Duration {
+        let (path, end_dist) = self.path_to_stop(route, stop_idx);
+        let steps = path.get_steps();
+        let mut total = Duration::ZERO;
+        let mut started = false;
+        for (idx, step) in steps.iter().enumerate() {
+            let on = step.as_traversable();
+            let mut dist = if idx == steps.len() - 1 {
+                end_dist
+            } else {
+                on.length(map)
+            };
+            // Match where start_bus puts the bus: the front of the first lane that fits it
+            if !started {
+                match step {
+                    PathStep::Lane(l) if map.get_l(*l).length() >= BUS_LENGTH => {
+                        started = true;
+                        dist = dist - BUS_LENGTH;
+                    }
+                    _ => {
+                        continue;
+                    }
+                }
+            }
+            total += dist.max(Distance::ZERO) / on.speed_limit(map);
+        }
+        total
+    }
+
+    pub fn bus_created(
+        &mut self,
+        bus: CarID,
+        route: BusRouteID,
+        next_stop_idx: StopIdx,
+        // (index into the route's schedule, number of stops to visit)
+        run: Option<(usize, usize)>,
+    ) {
         self.routes.get_mut(&route).unwrap().buses.push(bus);
+        if let Some((idx, _)) = run {
+            self.events.push(Event::BusStartedRun(bus, route, idx));
+        }
         self.buses.insert(
             bus,
             Bus {
@@ -134,10 +249,14 @@ impl TransitSimState {
                 route,
                 passengers: Vec::new(),
                 state: BusState::DrivingToStop(next_stop_idx),
+                extra_dwell: Duration::ZERO,
+                stops_left: run.map(|(_, n)| n),
             },
         );
     }
 
+    // Returns how long the bus needs to wait at the stop for passengers to get off and on, or None
+    // if the bus is out of service and should vanish.
     pub fn bus_arrived_at_stop(
         &mut self,
         now: Duration,
@@ -146,42 +265,82 @@ impl TransitSimState {
         walking: &mut WalkingSimState,
         scheduler: &mut Scheduler,
         map: &Map,
-    ) {
+    ) -> Option<Duration> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
                 bus.state = BusState::AtStop(stop_idx);
-                let stop = self.routes[&bus.route].stops[stop_idx].id;
+                if let Some(ref mut n) = bus.stops_left {
+                    *n -= 1;
+                }
+                let route = &self.routes[&bus.route];
+                let stop = route.stops[stop_idx].id;
                 self.events
                     .push(Event::BusArrivedAtStop(id, bus.route, stop));
 
                 // Deboard existing passengers.
                 let mut still_riding = Vec::new();
+                let mut dwell = MIN_DWELL_TIME;
                 for (ped, stop2) in bus.passengers.drain(..) {
                     if stop == stop2 {
                         self.events.push(Event::PedLeavesBus(ped, id, bus.route));
-                        trips.ped_left_bus(now, ped, map, scheduler);
+                        trips.ped_left_transit(now, ped, map, scheduler);
+                        dwell += TIME_PER_ALIGHTING;
                     } else {
                         still_riding.push((ped, stop2));
                     }
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                if bus.stops_left == Some(0) {
+                    assert!(bus.passengers.is_empty());
+                    self.routes
+                        .get_mut(&bus.route)
+                        .unwrap()
+                        .buses
+                        .retain(|b| *b != id);
+                    self.buses.remove(&id);
+                    trips.bus_out_of_service(now, id);
+                    return None;
+                }
+
+                // Board new passengers, until the bus is full.
                 let mut still_waiting = Vec::new();
-                for (ped, stop1, route, stop2) in self.peds_waiting.drain(..) {
-                    if stop == stop1 && bus.route == route {
+                for (ped, stop1, route_id, stop2) in self.peds_waiting.drain(..) {
+                    if stop == stop1 && bus.route == route_id && will_reach(bus, route, stop2) {
+                        if bus.passengers.len() == route.capacity.total() {
+                            self.events
+                                .push(Event::PedDeniedBoarding(ped, id, route_id));
+                            still_waiting.push((ped, stop1, route_id, stop2));
+                            continue;
+                        }
+                        dwell += if bus.passengers.len() < route.capacity.seated {
+                            TIME_PER_BOARDING
+                        } else {
+                            TIME_PER_CROWDED_BOARDING
+                        };
                         bus.passengers.push((ped, stop2));
-                        self.events.push(Event::PedEntersBus(ped, id, route));
-                        trips.ped_boarded_bus(ped, walking);
+                        self.events.push(Event::PedEntersBus(ped, id, route_id));
+                        trips.ped_boarded_transit(ped, walking);
                     } else {
-                        still_waiting.push((ped, stop1, route, stop2));
+                        still_waiting.push((ped, stop1, route_id, stop2));
                     }
                 }
                 self.peds_waiting = still_waiting;
+                Some(dwell)
             }
             BusState::AtStop(_) => unreachable!(),
-        };
+        }
+    }
+
+    // When the bus is about to leave a stop, it first waits for anybody who boarded late. Returns
+    // how much longer to wait, if at all.
+    pub fn extra_dwell(&mut self, id: CarID) -> Option<Duration> {
+        let bus = self.buses.get_mut(&id).unwrap();
+        if bus.extra_dwell == Duration::ZERO {
+            return None;
+        }
+        Some(std::mem::replace(&mut bus.extra_dwell, Duration::ZERO))
     }
 
     pub fn bus_departed_from_stop(&mut self, id: CarID) -> Router {
@@ -215,12 +374,19 @@ impl TransitSimState {
         let route = &self.routes[&route_id];
         for bus in &route.buses {
             if let BusState::AtStop(idx) = self.buses[bus].state {
-                if route.stops[idx].id == stop1 {
-                    self.buses
-                        .get_mut(bus)
-                        .unwrap()
-                        .passengers
-                        .push((ped, stop2));
+                if route.stops[idx].id == stop1 && will_reach(&self.buses[bus], route, stop2) {
+                    if self.buses[bus].passengers.len() == route.capacity.total() {
+                        self.events
+                            .push(Event::PedDeniedBoarding(ped, *bus, route_id));
+                        continue;
+                    }
+                    let b = self.buses.get_mut(bus).unwrap();
+                    b.extra_dwell += if b.passengers.len() < route.capacity.seated {
+                        TIME_PER_BOARDING
+                    } else {
+                        TIME_PER_CROWDED_BOARDING
+                    };
+                    b.passengers.push((ped, stop2));
                     // TODO shift trips
                     self.events.push(Event::PedEntersBus(ped, *bus, route_id));
                     return true;
@@ -240,6 +406,25 @@ impl TransitSimState {
         &self.buses[&bus].passengers
     }
 
+    // (seated, standing) passengers
+    pub fn get_bus_load(&self, bus: CarID) -> (usize, usize) {
+        let seated = self.routes[&self.buses[&bus].route].capacity.seated;
+        let riding = self.buses[&bus].passengers.len();
+        if riding <= seated {
+            (riding, 0)
+        } else {
+            (seated, riding - seated)
+        }
+    }
+
+    // Which bus is this pedestrian riding?
+    pub fn bus_for_passenger(&self, ped: PedestrianID) -> Option<CarID> {
+        self.buses
+            .values()
+            .find(|bus| bus.passengers.iter().any(|(p, _)| *p == ped))
+            .map(|bus| bus.car)
+    }
+
     pub fn bus_route(&self, bus: CarID) -> BusRouteID {
         self.buses[&bus].route
     }
@@ -252,3 +437,15 @@ impl TransitSimState {
         }
     }
 }
+
+// Will the bus stop here before going out of service?
+fn will_reach(bus: &Bus, route: &Route, stop: BusStopID) -> bool {
+    let idx = match bus.state {
+        BusState::AtStop(idx) => idx,
+        BusState::DrivingToStop(_) => unreachable!(),
+    };
+    match bus.stops_left {
+        Some(n) => (1..=n).any(|i| route.stops[(idx + i) % route.stops.len()].id == stop),
+        None => true,
+    }
+}
@@ -1,12 +1,13 @@
 use crate::{
     AgentID, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event, ParkingSimState,
-    ParkingSpot, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
-    Vehicle, WalkingSimState,
+    ParkingSpot, PedestrianID, PersonID, RailSimState, Scheduler, SidewalkPOI, SidewalkSpot,
+    TransitSimState, TripID, Vehicle, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Duration, Speed};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
+    RailLineID, StationID,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, VecDeque};
@@ -42,6 +43,7 @@ impl TripManager {
         spawned_at: Duration,
         start: TripStart,
         legs: Vec<TripLeg>,
+        person: Option<PersonID>,
     ) -> TripID {
         assert!(!legs.is_empty());
         // TODO Make sure the legs constitute a valid state machine.
@@ -59,7 +61,7 @@ impl TripManager {
                 TripLeg::Drive(_, _) => {
                     mode = TripMode::Drive;
                 }
-                TripLeg::RideBus(_, _, _) => {
+                TripLeg::RideBus(_, _, _) | TripLeg::RideTrain(_, _, _) => {
                     mode = TripMode::Transit;
                 }
                 TripLeg::ServeBusRoute(_, _) => {
@@ -95,6 +97,7 @@ impl TripManager {
             legs: VecDeque::from(legs),
             start,
             end,
+            person,
         };
         if !trip.is_bus_trip() {
             self.unfinished_trips += 1;
@@ -355,33 +358,68 @@ impl TripManager {
         }
     }
 
-    pub fn ped_boarded_bus(&mut self, ped: PedestrianID, walking: &mut WalkingSimState) {
-        // TODO Make sure canonical pt is the bus while the ped is riding it
+    // If no line is returned, the pedestrian boarded a train immediately.
+    pub fn ped_reached_station(
+        &mut self,
+        ped: PedestrianID,
+        station: StationID,
+        map: &Map,
+        rail: &mut RailSimState,
+    ) -> Option<RailLineID> {
+        self.events.push(Event::PedReachedStation(ped, station));
+        let trip = &mut self.trips[self.active_trip_mode[&AgentID::Pedestrian(ped)].0];
+        match trip.legs[0] {
+            TripLeg::Walk(p, _, ref spot) => {
+                assert_eq!(p, ped);
+                assert_eq!(*spot, SidewalkSpot::station(station, map));
+            }
+            _ => unreachable!(),
+        }
+        match trip.legs[1] {
+            TripLeg::RideTrain(_, line, station2) => {
+                if rail.ped_waiting_for_train(ped, station, line, station2, map) {
+                    trip.legs.pop_front();
+                    None
+                } else {
+                    Some(line)
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Works for buses and trains
+    pub fn ped_boarded_transit(&mut self, ped: PedestrianID, walking: &mut WalkingSimState) {
         let trip = &mut self.trips[self.active_trip_mode[&AgentID::Pedestrian(ped)].0];
         trip.legs.pop_front();
-        walking.ped_boarded_bus(ped);
+        walking.ped_boarded_transit(ped);
     }
 
-    pub fn ped_left_bus(
+    // If a line and station are returned, the pedestrian stays on the platform to transfer to
+    // another train there, instead of walking away.
+    pub fn ped_left_transit(
         &mut self,
         now: Duration,
         ped: PedestrianID,
         map: &Map,
         scheduler: &mut Scheduler,
-    ) {
-        let trip = &mut self.trips[self
-            .active_trip_mode
-            .remove(&AgentID::Pedestrian(ped))
-            .unwrap()
-            .0];
+    ) -> Option<(RailLineID, StationID)> {
+        let trip_id = self.active_trip_mode[&AgentID::Pedestrian(ped)];
+        let trip = &mut self.trips[trip_id.0];
         let start = match trip.legs.pop_front().unwrap() {
             TripLeg::RideBus(_, _, stop) => SidewalkSpot::bus_stop(stop, map),
+            TripLeg::RideTrain(_, _, station) => SidewalkSpot::station(station, map),
             _ => unreachable!(),
         };
+        if let TripLeg::RideTrain(_, line, station2) = trip.legs[0] {
+            return Some((line, station2));
+        }
 
-        if !trip.spawn_ped(now, start, map, scheduler) {
+        self.active_trip_mode.remove(&AgentID::Pedestrian(ped));
+        if !self.trips[trip_id.0].spawn_ped(now, start, map, scheduler) {
             self.unfinished_trips -= 1;
         }
+        None
     }
 
     pub fn ped_reached_border(
@@ -427,6 +465,15 @@ impl TripManager {
         ));
     }
 
+    pub fn bus_out_of_service(&mut self, now: Duration, bus: CarID) {
+        let trip = &mut self.trips[self.active_trip_mode.remove(&AgentID::Car(bus)).unwrap().0];
+        // Leave the leg, so this is still recognized as a bus trip. Bus trips aren't counted as
+        // unfinished, so they don't need a TripFinished event either.
+        assert!(trip.is_bus_trip());
+        trip.finished_at = Some(now);
+        self.num_bus_trips -= 1;
+    }
+
     pub fn abort_trip_failed_start(&mut self, id: TripID) {
         self.trips[id.0].aborted = true;
         if !self.trips[id.0].is_bus_trip() {
@@ -436,7 +483,12 @@ impl TripManager {
     }
 
     pub fn abort_trip_impossible_parking(&mut self, car: CarID) {
-        let trip = self.active_trip_mode.remove(&AgentID::Car(car)).unwrap();
+        self.abort_trip(AgentID::Car(car));
+    }
+
+    // For an agent in the middle of their trip, who's already been removed from the map
+    pub fn abort_trip(&mut self, agent: AgentID) {
+        let trip = self.active_trip_mode.remove(&agent).unwrap();
         assert!(!self.trips[trip.0].is_bus_trip());
         self.trips[trip.0].aborted = true;
         self.unfinished_trips -= 1;
@@ -465,7 +517,9 @@ impl TripManager {
             TripLeg::Walk(id, _, _) => TripResult::Ok(AgentID::Pedestrian(*id)),
             TripLeg::Drive(vehicle, _) => TripResult::Ok(AgentID::Car(vehicle.id)),
             // TODO Should be the bus, but apparently transit sim tracks differently?
-            TripLeg::RideBus(ped, _, _) => TripResult::Ok(AgentID::Pedestrian(*ped)),
+            TripLeg::RideBus(ped, _, _) | TripLeg::RideTrain(ped, _, _) => {
+                TripResult::Ok(AgentID::Pedestrian(*ped))
+            }
             TripLeg::ServeBusRoute(id, _) => TripResult::Ok(AgentID::Car(*id)),
         }
     }
@@ -502,9 +556,11 @@ impl TripManager {
         };
         for t in &self.trips {
             if let Some(end) = t.finished_at {
-                result
-                    .finished_trips
-                    .push((t.id, t.mode, end - t.spawned_at));
+                if !t.is_bus_trip() {
+                    result
+                        .finished_trips
+                        .push((t.id, t.mode, end - t.spawned_at));
+                }
             } else if t.aborted {
                 result.aborted_trips += 1;
             }
@@ -520,6 +576,10 @@ impl TripManager {
         std::mem::replace(&mut self.events, Vec::new())
     }
 
+    pub fn trip_to_person(&self, id: TripID) -> Option<PersonID> {
+        self.trips[id.0].person
+    }
+
     pub fn trip_status(&self, id: TripID) -> TripStatus {
         let trip = &self.trips[id.0];
         TripStatus {
@@ -591,6 +651,7 @@ struct Trip {
     mode: TripMode,
     start: TripStart,
     end: TripEnd,
+    person: Option<PersonID>,
 }
 
 impl Trip {
@@ -674,6 +735,7 @@ pub enum TripLeg {
     Walk(PedestrianID, Speed, SidewalkSpot),
     Drive(Vehicle, DrivingGoal),
     RideBus(PedestrianID, BusRouteID, BusStopID),
+    RideTrain(PedestrianID, RailLineID, StationID),
     ServeBusRoute(CarID, BusRouteID),
 }
 
@@ -0,0 +1,183 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Duration};
+use map_model::{
+    EditCmd, IntersectionType, LaneType, Map, MapEdits, PathConstraints, PathRequest, PathStep,
+    Position, TimedEditCmd, Traversable,
+};
+use sim::{CarID, GetDrawAgents, Scenario, Sim, SimFlags, VehicleType};
+use std::collections::BTreeMap;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_slow("small_spawn_completes_after_live_edits", |h| {
+        let flags = SimFlags::for_test("live_edits_complete");
+        let (mut map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.step(&map, Duration::minutes(5));
+
+        // Close the lane with the most cars on it
+        let lane = map
+            .all_lanes()
+            .iter()
+            .filter(|l| l.is_driving())
+            .max_by_key(|l| sim.get_draw_cars(Traversable::Lane(l.id), &map).len())
+            .unwrap()
+            .id;
+        let on_lane: Vec<CarID> = sim
+            .get_draw_cars(Traversable::Lane(lane), &map)
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        let num_trips = on_lane.iter().filter(|c| c.1 != VehicleType::Bus).count();
+        assert!(num_trips > 0);
+        let aborted_before = sim.get_finished_trips().aborted_trips;
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeLaneType {
+            id: lane,
+            lt: LaneType::Construction,
+            orig_lt: LaneType::Driving,
+        });
+        let mut timer = Timer::throwaway();
+        let effects = map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        let (rerouted, aborted) = sim.handle_live_edits(&map, &effects, &mut timer);
+        // Everybody on the lane is stuck (buses too), and anybody headed for it found another way
+        assert!(aborted >= num_trips);
+        assert!(rerouted > 0);
+        assert_eq!(
+            sim.get_finished_trips().aborted_trips,
+            aborted_before + aborted
+        );
+        for car in on_lane {
+            assert!(sim.get_draw_car(car, &map).is_none());
+        }
+        sim.step(&map, Duration::minutes(1));
+
+        // Close the busiest intersection while everybody's on the move
+        let i = map
+            .all_intersections()
+            .iter()
+            .filter(|i| !i.is_border())
+            .max_by_key(|i| i.turns.len())
+            .unwrap();
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::CloseIntersection {
+            id: i.id,
+            orig_it: i.intersection_type,
+        });
+        let effects = map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        sim.handle_live_edits(&map, &effects, &mut timer);
+
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+    });
+
+    t.run_slow("timed_edits_close_and_reopen", |h| {
+        let flags = SimFlags::for_test("timed_edits_close_and_reopen");
+        let (mut map, _, mut rng) = flags.load(&mut Timer::throwaway());
+        let i = map
+            .all_intersections()
+            .iter()
+            .filter(|i| !i.is_border())
+            .max_by_key(|i| i.turns.len())
+            .unwrap();
+        let (id, orig_it) = (i.id, i.intersection_type);
+        let mut edits = map.get_edits().clone();
+        edits.edits_name = "timed_edits_test".to_string();
+        edits.dirty = true;
+        edits.timed_commands.push(TimedEditCmd {
+            cmd: EditCmd::CloseIntersection { id, orig_it },
+            start: Duration::minutes(5),
+            end: Duration::minutes(15),
+        });
+        let mut timer = Timer::throwaway();
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        // Nothing's active yet
+        assert_eq!(map.get_i(id).intersection_type, orig_it);
+
+        // Travel times from somewhere else, like traffic assignment, survive the closure
+        let jammed: BTreeMap<Traversable, Duration> = map
+            .all_lanes()
+            .iter()
+            .find(|l| l.is_driving() && l.src_i != id && l.dst_i != id)
+            .map(|l| (Traversable::Lane(l.id), Duration::minutes(10)))
+            .into_iter()
+            .collect();
+        map.set_driving_travel_times(jammed.clone(), &mut timer);
+
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut timer);
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut timer);
+        h.setup_done(&sim);
+
+        // Before the closure starts, new driving paths already try to avoid it. Look at trips
+        // through the intersection, starting and ending one lane away, so there's room to detour.
+        let trips: Vec<PathRequest> = map
+            .get_i(id)
+            .turns
+            .iter()
+            .filter(|t| map.get_l(t.src).is_driving() && map.get_l(t.dst).is_driving())
+            .filter_map(|t| {
+                let from = map
+                    .get_turns_to_lane(t.src)
+                    .into_iter()
+                    .find(|u| map.get_l(u.id.src).is_driving())?
+                    .id
+                    .src;
+                let to = map
+                    .get_turns_from_lane(t.dst)
+                    .into_iter()
+                    .find(|v| map.get_l(v.id.dst).is_driving())?
+                    .id
+                    .dst;
+                Some(PathRequest {
+                    start: Position::new(from, Distance::ZERO),
+                    end: Position::new(to, map.get_l(to).length()),
+                    constraints: PathConstraints::Car,
+                })
+            })
+            .collect();
+        let num_through = |map: &Map| {
+            trips
+                .iter()
+                .filter(|req| {
+                    map.pathfind((*req).clone()).map_or(false, |path| {
+                        path.get_steps().iter().any(|step| match step {
+                            PathStep::Turn(t) => t.parent == id,
+                            _ => false,
+                        })
+                    })
+                })
+                .count()
+        };
+        let before = num_through(&map);
+        assert!(before > 0);
+        sim.step_with_timed_edits(&mut map, Duration::minutes(1), &mut timer);
+        assert_eq!(map.get_i(id).intersection_type, orig_it);
+        assert!(num_through(&map) < before);
+        for (t, dt) in &jammed {
+            assert_eq!(map.get_driving_travel_times()[t], *dt);
+        }
+
+        sim.step_with_timed_edits(&mut map, Duration::minutes(9), &mut timer);
+        assert_eq!(
+            map.get_i(id).intersection_type,
+            IntersectionType::Construction
+        );
+        // Saving in the middle of the window keeps the closure timed, instead of baking it in
+        map.save_edits();
+        let saved = MapEdits::load(map.get_name(), "timed_edits_test", &mut timer);
+        assert!(saved.commands.is_empty());
+        assert_eq!(saved.timed_commands.len(), 1);
+
+        sim.step_with_timed_edits(&mut map, Duration::minutes(10), &mut timer);
+        assert_eq!(map.get_i(id).intersection_type, orig_it);
+
+        while !sim.is_done() {
+            assert!(sim.time() < Duration::minutes(70));
+            sim.step_with_timed_edits(&mut map, Duration::minutes(1), &mut timer);
+        }
+        assert_eq!(map.get_driving_travel_times(), &jammed);
+    });
+}
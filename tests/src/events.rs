@@ -0,0 +1,56 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::Duration;
+use sim::{Event, EventLogFormat, EventLogOptions, Scenario, SimFlags};
+
+pub fn run(t: &mut TestRunner) {
+    t.run_fast("event_log_options", |_| {
+        let log = EventLogOptions::new("logs/run.bin".to_string());
+        let suffixed = log.with_suffix("with edits");
+        assert_eq!(suffixed.path, "logs/run_with_edits.bin");
+        assert_eq!(suffixed.format, EventLogFormat::Binary);
+        assert_eq!(
+            EventLogOptions::new("run".to_string())
+                .with_suffix("a")
+                .path,
+            "run_a"
+        );
+
+        let mut log = EventLogOptions::new("run.json".to_string());
+        assert_eq!(log.format, EventLogFormat::Json);
+        assert!(log
+            .only_record(vec!["TripFinished".to_string(), "PedEntersBus".to_string()])
+            .is_ok());
+        assert_eq!(log.only.len(), 2);
+        assert!(log
+            .only_record(Event::ALL_NAMES.iter().map(|n| n.to_string()).collect())
+            .is_ok());
+        assert_eq!(log.only.len(), Event::ALL_NAMES.len());
+        assert!(log.only_record(vec!["TripFinshed".to_string()]).is_err());
+        // A bad name doesn't clobber the filter
+        assert_eq!(log.only.len(), Event::ALL_NAMES.len());
+    });
+
+    t.run_slow("event_log_records_finished_trips", |h| {
+        let path = abstutil::path1_json("montlake", "event_logs", "event_log_test");
+        // Leftovers from an earlier run get replaced
+        std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+        std::fs::write(&path, "stale\n").unwrap();
+
+        let mut flags = SimFlags::for_test("event_log_records_finished_trips");
+        let mut log = EventLogOptions::new(path.clone());
+        log.only_record(vec!["TripFinished".to_string()]).unwrap();
+        flags.opts.event_log = Some(log);
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), sim.get_finished_trips().finished_trips.len());
+        for line in lines {
+            assert!(line.contains("\"event_type\":\"TripFinished\""));
+        }
+    });
+}
@@ -0,0 +1,241 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Duration, Speed};
+use headless::assignment::{assign, average_costs};
+use headless::compare::compare_trips;
+use headless::green_wave::{green_wave_offsets, positive_mod};
+use headless::signal_timing::neighbors;
+use map_model::{LaneID, PhaseType, Traversable};
+use sim::{FinishedTrips, Scenario, SimFlags, TripID, TripMode};
+use std::collections::BTreeMap;
+
+pub fn run(t: &mut TestRunner) {
+    t.run_fast("compare_trips_keeps_unchanged_trips", |_| {
+        let baseline = FinishedTrips {
+            unfinished_trips: 0,
+            aborted_trips: 0,
+            finished_trips: (0..10)
+                .map(|id| (TripID(id), TripMode::Drive, Duration::seconds(100.0)))
+                .collect(),
+        };
+        let mut finished_trips = Vec::new();
+        for id in 0..6 {
+            finished_trips.push((TripID(id), TripMode::Drive, Duration::seconds(100.0)));
+        }
+        for id in 6..9 {
+            finished_trips.push((TripID(id), TripMode::Drive, Duration::seconds(90.0)));
+        }
+        finished_trips.push((TripID(9), TripMode::Drive, Duration::seconds(150.0)));
+        // Didn't finish in the baseline, so it can't be compared
+        finished_trips.push((TripID(10), TripMode::Drive, Duration::seconds(10.0)));
+        let edited = FinishedTrips {
+            unfinished_trips: 0,
+            aborted_trips: 0,
+            finished_trips,
+        };
+
+        let cmp = compare_trips(
+            "baseline".to_string(),
+            &baseline,
+            "edits".to_string(),
+            edited,
+        );
+        assert_eq!(cmp.trips.len(), 10);
+        let deltas = &cmp.per_mode[&TripMode::Drive];
+        assert_eq!(deltas.same, 6);
+        assert_eq!(deltas.faster, 3);
+        assert_eq!(deltas.slower, 1);
+        // Most trips didn't change, so the median shouldn't either.
+        assert_eq!(deltas.delta_p50, Some(Duration::ZERO));
+        assert_eq!(deltas.delta_p90, Some(Duration::seconds(50.0)));
+    });
+    t.run_fast("average_costs_blends_toward_free_flow", |_| {
+        let free_flow = |_: Traversable| Duration::seconds(10.0);
+        let (l0, l1) = (Traversable::Lane(LaneID(0)), Traversable::Lane(LaneID(1)));
+        let mut costs = BTreeMap::new();
+
+        let mut observed = BTreeMap::new();
+        observed.insert(l0, Duration::seconds(30.0));
+        // The first day replaces free-flow entirely
+        let change = average_costs(&mut costs, &observed, 1, free_flow);
+        assert_eq!(costs[&l0], Duration::seconds(30.0));
+        assert_eq!(change, Duration::seconds(20.0));
+
+        // Nobody used l0 this time, so it heads back towards free-flow. l1 is new and starts from
+        // free-flow, not from what was observed.
+        let mut observed = BTreeMap::new();
+        observed.insert(l1, Duration::seconds(20.0));
+        let change = average_costs(&mut costs, &observed, 2, free_flow);
+        assert_eq!(costs[&l0], Duration::seconds(20.0));
+        assert_eq!(costs[&l1], Duration::seconds(15.0));
+        assert_eq!(change, Duration::seconds(15.0));
+
+        // Later days move less
+        average_costs(&mut costs, &BTreeMap::new(), 3, free_flow);
+        assert!(costs[&l0] > Duration::seconds(16.0) && costs[&l0] < Duration::seconds(17.0));
+        assert!(costs[&l1] > Duration::seconds(13.0) && costs[&l1] < Duration::seconds(14.0));
+    });
+
+    t.run_slow("assignment_iterates", |_| {
+        let flags = SimFlags::for_test("assignment_iterates");
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+        let scenario = Scenario::small_run(&map);
+        let report = assign(map, &scenario, &flags, 2, 0.0, &mut Timer::throwaway());
+
+        assert_eq!(report.scenario_name, "small_run");
+        assert_eq!(report.iterations.len(), 2);
+        let first = &report.iterations[0];
+        assert!(first.cars > 0);
+        // Nothing to compare the first day against
+        assert_eq!(first.changed_routes, first.cars);
+        assert!(first.mean_cost_change > Duration::ZERO);
+        // With a threshold of 0, only identical routes count as converged.
+        assert_eq!(report.converged, report.iterations[1].changed_routes == 0);
+        for iteration in &report.iterations {
+            assert!(iteration.finished_trips > 0);
+        }
+    });
+
+    t.run_fast("positive_mod_wraps_into_the_cycle", |_| {
+        let cycle = Duration::seconds(90.0);
+        assert_eq!(
+            positive_mod(Duration::seconds(10.0), cycle),
+            Duration::seconds(10.0)
+        );
+        assert_eq!(
+            positive_mod(Duration::seconds(100.0), cycle),
+            Duration::seconds(10.0)
+        );
+        assert_eq!(
+            positive_mod(Duration::seconds(-10.0), cycle),
+            Duration::seconds(80.0)
+        );
+        assert_eq!(
+            positive_mod(Duration::seconds(-190.0), cycle),
+            Duration::seconds(80.0)
+        );
+        assert_eq!(positive_mod(Duration::ZERO, cycle), Duration::ZERO);
+        assert_eq!(positive_mod(-cycle, cycle), Duration::ZERO);
+    });
+
+    t.run_slow("green_wave_offsets_line_up_the_phases", |_| {
+        let flags = SimFlags::for_test("green_wave_offsets_line_up_the_phases");
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+        let speed = Speed::miles_per_hour(25.0);
+
+        // Two neighboring signals with a phase serving the road between them
+        let (corridor, (signals, offsets, _)) = map
+            .all_roads()
+            .iter()
+            .filter(|r| {
+                map.get_i(r.src_i).is_traffic_signal() && map.get_i(r.dst_i).is_traffic_signal()
+            })
+            .find_map(|r| {
+                let corridor = vec![r.src_i, r.dst_i];
+                green_wave_offsets(&map, &corridor, speed)
+                    .ok()
+                    .map(|result| (corridor, result))
+            })
+            .expect("No neighboring signals on the test map");
+        assert_eq!(offsets.len(), 2);
+        assert_eq!(offsets[0].id, corridor[0]);
+        assert_eq!(offsets[0].arrival, Duration::ZERO);
+        assert_eq!(offsets[0].new_offset, offsets[0].old_offset);
+        assert!(offsets[1].arrival > Duration::ZERO);
+        // Only changed signals are returned
+        assert_eq!(
+            signals.iter().map(|s| s.id).collect::<Vec<_>>(),
+            offsets
+                .iter()
+                .filter(|o| o.new_offset != o.old_offset)
+                .map(|o| o.id)
+                .collect::<Vec<_>>()
+        );
+
+        // When the corridor phase starts at each signal, relative to midnight
+        let phase_starts: Vec<Duration> = offsets
+            .iter()
+            .map(|o| {
+                let signal = map.get_traffic_signal(o.id);
+                let phase_start = signal.phases[0..o.phase]
+                    .iter()
+                    .fold(Duration::ZERO, |sum, p| sum + p.duration);
+                positive_mod(phase_start - o.new_offset, o.cycle_length)
+            })
+            .collect();
+        // The second signal turns green just as the platoon from the first one arrives.
+        let lag = positive_mod(
+            phase_starts[1] - phase_starts[0] - offsets[1].arrival,
+            offsets[1].cycle_length,
+        );
+        let epsilon = Duration::seconds(0.1);
+        assert!(lag < epsilon || offsets[1].cycle_length - lag < epsilon);
+
+        assert!(green_wave_offsets(&map, &vec![corridor[0]], speed).is_err());
+        assert!(green_wave_offsets(&map, &corridor, Speed::ZERO).is_err());
+    });
+
+    t.run_slow("signal_timing_neighbors_change_one_thing", |_| {
+        let flags = SimFlags::for_test("signal_timing_neighbors_change_one_thing");
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+        let mut signal = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_traffic_signal())
+            .map(|i| map.get_traffic_signal(i.id).clone())
+            .find(|s| s.phases.len() >= 2)
+            .expect("No signal with two phases on the test map");
+        // The first phase can only get longer, the second only shorter.
+        signal.phases[0].duration = Duration::seconds(5.0);
+        signal.phases[1].duration = Duration::seconds(120.0);
+
+        let results = neighbors(&signal);
+        let mut longer = 0;
+        let mut shorter = 0;
+        let mut swapped = 0;
+        for (_, candidate) in &results {
+            assert!(candidate.clone().validate().is_ok());
+            assert_eq!(candidate.phases.len(), signal.phases.len());
+            let changed: Vec<usize> = (0..signal.phases.len())
+                .filter(|idx| candidate.phases[*idx] != signal.phases[*idx])
+                .collect();
+            match changed.len() {
+                1 => {
+                    let idx = changed[0];
+                    let delta = candidate.phases[idx].duration - signal.phases[idx].duration;
+                    if delta == Duration::seconds(5.0) {
+                        longer += 1;
+                        assert_ne!(idx, 1);
+                    } else if delta == Duration::seconds(-5.0) {
+                        shorter += 1;
+                        assert_ne!(idx, 0);
+                    } else {
+                        panic!("Phase {} changed by {}", idx, delta);
+                    }
+                }
+                2 => {
+                    let (a, b) = (changed[0], changed[1]);
+                    assert_eq!(a + 1, b);
+                    assert_eq!(candidate.phases[a], signal.phases[b]);
+                    assert_eq!(candidate.phases[b], signal.phases[a]);
+                    swapped += 1;
+                }
+                _ => panic!("{} phases changed at once", changed.len()),
+            }
+        }
+        assert!(longer > 0 && shorter > 0 && swapped > 0);
+
+        // An actuated phase can't get shorter than its minimum green.
+        signal.phases[1].duration = Duration::seconds(20.0);
+        signal.phases[1].phase_type = PhaseType::Actuated {
+            min_green: Duration::seconds(20.0),
+            gap: Duration::seconds(3.0),
+        };
+        for (_, candidate) in neighbors(&signal) {
+            assert!(candidate.phases.iter().all(|p| match p.phase_type {
+                PhaseType::Fixed => p.duration >= Duration::seconds(5.0),
+                PhaseType::Actuated { min_green, .. } => p.duration >= min_green,
+            }));
+        }
+    });
+}
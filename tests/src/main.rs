@@ -1,9 +1,13 @@
+mod edits;
+mod events;
 mod geom;
+mod headless_modes;
 mod map_conversion;
 mod parking;
 mod runner;
 mod sim_completion;
 mod sim_determinism;
+mod traffic_signals;
 mod transit;
 mod trips;
 
@@ -29,11 +33,15 @@ fn main() {
 
     let mut t = runner::TestRunner::new(flags);
 
+    edits::run(t.suite("edits"));
+    events::run(t.suite("events"));
     geom::run(t.suite("geom"));
+    headless_modes::run(t.suite("headless_modes"));
     map_conversion::run(t.suite("map_conversion"));
     parking::run(t.suite("parking"));
     sim_completion::run(t.suite("sim_completion"));
     sim_determinism::run(t.suite("sim_determinism"));
+    traffic_signals::run(t.suite("traffic_signals"));
     transit::run(t.suite("transit"));
     trips::run(t.suite("trips"));
 
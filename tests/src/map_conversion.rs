@@ -1,6 +1,235 @@
 use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::{Distance, Speed};
+use map_model::raw::{DrivingSide, RawRoad};
+use map_model::{
+    get_lane_widths, get_speed_limits, get_turn_lanes, osm, IntersectionID, LaneID, LaneType, Map,
+    PathConstraints, PathRequest, PathStep, Position, RoadID, Traversable, Turn, TurnType,
+    LANE_THICKNESS,
+};
+use sim::SimFlags;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub fn run(t: &mut TestRunner) {
+    t.run_fast("parse_osm_values", |_| {
+        assert_eq!(osm::parse_speed("50"), Some(Speed::km_per_hour(50.0)));
+        assert_eq!(
+            osm::parse_speed("30 mph"),
+            Some(Speed::miles_per_hour(30.0))
+        );
+        assert_eq!(osm::parse_speed("10 knots"), Some(Speed::knots(10.0)));
+        assert_eq!(osm::parse_speed("DE:urban"), Some(Speed::km_per_hour(50.0)));
+        assert_eq!(
+            osm::parse_speed("DE:zone:30"),
+            Some(Speed::km_per_hour(30.0))
+        );
+        assert_eq!(
+            osm::parse_speed("GB:nsl_single"),
+            Some(Speed::miles_per_hour(60.0))
+        );
+        assert_eq!(osm::parse_speed("signals"), None);
+        assert_eq!(osm::parse_speed("none"), None);
+
+        assert_eq!(osm::parse_width("3.5"), Some(Distance::meters(3.5)));
+        assert_eq!(osm::parse_width("3.5 m"), Some(Distance::meters(3.5)));
+        assert_eq!(osm::parse_width("12 ft"), Some(Distance::feet(12.0)));
+        assert_eq!(
+            osm::parse_width("10'6\""),
+            Some(Distance::feet(10.0) + Distance::inches(6.0))
+        );
+        assert_eq!(osm::parse_width("narrow"), None);
+    });
+
+    t.run_fast("off_street_lane_types", |_| {
+        let spec = |tags: Vec<(&str, &str)>| lane_spec(tags, DrivingSide::Right);
+        assert_eq!(spec(vec![("highway", "cycleway")]), "b/b");
+        assert_eq!(spec(vec![("highway", "cycleway"), ("oneway", "yes")]), "b/");
+        assert_eq!(
+            spec(vec![("highway", "cycleway"), ("foot", "designated")]),
+            "bs/bs"
+        );
+        assert_eq!(spec(vec![("highway", "footway")]), "s/s");
+        assert_eq!(
+            spec(vec![("highway", "footway"), ("bicycle", "yes")]),
+            "bs/bs"
+        );
+        assert_eq!(spec(vec![("highway", "steps"), ("bicycle", "yes")]), "s/s");
+        assert_eq!(spec(vec![("highway", "path")]), "bs/bs");
+        assert_eq!(spec(vec![("highway", "path"), ("bicycle", "no")]), "s/s");
+        assert_eq!(spec(vec![("highway", "path"), ("foot", "no")]), "b/b");
+
+        // Roads alongside separately mapped sidewalks get their own.
+        let road = |sidewalk: (&str, &str)| vec![("highway", "residential"), sidewalk];
+        assert_eq!(spec(road(("sidewalk", "separate"))), "ds/ds");
+        assert_eq!(spec(road(("sidewalk:right", "separate"))), "ds/d");
+        assert_eq!(spec(road(("sidewalk:both", "yes"))), "ds/ds");
+        // The right side of the way is the backwards side when driving on the left.
+        assert_eq!(
+            lane_spec(road(("sidewalk:right", "separate")), DrivingSide::Left),
+            "d/ds"
+        );
+    });
+
+    t.run_fast("lane_widths", |_| {
+        use LaneType::{Driving, Sidewalk};
+        let fwd = vec![Driving, Driving, Sidewalk];
+        let back = vec![Driving, Sidewalk];
+        let widths = |kv: Vec<(&str, &str)>, driving_side: DrivingSide| {
+            get_lane_widths(&tags(kv), driving_side, &fwd, &back)
+        };
+        let m = Distance::meters;
+
+        assert_eq!(
+            widths(Vec::new(), DrivingSide::Right),
+            (vec![LANE_THICKNESS; 3], vec![LANE_THICKNESS; 2])
+        );
+        // The total width is split between everything but the sidewalks.
+        assert_eq!(
+            widths(vec![("width", "9")], DrivingSide::Right),
+            (
+                vec![m(3.0), m(3.0), LANE_THICKNESS],
+                vec![m(3.0), LANE_THICKNESS]
+            )
+        );
+        // Silly values are clamped.
+        assert_eq!(
+            widths(vec![("width:lanes:forward", "20|0.1")], DrivingSide::Right).0,
+            vec![m(6.0), m(1.0), LANE_THICKNESS]
+        );
+        // A count that disagrees with the lanes is ignored.
+        assert_eq!(
+            widths(vec![("width:lanes", "2.5|3")], DrivingSide::Right),
+            widths(Vec::new(), DrivingSide::Right)
+        );
+
+        // width:lanes goes left to right facing forwards, over the whole way. When driving on the
+        // right, that's the backwards lane, then the forwards lanes from the center out.
+        assert_eq!(
+            widths(vec![("width:lanes", "2.5|3|3.5")], DrivingSide::Right),
+            (
+                vec![m(3.0), m(3.5), LANE_THICKNESS],
+                vec![m(2.5), LANE_THICKNESS]
+            )
+        );
+        // When driving on the left, it's the forwards lanes from the outside in, then the
+        // backwards lane.
+        assert_eq!(
+            widths(vec![("width:lanes", "2.5|3|3.5")], DrivingSide::Left),
+            (
+                vec![m(3.0), m(2.5), LANE_THICKNESS],
+                vec![m(3.5), LANE_THICKNESS]
+            )
+        );
+        assert_eq!(
+            widths(vec![("width:lanes:forward", "3|4")], DrivingSide::Right).0,
+            vec![m(3.0), m(4.0), LANE_THICKNESS]
+        );
+        assert_eq!(
+            widths(vec![("width:lanes:forward", "3|4")], DrivingSide::Left).0,
+            vec![m(4.0), m(3.0), LANE_THICKNESS]
+        );
+    });
+
+    t.run_fast("speed_limits", |_| {
+        let mph = Speed::miles_per_hour;
+        assert_eq!(
+            get_speed_limits(&tags(vec![("highway", "residential")])),
+            (mph(20.0), mph(20.0))
+        );
+        assert_eq!(
+            get_speed_limits(&tags(vec![("maxspeed", "30 mph")])),
+            (mph(30.0), mph(30.0))
+        );
+        // Per-direction limits override maxspeed and the road class.
+        assert_eq!(
+            get_speed_limits(&tags(vec![
+                ("maxspeed", "30 mph"),
+                ("maxspeed:forward", "40 mph")
+            ])),
+            (mph(40.0), mph(30.0))
+        );
+        assert_eq!(
+            get_speed_limits(&tags(vec![
+                ("highway", "primary"),
+                ("maxspeed:backward", "25 mph")
+            ])),
+            (mph(40.0), mph(25.0))
+        );
+    });
+
+    t.run_fast("turn_lanes", |_| {
+        use LaneType::{Driving, Sidewalk};
+        let fwd = vec![Driving, Driving, Sidewalk];
+        let back = vec![Driving, Sidewalk];
+        let turns = |kv: Vec<(&str, &str)>, driving_side: DrivingSide| {
+            let mut kv = kv;
+            kv.push((osm::ENDPT_FWD, "true"));
+            kv.push((osm::ENDPT_BACK, "true"));
+            get_turn_lanes(&tags(kv), driving_side, &fwd, &back)
+        };
+        let set = |types: Vec<TurnType>| -> Option<BTreeSet<TurnType>> {
+            Some(types.into_iter().collect())
+        };
+        let through_or_right = set(vec![
+            TurnType::Straight,
+            TurnType::LaneChangeLeft,
+            TurnType::LaneChangeRight,
+            TurnType::Right,
+        ]);
+
+        // Lanes are listed left to right facing forwards, which is from the center out when
+        // driving on the right.
+        assert_eq!(
+            turns(
+                vec![("turn:lanes:forward", "left|through;right")],
+                DrivingSide::Right
+            )
+            .0,
+            vec![set(vec![TurnType::Left]), through_or_right.clone(), None]
+        );
+        // And from the outside in when driving on the left.
+        assert_eq!(
+            turns(
+                vec![("turn:lanes:forward", "left|through;right")],
+                DrivingSide::Left
+            )
+            .0,
+            vec![through_or_right.clone(), set(vec![TurnType::Left]), None]
+        );
+        // U-turns cross the center line.
+        assert_eq!(
+            turns(vec![("turn:lanes", "reverse|none")], DrivingSide::Right).0,
+            vec![set(vec![TurnType::Left]), None, None]
+        );
+        assert_eq!(
+            turns(vec![("turn:lanes", "none|reverse")], DrivingSide::Left).0,
+            vec![set(vec![TurnType::Right]), None, None]
+        );
+        assert_eq!(
+            turns(vec![("turn:lanes:backward", "right")], DrivingSide::Right).1,
+            vec![set(vec![TurnType::Right]), None]
+        );
+
+        // A count that disagrees with the lanes is ignored.
+        assert_eq!(
+            turns(
+                vec![("turn:lanes:forward", "left|through|right")],
+                DrivingSide::Right
+            ),
+            (vec![None; 3], vec![None; 2])
+        );
+        // So are tags on roads that don't end where the OSM way does.
+        assert_eq!(
+            get_turn_lanes(
+                &tags(vec![("turn:lanes:forward", "left|through")]),
+                DrivingSide::Right,
+                &fwd,
+                &back
+            ),
+            (vec![None; 3], vec![None; 2])
+        );
+    });
+
     t.run_slow("convert_osm_twice", |_| {
         let flags = convert_osm::Flags {
             osm: "../data/input/montlake.osm".to_string(),
@@ -11,6 +240,7 @@ pub fn run(t: &mut TestRunner) {
             neighborhoods: Some("../data/input/neighborhoods.geojson".to_string()),
             clip: Some(abstutil::path_polygon("montlake")),
             output: "convert_osm_twice.bin".to_string(),
+            drive_on_left: false,
         };
 
         let map1 = convert_osm::convert(&flags, &mut abstutil::Timer::throwaway());
@@ -24,6 +254,233 @@ pub fn run(t: &mut TestRunner) {
         }
     });
 
+    t.run_slow("convert_osm_pbf_matches_xml", |_| {
+        let flags = |osm: &str| convert_osm::Flags {
+            osm: osm.to_string(),
+            parking_shapes: None,
+            offstreet_parking: None,
+            sidewalks: None,
+            gtfs: None,
+            neighborhoods: None,
+            clip: Some(abstutil::path_polygon("montlake")),
+            output: "convert_osm_pbf_matches_xml.bin".to_string(),
+            drive_on_left: false,
+        };
+
+        // import.sh makes the .pbf from the .osm
+        let map1 = convert_osm::convert(
+            &flags("../data/input/montlake.osm"),
+            &mut abstutil::Timer::throwaway(),
+        );
+        let map2 = convert_osm::convert(
+            &flags("../data/input/montlake.osm.pbf"),
+            &mut abstutil::Timer::throwaway(),
+        );
+
+        if abstutil::to_json(&map1) != abstutil::to_json(&map2) {
+            // TODO tmp files
+            abstutil::write_json("map1.json", &map1).unwrap();
+            abstutil::write_json("map2.json", &map2).unwrap();
+            panic!("map1.json and map2.json differ");
+        }
+    });
+
+    t.run_slow("bike_routes_over_imported_cycleway", |_| {
+        let flags = SimFlags::for_test("bike_routes_over_imported_cycleway");
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+
+        let cycleway_lanes: Vec<LaneID> = map
+            .all_roads()
+            .iter()
+            .filter(|r| r.osm_tags.get(osm::HIGHWAY) == Some(&"cycleway".to_string()))
+            .flat_map(|r| {
+                r.children_forwards
+                    .iter()
+                    .chain(r.children_backwards.iter())
+            })
+            .filter(|(_, lt)| *lt == LaneType::Biking)
+            .map(|(l, _)| *l)
+            .collect();
+        assert!(!cycleway_lanes.is_empty());
+
+        // Wherever there's somewhere else to bike, the cycleway connects to it.
+        for l in &cycleway_lanes {
+            let lane = map.get_l(*l);
+            if can_bike_elsewhere(&map, lane.parent, lane.dst_i, true) {
+                assert!(
+                    !map.get_turns_from_lane(*l).is_empty(),
+                    "Stuck at the end of {}",
+                    l
+                );
+            }
+            if can_bike_elsewhere(&map, lane.parent, lane.src_i, false) {
+                assert!(
+                    !map.get_turns_to_lane(*l).is_empty(),
+                    "Can't get onto {}",
+                    l
+                );
+            }
+        }
+
+        // Ride from a street onto a cycleway, and from the cycleway to somewhere else.
+        let (from, via, to) = cycleway_lanes
+            .iter()
+            .find_map(|l| {
+                let from = map
+                    .get_turns_to_lane(*l)
+                    .into_iter()
+                    .map(|t| t.id.src)
+                    .find(|src| map.get_l(*src).is_driving())?;
+                let to = map.get_turns_from_lane(*l).into_iter().next()?.id.dst;
+                Some((from, *l, to))
+            })
+            .expect("No cycleway connects to a street");
+        let onto = map
+            .pathfind(PathRequest {
+                start: Position::new(from, Distance::ZERO),
+                end: Position::new(via, map.get_l(via).length()),
+                constraints: PathConstraints::Bike,
+            })
+            .expect("Can't bike onto the cycleway");
+        assert_eq!(onto.last_step(), PathStep::Lane(via));
+        let off = map
+            .pathfind(PathRequest {
+                start: Position::new(via, Distance::ZERO),
+                end: Position::new(to, map.get_l(to).length()),
+                constraints: PathConstraints::Bike,
+            })
+            .expect("Can't bike off the cycleway");
+        assert_eq!(
+            off.get_steps().front().map(|step| step.as_traversable()),
+            Some(Traversable::Lane(via))
+        );
+    });
+
+    t.run_slow("left_hand_traffic_mirrors_lanes_and_turns", |_| {
+        let flags = convert_osm::Flags {
+            osm: "../data/input/montlake.osm".to_string(),
+            parking_shapes: None,
+            offstreet_parking: None,
+            sidewalks: None,
+            gtfs: None,
+            neighborhoods: None,
+            clip: Some(abstutil::path_polygon("montlake")),
+            output: "left_hand_traffic.bin".to_string(),
+            drive_on_left: true,
+        };
+        let raw = convert_osm::convert(&flags, &mut Timer::throwaway());
+        // TODO tmp files
+        abstutil::write_binary(&flags.output, &raw).unwrap();
+        let map = Map::new(&flags.output, false, &mut Timer::throwaway()).unwrap();
+        std::fs::remove_file(&flags.output).unwrap();
+        assert_eq!(map.get_driving_side(), DrivingSide::Left);
+
+        // On straight roads, forwards lanes are on the left of the center line, backwards lanes on
+        // the right, and both go outwards in order.
+        for r in map.all_roads() {
+            if r.center_pts.points().len() != 2 {
+                continue;
+            }
+            let line = r.center_pts.first_line();
+            let right = line.shift_right(Distance::meters(1.0)).pt1();
+            // Positive to the right of the center line, in meters
+            let offset = |l: LaneID| {
+                let pt = map.get_l(l).first_pt();
+                (pt.x() - line.pt1().x()) * (right.x() - line.pt1().x())
+                    + (pt.y() - line.pt1().y()) * (right.y() - line.pt1().y())
+            };
+            let fwd: Vec<f64> = r
+                .children_forwards
+                .iter()
+                .map(|(l, _)| -offset(*l))
+                .collect();
+            let back: Vec<f64> = r
+                .children_backwards
+                .iter()
+                .map(|(l, _)| offset(*l))
+                .collect();
+            for offsets in vec![fwd, back] {
+                assert!(
+                    offsets.iter().all(|x| *x > 0.0),
+                    "{} has lanes on the wrong side",
+                    r.id
+                );
+                assert!(
+                    offsets.windows(2).all(|pair| pair[0] < pair[1]),
+                    "{} is out of order",
+                    r.id
+                );
+            }
+        }
+
+        // Left is the near side turn, so only the outermost lane makes it, and only the innermost
+        // lane turns right. Lanes going straight too means no other lane had to pick up a turn.
+        let mut checked = 0;
+        for i in map.all_intersections() {
+            for r in &i.roads {
+                let driving: Vec<LaneID> = map
+                    .get_r(*r)
+                    .incoming_lanes(i.id)
+                    .iter()
+                    .filter(|(_, lt)| *lt == LaneType::Driving)
+                    .map(|(l, _)| *l)
+                    .collect();
+                if driving.len() < 2 {
+                    continue;
+                }
+                let turns: Vec<&Turn> = driving
+                    .iter()
+                    .flat_map(|l| map.get_turns_from_lane(*l))
+                    .filter(|t| map.get_l(t.id.dst).is_driving())
+                    .collect();
+                if !turns.iter().any(|t| t.turn_type == TurnType::Straight) {
+                    continue;
+                }
+                for t in turns {
+                    if t.turn_type == TurnType::Left {
+                        assert_eq!(
+                            t.id.src,
+                            *driving.last().unwrap(),
+                            "{} isn't outermost",
+                            t.id
+                        );
+                        checked += 1;
+                    } else if t.turn_type == TurnType::Right {
+                        assert_eq!(t.id.src, driving[0], "{} isn't innermost", t.id);
+                        checked += 1;
+                    }
+                }
+            }
+        }
+        assert!(checked > 0);
+
+        // Sidewalk corners stay on one side of the road they lead to, instead of crossing it.
+        for t in map.all_turns().values() {
+            if t.turn_type != TurnType::SharedSidewalkCorner
+                || map.get_i(t.id.parent).roads.len() < 3
+            {
+                continue;
+            }
+            let src = map.get_l(t.id.src).endpoint(t.id.parent);
+            let dst = map.get_l(t.id.dst);
+            let dst_road = map.get_r(dst.parent);
+            let far_side = if dst_road.is_forwards(dst.id) {
+                &dst_road.children_backwards
+            } else {
+                &dst_road.children_forwards
+            };
+            if let Some((other, _)) = far_side.iter().find(|(_, lt)| *lt == LaneType::Sidewalk) {
+                let other = map.get_l(*other).endpoint(t.id.parent);
+                assert!(
+                    src.dist_to(dst.endpoint(t.id.parent)) < src.dist_to(other),
+                    "{} goes to the far side of {}",
+                    t.id,
+                    dst_road.id
+                );
+            }
+        }
+    });
+
     t.run_slow("raw_to_map_twice", |_| {
         let map1 = map_model::Map::new(
             &abstutil::path_raw_map("montlake"),
@@ -64,3 +521,38 @@ pub fn run(t: &mut TestRunner) {
         .expect("huge_seattle broke");
     });
 }
+
+fn tags(kv: Vec<(&str, &str)>) -> BTreeMap<String, String> {
+    kv.into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn lane_spec(kv: Vec<(&str, &str)>, driving_side: DrivingSide) -> String {
+    RawRoad {
+        center_points: Vec::new(),
+        osm_tags: tags(kv),
+        turn_restrictions: Vec::new(),
+    }
+    .get_spec(driving_side)
+    .to_string()
+}
+
+// Does another road at the intersection have a lane bikes can use, going the right way?
+fn can_bike_elsewhere(map: &Map, r: RoadID, i: IntersectionID, leaving: bool) -> bool {
+    if map.get_i(i).is_border() {
+        return false;
+    }
+    map.get_i(i).roads.iter().any(|other| {
+        let road = map.get_r(*other);
+        let lanes = if leaving {
+            road.outgoing_lanes(i)
+        } else {
+            road.incoming_lanes(i)
+        };
+        *other != r
+            && lanes
+                .iter()
+                .any(|(_, lt)| *lt == LaneType::Biking || *lt == LaneType::Driving)
+    })
+}
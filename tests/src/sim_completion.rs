@@ -1,7 +1,7 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::Duration;
-use sim::{Scenario, SimFlags};
+use geom::{Distance, Duration, Speed};
+use sim::{DistanceInterval, Scenario, SimFlags, SpeedProfile, VehicleType};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("small_spawn_completes", |h| {
@@ -12,4 +12,130 @@ pub fn run(t: &mut TestRunner) {
         h.setup_done(&sim);
         sim.just_run_until_done(&map, Some(Duration::minutes(70)));
     });
+
+    t.run_slow("small_spawn_completes_with_kinematic_driving", |h| {
+        let mut flags = SimFlags::for_test("kinematic_driving_completes");
+        flags.opts.kinematic_driving = true;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+    });
+
+    t.run_slow("small_spawn_completes_with_informed_drivers", |h| {
+        let mut flags = SimFlags::for_test("informed_drivers_complete");
+        flags.opts.informed_drivers = 1.0;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+    });
+
+    t.run_fast("kinematic_speed_changes_are_bounded", |_| {
+        let mph = Speed::miles_per_hour;
+        let mps = Speed::meters_per_second;
+        // (distance, start speed, cruise speed, desired end speed, expected end speed, vehicle)
+        let cases = vec![
+            (
+                100.0,
+                mps(0.0),
+                mph(30.0),
+                mps(0.0),
+                mps(0.0),
+                VehicleType::Car,
+            ),
+            (
+                200.0,
+                mps(0.0),
+                mph(20.0),
+                mps(0.0),
+                mps(0.0),
+                VehicleType::Bus,
+            ),
+            // Entering a slower road
+            (
+                50.0,
+                mps(15.0),
+                mps(10.0),
+                mps(5.0),
+                mps(5.0),
+                VehicleType::Car,
+            ),
+            (
+                80.0,
+                mps(4.0),
+                mps(5.0),
+                mps(5.0),
+                mps(5.0),
+                VehicleType::Bike,
+            ),
+            // Not enough room to reach the desired end speed
+            (
+                5.0,
+                mps(0.0),
+                mps(15.0),
+                mps(15.0),
+                mps(5.0),
+                VehicleType::Car,
+            ),
+            (
+                300.0,
+                mps(0.0),
+                mps(15.0),
+                mps(0.0),
+                mps(0.0),
+                VehicleType::Bus,
+            ),
+        ];
+        let epsilon = 0.01;
+        let step = Duration::seconds(0.1);
+
+        for (dist, v0, cruise, v1, expected_end, vt) in cases {
+            let profile = SpeedProfile::new(Distance::meters(dist), v0, cruise, v1, vt);
+            let dist_int = DistanceInterval::new_driving(Distance::ZERO, Distance::meters(dist));
+            let (accel, decel) = profile.max_rates();
+            assert!(accel <= vt.max_accel() + epsilon);
+            assert!(decel <= vt.max_decel() + epsilon);
+
+            let mut t = Duration::ZERO;
+            let mut last_speed = profile.speed_after(t).inner_meters_per_second();
+            let mut last_dist = profile.lerp(&dist_int, t);
+            assert_eq!(last_speed, v0.inner_meters_per_second());
+            while t < profile.total_time() {
+                t += step;
+                let speed = profile.speed_after(t).inner_meters_per_second();
+                let dist = profile.lerp(&dist_int, t);
+                let rate = (speed - last_speed) / step.inner_seconds();
+                assert!(
+                    rate <= vt.max_accel() + epsilon && rate >= -vt.max_decel() - epsilon,
+                    "{:?} changed speed at {} m/s^2 at {}",
+                    vt,
+                    rate,
+                    t
+                );
+                assert!(
+                    speed
+                        <= cruise
+                            .inner_meters_per_second()
+                            .max(v0.inner_meters_per_second())
+                            + epsilon
+                );
+                assert!(dist >= last_dist);
+                last_speed = speed;
+                last_dist = dist;
+            }
+            assert_eq!(profile.lerp(&dist_int, profile.total_time()), dist_int.end);
+            assert!(
+                (profile
+                    .speed_after(profile.total_time())
+                    .inner_meters_per_second()
+                    - expected_end.inner_meters_per_second())
+                .abs()
+                    < epsilon
+            );
+            // The profile covers the distance smoothly, instead of jumping at the end
+            let almost_done = profile.lerp(&dist_int, profile.total_time() - step);
+            assert!(dist_int.end - almost_done < Distance::meters(2.0));
+        }
+    });
 }
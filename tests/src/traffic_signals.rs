@@ -0,0 +1,314 @@
+use crate::runner::TestRunner;
+use abstutil::Timer;
+use geom::Duration;
+use map_model::{
+    ControlTrafficSignal, EditCmd, IntersectionID, Map, PathConstraints, PathRequest, Phase,
+    PhaseType, Position, RoadID, SignalPlan, TimingSheet, TurnGroupID, TurnPriority, TurnType,
+};
+use sim::{DrivingGoal, Scenario, Sim, SimFlags, TripSpec, MAX_CAR_LENGTH};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub fn run(t: &mut TestRunner) {
+    t.run_fast("signal_plans_switch_after_the_running_phase", |_| {
+        let phases = |durations: Vec<f64>| -> Vec<Phase> {
+            durations
+                .into_iter()
+                .map(|d| Phase {
+                    protected_groups: BTreeSet::new(),
+                    yield_groups: BTreeSet::new(),
+                    duration: Duration::seconds(d),
+                    phase_type: PhaseType::Fixed,
+                })
+                .collect()
+        };
+        let plan = |start: f64, end: f64, durations: Vec<f64>| SignalPlan {
+            start: Duration::seconds(start),
+            end: Duration::seconds(end),
+            phases: phases(durations),
+            offset: Duration::ZERO,
+        };
+        let hour = 3600.0;
+        let day = 24.0 * hour;
+        let signal = ControlTrafficSignal {
+            id: IntersectionID(0),
+            phases: phases(vec![30.0, 30.0]),
+            offset: Duration::ZERO,
+            plans: vec![
+                plan(0.0, 6.0 * hour, vec![50.0, 50.0]),
+                plan(8.0 * hour, 9.0 * hour, vec![20.0, 25.0]),
+                plan(23.0 * hour, day, vec![40.0, 40.0]),
+            ],
+            turn_groups: BTreeMap::new(),
+        };
+        // (time, plan in effect, phase, remaining time in the phase)
+        let check = |now: f64, plan: Option<usize>, phase: usize, remaining: f64| {
+            let now = Duration::seconds(now);
+            assert_eq!(signal.plan_in_effect(now), plan, "plan at {}", now);
+            let (idx, _, left) = signal.current_phase_and_remaining_time(now);
+            assert_eq!(
+                (idx, left),
+                (phase, Duration::seconds(remaining)),
+                "phase at {}",
+                now
+            );
+        };
+
+        // The very start of the simulation doesn't wait for the previous day
+        check(10.0, Some(0), 0, 40.0);
+        // The early plan ends at 6am, but its phase at that moment finishes first
+        check(6.0 * hour + 10.0, Some(0), 0, 40.0);
+        check(6.0 * hour + 50.0, None, 1, 10.0);
+        check(8.0 * hour - 10.0, None, 1, 10.0);
+        check(8.0 * hour, None, 0, 30.0);
+        check(8.0 * hour + 30.0, Some(1), 1, 15.0);
+        check(9.0 * hour + 10.0, Some(1), 0, 10.0);
+        check(9.0 * hour + 20.0, None, 0, 10.0);
+        // At midnight, the late plan finishes its phase before the next day's first plan starts
+        check(day + 10.0, Some(2), 0, 30.0);
+        check(day + 40.0, Some(0), 0, 10.0);
+        check(day + 8.0 * hour + 30.0, Some(1), 1, 15.0);
+    });
+    t.run_slow("actuated_signal_only_serves_phases_with_demand", |h| {
+        let flags = SimFlags::for_test("actuated_signal_only_serves_phases_with_demand");
+        let mut timer = Timer::throwaway();
+        let (mut map, _, mut rng) = flags.load(&mut timer);
+
+        // Find a signal with an approach that phase 0 never lets through, but some other phase
+        // does. All of the demand will come from there.
+        let (i, src) = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_traffic_signal())
+            .find_map(|i| {
+                let signal = map.get_traffic_signal(i.id);
+                if signal.phases.len() < 2 {
+                    return None;
+                }
+                i.get_incoming_lanes(&map, PathConstraints::Car)
+                    .into_iter()
+                    .find(|l| {
+                        let turns = map.get_turns_from_lane(*l);
+                        map.get_l(*l).length() > MAX_CAR_LENGTH * 2.0
+                            && !turns.is_empty()
+                            && turns.iter().all(|t| {
+                                signal.phases[0].get_priority_of_turn(t.id, signal)
+                                    == TurnPriority::Banned
+                            })
+                    })
+                    .map(|l| (i.id, l))
+            })
+            .expect("no signal with an approach banned in phase 0");
+        let start_pos = Position::new(src, map.get_l(src).length() / 2.0);
+
+        // Make every phase actuated
+        let mut signal = map.get_traffic_signal(i).clone();
+        signal.plans.clear();
+        for phase in &mut signal.phases {
+            phase.duration = phase.duration.max(Duration::seconds(10.0));
+            phase.phase_type = PhaseType::Actuated {
+                min_green: Duration::seconds(5.0),
+                gap: Duration::seconds(3.0),
+            };
+        }
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+        map.apply_edits(edits, &mut timer);
+
+        // Which phases could the demand from src use?
+        let signal = map.get_traffic_signal(i);
+        let turns_from_src: Vec<_> = map
+            .get_turns_from_lane(src)
+            .into_iter()
+            .filter(|t| t.turn_type != TurnType::SharedSidewalkCorner)
+            .map(|t| t.id)
+            .collect();
+        let useful_phases: BTreeSet<usize> = (0..signal.phases.len())
+            .filter(|idx| {
+                turns_from_src.iter().any(|t| {
+                    signal.phases[*idx].get_priority_of_turn(*t, signal) != TurnPriority::Banned
+                })
+            })
+            .collect();
+        assert!(!useful_phases.contains(&0));
+
+        let goal = map
+            .all_outgoing_borders()
+            .into_iter()
+            .flat_map(|b| {
+                b.get_incoming_lanes(&map, PathConstraints::Car)
+                    .into_iter()
+                    .map(move |l| DrivingGoal::Border(b.id, l))
+            })
+            .find(|goal| {
+                map.pathfind(PathRequest {
+                    start: start_pos,
+                    end: goal.goal_pos(PathConstraints::Car, &map),
+                    constraints: PathConstraints::Car,
+                })
+                .is_some()
+            })
+            .expect("no border reachable from the approach");
+
+        // A fixed pattern: one car every 10 seconds for the first minute, then nothing.
+        let mut sim = Sim::new(&map, flags.opts.clone(), &mut timer);
+        let num_cars = 6;
+        for idx in 0..num_cars {
+            sim.schedule_trip(
+                Duration::seconds(10.0 * (idx as f64)),
+                TripSpec::CarAppearing {
+                    start_pos,
+                    goal: goal.clone(),
+                    vehicle_spec: Scenario::rand_car(&mut rng),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+        }
+        sim.spawn_all_trips(&map, &mut timer, true);
+        h.setup_done(&sim);
+
+        let mut served = BTreeSet::new();
+        let mut last_phase = sim.current_signal_phase(i, &map).0;
+        assert_eq!(last_phase, 0);
+        while sim.time() < Duration::minutes(10) {
+            sim.step(&map, Duration::seconds(1.0));
+            let (phase, _, _) = sim.current_signal_phase(i, &map);
+            if phase != last_phase {
+                served.insert(phase);
+                last_phase = phase;
+            }
+        }
+
+        // Every phase the signal switched to had somebody waiting for it. Nobody asked for phase 0
+        // or any other phase banning the approach, so the signal rests instead of cycling through
+        // them.
+        assert!(!served.is_empty());
+        assert!(
+            served.is_subset(&useful_phases),
+            "served {:?}, but only {:?} had demand",
+            served,
+            useful_phases
+        );
+        let finished = sim.get_finished_trips();
+        assert_eq!(finished.finished_trips.len(), num_cars);
+        assert_eq!(finished.aborted_trips, 0);
+    });
+
+    t.run_slow("timing_sheets_only_fill_in_near_turns", |_| {
+        let flags = SimFlags::for_test("timing_sheets_only_fill_in_near_turns");
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+
+        // Approaches are easier to name when no OSM way continues through the intersection.
+        let (signal, imported) = map
+            .all_intersections()
+            .iter()
+            .filter(|i| {
+                let ways: BTreeSet<i64> = i
+                    .roads
+                    .iter()
+                    .map(|r| map.get_r(*r).orig_id.osm_way_id)
+                    .collect();
+                i.is_traffic_signal() && ways.len() == i.roads.len()
+            })
+            .find_map(|i| {
+                let signal = map.get_traffic_signal(i.id);
+                timing_sheet(&map, signal, None)
+                    .to_signal(&map, i.id)
+                    .ok()
+                    .map(|imported| (signal, imported))
+            })
+            .expect("No signal on the test map survives a round trip through a timing sheet");
+
+        assert_eq!(imported.offset, Duration::seconds(10.0));
+        assert_eq!(imported.phases.len(), signal.phases.len());
+        for (phase, orig) in imported.phases.iter().zip(signal.phases.iter()) {
+            assert_eq!(phase.duration, orig.duration);
+            assert_eq!(phase.phase_type, PhaseType::Fixed);
+            for g in &phase.protected_groups {
+                let turn_type = signal.turn_groups[g].turn_type;
+                // Everything else has to come from the sheet.
+                if turn_type != TurnType::Right && turn_type != TurnType::Crosswalk {
+                    assert!(orig.protected_groups.contains(g));
+                }
+            }
+            for g in &orig.protected_groups {
+                if signal.turn_groups[g].turn_type == TurnType::Straight {
+                    assert!(phase.protected_groups.contains(g));
+                }
+            }
+            for g in &phase.yield_groups {
+                assert!(orig.yield_groups.contains(g));
+            }
+        }
+
+        // A through movement missing from the sheet isn't invented.
+        let straight = signal
+            .turn_groups
+            .iter()
+            .find(|(_, g)| g.turn_type == TurnType::Straight)
+            .map(|(id, _)| *id)
+            .unwrap();
+        let err = timing_sheet(&map, signal, Some(straight))
+            .to_signal(&map, signal.id)
+            .unwrap_err();
+        assert!(err.contains("Missing"));
+    });
+}
+
+// Writes the JSON a city might publish for an existing signal, leaving out protected right turns
+// like they usually do. Assumes no OSM way continues through the intersection.
+fn timing_sheet(
+    map: &Map,
+    signal: &ControlTrafficSignal,
+    skip: Option<TurnGroupID>,
+) -> TimingSheet {
+    let approach = |r: RoadID| {
+        serde_json::json!({
+            "osm_way_id": map.get_r(r).orig_id.osm_way_id,
+            "side": null,
+        })
+    };
+    let movements = |groups: &BTreeSet<TurnGroupID>, protected: bool| -> Vec<serde_json::Value> {
+        groups
+            .iter()
+            .filter(|g| {
+                let turn_type = signal.turn_groups[*g].turn_type;
+                turn_type != TurnType::Crosswalk
+                    && !(protected && turn_type == TurnType::Right)
+                    && Some(**g) != skip
+            })
+            .map(|g| serde_json::json!({ "from": approach(g.from), "to": approach(g.to) }))
+            .collect()
+    };
+
+    let mut phases = Vec::new();
+    for phase in &signal.phases {
+        // Crosswalks are listed by road, so only list roads whose crosswalks all walk now.
+        let crosswalks: BTreeSet<RoadID> = signal
+            .turn_groups
+            .keys()
+            .filter(|g| g.crosswalk.is_some())
+            .map(|g| g.from)
+            .filter(|r| {
+                signal
+                    .turn_groups
+                    .keys()
+                    .filter(|g| g.crosswalk.is_some() && g.from == *r)
+                    .all(|g| phase.protected_groups.contains(g))
+            })
+            .collect();
+        phases.push(serde_json::json!({
+            "duration": phase.duration.inner_seconds(),
+            "protected": movements(&phase.protected_groups, true),
+            "permitted": movements(&phase.yield_groups, false),
+            "crosswalks": crosswalks.into_iter().map(approach).collect::<Vec<_>>(),
+            "actuated": null,
+        }));
+    }
+    serde_json::from_value(serde_json::json!({
+        "osm_node_id": map.get_i(signal.id).orig_id.osm_node_id,
+        "offset": 10.0,
+        "phases": phases,
+    }))
+    .unwrap()
+}
@@ -1,9 +1,59 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::Duration;
-use sim::{Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use geom::{Distance, Duration};
+use map_model::{BusCapacity, BusRoute, BusRouteID, BusRun, BusStopID, LaneID, TransitRide};
+use sim::{Event, Scenario, SidewalkSpot, SimFlags, TripMode, TripSpec};
 
 pub fn run(t: &mut TestRunner) {
+    t.run_fast("run_span_skips_stops_off_the_route", |_| {
+        let stop = |idx| BusStopID {
+            sidewalk: LaneID(0),
+            idx,
+        };
+        let route = BusRoute {
+            id: BusRouteID(0),
+            name: "loop".to_string(),
+            forwards: true,
+            stops: vec![stop(0), stop(1), stop(2)],
+            schedule: Vec::new(),
+            capacity: BusCapacity::STANDARD,
+        };
+        let run = |stops: Vec<usize>| BusRun {
+            stop_times: stops
+                .into_iter()
+                .map(|idx| (stop(idx), Duration::ZERO))
+                .collect(),
+        };
+
+        // Wraps around, and visits the stops the timetable skips
+        assert_eq!(route.run_span(&run(vec![1, 0])), Some((1, 3)));
+        assert_eq!(route.run_span(&run(vec![0, 2])), Some((0, 3)));
+        assert_eq!(route.run_span(&run(vec![0, 5])), None);
+        assert_eq!(route.run_span(&run(vec![5, 0])), None);
+    });
+
+    t.run_slow("bus_routes_follow_one_direction", |_| {
+        let flags = SimFlags::for_test("bus_routes_follow_one_direction");
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+        let routes = map.get_all_bus_routes();
+        assert!(!routes.is_empty());
+        for route in routes {
+            // At most one route per direction
+            assert_eq!(
+                routes
+                    .iter()
+                    .filter(|r| r.name == route.name && r.forwards == route.forwards)
+                    .count(),
+                1
+            );
+            // A run never has to wrap around the end of its route into the other direction.
+            for run in &route.schedule {
+                let (first_stop, num_stops) = route.run_span(run).unwrap();
+                assert!(first_stop + num_stops <= route.stops.len());
+            }
+        }
+    });
+
     t.run_slow("bus_reaches_stops", |h| {
         let mut flags = SimFlags::for_test("bus_reaches_stops");
         flags.opts.savestate_every = Some(Duration::seconds(30.0));
@@ -11,16 +61,42 @@ pub fn run(t: &mut TestRunner) {
         let route = map.get_bus_route("49").unwrap();
         let buses = sim.seed_bus_route(route, &map, &mut Timer::throwaway());
         let bus = buses[0];
+        let run = &route.schedule[0];
+        let (first_stop, num_stops) = route.run_span(run).unwrap();
         h.setup_done(&sim);
 
         let mut expectations: Vec<Event> = Vec::new();
         // TODO assert stuff about other buses as well, although the timing is a little unclear
-        for stop in route.stops.iter().skip(1) {
-            expectations.push(Event::BusArrivedAtStop(bus, route.id, *stop));
-            expectations.push(Event::BusDepartedFromStop(bus, route.id, *stop));
+        for i in 0..num_stops {
+            let stop = route.stops[(first_stop + i) % route.stops.len()];
+            expectations.push(Event::BusArrivedAtStop(bus, route.id, stop));
+            // The bus goes out of service at the last stop
+            if i != num_stops - 1 {
+                expectations.push(Event::BusDepartedFromStop(bus, route.id, stop));
+            }
         }
 
-        sim.run_until_expectations_met(&map, expectations, Duration::minutes(10));
+        sim.run_until_expectations_met(
+            &map,
+            expectations,
+            run.stop_times.last().unwrap().1 + Duration::minutes(10),
+        );
+        // The bus starts early enough to make its first departure, give or take some waiting at
+        // intersections on the way
+        let (arrival, _, _, _) = sim
+            .get_analytics()
+            .bus_arrivals
+            .iter()
+            .find(|(_, car, _, _)| *car == bus)
+            .unwrap();
+        assert!(*arrival <= run.stop_times[0].1 + Duration::minutes(1));
+        // Departures from the first stop get compared to the timetable
+        let stats = sim
+            .get_analytics()
+            .bus_stop_stats(sim.time(), route.id, &map);
+        assert!(!stats[&route.stops[first_stop]]
+            .schedule_deviation
+            .is_empty());
         // Make sure buses don't block a sim from being considered done
         sim.just_run_until_done(&map, Some(Duration::minutes(11)));
     });
@@ -32,8 +108,13 @@ pub fn run(t: &mut TestRunner) {
         let route = map.get_bus_route("49").unwrap();
         let buses = sim.seed_bus_route(route, &map, &mut Timer::throwaway());
         let bus = buses[0];
-        let ped_stop1 = route.stops[1];
-        let ped_stop2 = route.stops[2];
+        let run = &route.schedule[0];
+        let (first_stop, num_stops) = route.run_span(run).unwrap();
+        assert!(num_stops >= 4);
+        let stop_idx = |i: usize| route.stops[(first_stop + i) % route.stops.len()];
+        let ped_stop1 = stop_idx(1);
+        let ped_stop2 = stop_idx(2);
+        let departure = run.stop_times[0].1;
         // TODO These should be buildings near the two stops. Programmatically find these?
         let start_bldg = *map
             .get_l(map.get_bs(ped_stop1).sidewalk_pos.lane())
@@ -47,12 +128,10 @@ pub fn run(t: &mut TestRunner) {
             .building_paths[0];
         let ped = sim
             .schedule_trip(
-                Duration::ZERO,
+                departure - Duration::minutes(5),
                 TripSpec::UsingTransit {
                     start: SidewalkSpot::building(start_bldg, &map),
-                    route: route.id,
-                    stop1: ped_stop1,
-                    stop2: ped_stop2,
+                    rides: vec![TransitRide::Bus(route.id, ped_stop1, ped_stop2)],
                     goal: SidewalkSpot::building(goal_bldg, &map),
                     ped_speed: Scenario::rand_ped_speed(&mut rng),
                 },
@@ -74,9 +153,223 @@ pub fn run(t: &mut TestRunner) {
                 Event::PedLeavesBus(ped, bus, route.id),
                 Event::PedReachedBuilding(ped, goal_bldg),
                 Event::BusDepartedFromStop(bus, route.id, ped_stop2),
-                Event::BusArrivedAtStop(bus, route.id, route.stops[3]),
+                Event::BusArrivedAtStop(bus, route.id, stop_idx(3)),
+            ],
+            departure + Duration::minutes(9),
+        );
+    });
+
+    t.run_slow("full_bus_denies_boarding", |h| {
+        let flags = SimFlags::for_test("full_bus_denies_boarding");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let orig_route = map.get_bus_route("49").unwrap();
+        // Only room for one passenger
+        let route = BusRoute {
+            id: orig_route.id,
+            name: orig_route.name.clone(),
+            forwards: orig_route.forwards,
+            stops: orig_route.stops.clone(),
+            schedule: orig_route.schedule.clone(),
+            capacity: BusCapacity {
+                seated: 1,
+                standing: 0,
+            },
+        };
+        let buses = sim.seed_bus_route(&route, &map, &mut Timer::throwaway());
+        let bus = buses[0];
+        let run = &route.schedule[0];
+        let (first_stop, num_stops) = route.run_span(run).unwrap();
+        assert!(num_stops >= 3);
+        let stop_idx = |i: usize| route.stops[(first_stop + i) % route.stops.len()];
+        let ped_stop1 = stop_idx(1);
+        let ped_stop2 = stop_idx(2);
+        let departure = run.stop_times[0].1;
+        let start_bldg = *map
+            .get_l(map.get_bs(ped_stop1).sidewalk_pos.lane())
+            .building_paths
+            .last()
+            .unwrap();
+        let goal_bldg = map
+            .get_l(map.get_bs(ped_stop2).sidewalk_pos.lane())
+            .building_paths[0];
+        // Both reach the stop well before the bus, one after the other
+        let mut peds = Vec::new();
+        for offset in vec![Duration::minutes(5), Duration::minutes(4)] {
+            peds.push(
+                sim.schedule_trip(
+                    departure - offset,
+                    TripSpec::UsingTransit {
+                        start: SidewalkSpot::building(start_bldg, &map),
+                        rides: vec![TransitRide::Bus(route.id, ped_stop1, ped_stop2)],
+                        goal: SidewalkSpot::building(goal_bldg, &map),
+                        ped_speed: Scenario::rand_ped_speed(&mut rng),
+                    },
+                    &map,
+                )
+                .0
+                .unwrap(),
+            );
+        }
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![
+                Event::PedReachedBusStop(peds[0], ped_stop1),
+                Event::PedReachedBusStop(peds[1], ped_stop1),
+                Event::BusArrivedAtStop(bus, route.id, ped_stop1),
+                Event::PedEntersBus(peds[0], bus, route.id),
+                Event::PedDeniedBoarding(peds[1], bus, route.id),
+                Event::BusDepartedFromStop(bus, route.id, ped_stop1),
+                Event::PedLeavesBus(peds[0], bus, route.id),
+            ],
+            departure + Duration::minutes(9),
+        );
+        assert!(sim
+            .get_analytics()
+            .bus_denied_boardings
+            .iter()
+            .any(|(_, r, stop)| *r == route.id && *stop == ped_stop1));
+    });
+
+    t.run_slow("ped_rides_train", |h| {
+        let flags = SimFlags::synthetic_test("lightrail", "ped_rides_train");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let line = map
+            .get_all_rail_lines()
+            .iter()
+            .find(|l| !l.schedule.is_empty())
+            .expect("no rail line with a timetable");
+        let trains = sim.seed_rail_line(line);
+        // The first run leaves before any other train appears, so nobody else can pick up the
+        // pedestrian.
+        let train = trains[0];
+        let run = &line.schedule[0];
+        assert!(run.num_stations >= 2);
+        let station1 = line.stations[run.first_station];
+        let station2 = line.stations[line.next_station_idx(run.first_station)];
+        let start_bldg = *map
+            .get_l(map.get_station(station1).sidewalk_pos.lane())
+            .building_paths
+            .last()
+            .unwrap();
+        let goal_bldg = map
+            .get_l(map.get_station(station2).sidewalk_pos.lane())
+            .building_paths[0];
+        let ped = sim
+            .schedule_trip(
+                run.departure - Duration::minutes(5),
+                TripSpec::UsingTransit {
+                    start: SidewalkSpot::building(start_bldg, &map),
+                    rides: vec![TransitRide::Train(line.id, station1, station2)],
+                    goal: SidewalkSpot::building(goal_bldg, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            )
+            .0
+            .unwrap();
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![
+                Event::PedReachedStation(ped, station1),
+                Event::TrainArrivedAtStation(train, line.id, station1),
+                Event::PedEntersTrain(ped, train, line.id),
+                Event::TrainDepartedFromStation(train, line.id, station1),
+                Event::TrainArrivedAtStation(train, line.id, station2),
+                Event::PedLeavesTrain(ped, train, line.id),
+                Event::PedReachedBuilding(ped, goal_bldg),
+            ],
+            run.departure + line.tracks[run.first_station].travel_time + Duration::minutes(15),
+        );
+        let finished = sim.get_finished_trips();
+        assert_eq!(finished.finished_trips.len(), 1);
+        assert_eq!(finished.finished_trips[0].1, TripMode::Transit);
+    });
+
+    t.run_slow("ped_transfers_from_bus_to_train", |h| {
+        let flags = SimFlags::synthetic_test("lightrail", "ped_transfers_from_bus_to_train");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+
+        // Find a scheduled bus stopping close to a station, after at least one earlier stop
+        let (line, station_idx, route, first_stop, stop_offset) = map
+            .get_all_rail_lines()
+            .iter()
+            .filter(|l| !l.schedule.is_empty())
+            .flat_map(|l| (0..l.stations.len()).map(move |idx| (l, idx)))
+            .find_map(|(line, idx)| {
+                let pt = map.get_station(line.stations[idx]).sidewalk_pos.pt(&map);
+                map.get_all_bus_routes()
+                    .iter()
+                    .filter(|r| !r.schedule.is_empty())
+                    .find_map(|route| {
+                        let (first_stop, num_stops) = route.run_span(&route.schedule[0])?;
+                        (1..num_stops)
+                            .find(|i| {
+                                let stop = route.stops[(first_stop + i) % route.stops.len()];
+                                map.get_bs(stop).sidewalk_pos.pt(&map).dist_to(pt)
+                                    < Distance::meters(200.0)
+                            })
+                            .map(|i| (route, first_stop, i))
+                    })
+                    .map(|(route, first_stop, i)| (line, idx, route, first_stop, i))
+            })
+            .expect("no scheduled bus stops near a station");
+        let stop_idx = |i: usize| route.stops[(first_stop + i) % route.stops.len()];
+        let ped_stop1 = stop_idx(stop_offset - 1);
+        let ped_stop2 = stop_idx(stop_offset);
+        let station1 = line.stations[station_idx];
+        let station2 = line.stations[line.next_station_idx(station_idx)];
+
+        let bus = sim.seed_bus_route(route, &map, &mut Timer::throwaway())[0];
+        sim.seed_rail_line(line);
+        let departure = route.schedule[0].stop_times[0].1;
+        let start_bldg = *map
+            .get_l(map.get_bs(ped_stop1).sidewalk_pos.lane())
+            .building_paths
+            .last()
+            .unwrap();
+        let goal_bldg = map
+            .get_l(map.get_station(station2).sidewalk_pos.lane())
+            .building_paths[0];
+        let ped = sim
+            .schedule_trip(
+                departure - Duration::minutes(5),
+                TripSpec::UsingTransit {
+                    start: SidewalkSpot::building(start_bldg, &map),
+                    rides: vec![
+                        TransitRide::Bus(route.id, ped_stop1, ped_stop2),
+                        TransitRide::Train(line.id, station1, station2),
+                    ],
+                    goal: SidewalkSpot::building(goal_bldg, &map),
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            )
+            .0
+            .unwrap();
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&sim);
+
+        // Whichever train comes first to take the pedestrian onwards is fine
+        sim.run_until_expectations_met(
+            &map,
+            vec![
+                Event::PedReachedBusStop(ped, ped_stop1),
+                Event::PedEntersBus(ped, bus, route.id),
+                Event::PedLeavesBus(ped, bus, route.id),
+                Event::PedReachedStation(ped, station1),
+                Event::PedReachedBuilding(ped, goal_bldg),
             ],
-            Duration::minutes(9),
+            departure + Duration::minutes(120),
         );
+        let finished = sim.get_finished_trips();
+        assert_eq!(finished.finished_trips.len(), 1);
+        assert_eq!(finished.finished_trips[0].1, TripMode::Transit);
+        assert_eq!(finished.aborted_trips, 0);
     });
 }
@@ -1,8 +1,16 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::Duration;
-use map_model::{BuildingID, IntersectionID};
-use sim::{DrivingGoal, Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use geom::{Distance, Duration};
+use map_model::{
+    BuildingID, EditCmd, IntersectionID, LaneType, PathConstraints, PathRequest, PathStep,
+    Position, Traversable,
+};
+use sim::{
+    AgentID, CarID, DrivingGoal, Event, EventLogOptions, LoggedEvent, ModeChoice, PedestrianID,
+    PersonID, PersonSpec, PersonTrip, Purpose, Scenario, ScenarioProblem, SidewalkSpot, SimFlags,
+    SpawnTrip, Trajectory, TrajectorySample, TripID, TripMode, TripSpec,
+};
+use std::collections::BTreeMap;
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("bike_from_border", |h| {
@@ -37,4 +45,452 @@ pub fn run(t: &mut TestRunner) {
         );
         sim.just_run_until_done(&map, Some(Duration::minutes(1)));
     });
+
+    t.run_slow("validate_scenario_with_missing_neighborhood", |_| {
+        let flags = SimFlags::for_test("validate_scenario_with_missing_neighborhood");
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+        let mut scenario = Scenario::small_run(&map);
+        scenario.spawn_over_time[0].start_from_neighborhood = "nowhere".to_string();
+
+        let problems = scenario.validate(&map, &mut Timer::throwaway());
+        assert!(problems.iter().any(|p| match p {
+            ScenarioProblem::MissingNeighborhood(src, n) => {
+                src == "spawn_over_time[0]" && n == "nowhere"
+            }
+            _ => false,
+        }));
+    });
+    t.run_slow("person_goes_back_home", |h| {
+        let flags = SimFlags::for_test("person_goes_back_home");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let home = BuildingID(319);
+        let work = BuildingID(0);
+        let mut scenario = Scenario::empty(&map);
+        scenario.people.push(PersonSpec {
+            id: PersonID(0),
+            home,
+            num_cars: 0,
+            trips: vec![
+                PersonTrip {
+                    trip: SpawnTrip::JustWalking(
+                        Duration::ZERO,
+                        SidewalkSpot::building(home, &map),
+                        SidewalkSpot::building(work, &map),
+                    ),
+                    purpose: Purpose::Work,
+                },
+                // The second trip can't start until the first is done, no matter what this says.
+                PersonTrip {
+                    trip: SpawnTrip::JustWalking(
+                        Duration::ZERO,
+                        SidewalkSpot::building(work, &map),
+                        SidewalkSpot::building(home, &map),
+                    ),
+                    purpose: Purpose::Home,
+                },
+            ],
+        });
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![
+                Event::PedReachedBuilding(PedestrianID(0), work),
+                Event::PedReachedBuilding(PedestrianID(1), home),
+            ],
+            Duration::minutes(30),
+        );
+        sim.just_run_until_done(&map, Some(Duration::minutes(1)));
+        assert_eq!(sim.trip_to_person(TripID(1)), Some(PersonID(0)));
+    });
+    t.run_slow("commuter_drives_same_car_home", |h| {
+        let flags = SimFlags::for_test("commuter_drives_same_car_home");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let home = BuildingID(319);
+        let work = BuildingID(0);
+        let mut scenario = Scenario::empty(&map);
+        scenario.people.push(PersonSpec {
+            id: PersonID(0),
+            home,
+            num_cars: 1,
+            trips: vec![
+                PersonTrip {
+                    trip: SpawnTrip::MaybeUsingParkedCar(
+                        Duration::ZERO,
+                        home,
+                        DrivingGoal::ParkNear(work),
+                    ),
+                    purpose: Purpose::Work,
+                },
+                PersonTrip {
+                    trip: SpawnTrip::MaybeUsingParkedCar(
+                        Duration::ZERO,
+                        work,
+                        DrivingGoal::ParkNear(home),
+                    ),
+                    purpose: Purpose::Home,
+                },
+            ],
+        });
+        // A housemate without a car wants one while the commuter is still walking to theirs
+        scenario.people.push(PersonSpec {
+            id: PersonID(1),
+            home,
+            num_cars: 0,
+            trips: vec![PersonTrip {
+                trip: SpawnTrip::MaybeUsingParkedCar(
+                    Duration::seconds(1.0),
+                    home,
+                    DrivingGoal::ParkNear(work),
+                ),
+                purpose: Purpose::Work,
+            }],
+        });
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        let cars: Vec<CarID> = sim
+            .get_parked_cars_by_owner(home)
+            .into_iter()
+            .map(|p| p.vehicle.id)
+            .collect();
+        assert_eq!(cars.len(), 1);
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(60)));
+
+        let finished = sim.get_finished_trips();
+        let commutes: Vec<TripMode> = finished
+            .finished_trips
+            .iter()
+            .filter(|(trip, _, _)| sim.trip_to_person(*trip) == Some(PersonID(0)))
+            .map(|(_, mode, _)| *mode)
+            .collect();
+        assert_eq!(commutes, vec![TripMode::Drive, TripMode::Drive]);
+        // The commuter's car was never up for grabs, so the housemate walked
+        assert_eq!(finished.aborted_trips, 0);
+        let housemate: Vec<TripMode> = finished
+            .finished_trips
+            .iter()
+            .filter(|(trip, _, _)| sim.trip_to_person(*trip) == Some(PersonID(1)))
+            .map(|(_, mode, _)| *mode)
+            .collect();
+        assert_eq!(housemate, vec![TripMode::Walk]);
+        // Parked again at the end of the day, and still the only car the household has
+        let parked: Vec<CarID> = sim
+            .get_parked_cars_by_owner(home)
+            .into_iter()
+            .map(|p| p.vehicle.id)
+            .collect();
+        assert_eq!(parked, cars);
+    });
+    t.run_slow("mode_choice_records_modes", |_| {
+        let flags = SimFlags::for_test("mode_choice_records_modes");
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        let mut scenario = Scenario::small_run(&map);
+        scenario.mode_choice = Some(ModeChoice::default_logit());
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+
+        let split = sim.get_analytics().mode_split();
+        // Every SpawnOverTime agent ends between two buildings, so most of them should have gone
+        // through the model.
+        let total: usize = split.values().sum();
+        assert!(total > scenario.spawn_over_time[0].num_agents / 2);
+        assert!(split.contains_key(&TripMode::Walk));
+    });
+    t.run_fast("mode_choice_probabilities", |_| {
+        let model = ModeChoice::default_logit();
+        let probs = model.probabilities(&vec![
+            (TripMode::Walk, Duration::minutes(10)),
+            (TripMode::Drive, Duration::minutes(10)),
+        ]);
+        // Walking costs 1.5 and driving 2.3, so walking wins with 1 / (1 + e^-0.8)
+        assert_eq!(probs[0].0, TripMode::Walk);
+        assert!((probs[0].1 - 1.0 / (1.0 + (-0.8_f64).exp())).abs() < 1e-9);
+        assert!((probs[0].1 + probs[1].1 - 1.0).abs() < 1e-9);
+
+        // A longer walk shifts people to driving
+        let longer = model.probabilities(&vec![
+            (TripMode::Walk, Duration::minutes(30)),
+            (TripMode::Drive, Duration::minutes(10)),
+        ]);
+        assert!(longer[0].1 < probs[0].1);
+        assert!(longer[1].1 > 0.5);
+
+        // Long trips don't overflow
+        let long = model.probabilities(&vec![
+            (TripMode::Walk, Duration::minutes(100_000)),
+            (TripMode::Bike, Duration::minutes(100_000)),
+        ]);
+        assert!((long[0].1 + long[1].1 - 1.0).abs() < 1e-9);
+        assert!(long[1].1 > 0.99);
+
+        assert_eq!(
+            model.probabilities(&vec![(TripMode::Transit, Duration::minutes(5))]),
+            vec![(TripMode::Transit, 1.0)]
+        );
+    });
+    t.run_slow("mode_choice_shifts_after_edits", |_| {
+        let flags = SimFlags::for_test("mode_choice_shifts_after_edits");
+        let (mut map, _, _) = flags.load(&mut Timer::throwaway());
+        let model = ModeChoice::default_logit();
+        // TODO Hardcoding IDs is fragile
+        let (from, to) = (BuildingID(0), BuildingID(319));
+
+        let before = ModeChoice::estimate_times(from, to, true, &map);
+        let time = |times: &Vec<(TripMode, Duration)>, mode| {
+            times.iter().find(|(m, _)| *m == mode).map(|(_, t)| *t)
+        };
+        let walk_before = time(&before, TripMode::Walk).unwrap();
+        let drive_before = time(&before, TripMode::Drive).unwrap();
+        // Without a car, driving isn't an option
+        assert_eq!(
+            time(
+                &ModeChoice::estimate_times(from, to, false, &map),
+                TripMode::Drive
+            ),
+            None
+        );
+
+        // Turn the driving lanes along the middle of the route into bus lanes
+        let path = map
+            .pathfind(PathRequest {
+                start: Position::new(map.find_driving_lane_near_building(from), Distance::ZERO),
+                end: DrivingGoal::ParkNear(to).goal_pos(PathConstraints::Car, &map),
+                constraints: PathConstraints::Car,
+            })
+            .unwrap();
+        let lanes: Vec<_> = path
+            .get_steps()
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Lane(l) => Some(*l),
+                _ => None,
+            })
+            .collect();
+        assert!(lanes.len() > 2);
+        let mut edits = map.get_edits().clone();
+        for l in &lanes[1..lanes.len() - 1] {
+            let r = map.get_parent(*l);
+            let children = if r.dir_and_offset(*l).0 {
+                &r.children_forwards
+            } else {
+                &r.children_backwards
+            };
+            for (id, lt) in children {
+                if *lt == LaneType::Driving {
+                    edits.commands.push(EditCmd::ChangeLaneType {
+                        id: *id,
+                        lt: LaneType::Bus,
+                        orig_lt: LaneType::Driving,
+                    });
+                }
+            }
+        }
+        let mut timer = Timer::throwaway();
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+
+        let after = ModeChoice::estimate_times(from, to, true, &map);
+        assert_eq!(time(&after, TripMode::Walk), Some(walk_before));
+        // Driving has to detour, if it's possible at all
+        if let Some(drive_after) = time(&after, TripMode::Drive) {
+            assert!(drive_after > drive_before);
+        }
+        let p_walk = |times: &Vec<(TripMode, Duration)>| {
+            model
+                .probabilities(times)
+                .into_iter()
+                .find(|(m, _)| *m == TripMode::Walk)
+                .unwrap()
+                .1
+        };
+        assert!(p_walk(&after) > p_walk(&before));
+    });
+    t.run_slow("experienced_travel_times", |_| {
+        let flags = SimFlags::for_test("experienced_travel_times");
+        let (mut map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        sim.step(&map, Duration::minutes(30));
+
+        let times = sim.get_analytics().car_travel_times();
+        assert!(!times.is_empty());
+        assert!(!sim.get_analytics().car_routes.is_empty());
+        // Reweight the car graph, then go back to free-flow
+        map.set_driving_travel_times(times, &mut Timer::throwaway());
+        map.set_driving_travel_times(BTreeMap::new(), &mut Timer::throwaway());
+
+        // Make the middle of one route look jammed, and cars should avoid it.
+        // TODO Hardcoding IDs is fragile
+        let req = PathRequest {
+            start: Position::new(
+                map.find_driving_lane_near_building(BuildingID(0)),
+                Distance::ZERO,
+            ),
+            end: DrivingGoal::ParkNear(BuildingID(319)).goal_pos(PathConstraints::Car, &map),
+            constraints: PathConstraints::Car,
+        };
+        let free_flow = map.pathfind(req.clone()).unwrap().get_steps().clone();
+        let lanes: Vec<_> = free_flow
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Lane(l) => Some(Traversable::Lane(*l)),
+                _ => None,
+            })
+            .collect();
+        assert!(lanes.len() > 2);
+        let jammed = lanes[1..lanes.len() - 1]
+            .iter()
+            .map(|t| (*t, Duration::minutes(60)))
+            .collect();
+        map.set_driving_travel_times(jammed, &mut Timer::throwaway());
+        let detour = map.pathfind(req.clone()).unwrap().get_steps().clone();
+        assert_ne!(detour, free_flow);
+
+        map.set_driving_travel_times(BTreeMap::new(), &mut Timer::throwaway());
+        assert_eq!(map.pathfind(req).unwrap().get_steps().clone(), free_flow);
+    });
+
+    t.run_slow("trajectories_recorded_for_finished_trips", |h| {
+        let mut flags = SimFlags::for_test("trajectories_recorded_for_finished_trips");
+        flags.opts.record_trajectories = Some(Duration::seconds(10.0));
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+
+        let trajectories = sim.get_trajectories().unwrap();
+        for (trip, mode, dt) in sim.get_finished_trips().finished_trips {
+            // Short trips might fall between samples
+            if dt < Duration::seconds(10.0) {
+                continue;
+            }
+            let samples = &trajectories.get(trip).unwrap().samples;
+            for pair in samples.windows(2) {
+                assert!(pair[0].time < pair[1].time);
+                // Riders are sampled at their bus or train, so transit trips don't have gaps
+                // longer than a sample or two around boarding and leaving.
+                if mode == TripMode::Transit {
+                    assert!(pair[1].time - pair[0].time <= Duration::seconds(30.0));
+                }
+            }
+        }
+    });
+
+    t.run_slow("trajectory_exports_round_trip", |h| {
+        let mut flags = SimFlags::for_test("trajectory_exports_round_trip");
+        flags.opts.record_trajectories = Some(Duration::seconds(10.0));
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        Scenario::small_run(&map).instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.step(&map, Duration::minutes(5));
+
+        let trajectories = sim.get_trajectories().unwrap();
+        let gps_bounds = map.get_gps_bounds();
+        let agent_name = |id: AgentID| match id {
+            AgentID::Car(c) => format!("{:?}{}", c.1, c.0).to_lowercase(),
+            AgentID::Pedestrian(p) => format!("ped{}", p.0),
+        };
+        // Points are stored with limited precision, so allow about a centimeter of error
+        let close = |x: f64, y: f64| (x - y).abs() < 1e-7;
+
+        let csv_path = std::env::temp_dir()
+            .join("trajectory_exports_round_trip.csv")
+            .to_string_lossy()
+            .to_string();
+        trajectories.export_csv(&csv_path, gps_bounds).unwrap();
+        let contents = std::fs::read_to_string(&csv_path).unwrap();
+        let mut lines = contents.lines();
+        assert_eq!(lines.next(), Some("trip,agent,time,longitude,latitude"));
+        let rows: Vec<Vec<&str>> = lines.map(|l| l.split(',').collect()).collect();
+        let expected: Vec<(TripID, &TrajectorySample)> = trajectories
+            .all()
+            .flat_map(|t| t.samples.iter().map(move |s| (t.trip, s)))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(rows.len(), expected.len());
+        for (row, (trip, sample)) in rows.iter().zip(expected) {
+            let gps = sample.pt.forcibly_to_gps(gps_bounds);
+            assert_eq!(row[0], trip.0.to_string());
+            assert_eq!(row[1], agent_name(sample.agent));
+            assert!(close(row[2].parse().unwrap(), sample.time.inner_seconds()));
+            assert!(close(row[3].parse().unwrap(), gps.longitude));
+            assert!(close(row[4].parse().unwrap(), gps.latitude));
+        }
+
+        let geojson_path = std::env::temp_dir()
+            .join("trajectory_exports_round_trip.geojson")
+            .to_string_lossy()
+            .to_string();
+        trajectories
+            .export_geojson(&geojson_path, gps_bounds)
+            .unwrap();
+        let collection: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&geojson_path).unwrap()).unwrap();
+        assert_eq!(collection["type"], "FeatureCollection");
+        // A single sample can't make a LineString
+        let long_enough: Vec<&Trajectory> = trajectories
+            .all()
+            .filter(|t| t.samples.len() >= 2)
+            .collect();
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), long_enough.len());
+        for (feature, t) in features.iter().zip(long_enough) {
+            assert_eq!(feature["geometry"]["type"], "LineString");
+            assert_eq!(feature["properties"]["trip"], t.trip.0);
+            let agents = feature["properties"]["agents"].as_array().unwrap();
+            let times = feature["properties"]["times"].as_array().unwrap();
+            let coords = feature["geometry"]["coordinates"].as_array().unwrap();
+            assert_eq!(agents.len(), t.samples.len());
+            assert_eq!(times.len(), t.samples.len());
+            assert_eq!(coords.len(), t.samples.len());
+            for (idx, sample) in t.samples.iter().enumerate() {
+                let gps = sample.pt.forcibly_to_gps(gps_bounds);
+                assert_eq!(agents[idx], agent_name(sample.agent));
+                assert!(close(
+                    times[idx].as_f64().unwrap(),
+                    sample.time.inner_seconds()
+                ));
+                assert!(close(coords[idx][0].as_f64().unwrap(), gps.longitude));
+                assert!(close(coords[idx][1].as_f64().unwrap(), gps.latitude));
+            }
+        }
+    });
+
+    t.run_slow("informed_drivers_reroute_around_jams", |h| {
+        let path = abstutil::path1_json("montlake", "event_logs", "reroute_test");
+        let mut flags = SimFlags::for_test("informed_drivers_reroute_around_jams");
+        flags.opts.informed_drivers = 1.0;
+        let mut log = EventLogOptions::new(path.clone());
+        log.only_record(vec!["CarRerouted".to_string()]).unwrap();
+        flags.opts.event_log = Some(log);
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        // Flood every border with cars, so the lanes near them back up
+        let mut scenario = Scenario::small_run(&map);
+        for s in &mut scenario.border_spawn_over_time {
+            s.num_cars = 100;
+        }
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        h.setup_done(&sim);
+        sim.step(&map, Duration::minutes(30));
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let reroutes: Vec<LoggedEvent> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(!reroutes.is_empty());
+        // Every car waits a while before looking for another faster path
+        let mut last_reroute: BTreeMap<CarID, Duration> = BTreeMap::new();
+        for ev in reroutes {
+            if let Event::CarRerouted(car, _) = ev.event {
+                if let Some(t) = last_reroute.get(&car) {
+                    assert!(ev.time - *t >= Duration::seconds(60.0));
+                }
+                last_reroute.insert(car, ev.time);
+            } else {
+                panic!("Logged {:?}, not just CarRerouted", ev);
+            }
+        }
+    });
 }